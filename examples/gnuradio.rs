@@ -0,0 +1,57 @@
+use std::io::Read;
+use std::time::Duration;
+
+use thunderscope::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration, DeviceParameters};
+use thunderscope::gnuradio::Publisher;
+
+const CHUNK_SIZE: usize = 1 << 20;
+
+fn usage() -> ! {
+    eprintln!("usage: gnuradio [--endpoint tcp://*:5555] [--decimation N] \
+               [--channel 1|2|3|4]");
+    std::process::exit(2)
+}
+
+fn main() -> thunderscope::Result<()> {
+    env_logger::Builder::from_default_env()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    let mut endpoint = "tcp://*:5555".to_owned();
+    let mut decimation = 100;
+    let mut channel_index = 0;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--endpoint", Some(value)) => endpoint = value,
+            ("--decimation", Some(value)) => match value.parse::<usize>() {
+                Ok(value @ 1..) => decimation = value,
+                _ => usage()
+            }
+            ("--channel", Some(value)) => match value.parse::<usize>() {
+                Ok(value @ 1..=4) => channel_index = value - 1,
+                _ => usage()
+            }
+            _ => usage()
+        }
+    }
+    let mut publisher = Publisher::bind(&endpoint, decimation)?;
+    thunderscope::Device::with(|device| {
+        let mut config = DeviceConfiguration { channels: [None; 4], ..Default::default() };
+        config.channels[channel_index] = Some(ChannelConfiguration::default());
+        let params = DeviceParameters::derive(&DeviceCalibration::default(), &config);
+        device.configure(&params)?;
+        log::info!("output sample rate: {} S/s", params.sample_rate() / decimation as f32);
+        let volts_table = params.volts_table(channel_index);
+        let mut stream = device.stream_data();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            let length = stream.read(&mut buffer[..])?;
+            if length == 0 {
+                std::thread::sleep(Duration::from_micros(100));
+                continue
+            }
+            publisher.publish(&volts_table, bytemuck::cast_slice(&buffer[..length]))?;
+        }
+    })
+}
@@ -0,0 +1,1200 @@
+//! The acquisition engine: reads the sample stream, finds trigger points in it according to
+//! the operation mode, and captures waveforms at them.
+//!
+//! `Acquisition` performs one acquisition step at a time. `Sampler` runs it in a loop on a thread
+//! of its own, and exchanges parameters and waveforms with the rest of the application over
+//! channels; a frontend interleaves its own processing (e.g. limit checking, or session
+//! recording) with the acquisition steps by implementing `SamplerHooks`.
+
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::{Result, Error};
+use crate::config::DeviceConfiguration;
+use crate::params::{DeviceCalibration, DeviceParameters};
+use crate::device::{DataStream, Device};
+use crate::buffer::{RingBuffer, RingCursor};
+use crate::trigger::{Edge, EdgeFilter, Trigger};
+use crate::trigger_coupling::{TriggerConditioner, TriggerCoupling};
+use crate::window_trigger::{WindowCrossing, WindowFilter, WindowTrigger};
+use crate::timestamp::Timestamp;
+use crate::capture::Capture;
+use crate::channel_map::ChannelMap;
+
+const TRIGGER_HYSTERESIS: u8 = 2;
+
+/// How long the auto mode waits for a trigger before capturing without one.
+pub const AUTO_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerParameters {
+    channel: usize,
+    level: f32, // in volts
+    edge: EdgeFilter,
+    #[cfg_attr(feature = "serde", serde(default))]
+    coupling: TriggerCoupling,
+    #[cfg_attr(feature = "serde", serde(default))]
+    qualifier: Option<TriggerQualifier>,
+}
+
+impl TriggerParameters {
+    pub fn new(channel: usize, level: f32, edge: EdgeFilter) -> TriggerParameters {
+        TriggerParameters { channel, level, edge, coupling: TriggerCoupling::Dc, qualifier: None }
+    }
+}
+
+/// The state that the qualifying channel must be in for the trigger to fire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QualifierState {
+    Above,
+    Below,
+}
+
+/// A condition on a second channel that must hold at the edge for the trigger to fire; e.g. to
+/// only trigger on a bus while its chip select is asserted.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerQualifier {
+    channel: usize,
+    level: f32, // in volts
+    state: QualifierState,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowParameters {
+    channel: usize,
+    low: f32,  // in volts
+    high: f32, // in volts
+    crossing: WindowFilter,
+}
+
+impl WindowParameters {
+    pub fn new(channel: usize, low: f32, high: f32, crossing: WindowFilter) -> WindowParameters {
+        WindowParameters { channel, low, high, crossing }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OperationMode {
+    Idle,
+    FreeRunning,
+    SingleTrigger(TriggerParameters),
+    RepeatTrigger(TriggerParameters),
+    /// Like `RepeatTrigger`, but if there is no trigger within the timeout, capture anyway, so
+    /// that there is a live trace even without a signal.
+    Auto(TriggerParameters, Duration),
+    SingleWindow(WindowParameters),
+    RepeatWindow(WindowParameters),
+}
+
+impl OperationMode {
+    /// Returns `true` if acquisition stops after the first capture.
+    pub fn is_single(&self) -> bool {
+        matches!(self, OperationMode::SingleTrigger(_) | OperationMode::SingleWindow(_))
+    }
+}
+
+/// What a trigger has fired on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerCause {
+    Edge(Edge),
+    Window(WindowCrossing),
+}
+
+/// A notification that the trigger has fired, delivered as soon as the trigger point is found
+/// (see `Acquisition::acquire`), i.e. before the capture is complete and whether or not
+/// the capture is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriggerEvent {
+    /// Stream position of the first sample of the frame where the trigger has fired.
+    pub sample: u64,
+    pub channel: usize,
+    pub cause: TriggerCause,
+    /// Position of the crossing of the trigger level relative to `sample`, in samples of
+    /// the channel (see `Trigger::crossing`); zero if it is not known.
+    pub crossing: f32,
+}
+
+// the qualifying channel is at the given lane of each frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ArmedQualifier {
+    lane: usize,
+    level: i8,
+    state: QualifierState,
+}
+
+impl ArmedQualifier {
+    fn new(device: &DeviceParameters, qualifier: TriggerQualifier) -> Option<ArmedQualifier> {
+        Some(ArmedQualifier {
+            lane: ChannelMap::from_params(device).lane(qualifier.channel)?,
+            level: device.volts_to_code(qualifier.channel, qualifier.level),
+            state: qualifier.state,
+        })
+    }
+
+    // whether the condition holds in the frame starting at `samples[0]`
+    fn holds(&self, samples: &[i8]) -> bool {
+        match self.state {
+            QualifierState::Above => samples[self.lane] > self.level,
+            QualifierState::Below => samples[self.lane] < self.level,
+        }
+    }
+}
+
+/// A trigger mechanism set up according to the operation mode.
+#[derive(Debug, Clone)]
+enum ArmedTrigger {
+    // the trigger channel is at index `lane` of frames of `channels` interleaved channels
+    Edge {
+        trigger: Trigger,
+        conditioner: TriggerConditioner,
+        filter: EdgeFilter,
+        qualifier: Option<ArmedQualifier>,
+        channel: usize,
+        channels: usize,
+        lane: usize,
+    },
+    Window {
+        trigger: WindowTrigger,
+        filter: WindowFilter,
+        channel: usize,
+        channels: usize,
+        lane: usize,
+    },
+}
+
+impl ArmedTrigger {
+    fn new(params: &Parameters) -> Option<ArmedTrigger> {
+        let device = &params.device;
+        match params.mode {
+            OperationMode::Idle |
+            OperationMode::FreeRunning => None,
+            OperationMode::SingleTrigger(trigger) |
+            OperationMode::RepeatTrigger(trigger) |
+            OperationMode::Auto(trigger, _) => {
+                let channel_map = ChannelMap::from_params(device);
+                let lane = channel_map.lane(trigger.channel)?;
+                let qualifier = match trigger.qualifier {
+                    Some(qualifier) => Some(ArmedQualifier::new(device, qualifier)?),
+                    None => None,
+                };
+                Some(ArmedTrigger::Edge {
+                    trigger: Trigger::new(
+                        device.volts_to_code(trigger.channel, trigger.level),
+                        TRIGGER_HYSTERESIS
+                    ),
+                    conditioner: TriggerConditioner::new(trigger.coupling, TRIGGER_HYSTERESIS),
+                    filter: trigger.edge,
+                    qualifier,
+                    channel: trigger.channel,
+                    channels: channel_map.stream_channels(),
+                    lane,
+                })
+            }
+            OperationMode::SingleWindow(window) |
+            OperationMode::RepeatWindow(window) => {
+                // a window on a disabled channel cannot be armed
+                let channel_map = ChannelMap::from_params(device);
+                let lane = channel_map.lane(window.channel)?;
+                Some(ArmedTrigger::Window {
+                    trigger: WindowTrigger::new(
+                        device.volts_to_code(window.channel, window.low),
+                        device.volts_to_code(window.channel, window.high),
+                        TRIGGER_HYSTERESIS
+                    ),
+                    filter: window.crossing,
+                    channel: window.channel,
+                    channels: channel_map.stream_channels(),
+                    lane,
+                })
+            }
+        }
+    }
+
+    /// Set up `armed` according to `params`. If it is an edge trigger on the same channel, in
+    /// the same stream layout, and with the same filter and coupling as before, only its level
+    /// and qualifier are changed, so that an edge is not missed or detected spuriously, e.g.
+    /// while the level follows the compensation of baseline drift.
+    fn update(armed: &mut Option<ArmedTrigger>, params: &Parameters) {
+        match (armed.as_mut(), ArmedTrigger::new(params)) {
+            (Some(ArmedTrigger::Edge { trigger, conditioner, filter, qualifier, channel,
+                                       channels, lane }),
+             Some(ArmedTrigger::Edge { trigger: new_trigger, conditioner: new_conditioner,
+                                       filter: new_filter, qualifier: new_qualifier,
+                                       channel: new_channel, channels: new_channels,
+                                       lane: new_lane }))
+                    if (conditioner.coupling(), *filter, *channel, *channels, *lane) ==
+                        (new_conditioner.coupling(), new_filter, new_channel, new_channels,
+                         new_lane) => {
+                trigger.set_level(new_trigger.level(), TRIGGER_HYSTERESIS);
+                *qualifier = new_qualifier;
+            }
+            (_, new_armed) => *armed = new_armed,
+        }
+    }
+
+    /// Returns the amount of consumed samples, and the trigger event if the trigger has fired.
+    /// The first sample is at stream position `position`.
+    fn find(&mut self, samples: &[i8], position: u64) -> (usize, Option<TriggerEvent>) {
+        match self {
+            ArmedTrigger::Edge { trigger, conditioner, filter, qualifier, channel, channels,
+                                 lane } => {
+                let (channels, lane) = (*channels, *lane);
+                // the interleaved scan starts at a frame boundary
+                let mut consumed = ((channels - (position % channels as u64) as usize)
+                    % channels).min(samples.len());
+                loop {
+                    let conditioned =
+                        conditioner.condition(trigger, &samples[consumed..], channels, lane);
+                    let (processed, edge) =
+                        trigger.find_interleaved(conditioned, channels, lane, *filter);
+                    let Some(edge) = edge else { return (consumed + processed, None) };
+                    // the edges are found in groups of whole frames, so the frame with the edge
+                    // is complete; if the qualifier does not hold in it, the edge is ignored
+                    let frame = consumed + processed;
+                    if let Some(qualifier) = qualifier.filter(|q| !q.holds(&samples[frame..])) {
+                        log::debug!("acquire: {:?} edge disqualified by {:?}", edge, qualifier);
+                        consumed = frame + channels;
+                        continue
+                    }
+                    log::debug!("acquire: detected {:?} edge", edge);
+                    let event = TriggerEvent {
+                        sample: position + frame as u64,
+                        channel: *channel,
+                        cause: TriggerCause::Edge(edge),
+                        // with the level crossed before the consumed samples, the trace is
+                        // aligned on the sample where the edge was detected
+                        crossing: trigger.crossing(edge, conditioned, processed + lane, channels)
+                            .unwrap_or(0.0),
+                    };
+                    return (frame, Some(event))
+                }
+            }
+            ArmedTrigger::Window { trigger, filter, channel, channels, lane } => {
+                let (channels, lane) = (*channels, *lane);
+                // the interleaved scan starts at a frame boundary
+                let skipped = ((channels - (position % channels as u64) as usize)
+                    % channels).min(samples.len());
+                let (processed, crossing) =
+                    trigger.find_interleaved(&samples[skipped..], channels, lane, *filter);
+                let processed = skipped + processed;
+                let event = crossing.map(|crossing| {
+                    log::debug!("acquire: detected window {:?}", crossing);
+                    TriggerEvent {
+                        sample: position + processed as u64,
+                        channel: *channel,
+                        cause: TriggerCause::Window(crossing),
+                        crossing: 0.0,
+                    }
+                });
+                (processed, event)
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            ArmedTrigger::Edge { trigger, .. } => trigger.reset(),
+            ArmedTrigger::Window { trigger, .. } => trigger.reset(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parameters {
+    pub device: DeviceParameters,
+    pub mode: OperationMode,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            device: DeviceParameters::derive(
+                &DeviceCalibration::default(),
+                &DeviceConfiguration::default()
+            ),
+            mode: OperationMode::Idle
+        }
+    }
+}
+
+impl Parameters {
+    pub fn demo(probe_attenuation: [f32; 4]) -> Self {
+        let mut configuration = DeviceConfiguration {
+            channels: [Some(Default::default()), None, None, None],
+            ..Default::default()
+        };
+        for (channel, attenuation) in configuration.channels.iter_mut().zip(probe_attenuation) {
+            if let Some(channel) = channel {
+                channel.probe_attenuation = attenuation;
+            }
+        }
+        Self {
+            device: DeviceParameters::derive(&DeviceCalibration::default(), &configuration),
+            mode: OperationMode::Auto(TriggerParameters::new(0, 1.0, EdgeFilter::Rising),
+                AUTO_TIMEOUT)
+        }
+    }
+
+    /// Returns these parameters with the operation mode replaced by `mode`.
+    pub fn with_mode(self, mode: OperationMode) -> Parameters {
+        Parameters { mode, ..self }
+    }
+}
+
+/// A source of samples that may be able to recover from an acquisition error.
+pub trait SampleSource: Read {
+    /// Called when the device parameters change, before the device is reconfigured.
+    fn reconfigure(&mut self, _params: &DeviceParameters) {}
+
+    fn recover(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when acquisition is paused or resumed.
+    fn pause(&mut self, _paused: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl SampleSource for DataStream {
+    fn recover(&mut self) -> Result<()> {
+        self.restart()
+    }
+
+    // the data mover is halted while paused, so that the device FIFO does not overflow while
+    // the stream is not being read
+    fn pause(&mut self, paused: bool) -> Result<()> {
+        if paused { self.control().pause() } else { self.control().resume() }
+    }
+}
+
+/// Tracks the absolute stream position of the samples read, and the host time at which the most
+/// recent ones have been read, to timestamp trigger points.
+struct TimestampingReader<R: Read> {
+    inner: R,
+    position: u64,
+    sample_rate: u64,
+    timestamp: Option<Timestamp>,
+}
+
+impl<R: Read> TimestampingReader<R> {
+    fn new(inner: R) -> Self {
+        let sample_rate = DeviceParameters::default().stream_sample_rate();
+        Self { inner, position: 0, sample_rate, timestamp: None }
+    }
+
+    /// Returns the timestamp of the sample that is `behind` samples before the next one.
+    fn timestamp_behind(&self, behind: usize) -> Option<Timestamp> {
+        let sample = self.position - behind as u64;
+        self.timestamp.map(|timestamp| Timestamp {
+            sample,
+            time: timestamp.sample_to_time(sample),
+            ..timestamp
+        })
+    }
+}
+
+impl<R: SampleSource> SampleSource for TimestampingReader<R> {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        self.sample_rate = params.stream_sample_rate();
+        self.inner.reconfigure(params)
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        self.inner.recover()
+    }
+
+    fn pause(&mut self, paused: bool) -> Result<()> {
+        self.inner.pause(paused)
+    }
+}
+
+impl<R: Read> Read for TimestampingReader<R> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(data)?;
+        if length > 0 {
+            self.position += length as u64;
+            self.timestamp = Some(Timestamp::now(self.position - 1, self.sample_rate));
+        }
+        Ok(length)
+    }
+}
+
+/// A buffer that samples are read into, and the waveform captured in it, if any.
+#[derive(Debug)]
+pub struct Waveform {
+    params: Parameters,
+    buffer: RingBuffer,
+    capture: Option<(RingCursor, usize)>,
+    capture_position: u64,
+    trigger: Option<Timestamp>,
+    // position of the crossing of the trigger level relative to the first captured frame
+    crossing: f32,
+    start: Option<Timestamp>,
+    display: Vec<i8>,
+}
+
+impl Waveform {
+    /// Create a waveform with a buffer of at least `size` samples, which must be larger than
+    /// the capture length. If `verify` is `true`, captures are checked for corruption in host
+    /// memory (see `RingBuffer::set_checksums`).
+    pub fn new(size: usize, verify: bool) -> Result<Waveform> {
+        let mut buffer = RingBuffer::new(size)?;
+        buffer.set_checksums(verify);
+        Ok(Waveform {
+            params: Parameters::default(),
+            buffer,
+            capture: None,
+            capture_position: 0,
+            trigger: None,
+            crossing: 0.0,
+            start: None,
+            display: Vec::new(),
+        })
+    }
+
+    /// Returns the parameters that the waveform was acquired with.
+    pub fn params(&self) -> &Parameters {
+        &self.params
+    }
+
+    pub fn device_params(&self) -> &DeviceParameters {
+        &self.params.device
+    }
+
+    /// Returns `true` if there is a capture.
+    pub fn is_captured(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Discard the capture.
+    pub fn clear(&mut self) {
+        self.capture = None;
+    }
+
+    /// Returns the stream position of the first captured sample.
+    pub fn capture_position(&self) -> u64 {
+        self.capture_position
+    }
+
+    pub fn capture_data(&self) -> Option<&[i8]> {
+        self.capture.map(|(cursor, length)| self.buffer.read(cursor, length))
+    }
+
+    /// Process the captured data for display with `f`, if there is a capture. The function is
+    /// called with the amount of interleaved channels, the captured data starting at a frame
+    /// boundary, and the (emptied) buffer for the data to display.
+    pub fn process_display(&mut self, f: impl FnOnce(usize, &[i8], &mut Vec<i8>)) {
+        let Some((cursor, length)) = self.capture else { return };
+        let channels = self.params.device.stream_channels();
+        let skip = (channels - (self.capture_position % channels as u64) as usize) % channels;
+        let data = self.buffer.read(cursor, length);
+        self.display.clear();
+        f(channels, &data[skip.min(length)..], &mut self.display)
+    }
+
+    /// Returns the captured data, as processed for display by `process_display`.
+    pub fn display_data(&self) -> Option<&[i8]> {
+        self.capture.map(|_| self.display.as_slice())
+    }
+
+    /// Returns the captured data, de-interleaved into channels.
+    pub fn capture(&self) -> Option<Capture> {
+        self.capture_data().map(|data|
+            Capture::from_stream(&self.params.device, self.capture_position, data))
+    }
+
+    /// Returns the stream position and host time of the trigger point, if the capture was
+    /// triggered.
+    pub fn trigger(&self) -> Option<Timestamp> {
+        self.trigger
+    }
+
+    /// Returns the amount of displayed samples by which the trigger level was crossed before
+    /// the first one, found by interpolation, so that repetitive waveforms can be aligned with
+    /// a precision better than one sample. Zero if the capture was not triggered on an edge.
+    pub fn display_delay(&self) -> f32 {
+        match self.capture {
+            Some((_, length)) if length > 0 => -self.crossing *
+                self.params.device.stream_channels() as f32 *
+                self.display.len() as f32 / length as f32,
+            _ => 0.0
+        }
+    }
+
+    /// Returns the timestamp of the first captured sample, if there is a capture.
+    pub fn start(&self) -> Option<Timestamp> {
+        self.start
+    }
+}
+
+/// A step of acquisition that can be profiled (see `Acquisition::profile_with`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcquisitionStep {
+    /// Reading samples from the source.
+    Read,
+    /// Finding the trigger point.
+    Trigger,
+}
+
+/// Acquires waveforms from a sample source according to the operation mode.
+pub struct Acquisition<R: SampleSource> {
+    reader: TimestampingReader<R>,
+    params: Parameters,
+    trigger: Option<ArmedTrigger>,
+    // when the trigger was armed or last fired, for the auto mode
+    armed_at: Instant,
+    profile: Option<Box<dyn Fn(AcquisitionStep, Duration) + Send>>,
+}
+
+impl<R: SampleSource> Acquisition<R> {
+    /// Create an idle acquisition from `source`, which must start at a frame boundary.
+    pub fn new(source: R) -> Acquisition<R> {
+        Acquisition {
+            reader: TimestampingReader::new(source),
+            params: Parameters::default(),
+            trigger: None,
+            armed_at: Instant::now(),
+            profile: None,
+        }
+    }
+
+    pub fn params(&self) -> &Parameters {
+        &self.params
+    }
+
+    /// Returns the stream position of the next sample to be read.
+    pub fn position(&self) -> u64 {
+        self.reader.position
+    }
+
+    /// Switch to `params`, rearming the trigger, and notify the source of the change. The device
+    /// itself must be reconfigured by the caller.
+    pub fn set_params(&mut self, params: Parameters) {
+        self.params = params;
+        ArmedTrigger::update(&mut self.trigger, &params);
+        self.armed_at = Instant::now();
+        self.reader.reconfigure(&params.device);
+    }
+
+    /// Switch to `params`, which may only differ in details that do not affect the sample stream
+    /// (e.g. the compensation of baseline drift), without rearming the trigger. The capture in
+    /// `waveform` is updated to use them as well.
+    pub fn adjust_params(&mut self, waveform: &mut Waveform, params: Parameters) {
+        self.params = params;
+        ArmedTrigger::update(&mut self.trigger, &params);
+        waveform.params = params;
+    }
+
+    /// Stop capturing, until parameters are switched again.
+    pub fn stop(&mut self) {
+        self.params.mode = OperationMode::Idle;
+        self.trigger = None;
+    }
+
+    /// Establish the state of the trigger anew, e.g. once the samples read from now on are
+    /// discontinuous with the earlier ones.
+    pub fn restart(&mut self) {
+        if let Some(trigger) = self.trigger.as_mut() {
+            trigger.reset();
+        }
+        self.armed_at = Instant::now();
+    }
+
+    /// Try to recover the source from an acquisition error.
+    pub fn recover(&mut self) -> Result<()> {
+        self.reader.recover()
+    }
+
+    /// Pause or resume the source. While paused, the source is not read; once it is resumed,
+    /// acquisition restarts (see `restart`).
+    pub fn pause(&mut self, paused: bool) -> Result<()> {
+        self.reader.pause(paused)?;
+        if !paused {
+            // the samples acquired from now on are discontinuous with the earlier ones
+            self.restart();
+        }
+        Ok(())
+    }
+
+    /// Report the duration of each acquisition step to `hook`.
+    pub fn profile_with(&mut self, hook: impl Fn(AcquisitionStep, Duration) + Send + 'static) {
+        self.profile = Some(Box::new(hook));
+    }
+
+    fn refill(&mut self, buffer: &mut RingBuffer, length: usize) -> Result<usize> {
+        let reader = &mut self.reader;
+        let refilled = profile(&self.profile, AcquisitionStep::Read,
+            || buffer.append(length, |slice| reader.read(slice)))?;
+        log::debug!("acquire: refilled buffer by {} bytes", refilled);
+        Ok(refilled)
+    }
+
+    // the source may return fewer samples than requested (even none, e.g. if the device has not
+    // acquired any yet), so it is read until `available` reaches `length`
+    fn refill_to(&mut self, buffer: &mut RingBuffer, mut available: usize, length: usize)
+            -> Result<usize> {
+        while available < length {
+            available += self.refill(buffer, length - available)?;
+        }
+        Ok(available)
+    }
+
+    /// Read the sample stream into `waveform` and capture `capture_length` samples in it, if
+    /// the operation mode calls for it. Returns the trigger event if the trigger has fired,
+    /// which is also passed to `on_trigger` before the rest of the capture is read.
+    ///
+    /// Once a capture is taken, the source is read until all of its samples are available.
+    /// On error, the capture in progress is abandoned, and the source may need to be recovered
+    /// (see `recover`) before acquisition can continue.
+    pub fn acquire(&mut self, waveform: &mut Waveform, capture_length: usize,
+                   mut on_trigger: impl FnMut(&TriggerEvent)) -> Result<Option<TriggerEvent>> {
+        // set up capturing in the buffer
+        waveform.params = self.params;
+        waveform.capture = None;
+        waveform.trigger = None;
+        waveform.crossing = 0.0;
+        waveform.start = None;
+        let mut trigger_event = None;
+        let mut cursor = waveform.buffer.cursor();
+        // refill buffer
+        let refill_by = waveform.buffer.len();
+        let mut available = self.refill(&mut waveform.buffer, refill_by)?;
+        let timed_out = matches!(self.params.mode,
+            OperationMode::Auto(_, timeout) if self.armed_at.elapsed() >= timeout);
+        if let OperationMode::FreeRunning = self.params.mode {
+            // accept capture as-is, once it is complete
+            available = self.refill_to(&mut waveform.buffer, available, capture_length)?;
+            waveform.capture = Some((cursor, capture_length));
+            waveform.capture_position = self.reader.position - available as u64;
+            log::debug!("acquire: captured waveform free running ({}+{})",
+                cursor.into_inner(), capture_length);
+        } else if timed_out {
+            // accept capture as-is, once it is complete, and keep waiting for a trigger
+            available = self.refill_to(&mut waveform.buffer, available, capture_length)?;
+            waveform.capture = Some((cursor, capture_length));
+            waveform.capture_position = self.reader.position - available as u64;
+            log::debug!("acquire: captured waveform on auto timeout ({}+{})",
+                cursor.into_inner(), capture_length);
+            self.armed_at = Instant::now();
+        } else if let Some(trigger) = self.trigger.as_mut() {
+            // find trigger point
+            let data = waveform.buffer.read(cursor, available);
+            let position = self.reader.position - available as u64;
+            let (processed, event) = profile(&self.profile, AcquisitionStep::Trigger,
+                || trigger.find(data, position));
+            cursor += processed;
+            available -= processed;
+            log::debug!("acquire: trigger consumed {} bytes ({} available)",
+                processed, available);
+            if let Some(event) = event {
+                // reset trigger to resynchronize its state
+                trigger.reset();
+                self.armed_at = Instant::now();
+                on_trigger(&event);
+                trigger_event = Some(event);
+                // record the trigger point before more data is read
+                waveform.trigger = self.reader.timestamp_behind(available);
+                waveform.crossing = event.crossing;
+                // capture more if needed
+                available = self.refill_to(&mut waveform.buffer, available, capture_length)?;
+                // accept capture at trigger point
+                waveform.capture = Some((cursor, capture_length));
+                waveform.capture_position = self.reader.position - available as u64;
+                log::debug!("acquire: captured triggered waveform ({}+{})",
+                    cursor.into_inner(), capture_length);
+            }
+        }
+        if waveform.capture.is_some() {
+            let behind = self.reader.position - waveform.capture_position;
+            waveform.start = self.reader.timestamp_behind(behind as usize);
+        }
+        // if there is a capture, make sure it has not been corrupted in memory before it is
+        // analyzed or saved
+        if let Some((cursor, length)) = waveform.capture {
+            if let Err(error) = waveform.buffer.verify(cursor, length) {
+                log::error!("acquire: discarding capture: {}", error);
+                waveform.capture = None;
+            }
+        }
+        Ok(trigger_event)
+    }
+}
+
+fn profile<T>(hook: &Option<Box<dyn Fn(AcquisitionStep, Duration) + Send>>,
+              step: AcquisitionStep, f: impl FnOnce() -> T) -> T {
+    let Some(hook) = hook else { return f() };
+    let started_at = Instant::now();
+    let result = f();
+    hook(step, started_at.elapsed());
+    result
+}
+
+/// Stages of the `Sampler` loop that a frontend can extend, e.g. with postprocessing, limit
+/// checking, or session recording. Each method does what the sampler does by itself by default;
+/// `()` implements the trait with all of the defaults.
+pub trait SamplerHooks {
+    /// Called before acquisition starts, e.g. to set up profiling.
+    fn start<R: SampleSource>(&mut self, _acquisition: &mut Acquisition<R>) {}
+
+    /// Returns the parameters to switch to, if any, given those received through the parameter
+    /// channel since the last call.
+    fn switch_params<R: SampleSource>(&mut self, _acquisition: &Acquisition<R>,
+                                      received: Option<Parameters>) -> Option<Parameters> {
+        received
+    }
+
+    /// Returns whether acquisition should be paused (or resumed), if that is requested. This is
+    /// called repeatedly while acquisition is paused, and may block for a short time.
+    fn poll_pause(&mut self, _paused: bool) -> Option<bool> {
+        None
+    }
+
+    /// Returns the amount of samples to capture, given the amount the sampler was created with.
+    fn capture_length(&self, capture_length: usize) -> usize {
+        capture_length
+    }
+
+    /// Called when the trigger fires, before the rest of the capture is read.
+    fn on_trigger(&mut self, _event: &TriggerEvent) {}
+
+    /// Called after each acquisition step, whether or not `waveform` has a capture, before it is
+    /// submitted. The acquisition may be stopped or have its parameters adjusted here.
+    fn on_acquired<R: SampleSource>(&mut self, _acquisition: &mut Acquisition<R>,
+                                    _waveform: &mut Waveform,
+                                    _trigger_event: Option<TriggerEvent>) {}
+
+    /// Processes the capture in `waveform` for display. By default, the captured samples are
+    /// displayed as acquired.
+    fn process_display(&mut self, waveform: &mut Waveform) {
+        waveform.process_display(|_channels, samples, display|
+            display.extend_from_slice(samples));
+    }
+
+    /// Handles an acquisition error, after which the capture in progress is abandoned. Returns
+    /// `Continue` once the source has recovered, or `Break` with the result to stop with.
+    ///
+    /// When this is called with `Error::Overflow`, the stream has restarted acquisition already.
+    /// By default, acquisition continues after an overflow, and stops after any other error.
+    fn recover<R: SampleSource>(&mut self, _acquisition: &mut Acquisition<R>, error: Error)
+            -> ControlFlow<Result<()>> {
+        match error {
+            Error::Overflow { .. } => ControlFlow::Continue(()),
+            error => ControlFlow::Break(Err(error)),
+        }
+    }
+}
+
+impl SamplerHooks for () {}
+
+/// Runs acquisition on a thread of its own.
+pub struct Sampler {
+    params_recv: Receiver<Parameters>,
+    // Sampler does not allocate the waveform buffers. It relies on a pair of channels acting like
+    // a bucket brigade: any received `Waveform` objects are filled in with captures and sent for
+    // further processing. Eventually the `Waveform` object comes back from the processing engine,
+    // and the closed cycle continues.
+    waveform_recv: Receiver<Waveform>,
+    waveform_send: Sender<Waveform>,
+    capture_length: usize,
+}
+
+impl Sampler {
+    /// Create a sampler that switches to the parameters received through `params_recv`, and
+    /// fills the waveforms received through `waveform_recv` with captures of `capture_length`
+    /// samples, sending them through `waveform_send`. At least two waveforms must be in
+    /// circulation.
+    pub fn new(params_recv: Receiver<Parameters>, waveform_recv: Receiver<Waveform>,
+               waveform_send: Sender<Waveform>, capture_length: usize) -> Sampler {
+        Sampler { params_recv, waveform_recv, waveform_send, capture_length }
+    }
+
+    /// Start up `device` and acquire from it on a new thread, until either of the waveform
+    /// channels is disconnected or acquisition fails; then shut the device down.
+    pub fn run(mut self, device: Device) -> JoinHandle<Result<()>> {
+        std::thread::spawn(move || {
+            let device = device.guard()?;
+            let control = device.control();
+            let result = self.run_with(device.stream_data(), |params| control.configure(params));
+            // an acquisition error takes precedence over a shutdown error it may have caused
+            let shutdown = device.shutdown();
+            result.and(shutdown)
+        })
+    }
+
+    /// Acquire from `source` on the current thread, until either of the waveform channels is
+    /// disconnected. The device is reconfigured with `configure` whenever the parameters change.
+    ///
+    /// Overflows of the FIFO are logged and acquisition continues; any other error stops it.
+    pub fn run_with<R, F>(&mut self, source: R, configure: F) -> Result<()>
+            where R: SampleSource, F: FnMut(&DeviceParameters) -> Result<()> {
+        self.run_with_hooks(source, configure, &mut ())
+    }
+
+    /// Like `run_with`, but calls `hooks` at each stage of the loop.
+    pub fn run_with_hooks<R, F, H>(&mut self, source: R, mut configure: F, hooks: &mut H)
+            -> Result<()>
+            where R: SampleSource, F: FnMut(&DeviceParameters) -> Result<()>, H: SamplerHooks {
+        let Ok(mut wfm_active) = self.waveform_recv.recv() else { return Ok(()) };
+        let mut wfm_standby = None;
+        let mut acquisition = Acquisition::new(source);
+        hooks.start(&mut acquisition);
+        let mut pending_params = None;
+        let mut paused = false;
+        let result = loop {
+            // switch capture parameters, if requested
+            let received = self.params_recv.try_recv().ok();
+            if let Some(params) = hooks.switch_params(&acquisition, received)
+                    .or(pending_params.take()) {
+                log::info!("sampler: switching parameters to {:#?}", params);
+                acquisition.set_params(params);
+                if let Err(error) = configure(&params.device) {
+                    match recover(hooks, &mut acquisition, error) {
+                        ControlFlow::Continue(()) => {
+                            // the device is in an unknown state, so reconfigure it again
+                            pending_params = Some(params);
+                            continue
+                        }
+                        ControlFlow::Break(result) => break result,
+                    }
+                }
+            }
+            // at least one buffer must be available at all times to read samples into, so until
+            // a standby buffer is available, the active buffer will not be submitted
+            match self.waveform_recv.try_recv() {
+                Ok(waveform) => wfm_standby = Some(waveform),
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => break Ok(()),
+            }
+            // pause or resume acquisition, if requested
+            if let Some(pause) = hooks.poll_pause(paused).filter(|&pause| pause != paused) {
+                log::info!("sampler: {} acquisition", if pause { "pausing" } else { "resuming" });
+                match acquisition.pause(pause) {
+                    Ok(()) => paused = pause,
+                    Err(error) => match recover(hooks, &mut acquisition, error) {
+                        ControlFlow::Continue(()) => continue,
+                        ControlFlow::Break(result) => break result,
+                    }
+                }
+            }
+            if paused {
+                continue
+            }
+            // capture in active buffer
+            let capture_length = hooks.capture_length(self.capture_length);
+            let result = acquisition.acquire(&mut wfm_active, capture_length,
+                |event| hooks.on_trigger(event));
+            let trigger_event = match result {
+                Ok(trigger_event) => trigger_event,
+                Err(error) => match recover(hooks, &mut acquisition, error) {
+                    ControlFlow::Continue(()) => continue,
+                    ControlFlow::Break(result) => break result,
+                }
+            };
+            hooks.on_acquired(&mut acquisition, &mut wfm_active, trigger_event);
+            // if there is a capture, try to submit it for processing
+            if wfm_active.is_captured() {
+                if let Some(next_waveform) = wfm_standby.take() {
+                    if acquisition.params().mode.is_single() {
+                        // if only a single capture was requested, stop capturing
+                        acquisition.stop();
+                    }
+                    hooks.process_display(&mut wfm_active);
+                    if self.waveform_send.send(wfm_active).is_err() {
+                        break Ok(())
+                    }
+                    log::debug!("sampler: submitted waveform");
+                    wfm_active = next_waveform;
+                } else {
+                    wfm_active.clear();
+                    log::debug!("sampler: discarded waveform");
+                }
+            }
+        };
+        log::debug!("sampler: done");
+        result
+    }
+}
+
+fn recover<R: SampleSource>(hooks: &mut impl SamplerHooks, acquisition: &mut Acquisition<R>,
+                            error: Error) -> ControlFlow<Result<()>> {
+    if let Error::Overflow { lost_pages } = error {
+        // the stream has restarted acquisition already
+        log::warn!("sampler: data mover failure, {} pages lost", lost_pages);
+        // the samples read from now on are discontinuous with the earlier ones
+        acquisition.restart();
+    }
+    hooks.recover(acquisition, error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ChannelConfiguration;
+
+    // a square wave with a period of 100 samples, starting low, on every channel; each lane
+    // starts `lag` samples later than the previous one
+    struct SquareWave {
+        position: usize,
+        channels: usize,
+        lag: usize,
+    }
+
+    impl Read for SquareWave {
+        fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+            for byte in data.iter_mut() {
+                let lane = self.position % self.channels;
+                let sample = (self.position / self.channels).saturating_sub(lane * self.lag);
+                *byte = if sample % 100 >= 50 { 50 } else { -50i8 as u8 };
+                self.position += 1;
+            }
+            Ok(data.len())
+        }
+    }
+
+    impl SampleSource for SquareWave {
+        fn reconfigure(&mut self, params: &DeviceParameters) {
+            self.channels = params.stream_channels();
+        }
+    }
+
+    // a source that returns few samples at a time, and none every other time it is read
+    struct Trickle {
+        inner: SquareWave,
+        starved: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+            self.starved = !self.starved;
+            if self.starved {
+                return Ok(0)
+            }
+            let length = data.len().min(64);
+            self.inner.read(&mut data[..length])
+        }
+    }
+
+    impl SampleSource for Trickle {}
+
+    fn params(enabled: [bool; 4], mode: OperationMode) -> Parameters {
+        let device = DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: enabled.map(|enabled| enabled.then(ChannelConfiguration::default)),
+            ..Default::default()
+        });
+        Parameters { device, mode }
+    }
+
+    fn acquisition(params: Parameters) -> Acquisition<SquareWave> {
+        let mut acquisition = Acquisition::new(SquareWave { position: 0, channels: 1, lag: 0 });
+        acquisition.set_params(params);
+        acquisition
+    }
+
+    #[test]
+    fn test_free_running() {
+        let mut acquisition = acquisition(params([true, false, false, false],
+            OperationMode::FreeRunning));
+        let mut waveform = Waveform::new(1000, true).unwrap();
+        assert_eq!(acquisition.acquire(&mut waveform, 100, |_| ()).unwrap(), None);
+        assert!(waveform.is_captured());
+        assert_eq!(waveform.capture_position(), 0);
+        assert_eq!(waveform.capture_data().unwrap()[..3], [-50, -50, -50]);
+        assert!(waveform.trigger().is_none() && waveform.start().is_some());
+        assert_eq!(acquisition.position(), waveform.buffer.len() as u64);
+    }
+
+    #[test]
+    fn test_trigger() {
+        let rising = TriggerParameters::new(0, 0.0, EdgeFilter::Rising);
+        let mut acquisition = acquisition(params([true, false, false, false],
+            OperationMode::RepeatTrigger(rising)));
+        let mut waveform = Waveform::new(1000, false).unwrap();
+        let mut notified = Vec::new();
+        let event = acquisition.acquire(&mut waveform, 100, |event| notified.push(*event))
+            .unwrap().unwrap();
+        assert_eq!(notified, [event]);
+        assert_eq!((event.sample, event.cause), (50, TriggerCause::Edge(Edge::Rising)));
+        assert_eq!(waveform.capture_position(), 50);
+        assert_eq!(waveform.trigger().unwrap().sample, 50);
+        assert_eq!(waveform.capture_data().unwrap()[..3], [50, 50, 50]);
+        let event = acquisition.acquire(&mut waveform, 100, |_| ()).unwrap().unwrap();
+        assert!(event.sample > 50 && event.sample % 100 == 50);
+
+        acquisition.stop();
+        assert_eq!(acquisition.acquire(&mut waveform, 100, |_| ()).unwrap(), None);
+        assert!(!waveform.is_captured());
+    }
+
+    #[test]
+    fn test_interleaved() {
+        // the trigger fires on the first frame where CH2 is high
+        let rising = TriggerParameters::new(1, 0.0, EdgeFilter::Rising);
+        let mut acquisition = acquisition(params([true, true, false, false],
+            OperationMode::SingleTrigger(rising)));
+        let mut waveform = Waveform::new(1000, false).unwrap();
+        let event = acquisition.acquire(&mut waveform, 100, |_| ()).unwrap().unwrap();
+        assert_eq!((event.sample, event.channel), (100, 1));
+        waveform.process_display(|channels, samples, display| {
+            assert_eq!(channels, 2);
+            display.extend_from_slice(&samples[..4]);
+        });
+        assert_eq!(waveform.display_data(), Some(&[50, 50, 50, 50][..]));
+        assert_eq!(waveform.capture().unwrap().channel(1).unwrap()[0], 50);
+    }
+
+    #[test]
+    fn test_short_reads() {
+        let rising = TriggerParameters::new(0, 0.0, EdgeFilter::Rising);
+        for mode in [OperationMode::FreeRunning, OperationMode::RepeatTrigger(rising)] {
+            let inner = SquareWave { position: 0, channels: 1, lag: 0 };
+            let mut acquisition = Acquisition::new(Trickle { inner, starved: true });
+            acquisition.set_params(params([true, false, false, false], mode));
+            let mut waveform = Waveform::new(1000, true).unwrap();
+            while !waveform.is_captured() {
+                acquisition.acquire(&mut waveform, 100, |_| ()).unwrap();
+            }
+            let data = waveform.capture_data().unwrap();
+            assert_eq!(data.len(), 100);
+            // the capture consists of the samples read from the source, and nothing else
+            let expected = (waveform.capture_position()..).take(100)
+                .map(|sample| if sample % 100 >= 50 { 50 } else { -50 })
+                .collect::<Vec<i8>>();
+            assert_eq!(data, expected);
+            assert!(acquisition.position() >= waveform.capture_position() + 100);
+        }
+    }
+
+    #[test]
+    fn test_window_interleaved() {
+        // the high level of the square wave is within the window
+        let window = |channel| WindowParameters::new(channel, 0.0, 100.0, WindowFilter::Enter);
+        for (enabled, channel, sample) in [
+            ([true, true, false, false], 1, 150),
+            ([true, true, true, true], 2, 400),
+        ] {
+            let source = SquareWave { position: 0, channels: 1, lag: 25 };
+            let mut acquisition = Acquisition::new(source);
+            acquisition.set_params(params(enabled, OperationMode::SingleWindow(window(channel))));
+            let mut waveform = Waveform::new(1000, false).unwrap();
+            let event = acquisition.acquire(&mut waveform, 100, |_| ()).unwrap().unwrap();
+            assert_eq!((event.sample, event.channel), (sample, channel));
+        }
+        // a window on a disabled channel cannot be armed
+        let mut acquisition = acquisition(params([true, true, false, false],
+            OperationMode::SingleWindow(window(2))));
+        let mut waveform = Waveform::new(1000, false).unwrap();
+        assert_eq!(acquisition.acquire(&mut waveform, 100, |_| ()).unwrap(), None);
+        assert!(!waveform.is_captured());
+    }
+
+    #[test]
+    fn test_auto_timeout() {
+        // the level is never crossed, so the capture is only taken once the timeout expires
+        let trigger = TriggerParameters::new(0, 100.0, EdgeFilter::Rising);
+        let mut acquisition = acquisition(params([true, false, false, false],
+            OperationMode::Auto(trigger, Duration::from_millis(10))));
+        let mut waveform = Waveform::new(1000, false).unwrap();
+        assert_eq!(acquisition.acquire(&mut waveform, 100, |_| ()).unwrap(), None);
+        assert!(!waveform.is_captured());
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(acquisition.acquire(&mut waveform, 100, |_| ()).unwrap(), None);
+        assert!(waveform.is_captured());
+    }
+
+    #[test]
+    fn test_sampler() {
+        let (params_send, params_recv) = std::sync::mpsc::channel();
+        let (to_sampler, waveform_recv) = std::sync::mpsc::channel();
+        let (waveform_send, from_sampler) = std::sync::mpsc::channel();
+        let rising = TriggerParameters::new(0, 0.0, EdgeFilter::Rising);
+        params_send.send(params([true, false, false, false],
+            OperationMode::SingleTrigger(rising))).unwrap();
+        for _ in 0..2 {
+            to_sampler.send(Waveform::new(1000, false).unwrap()).unwrap();
+        }
+        let mut sampler = Sampler::new(params_recv, waveform_recv, waveform_send, 100);
+        let thread = std::thread::spawn(move || {
+            sampler.run_with(SquareWave { position: 0, channels: 1, lag: 0 }, |_params| Ok(()))
+        });
+        let waveform = from_sampler.recv().unwrap();
+        assert_eq!(waveform.capture_position(), 50);
+        assert_eq!(waveform.display_data().unwrap().len(), 100);
+        // only a single capture is taken
+        assert!(from_sampler.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(to_sampler);
+        thread.join().unwrap().unwrap();
+    }
+
+    #[derive(Default)]
+    struct CountingHooks {
+        triggers: usize,
+        captures: usize,
+    }
+
+    impl SamplerHooks for CountingHooks {
+        fn capture_length(&self, capture_length: usize) -> usize {
+            capture_length * 2
+        }
+
+        fn on_trigger(&mut self, _event: &TriggerEvent) {
+            self.triggers += 1;
+        }
+
+        fn on_acquired<R: SampleSource>(&mut self, acquisition: &mut Acquisition<R>,
+                                        waveform: &mut Waveform,
+                                        _trigger_event: Option<TriggerEvent>) {
+            if waveform.is_captured() {
+                self.captures += 1;
+                if self.captures == 3 {
+                    acquisition.stop();
+                }
+            }
+        }
+
+        fn process_display(&mut self, waveform: &mut Waveform) {
+            waveform.process_display(|_channels, samples, display|
+                display.extend(samples.iter().step_by(2)));
+        }
+    }
+
+    #[test]
+    fn test_sampler_hooks() {
+        let (params_send, params_recv) = std::sync::mpsc::channel();
+        let (to_sampler, waveform_recv) = std::sync::mpsc::channel();
+        let (waveform_send, from_sampler) = std::sync::mpsc::channel();
+        let rising = TriggerParameters::new(0, 0.0, EdgeFilter::Rising);
+        params_send.send(params([true, false, false, false],
+            OperationMode::RepeatTrigger(rising))).unwrap();
+        // one waveform to read samples into, and one to submit each capture in
+        for _ in 0..4 {
+            to_sampler.send(Waveform::new(1000, false).unwrap()).unwrap();
+        }
+        let mut sampler = Sampler::new(params_recv, waveform_recv, waveform_send, 100);
+        let thread = std::thread::spawn(move || {
+            let mut hooks = CountingHooks::default();
+            let source = SquareWave { position: 0, channels: 1, lag: 0 };
+            sampler.run_with_hooks(source, |_params| Ok(()), &mut hooks).map(|()| hooks)
+        });
+        for _ in 0..3 {
+            let waveform = from_sampler.recv().unwrap();
+            assert_eq!(waveform.display_data().unwrap().len(), 100);
+        }
+        // acquisition is stopped after the third capture
+        assert!(from_sampler.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(to_sampler);
+        let hooks = thread.join().unwrap().unwrap();
+        assert_eq!((hooks.triggers, hooks.captures), (3, 3));
+    }
+}
@@ -0,0 +1,122 @@
+//! Markers injected from external event sources, e.g. the serial log of the device under test or
+//! GPIO toggles observed by another tool, for correlating them with the captured signal.
+//!
+//! Annotations are placed on the capture timeline by their host time, using the `Timestamp` of
+//! the capture; their placement is only as precise as the host clock.
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::timestamp::Timestamp;
+use crate::export::Marker;
+
+/// Amount of annotations retained; older annotations are discarded.
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub time: SystemTime,
+    pub label: String,
+}
+
+/// A handle to a feed of annotations.
+///
+/// Cloning the handle does not clone the feed; all clones refer to the same feed, which makes it
+/// possible to inject annotations from a thread other than the one displaying or exporting them.
+#[derive(Debug, Clone, Default)]
+pub struct AnnotationFeed(Arc<Mutex<VecDeque<Annotation>>>);
+
+impl AnnotationFeed {
+    pub fn new() -> AnnotationFeed {
+        Default::default()
+    }
+
+    /// Inject an annotation for an event that happened at host time `time`.
+    pub fn inject(&self, time: SystemTime, label: impl Into<String>) {
+        let label = label.into();
+        log::trace!("annotation: {}", label);
+        let mut annotations = self.0.lock().unwrap();
+        if annotations.len() == CAPACITY {
+            annotations.pop_front();
+        }
+        // events from different sources may arrive out of order
+        let index = annotations.partition_point(|annotation| annotation.time <= time);
+        annotations.insert(index, Annotation { time, label });
+    }
+
+    /// Inject an annotation for an event that has just happened.
+    pub fn inject_now(&self, label: impl Into<String>) {
+        self.inject(SystemTime::now(), label)
+    }
+
+    /// Inject every non-empty line read from `reader` (e.g. a serial port or a named pipe) as
+    /// an annotation, timestamped when it is received, until the end of input.
+    pub fn follow(&self, reader: impl BufRead) -> std::io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim_end();
+            if !line.is_empty() {
+                self.inject_now(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the retained annotations, oldest first.
+    pub fn annotations(&self) -> Vec<Annotation> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Returns the markers for the annotations that fall within a capture of `length` samples
+    /// per channel, whose first sample was acquired at `start`, from a stream of `channels`
+    /// interleaved channels.
+    pub fn markers(&self, start: &Timestamp, channels: usize, length: usize) -> Vec<Marker> {
+        let end = start.sample_to_time(start.sample + (length * channels) as u64);
+        self.0.lock().unwrap().iter()
+            .filter(|annotation| annotation.time >= start.time && annotation.time < end)
+            .map(|annotation| Marker {
+                position: ((start.time_to_sample(annotation.time) - start.sample) as usize /
+                    channels).min(length - 1),
+                label: annotation.label.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_markers() {
+        let start = Timestamp {
+            sample: 1_000,
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(100),
+            sample_rate: 1_000_000_000,
+        };
+        let feed = AnnotationFeed::new();
+        feed.inject(start.time + Duration::from_nanos(500), "second");
+        feed.inject(start.time + Duration::from_nanos(100), "first");
+        feed.inject(start.time - Duration::from_nanos(1), "before");
+        feed.inject(start.time + Duration::from_nanos(1_000), "after");
+        assert_eq!(feed.annotations()[1].label, "first");
+        // 2 channels, so a sample of each channel is acquired every 2 ns
+        assert_eq!(feed.markers(&start, 2, 500), [
+            Marker { position: 50, label: "first".to_owned() },
+            Marker { position: 250, label: "second".to_owned() },
+        ]);
+    }
+
+    #[test]
+    fn test_follow() {
+        let feed = AnnotationFeed::new();
+        feed.follow(&b"boot\r\n\nready\n"[..]).unwrap();
+        let labels = feed.annotations().into_iter()
+            .map(|annotation| annotation.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["boot", "ready"]);
+    }
+}
@@ -0,0 +1,66 @@
+//! Asynchronous streaming of samples, for use with the Tokio runtime.
+//!
+//! Waiting for the data mover to signal that new data is available would block the executor, so
+//! the device is polled on a timer whenever a read finds no new data instead. This avoids
+//! dedicating a thread per device.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::device::DataStream;
+use crate::timestamp::Timestamp;
+
+/// How long to wait before polling the device again if no data was available. At 1 GS/s, this
+/// is 1 MB of samples, a small fraction of the device memory.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// An asynchronous variant of `DataStream`, returned by `Device::stream_data_async()`.
+#[derive(Debug)]
+pub struct AsyncStreamer {
+    inner: DataStream,
+    delay: Pin<Box<Sleep>>,
+}
+
+impl AsyncStreamer {
+    pub(crate) fn new(inner: DataStream) -> Self {
+        Self { inner, delay: Box::pin(tokio::time::sleep(Duration::ZERO)) }
+    }
+
+    /// Returns the amount of samples read so far.
+    pub fn position(&self) -> u64 {
+        self.inner.position()
+    }
+
+    /// Returns the timestamp of the most recently acquired sample.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.inner.timestamp()
+    }
+
+    /// Restart acquisition; see `DataStream::restart()`.
+    pub fn restart(&mut self) -> crate::Result<()> {
+        self.inner.restart()
+    }
+}
+
+impl AsyncRead for AsyncStreamer {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>)
+            -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            // reading the data mover status and memory completes quickly, so this is done
+            // right in the executor
+            let length = this.inner.read_available(buf.initialize_unfilled())?;
+            if length > 0 || buf.remaining() == 0 {
+                buf.advance(length);
+                return Poll::Ready(Ok(()))
+            }
+            ready!(this.delay.as_mut().poll(cx));
+            this.delay.as_mut().reset(Instant::now() + POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Implements a trigger on the energy in a frequency band, computed with short FFTs.
+//!
+//! This is much slower than the edge trigger and cannot keep up with the full sample rate, but
+//! it catches events that have no well-defined edge, such as bursts of interference or
+//! an oscillation building up.
+
+use std::ops::RangeInclusive;
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+
+#[derive(Debug, Clone)]
+pub struct BandTrigger {
+    channels: usize,
+    lane: usize,
+    // index of the next sample in its frame
+    phase: usize,
+    bins: RangeInclusive<usize>,
+    // in units of FFT energy
+    threshold: f32,
+    full_scale: f32,
+    window: Vec<f32>,
+    block: Vec<f32>,
+}
+
+impl BandTrigger {
+    /// Create a new trigger on the energy in `band` (in Hz) of faceplate channel `channel_index`
+    /// in a sample stream acquired with `params`, computed over blocks of `size` samples.
+    /// The trigger fires when the energy exceeds `threshold`, in dBFS (the energy of a full
+    /// scale sine wave is 0 dBFS).
+    ///
+    /// Returns `None` if the channel is disabled. Panics if `size` is not a power of two.
+    pub fn new(params: &DeviceParameters, channel_index: usize, size: usize,
+               band: RangeInclusive<f32>, threshold: f32) -> Option<BandTrigger> {
+        assert!(size.is_power_of_two(), "FFT size must be a power of two");
+        let channel_map = ChannelMap::from_params(params);
+        let lane = channel_map.lane(channel_index)?;
+        let bin_width = params.sample_rate() / size as f32;
+        let first_bin = ((band.start() / bin_width).round() as usize).max(1);
+        let last_bin = ((band.end() / bin_width).round() as usize).min(size / 2);
+        // Hann window
+        let window = (0..size)
+            .map(|index| 0.5 - 0.5 * (std::f32::consts::TAU * index as f32 / size as f32).cos())
+            .collect::<Vec<_>>();
+        // sum of |X[k]|^2 over the positive frequencies for a full scale sine wave; with
+        // the Hann window, this is `A^2 * N * sum(w^2) / 4`, where `sum(w^2)` is `3 * N / 8`
+        let full_scale = (i8::MAX as f32).powi(2) * 3.0 * (size as f32).powi(2) / 32.0;
+        Some(BandTrigger {
+            channels: channel_map.stream_channels(),
+            lane,
+            phase: 0,
+            bins: first_bin..=last_bin,
+            threshold: full_scale * 10.0f32.powf(threshold / 10.0),
+            full_scale,
+            window,
+            block: Vec::with_capacity(size),
+        })
+    }
+
+    /// Reset the trigger, discarding the samples of the block in progress.
+    ///
+    /// After this method is called, the next sample must start a frame.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.block.clear();
+    }
+
+    /// Scan incoming data (interleaved as in the sample stream) for a block where the energy in
+    /// the band exceeds the threshold.
+    ///
+    /// Returns the amount of consumed samples, and the energy of the block in dBFS if the trigger
+    /// has fired. If it has, the last consumed sample is the one that completed the block.
+    pub fn find(&mut self, samples: &[i8]) -> (usize, Option<f32>) {
+        for (index, &sample) in samples.iter().enumerate() {
+            let lane = self.phase;
+            self.phase = (self.phase + 1) % self.channels;
+            if lane != self.lane { continue }
+            self.block.push(sample as f32);
+            if self.block.len() == self.window.len() {
+                let energy = self.band_energy();
+                self.block.clear();
+                if energy > self.threshold {
+                    return (index + 1, Some(10.0 * (energy / self.full_scale).log10()))
+                }
+            }
+        }
+        (samples.len(), None)
+    }
+
+    fn band_energy(&self) -> f32 {
+        let mut re = self.block.iter().zip(self.window.iter())
+            .map(|(sample, weight)| sample * weight)
+            .collect::<Vec<_>>();
+        let mut im = vec![0.0; re.len()];
+        fft(&mut re, &mut im);
+        self.bins.clone().map(|bin| re[bin] * re[bin] + im[bin] * im[bin]).sum()
+    }
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let length = re.len();
+    let bits = length.trailing_zeros();
+    if bits == 0 { return }
+    for index in 0..length {
+        let reversed = index.reverse_bits() >> (usize::BITS - bits);
+        if reversed > index {
+            re.swap(index, reversed);
+            im.swap(index, reversed);
+        }
+    }
+    let mut span = 1;
+    while span < length {
+        let step = -std::f32::consts::PI / span as f32;
+        for start in (0..length).step_by(span * 2) {
+            for offset in 0..span {
+                let (sin, cos) = (step * offset as f32).sin_cos();
+                let (even, odd) = (start + offset, start + offset + span);
+                let odd_re = re[odd] * cos - im[odd] * sin;
+                let odd_im = re[odd] * sin + im[odd] * cos;
+                re[odd] = re[even] - odd_re;
+                im[odd] = im[even] - odd_im;
+                re[even] += odd_re;
+                im[even] += odd_im;
+            }
+        }
+        span *= 2;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn two_channels() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None,
+                       Some(ChannelConfiguration::default()), None],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_fft() {
+        let mut re = [1.0, 0.0, -1.0, 0.0, 1.0, 0.0, -1.0, 0.0];
+        let mut im = [0.0; 8];
+        fft(&mut re, &mut im);
+        let magnitudes = re.iter().zip(im.iter())
+            .map(|(re, im)| re.hypot(*im).round())
+            .collect::<Vec<_>>();
+        assert_eq!(magnitudes, [0.0, 0.0, 4.0, 0.0, 0.0, 0.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_burst() {
+        // 500 MS/s per channel, so 256 samples per block are 512 ns; the band is 50 MHz +/- 10 MHz
+        let params = two_channels();
+        let mut trigger = BandTrigger::new(&params, 2, 256, 40e6..=60e6, -20.0).unwrap();
+        assert!(BandTrigger::new(&params, 1, 256, 40e6..=60e6, -20.0).is_none());
+        // CH1 carries a 50 MHz tone throughout, and CH3 is quiet except for a burst
+        let samples = (0..4096).flat_map(|index| {
+            let tone = (100.0 * (std::f32::consts::TAU * index as f32 * 0.1).sin()) as i8;
+            [tone, if (2000..2400).contains(&index) { tone } else { 0 }]
+        }).collect::<Vec<_>>();
+        let (consumed, energy) = trigger.find(&samples);
+        // the burst is first fully within the block that ends at sample 2303
+        assert_eq!(consumed, 2304 * 2);
+        assert!(energy.unwrap() > -3.0 && energy.unwrap() < 0.0, "{:?}", energy);
+        // the tail of the burst is at the start of the next block, where the window attenuates it
+        let (tail, energy) = trigger.find(&samples[consumed..]);
+        assert_eq!(tail, 256 * 2);
+        assert!(energy.unwrap() < -6.0, "{:?}", energy);
+        let rest = &samples[consumed + tail..];
+        assert_eq!(trigger.find(rest), (rest.len(), None));
+    }
+}
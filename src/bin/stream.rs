@@ -0,0 +1,235 @@
+use std::io::{ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use thunderscope::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration, DeviceParameters,
+                   EventCounter, FileWatcher, PeakDetector, SampleRate};
+
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Hysteresis of the threshold (at 0 V) used with `--count`, in ADC codes.
+const COUNT_HYSTERESIS: u8 = 2;
+
+/// How often the configuration file is checked for changes with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Contents of the file used with `--config`. Only the streamed channel is enabled.
+#[cfg(feature = "config")]
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StreamConfiguration {
+    channel: ChannelConfiguration,
+    sample_rate: SampleRate,
+    calibration: DeviceCalibration,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    /// Signed 8-bit ADC codes, as captured.
+    Raw,
+    /// 32-bit little-endian floats, in volts.
+    F32,
+    /// Signed 8-bit ADC codes, in chunks prefixed with a 32-bit little-endian length. An empty
+    /// chunk marks a gap, where samples were lost.
+    Framed,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: thunderscope-stream [--format raw|f32|framed] [--channel 1|2|3|4] \
+               [--rate 1000|500|250|125] [--count <gate time in ms> | --peak-detect FACTOR] \
+               [--config STREAM.toml [--watch]]");
+    std::process::exit(2)
+}
+
+/// Returns the parameters from the configuration file if it has changed since the last call.
+#[cfg(feature = "config")]
+fn load_config(watcher: &mut FileWatcher, channel_index: usize)
+        -> Result<Option<DeviceParameters>, String> {
+    let Some(text) = watcher.changed().map_err(|error| error.to_string())? else {
+        return Ok(None)
+    };
+    let stream_config: StreamConfiguration =
+        toml::from_str(&text).map_err(|error| error.to_string())?;
+    let mut config = DeviceConfiguration {
+        channels: [None; 4], sample_rate: stream_config.sample_rate, ..Default::default() };
+    config.channels[channel_index] = Some(stream_config.channel);
+    Ok(Some(DeviceParameters::derive(&stream_config.calibration, &config)))
+}
+
+#[cfg(not(feature = "config"))]
+fn load_config(_watcher: &mut FileWatcher, _channel_index: usize)
+        -> Result<Option<DeviceParameters>, String> {
+    Err("built without the `config` feature".to_owned())
+}
+
+fn write_samples(output: &mut impl Write, format: Format, volts_table: &[f32; 256],
+        samples: &[i8]) -> std::io::Result<()> {
+    match format {
+        Format::Raw =>
+            output.write_all(bytemuck::cast_slice(samples)),
+        Format::F32 => {
+            let volts = samples.iter()
+                .flat_map(|&code| volts_table[code as u8 as usize].to_le_bytes())
+                .collect::<Vec<u8>>();
+            output.write_all(&volts)
+        }
+        Format::Framed => {
+            output.write_all(&(samples.len() as u32).to_le_bytes())?;
+            output.write_all(bytemuck::cast_slice(samples))
+        }
+    }
+}
+
+fn main() -> thunderscope::Result<()> {
+    env_logger::init();
+    let mut format = Format::Raw;
+    let mut channel_index = 0;
+    let mut sample_rate = SampleRate::default();
+    let mut gate_time = None;
+    let mut peak_factor = None;
+    let mut config_path = None;
+    let mut watch = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--watch" {
+            watch = true;
+            continue
+        }
+        match (arg.as_str(), args.next().as_deref()) {
+            ("--format", Some("raw"))    => format = Format::Raw,
+            ("--format", Some("f32"))    => format = Format::F32,
+            ("--format", Some("framed")) => format = Format::Framed,
+            ("--channel", Some(number))  => match number.parse::<usize>() {
+                Ok(number @ 1..=4) => channel_index = number - 1,
+                _ => usage()
+            }
+            ("--rate", Some("1000"))     => sample_rate = SampleRate::MSps1000,
+            ("--rate", Some("500"))      => sample_rate = SampleRate::MSps500,
+            ("--rate", Some("250"))      => sample_rate = SampleRate::MSps250,
+            ("--rate", Some("125"))      => sample_rate = SampleRate::MSps125,
+            ("--count", Some(milliseconds)) => match milliseconds.parse::<f32>() {
+                Ok(milliseconds) if milliseconds > 0.0 => gate_time = Some(milliseconds / 1e3),
+                _ => usage()
+            }
+            ("--peak-detect", Some(factor)) => match factor.parse::<usize>() {
+                Ok(factor @ 1..) => peak_factor = Some(factor),
+                _ => usage()
+            }
+            ("--config", Some(path))     => config_path = Some(path.to_owned()),
+            _ => usage()
+        }
+    }
+    if watch && config_path.is_none() || gate_time.is_some() && peak_factor.is_some() {
+        usage()
+    }
+    let mut watcher = config_path.map(FileWatcher::new);
+    let mut params = match watcher.as_mut() {
+        Some(watcher) => match load_config(watcher, channel_index) {
+            Ok(Some(params)) => params,
+            Ok(None) => {
+                eprintln!("cannot load configuration {}: not found", watcher.path().display());
+                std::process::exit(2)
+            }
+            Err(error) => {
+                eprintln!("cannot load configuration {}: {}", watcher.path().display(), error);
+                std::process::exit(2)
+            }
+        }
+        None => {
+            let mut config = DeviceConfiguration {
+                channels: [None; 4], sample_rate, ..Default::default() };
+            config.channels[channel_index] = Some(ChannelConfiguration::default());
+            DeviceParameters::derive(&DeviceCalibration::default(), &config)
+        }
+    };
+    thunderscope::Device::with(|device| {
+        device.configure(&params)?;
+        let mut stream = device.stream_data();
+        // instead of the samples, print the statistics of every gate time as a line of text
+        let new_counter = |params: &DeviceParameters| gate_time.map(|gate_time|
+            EventCounter::new(params, channel_index, 0, COUNT_HYSTERESIS, gate_time).unwrap());
+        let mut counter = new_counter(&params);
+        let mut volts_table = params.volts_table(channel_index);
+        let mut readings = Vec::new();
+        // instead of every sample, output the minimum and the maximum of every group of samples
+        let mut peak_detector = peak_factor.map(|factor| PeakDetector::new(factor, 1));
+        let mut peaks = Vec::new();
+        let mut output = std::io::stdout().lock();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut last_checked = Instant::now();
+        loop {
+            if let Some(watcher) = watcher.as_mut().filter(|_| watch) {
+                if last_checked.elapsed() >= WATCH_INTERVAL {
+                    last_checked = Instant::now();
+                    // only the changed parts of the configuration are written to the device,
+                    // so the stream continues with at most a brief glitch
+                    match load_config(watcher, channel_index) {
+                        Ok(Some(new_params)) => {
+                            device.configure(&new_params)?;
+                            params = new_params;
+                            counter = new_counter(&params);
+                            volts_table = params.volts_table(channel_index);
+                            log::info!("applied configuration {}", watcher.path().display());
+                        }
+                        Ok(None) => (),
+                        Err(error) =>
+                            log::warn!("cannot load configuration {}: {}",
+                                watcher.path().display(), error),
+                    }
+                }
+            }
+            let length = match stream.read(&mut buffer[..]).map_err(thunderscope::Error::from) {
+                Ok(length) => length,
+                // acquisition has been restarted, and the samples that follow are not contiguous
+                // with those before
+                Err(thunderscope::Error::Overflow { lost_pages }) => {
+                    log::warn!("data mover failure, {} pages of samples lost", lost_pages);
+                    if let Some(counter) = counter.as_mut() {
+                        counter.reset();
+                    } else {
+                        if let Some(peak_detector) = peak_detector.as_mut() {
+                            peak_detector.reset();
+                        }
+                        if let Format::Framed = format {
+                            match output.write_all(&0u32.to_le_bytes()) {
+                                Err(error) if error.kind() == ErrorKind::BrokenPipe => break,
+                                result => result?,
+                            }
+                        }
+                    }
+                    continue
+                }
+                Err(error) => return Err(error),
+            };
+            if length == 0 {
+                // no new data yet; the data mover fills a page in a few microseconds
+                std::thread::sleep(Duration::from_micros(100));
+                continue
+            }
+            let samples = bytemuck::cast_slice(&buffer[..length]);
+            let result = match counter.as_mut() {
+                Some(counter) => {
+                    readings.clear();
+                    counter.process(samples, &mut readings);
+                    readings.iter().try_for_each(|reading| writeln!(output, "{}", reading))
+                }
+                None => match peak_detector.as_mut() {
+                    Some(peak_detector) => {
+                        peaks.clear();
+                        peak_detector.process(samples, &mut peaks);
+                        if peaks.is_empty() {
+                            continue
+                        }
+                        write_samples(&mut output, format, &volts_table, &peaks)
+                    }
+                    None => write_samples(&mut output, format, &volts_table, samples)
+                }
+            };
+            match result {
+                // the consumer has gone away; this is the normal way to stop streaming
+                Err(error) if error.kind() == ErrorKind::BrokenPipe => break,
+                result => result?,
+            }
+        }
+        Ok(())
+    })
+}
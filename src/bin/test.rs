@@ -1,20 +1,29 @@
 use std::io::Read;
+use std::ops::ControlFlow;
 
 use thunderscope::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration, DeviceParameters};
 
 const FILENAME: &str = "test.data";
+const SAMPLE_COUNT: usize = 200000;
 
 fn main() -> thunderscope::Result<()> {
     env_logger::init();
     thunderscope::Device::with(|device| {
         let config = DeviceConfiguration {
-            channels: [Some(ChannelConfiguration::default()), None, None, None]
+            channels: [Some(ChannelConfiguration::default()), None, None, None],
+            ..Default::default()
         };
         let params = DeviceParameters::derive(&DeviceCalibration::default(), &config);
         device.configure(&params)?;
-        let mut samples = vec![0; 200000];
+        let mut samples = vec![0; SAMPLE_COUNT];
         device.stream_data().read_exact(samples.as_mut())?; // let the signal path stabilize
-        device.stream_data().read_exact(samples.as_mut())?;
+        let mut samples = Vec::with_capacity(SAMPLE_COUNT);
+        device.read_data(|chunk| {
+            let length = chunk.len().min(SAMPLE_COUNT - samples.len());
+            samples.extend_from_slice(&chunk[..length]);
+            Ok(if samples.len() == SAMPLE_COUNT { ControlFlow::Break(()) }
+               else { ControlFlow::Continue(()) })
+        })?;
         println!("channel gain: {:.2} dB", params.gain(0));
         let full_scale = params.full_scale(0);
         println!("full scale: {:-.3} V to {:+.3} V", -full_scale/2.0, full_scale/2.0);
@@ -24,9 +33,9 @@ fn main() -> thunderscope::Result<()> {
             .collect::<Vec<_>>());
         println!("first {} voltages:\n  {:.2?}", count, samples.iter()
             .take(count)
-            .map(|&code| params.code_to_volts(0, code as i8))
+            .map(|&code| params.code_to_volts(0, code))
             .collect::<Vec<_>>());
-        std::fs::write(FILENAME, &samples[..]).unwrap();
+        std::fs::write(FILENAME, bytemuck::cast_slice::<i8, u8>(&samples)).unwrap();
         println!("saved {} samples, run `python3 ./doc/plot_1ch.py {}`", samples.len(), FILENAME);
         Ok(())
     })
@@ -1,7 +1,8 @@
 use core::{ops::{Add, Sub}, slice};
+use std::collections::VecDeque;
 use std::ops::{AddAssign, Index, IndexMut, Range, RangeFrom, RangeFull, RangeTo, SubAssign};
 
-use crate::Result;
+use crate::{Error, Result};
 
 #[derive(Debug)]
 pub struct RingSlice {
@@ -144,17 +145,39 @@ impl SubAssign<usize> for RingCursor {
     }
 }
 
+/// CRC-32 of the data appended to a ring buffer by one call to `RingBuffer::append()`.
+#[derive(Debug, Clone, Copy)]
+struct Checksum {
+    // in bytes appended since the buffer was created
+    start: u64,
+    length: usize,
+    crc: u32,
+}
+
 #[derive(Debug)]
 pub struct RingBuffer {
     buffer: RingSlice,
     cursor: RingCursor,
+    // bytes appended since the buffer was created
+    appended: u64,
+    // `None` if checksums are disabled; oldest first
+    checksums: Option<VecDeque<Checksum>>,
 }
 
 impl RingBuffer {
     pub fn new(min_size: usize) -> Result<RingBuffer> {
         let buffer = RingSlice::new(min_size)?;
         let cursor = RingCursor::new(buffer.len());
-        Ok(RingBuffer { buffer, cursor })
+        Ok(RingBuffer { buffer, cursor, appended: 0, checksums: None })
+    }
+
+    /// Enable or disable integrity checking. While it is enabled, a checksum of the data is
+    /// computed as it is appended, which `verify()` uses to detect data that has been corrupted
+    /// while in the buffer (e.g. by a driver bug) instead of analyzing it as signal.
+    pub fn set_checksums(&mut self, enabled: bool) {
+        if enabled != self.checksums.is_some() {
+            self.checksums = enabled.then(VecDeque::new);
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -169,15 +192,71 @@ impl RingBuffer {
             where F: FnOnce(&mut [u8]) -> core::result::Result<usize, E> {
         assert!(max_size <= self.buffer.len());
         let result = writer(&mut self.buffer[self.cursor.index..][..max_size]);
-        if let Ok(written) = result { self.cursor += written }
+        if let Ok(written) = result {
+            if let Some(checksums) = self.checksums.as_mut() {
+                if written > 0 {
+                    let crc = crc32fast::hash(&self.buffer[self.cursor.index..][..written]);
+                    checksums.push_back(Checksum { start: self.appended, length: written, crc });
+                }
+                // discard the checksums of data that has been (partly) overwritten
+                let oldest = (self.appended + written as u64)
+                    .saturating_sub(self.buffer.len() as u64);
+                while checksums.front().is_some_and(|checksum| checksum.start < oldest) {
+                    checksums.pop_front();
+                }
+            }
+            self.cursor += written;
+            self.appended += written as u64;
+        }
         result
     }
 
+    /// Check that the `count` bytes at `cursor` have not changed since they were appended.
+    /// Returns `Error::Corrupted` if any have.
+    ///
+    /// The checksums cover the data appended by each call to `append()` as a whole, so all of
+    /// the data appended by the calls that overlap the range is checked. Data whose checksum
+    /// has been discarded (because the data appended by the same call has been partly
+    /// overwritten) or which was appended while checksums were disabled is not checked.
+    pub fn verify(&self, cursor: RingCursor, count: usize) -> Result<()> {
+        assert!(cursor.bound == self.buffer.len());
+        assert!(count <= self.buffer.len());
+        let Some(checksums) = self.checksums.as_ref() else { return Ok(()) };
+        // the range always ends at or before the most recently appended data
+        let mut behind = (self.cursor - cursor.index).index;
+        if behind < count {
+            behind += self.buffer.len();
+        }
+        let start = self.appended.saturating_sub(behind as u64);
+        let end = start + count as u64;
+        for checksum in checksums {
+            if checksum.start >= end || checksum.start + checksum.length as u64 <= start {
+                continue
+            }
+            let index = (checksum.start % self.buffer.len() as u64) as usize;
+            let crc = crc32fast::hash(&self.buffer[index..][..checksum.length]);
+            if crc != checksum.crc {
+                log::error!("ring buffer data at {}+{} corrupted: CRC {:#010x} instead of {:#010x}",
+                    checksum.start, checksum.length, crc, checksum.crc);
+                return Err(Error::Corrupted)
+            }
+        }
+        Ok(())
+    }
+
     pub fn read(&self, cursor: RingCursor, count: usize) -> &[i8] {
         assert!(cursor.bound == self.buffer.len());
         assert!(count <= self.buffer.len());
         bytemuck::cast_slice(&self.buffer[cursor.index..][..count])
     }
+
+    /// Like `read`, but for 16-bit samples (see `Resolution`); `count` is in samples, and
+    /// `cursor` must be aligned to a sample.
+    pub fn read_i16(&self, cursor: RingCursor, count: usize) -> &[i16] {
+        assert!(cursor.bound == self.buffer.len());
+        assert!(cursor.index.is_multiple_of(2) && count * 2 <= self.buffer.len());
+        bytemuck::cast_slice(&self.buffer[cursor.index..][..count * 2])
+    }
 }
 
 #[cfg(test)]
@@ -206,6 +285,35 @@ mod test {
         assert_eq!(&buf[8186..6], &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
     }
 
+    #[test]
+    fn test_ring_buffer_verify() {
+        let mut buf = RingBuffer::new(8192).unwrap();
+        buf.set_checksums(true);
+        for byte in 1..=3 {
+            buf.append(4096, |slice| { slice[..4096].fill(byte); Ok::<_, ()>(4096) }).unwrap();
+        }
+        // the first chunk has been overwritten by the third one
+        let cursor = buf.cursor();
+        buf.verify(cursor - 8192, 8192).unwrap();
+        buf.verify(cursor - 100, 10).unwrap();
+        buf.buffer[5000..5001][0] = 0; // in the second chunk
+        buf.verify(cursor - 100, 10).unwrap();
+        assert!(matches!(buf.verify(cursor - 8192, 10), Err(Error::Corrupted)));
+    }
+
+    #[test]
+    fn test_ring_buffer_read_i16() {
+        let mut buf = RingBuffer::new(8192).unwrap();
+        buf.append(8190, |slice| Ok::<_, ()>(slice.len())).unwrap();
+        let cursor = buf.cursor();
+        buf.append(4, |slice| {
+            slice.copy_from_slice(&[0x10, 0x7f, 0xf0, 0x80]);
+            Ok::<_, ()>(4)
+        }).unwrap();
+        // the samples wrap around the end of the buffer
+        assert_eq!(buf.read_i16(cursor, 2), [0x7f10, -0x7f10]);
+    }
+
     #[test]
     fn test_ring_cursor() {
         let cursor = RingCursor::new(128);
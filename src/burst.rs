@@ -0,0 +1,229 @@
+//! Implements burst capture, which captures a number of consecutive triggered segments of
+//! the sample stream back-to-back, e.g. the packets of a protocol burst.
+//!
+//! All of the segments are captured into memory that is allocated up front, and the trigger is
+//! rearmed within the same call that completes a segment, so there is no hand-off of buffers
+//! between segments. The dead time after a segment is a single frame (during which the state of
+//! the rearmed trigger is established), plus however long the signal takes to cross the trigger
+//! level again.
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+use crate::capture::Capture;
+use crate::trigger::{Edge, EdgeFilter, Trigger};
+
+/// A segment of a burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstSegment {
+    /// Position of the first sample of the frame where the trigger has fired, in samples since
+    /// the burst was started. The segment starts with this frame.
+    pub position: u64,
+    pub edge: Edge,
+}
+
+#[derive(Debug, Clone)]
+pub struct BurstCapture {
+    params: DeviceParameters,
+    channels: usize,
+    lane: usize,
+    trigger: Trigger,
+    filter: EdgeFilter,
+    // length of each segment, in samples of all channels
+    segment_len: usize,
+    count: usize,
+    data: Vec<i8>,
+    segments: Vec<BurstSegment>,
+    // amount of samples of the segment being captured that have been captured, or `None` if
+    // the trigger is armed
+    filled: Option<usize>,
+    // position of the next sample
+    position: u64,
+}
+
+impl BurstCapture {
+    /// Create a burst capture of `count` segments of `segment_frames` frames, each starting
+    /// where `trigger` fires on `filter` on faceplate channel `channel_index` in a sample stream
+    /// acquired with `params`.
+    ///
+    /// Returns `None` if the channel is disabled.
+    pub fn new(params: &DeviceParameters, channel_index: usize, trigger: Trigger,
+               filter: EdgeFilter, segment_frames: usize, count: usize) -> Option<BurstCapture> {
+        let channel_map = ChannelMap::from_params(params);
+        let channels = channel_map.stream_channels();
+        let segment_len = segment_frames * channels;
+        Some(BurstCapture {
+            params: *params,
+            channels,
+            lane: channel_map.lane(channel_index)?,
+            trigger,
+            filter,
+            segment_len,
+            count,
+            data: vec![0; segment_len * count],
+            segments: Vec::with_capacity(count),
+            filled: None,
+            position: 0,
+        })
+    }
+
+    /// Discard the captured segments and start a new burst, reusing the memory.
+    ///
+    /// After this method is called, the next sample must start a frame.
+    pub fn reset(&mut self) {
+        self.trigger.reset();
+        self.segments.clear();
+        self.filled = None;
+        self.position = 0;
+    }
+
+    /// Returns `true` once all of the segments have been captured.
+    pub fn is_complete(&self) -> bool {
+        self.segments().len() == self.count
+    }
+
+    /// Returns the segments that have been captured so far.
+    pub fn segments(&self) -> &[BurstSegment] {
+        // the last segment is being captured if the trigger is not armed
+        &self.segments[..self.segments.len() - self.filled.is_some() as usize]
+    }
+
+    /// Returns the samples of segment `index` (interleaved as in the sample stream).
+    ///
+    /// Panics if the segment has not been captured yet.
+    pub fn segment_data(&self, index: usize) -> &[i8] {
+        assert!(index < self.segments().len(), "segment {} has not been captured", index);
+        &self.data[index * self.segment_len..][..self.segment_len]
+    }
+
+    /// Returns the samples of segment `index`, de-interleaved.
+    ///
+    /// Panics if the segment has not been captured yet.
+    pub fn capture(&self, index: usize) -> Capture {
+        Capture::new(&self.params, self.segment_data(index))
+    }
+
+    /// Process incoming data (interleaved as in the sample stream, and starting at a frame
+    /// boundary), capturing segments until the burst is complete.
+    ///
+    /// Returns the amount of consumed samples, which is always a whole amount of frames. Any
+    /// samples that are not consumed must be passed again with the next chunk of data.
+    pub fn process(&mut self, samples: &[i8]) -> usize {
+        let mut consumed = 0;
+        while !self.is_complete() {
+            let available = &samples[consumed..];
+            match self.filled {
+                None => {
+                    let (processed, edge) = self.trigger.find_interleaved(available,
+                        self.channels, self.lane, self.filter);
+                    consumed += processed;
+                    self.position += processed as u64;
+                    let Some(edge) = edge else { break };
+                    self.segments.push(BurstSegment { position: self.position, edge });
+                    self.filled = Some(0);
+                }
+                Some(filled) => {
+                    let index = self.segments.len() - 1;
+                    let length = (self.segment_len - filled)
+                        .min(available.len() / self.channels * self.channels);
+                    if length == 0 {
+                        break
+                    }
+                    self.data[index * self.segment_len + filled..][..length]
+                        .copy_from_slice(&available[..length]);
+                    consumed += length;
+                    self.position += length as u64;
+                    if filled + length == self.segment_len {
+                        // rearm right away; the edge that fired the trigger is not detected
+                        // again, since the state of the trigger is established anew
+                        self.trigger.reset();
+                        self.filled = None;
+                    } else {
+                        self.filled = Some(filled + length);
+                    }
+                }
+            }
+        }
+        consumed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn channels(enabled: [bool; 4]) -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: enabled.map(|enabled| enabled.then(ChannelConfiguration::default)),
+            ..Default::default()
+        })
+    }
+
+    // pulses of 8 samples every 24 samples, counting up from 50 while high
+    fn pulses(length: usize) -> Vec<i8> {
+        (0..length).map(|index| {
+            if index % 24 >= 16 { 50 + (index % 24 - 16) as i8 } else { -50 }
+        }).collect()
+    }
+
+    fn run(burst: &mut BurstCapture, samples: &[i8], chunk: usize) {
+        let mut consumed = 0;
+        let mut available = 0;
+        while !burst.is_complete() && available < samples.len() {
+            available = (available + chunk).min(samples.len());
+            consumed += burst.process(&samples[consumed..available]);
+        }
+    }
+
+    #[test]
+    fn test_burst() {
+        let params = channels([true, false, false, false]);
+        let samples = pulses(1000);
+        for chunk in [samples.len(), 1, 5, 16, 100] {
+            let mut burst = BurstCapture::new(&params, 0, Trigger::new(0, 2),
+                EdgeFilter::Rising, 6, 3).unwrap();
+            run(&mut burst, &samples, chunk);
+            assert!(burst.is_complete());
+            assert_eq!(burst.segments().iter().map(|segment| segment.position)
+                .collect::<Vec<_>>(), [16, 40, 64], "chunk {}", chunk);
+            assert_eq!(burst.segment_data(1), [50, 51, 52, 53, 54, 55]);
+            assert_eq!(burst.capture(2).channel(0), Some(&[50, 51, 52, 53, 54, 55][..]));
+        }
+    }
+
+    #[test]
+    fn test_back_to_back() {
+        // segments as long as the period of the pulses leave no dead time but the single
+        // frame that rearms the trigger
+        let params = channels([true, false, false, false]);
+        let samples = pulses(1000);
+        let mut burst = BurstCapture::new(&params, 0, Trigger::new(0, 2),
+            EdgeFilter::Rising, 23, 4).unwrap();
+        run(&mut burst, &samples, 64);
+        assert_eq!(burst.segments().iter().map(|segment| segment.position)
+            .collect::<Vec<_>>(), [16, 40, 64, 88]);
+        burst.reset();
+        assert!(burst.segments().is_empty());
+        run(&mut burst, &samples[8..], 64);
+        assert_eq!(burst.segments()[0].position, 8);
+    }
+
+    #[test]
+    fn test_interleaved() {
+        // CH2 has the pulses, and CH1 a ramp
+        let params = channels([true, true, false, false]);
+        let samples = pulses(200).iter().enumerate()
+            .flat_map(|(index, &sample)| [index as i8, sample])
+            .collect::<Vec<_>>();
+        assert!(BurstCapture::new(&params, 2, Trigger::new(0, 2),
+            EdgeFilter::Rising, 4, 2).is_none());
+        let mut burst = BurstCapture::new(&params, 1, Trigger::new(0, 2),
+            EdgeFilter::Rising, 4, 2).unwrap();
+        run(&mut burst, &samples, 7);
+        assert!(burst.is_complete());
+        assert_eq!(burst.segments()[1].position, 80);
+        let capture = burst.capture(1);
+        assert_eq!(capture.channel(0), Some(&[40, 41, 42, 43][..]));
+        assert_eq!(capture.channel(1), Some(&[50, 51, 52, 53][..]));
+    }
+}
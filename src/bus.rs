@@ -0,0 +1,194 @@
+//! Access to the SPI and I2C buses of the device, through the AXI4-Stream FIFO that feeds
+//! the SPI/I2C engine in the gateware.
+//!
+//! Each byte of a packet occupies one 32-bit FIFO word, of which the top three bytes are ignored.
+//! The first byte of a transmitted packet selects the transaction:
+//! * `0xfd - n` followed by the data: SPI transfer on bus `n`. The SPI engine is full duplex;
+//!   the bytes shifted in while the data is shifted out are returned as a received packet of
+//!   the same length.
+//! * `0xff`, the address, and the data: I2C write.
+//! * `0xfe`, the address, the amount of bytes to read, and the data: I2C write (if there is any
+//!   data) followed by a read with a repeated start. The bytes read are returned as a received
+//!   packet.
+//!
+//! Only the writes are supported by every gateware. Received packets (and the `0xfe` header) are
+//! only supported by gateware that reports `GatewareCaps::bus_readback`; with other gateware,
+//! the reads time out, so they must not be attempted.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+use crate::sys::Driver;
+use crate::regs::axi::{self, FifoIsr};
+
+/// How long a packet may take to be transmitted or received through the FIFO before the SPI/I2C
+/// engine is considered wedged. Transmission normally takes a few microseconds.
+const FIFO_TIMEOUT: Duration = Duration::from_millis(100);
+
+const HEADER_I2C_WRITE: u8 = 0xff;
+const HEADER_I2C_READ: u8 = 0xfe;
+
+fn read_user_u32(driver: &Driver, addr: usize) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    driver.read_user(addr, &mut bytes[..])?;
+    let data = u32::from_le_bytes(bytes);
+    log::trace!("read_user_u32({:#x}) = {:#x}", addr, data);
+    Ok(data)
+}
+
+fn write_user_u32(driver: &Driver, addr: usize, data: u32) -> Result<()> {
+    log::trace!("write_user_u32({:#x}, {:#x})", addr, data);
+    driver.write_user(addr, &u32::to_le_bytes(data)[..])
+}
+
+/// Wait until `flag` is set in the FIFO interrupt status register, and clear it.
+fn wait_fifo(driver: &Driver, flag: FifoIsr) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        let isr = FifoIsr::from_bits_retain(read_user_u32(driver, axi::ADDR_FIFO_ISR)?);
+        assert!(!isr.contains(FifoIsr::TPOE), "Transmit FIFO overflow! ISR = {:?}", isr);
+        if isr.contains(flag) { break } // done!
+        if started.elapsed() > FIFO_TIMEOUT {
+            log::error!("FIFO transaction timed out: ISR = {:?}", isr);
+            return Err(Error::Timeout)
+        }
+        thread::yield_now();
+    }
+    write_user_u32(driver, axi::ADDR_FIFO_ISR, flag.bits())
+}
+
+fn write_fifo(driver: &Driver, data: &[u8]) -> Result<()> {
+    log::trace!("write_fifo({:02x?})", data);
+    // enqueue data into the FIFO
+    for &byte in data {
+        write_user_u32(driver, axi::ADDR_FIFO_TDFD, byte as u32)?;
+    }
+    // start transmission; the FIFO is configured for 32-bit datapath length
+    write_user_u32(driver, axi::ADDR_FIFO_TLR, data.len() as u32 * 4)?;
+    // clear transmit complete flag
+    write_user_u32(driver, axi::ADDR_FIFO_ISR, FifoIsr::TC.bits())?;
+    // wait for the packet to be transmitted
+    wait_fifo(driver, FifoIsr::TC)
+}
+
+fn read_fifo(driver: &Driver) -> Result<Vec<u8>> {
+    wait_fifo(driver, FifoIsr::RC)?;
+    let length = read_user_u32(driver, axi::ADDR_FIFO_RLR)? as usize / 4;
+    let mut data = Vec::with_capacity(length);
+    for _ in 0..length {
+        data.push(read_user_u32(driver, axi::ADDR_FIFO_RDFD)? as u8);
+    }
+    let isr = FifoIsr::from_bits_retain(read_user_u32(driver, axi::ADDR_FIFO_ISR)?);
+    if isr.intersects(FifoIsr::RPUE | FifoIsr::RPORE | FifoIsr::RPURE) {
+        log::error!("FIFO receive error: ISR = {:?}", isr);
+        reset_receive(driver)?;
+        return Err(Error::Other(format!("FIFO receive error: ISR = {:?}", isr).into()))
+    }
+    log::trace!("read_fifo() = {:02x?}", data);
+    Ok(data)
+}
+
+/// Discard any received packets that have not been read, e.g. the ones received while only
+/// writing to an SPI bus.
+fn reset_receive(driver: &Driver) -> Result<()> {
+    write_user_u32(driver, axi::ADDR_FIFO_RDFR, axi::FIFO_RESET_KEY)?;
+    write_user_u32(driver, axi::ADDR_FIFO_ISR,
+        (FifoIsr::RC | FifoIsr::RRC | FifoIsr::RPUE | FifoIsr::RPORE | FifoIsr::RPURE).bits())
+}
+
+fn spi_packet(spi_bus: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::<u8>::new();
+    packet.push(0xfd - spi_bus);
+    packet.extend_from_slice(data);
+    packet
+}
+
+// bus 0 (0xfd): ADC
+// bus 2..5 (0xfb..0xf7): PGAn
+pub fn spi_write(driver: &Driver, spi_bus: u8, data: &[u8]) -> Result<()> {
+    log::debug!("spi_write({:?}, {:02x?})", spi_bus, data);
+    write_fifo(driver, &spi_packet(spi_bus, data))?;
+    // the SPI engine doesn't use TLAST either, but it runs at 16 MHz. the delay is enough
+    // for 160 bytes.
+    thread::sleep(Duration::from_micros(10));
+    Ok(())
+}
+
+/// Shift `data` out on `spi_bus`, and return the bytes shifted in at the same time.
+pub fn spi_transfer(driver: &Driver, spi_bus: u8, data: &[u8]) -> Result<Vec<u8>> {
+    reset_receive(driver)?;
+    write_fifo(driver, &spi_packet(spi_bus, data))?;
+    let received = read_fifo(driver)?;
+    log::debug!("spi_transfer({:?}, {:02x?}) = {:02x?}", spi_bus, data, received);
+    if received.len() != data.len() {
+        return Err(Error::Other(format!("SPI transfer returned {} bytes instead of {}",
+            received.len(), data.len()).into()))
+    }
+    Ok(received)
+}
+
+pub fn i2c_write(driver: &Driver, i2c_addr: u8, data: &[u8]) -> Result<()> {
+    log::debug!("i2c_write({:#08b}, {:02x?})", i2c_addr, data);
+    let mut packet = Vec::<u8>::new();
+    packet.push(HEADER_I2C_WRITE);
+    packet.push(i2c_addr);
+    packet.extend_from_slice(data);
+    write_fifo(driver, &packet)?;
+    // the I2C engine doesn't use TLAST to detect packet boundaries and runs at 400 kHz;
+    // make sure the engine is  done before releasing it. the delay has a 100% safety factor.
+    thread::sleep(Duration::from_micros((50 * data.len()) as u64));
+    Ok(())
+}
+
+/// Write `data` to the I2C device at `i2c_addr` (if there is any), then read `length` bytes
+/// from it. `length` must be at most 255.
+pub fn i2c_write_read(driver: &Driver, i2c_addr: u8, data: &[u8],
+                      length: usize) -> Result<Vec<u8>> {
+    assert!(length <= u8::MAX as usize, "I2C read is too long");
+    reset_receive(driver)?;
+    let mut packet = Vec::<u8>::new();
+    packet.push(HEADER_I2C_READ);
+    packet.push(i2c_addr);
+    packet.push(length as u8);
+    packet.extend_from_slice(data);
+    write_fifo(driver, &packet)?;
+    let received = read_fifo(driver)?;
+    log::debug!("i2c_write_read({:#08b}, {:02x?}, {}) = {:02x?}",
+        i2c_addr, data, length, received);
+    if received.len() != length {
+        return Err(Error::Other(format!("I2C read returned {} bytes instead of {}",
+            received.len(), length).into()))
+    }
+    Ok(received)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sys::SimulatedSignal;
+
+    #[test]
+    fn test_spi_transfer() {
+        let driver = Driver::simulated(SimulatedSignal::Ramp);
+        spi_write(&driver, 2, &[0x00, 0x04, 0x5a]).unwrap();
+        assert_eq!(spi_transfer(&driver, 2, &[0x80, 0x00, 0x00]).unwrap(), [0x00, 0x04, 0x5a]);
+        // a PGA on another bus is unaffected
+        assert_eq!(spi_transfer(&driver, 3, &[0x80, 0x00, 0x00]).unwrap(), [0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_i2c_write_read() {
+        let driver = Driver::simulated(SimulatedSignal::Ramp);
+        // digipot wiper 1
+        i2c_write(&driver, 0b0101100, &[0x10, 0x40]).unwrap();
+        assert_eq!(i2c_write_read(&driver, 0b0101100, &[0x1c], 2).unwrap(), [0x00, 0x40]);
+        // trimdac channel 2
+        // (multi-write command, channel 2 in bits 2:1)
+        i2c_write(&driver, 0b1100000, &[0b0101_1100, 0x87, 0x65]).unwrap();
+        let trimdac = i2c_write_read(&driver, 0b1100000, &[], 24).unwrap();
+        assert_eq!(trimdac[13..15], [0x87, 0x65]);
+        // nothing responds at this address
+        assert!(matches!(i2c_write_read(&driver, 0b1010000, &[], 1), Err(Error::Timeout)));
+    }
+}
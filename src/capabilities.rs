@@ -0,0 +1,102 @@
+//! Reports which optional subsystems were compiled into the library, so that frontends can hide
+//! functionality that is unavailable instead of failing when it is used.
+//!
+//! Each subsystem is enabled by the cargo feature of the same name.
+
+use std::fmt;
+
+/// Optional subsystems of the library, and whether each of them was compiled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The hardware driver; without it, only the simulated device can be opened. Also `false`
+    /// on platforms that have no driver.
+    pub hardware: bool,
+    /// Serialization of parameters and measurements with `serde`.
+    pub serde: bool,
+    /// Configuration files of `thunderscope-stream` (`--config`).
+    pub config: bool,
+    /// Export to Apache Arrow IPC files (`export::arrow`).
+    pub arrow: bool,
+    /// Export to Apache Parquet files (`export::arrow`).
+    pub parquet: bool,
+    /// Export to HDF5 files (`export::hdf5`).
+    pub hdf5: bool,
+    /// Export to sigrok session files (`export::sigrok`).
+    pub sigrok: bool,
+    /// Streaming samples to GNU Radio over ZeroMQ (`gnuradio`).
+    pub gnuradio: bool,
+    /// Asynchronous sample streams for tokio.
+    pub tokio: bool,
+}
+
+impl Capabilities {
+    const ALL_NAMES: [&'static str; 9] =
+        ["hardware", "serde", "config", "arrow", "parquet", "hdf5", "sigrok", "gnuradio", "tokio"];
+
+    fn flags(&self) -> [bool; 9] {
+        [self.hardware, self.serde, self.config, self.arrow, self.parquet, self.hdf5, self.sigrok,
+         self.gnuradio, self.tokio]
+    }
+
+    /// Returns the names of the subsystems that were compiled in.
+    pub fn enabled(&self) -> impl Iterator<Item = &'static str> {
+        Self::ALL_NAMES.into_iter()
+            .zip(self.flags())
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut any = false;
+        for name in self.enabled() {
+            write!(f, "{}{}", if any { ", " } else { "" }, name)?;
+            any = true;
+        }
+        if !any {
+            write!(f, "(none)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the optional subsystems compiled into this build of the library.
+pub const fn capabilities() -> Capabilities {
+    Capabilities {
+        hardware: cfg!(all(feature = "hardware", any(target_os = "linux", target_os = "windows"))),
+        serde: cfg!(feature = "serde"),
+        config: cfg!(feature = "config"),
+        arrow: cfg!(feature = "arrow"),
+        parquet: cfg!(feature = "parquet"),
+        hdf5: cfg!(feature = "hdf5"),
+        sigrok: cfg!(feature = "sigrok"),
+        gnuradio: cfg!(feature = "gnuradio"),
+        tokio: cfg!(feature = "tokio"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let example = Capabilities {
+            hardware: true,
+            serde: false,
+            config: false,
+            arrow: true,
+            parquet: false,
+            hdf5: false,
+            sigrok: false,
+            gnuradio: false,
+            tokio: true,
+        };
+        assert_eq!(example.to_string(), "hardware, arrow, tokio");
+        assert_eq!(Capabilities { hardware: false, arrow: false, tokio: false, ..example }
+            .to_string(), "(none)");
+        // implied features
+        assert!(!capabilities().parquet || capabilities().arrow);
+        assert!(!capabilities().config || capabilities().serde);
+    }
+}
@@ -0,0 +1,134 @@
+//! De-interleaving of the sample stream into per-channel lanes.
+//!
+//! The sample stream consists of frames of `DeviceParameters::stream_channels()` samples, one
+//! sample per lane, with all samples in a frame acquired on the same ADC sample clock tick.
+//! The assignment of faceplate channels to lanes is described by `ChannelMap`: in one and two
+//! channel modes, the lanes are the enabled channels in ascending order; in four channel mode
+//! (also used for three channels), the lanes are CH1..CH4, with the lane of a disabled channel
+//! carrying meaningless data.
+//!
+//! The data mover writes whole pages, which hold a whole number of frames, so the first sample
+//! ever read from a `DataStream` starts a frame. Data that starts elsewhere in the stream can be
+//! realigned using its stream position.
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+use crate::demux::demux;
+
+/// A multi-channel capture, de-interleaved into per-channel lanes.
+///
+/// Lanes are sample-aligned: sample `n` of every lane was acquired on the same sample clock tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    params: DeviceParameters,
+    lanes: [Option<Vec<i8>>; 4],
+}
+
+impl Capture {
+    /// De-interleave `data` captured with `params`, which must start at a frame boundary.
+    ///
+    /// A trailing partial frame is discarded.
+    pub fn new(params: &DeviceParameters, data: &[i8]) -> Capture {
+        Capture { params: *params, lanes: demux(&ChannelMap::from_params(params), data) }
+    }
+
+    /// De-interleave `data` captured with `params`, which starts at stream position `position`
+    /// (e.g. as returned by `DataStream::position()`).
+    ///
+    /// A leading and a trailing partial frame are discarded.
+    pub fn from_stream(params: &DeviceParameters, position: u64, data: &[i8]) -> Capture {
+        let stream_channels = params.stream_channels() as u64;
+        let skip = (stream_channels - position % stream_channels) % stream_channels;
+        Capture::new(params, &data[(skip as usize).min(data.len())..])
+    }
+
+    pub fn params(&self) -> &DeviceParameters {
+        &self.params
+    }
+
+    /// Returns the amount of samples in each lane.
+    pub fn len(&self) -> usize {
+        self.lanes.iter().flatten().map(|lane| lane.len()).next().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the samples of faceplate channel `channel_index`, or `None` if it is disabled.
+    pub fn channel(&self, channel_index: usize) -> Option<&[i8]> {
+        self.lanes[channel_index].as_deref()
+    }
+
+    /// Returns the samples of all faceplate channels, in the form accepted by the exporters.
+    pub fn channels(&self) -> [Option<&[i8]>; 4] {
+        std::array::from_fn(|channel_index| self.channel(channel_index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::DeviceConfiguration;
+    use crate::params::DeviceCalibration;
+
+    fn params(enabled: [bool; 4]) -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: enabled.map(|en| en.then(Default::default)),
+            ..Default::default()
+        })
+    }
+
+    // Emulates the ADC producing a ramp test pattern offset by 32 * (channel index + 1) on each
+    // input, after the permutation done by `enable_adc_channels`.
+    fn ramp_stream(params: &DeviceParameters, frames: usize) -> Vec<i8> {
+        let lanes = match params.stream_channels() {
+            4 => vec![0, 1, 2, 3],
+            _ => (0..4).filter(|&index| params.channels[index].is_some()).collect(),
+        };
+        (0..frames)
+            .flat_map(|frame| lanes.iter().map(move |&index|
+                (frame as u8).wrapping_add(32 * (index as u8 + 1)) as i8))
+            .collect()
+    }
+
+    fn assert_aligned(capture: &Capture, first_frame: usize) {
+        for index in 0..4 {
+            let Some(lane) = capture.channel(index) else { continue };
+            for (frame, &sample) in lane.iter().enumerate() {
+                let expected = ((first_frame + frame) as u8).wrapping_add(32 * (index as u8 + 1));
+                assert_eq!(sample, expected as i8, "CH{} sample {}", index + 1, frame);
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_configurations() {
+        for mask in 1..16u8 {
+            let params = params(std::array::from_fn(|index| mask & (1 << index) != 0));
+            let capture = Capture::new(&params, &ramp_stream(&params, 300));
+            assert_eq!(capture.len(), 300);
+            for index in 0..4 {
+                assert_eq!(capture.channel(index).is_some(), params.channels[index].is_some());
+            }
+            assert_aligned(&capture, 0);
+        }
+    }
+
+    #[test]
+    fn test_partial_frames() {
+        let params = params([true, false, true, true]);
+        let stream = ramp_stream(&params, 10);
+        let capture = Capture::new(&params, &stream[..39]);
+        assert_eq!(capture.len(), 9);
+        assert!(!capture.is_empty());
+        assert!(Capture::new(&params, &stream[..3]).is_empty());
+        assert_aligned(&capture, 0);
+        let capture = Capture::from_stream(&params, 1, &stream[1..]);
+        assert_eq!(capture.len(), 9);
+        assert_aligned(&capture, 1);
+        let capture = Capture::from_stream(&params, 4, &stream[4..]);
+        assert_eq!(capture.len(), 9);
+        assert_aligned(&capture, 1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Mapping between faceplate channels, ADC inputs, and lanes of the sample stream.
+//!
+//! Faceplate channels CH1..CH4 are wired to ADC inputs IN4..IN1. The ADC has four cores, each of
+//! which can sample any input; the input selection is chosen such that the lanes of the sample
+//! stream (see `Capture`) are in the order of faceplate channels.
+
+use crate::params::DeviceParameters;
+
+/// Channel mapping for a particular set of enabled channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMap {
+    enabled: [bool; 4],
+}
+
+impl ChannelMap {
+    /// Create a channel map for `enabled` faceplate channels. At least one must be enabled.
+    pub fn new(enabled: [bool; 4]) -> ChannelMap {
+        assert!(enabled.iter().any(|&en| en), "no channels enabled");
+        ChannelMap { enabled }
+    }
+
+    pub fn from_params(params: &DeviceParameters) -> ChannelMap {
+        ChannelMap::new(params.channels.map(|ch| ch.is_some()))
+    }
+
+    pub fn enabled(&self) -> [bool; 4] {
+        self.enabled
+    }
+
+    /// Returns the amount of lanes in the sample stream (and channels in the ADC).
+    pub fn stream_channels(&self) -> usize {
+        // three channel configurations use four channel mode
+        match self.enabled.iter().filter(|&&en| en).count() {
+            4 |
+            3 => 4,
+            2 => 2,
+            1 => 1,
+            _ => unreachable!()
+        }
+    }
+
+    /// Returns the index of the ADC input (0 for IN1) that faceplate channel `channel_index` is
+    /// wired to.
+    pub fn adc_input(channel_index: usize) -> usize {
+        3 - channel_index
+    }
+
+    /// Returns the ADC input selected for each of the ADC cores.
+    pub(crate) fn adc_insel(&self) -> [usize; 4] {
+        let mut enabled = (0..4).filter(|&index| self.enabled[index]).map(Self::adc_input);
+        match self.stream_channels() {
+            1 => {
+                let input = enabled.next().unwrap();
+                [input; 4]
+            }
+            2 => {
+                let (input1, input2) = (enabled.next().unwrap(), enabled.next().unwrap());
+                // this is permuted later again; the lanes are input1,input2
+                [input1, input1, input2, input2]
+            }
+            4 => [0, 1, 2, 3].map(Self::adc_input),
+            _ => unreachable!()
+        }
+    }
+
+    /// Returns the lane carrying faceplate channel `channel_index`, or `None` if the channel
+    /// is disabled.
+    pub fn lane(&self, channel_index: usize) -> Option<usize> {
+        if !self.enabled[channel_index] {
+            return None
+        }
+        match self.stream_channels() {
+            1 | 2 => Some(self.enabled[..channel_index].iter().filter(|&&en| en).count()),
+            _ => Some(channel_index),
+        }
+    }
+
+    /// Returns the faceplate channel carried by `lane`, or `None` if the lane carries data from
+    /// a disabled channel (which is the case for one lane in three channel configurations).
+    pub fn lane_channel(&self, lane: usize) -> Option<usize> {
+        (0..4).find(|&channel_index| self.lane(channel_index) == Some(lane))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn all_maps() -> impl Iterator<Item = ChannelMap> {
+        (1..16u8).map(|mask|
+            ChannelMap::new(std::array::from_fn(|index| mask & (1 << index) != 0)))
+    }
+
+    #[test]
+    fn test_insel() {
+        // the permutation originally computed in `Device::enable_adc_channels`
+        fn reference(enabled: [bool; 4]) -> [usize; 4] {
+            match enabled.iter().filter(|&&en| en).count() {
+                1 => {
+                    let ch1_index = enabled.iter().rev().position(|&en| en).unwrap();
+                    [ch1_index, ch1_index, ch1_index, ch1_index]
+                }
+                2 => {
+                    let ch1_index = enabled.iter().rev().position(|&en| en).unwrap();
+                    let ch2_index = ch1_index + 1 +
+                        enabled.iter().rev().skip(ch1_index + 1).position(|&en| en).unwrap();
+                    [ch2_index, ch2_index, ch1_index, ch1_index]
+                }
+                _ => [3, 2, 1, 0]
+            }
+        }
+
+        for map in all_maps() {
+            assert_eq!(map.adc_insel(), reference(map.enabled()), "{:?}", map);
+        }
+    }
+
+    #[test]
+    fn test_lanes() {
+        for map in all_maps() {
+            for channel_index in 0..4 {
+                match map.lane(channel_index) {
+                    Some(lane) => {
+                        assert!(lane < map.stream_channels());
+                        assert_eq!(map.lane_channel(lane), Some(channel_index));
+                    }
+                    None => assert!(!map.enabled()[channel_index]),
+                }
+            }
+        }
+        assert_eq!(ChannelMap::new([false, true, false, true]).lane(3), Some(1));
+        assert_eq!(ChannelMap::new([true, true, false, true]).lane(3), Some(3));
+        assert_eq!(ChannelMap::new([true, true, false, true]).lane_channel(2), None);
+    }
+}
@@ -4,6 +4,7 @@
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Termination {
     #[default]
     Ohm1M,
@@ -11,6 +12,7 @@ pub enum Termination {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Coupling {
     #[default]
     DC,
@@ -18,6 +20,7 @@ pub enum Coupling {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Bandwidth {
     #[default]
     MHz100,
@@ -26,6 +29,8 @@ pub enum Bandwidth {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ChannelConfiguration {
     /// Probe attenuation in dB. For a 1X probe, `0.0`; for a 10X probe, `20.0`.
     pub probe_attenuation: f32,
@@ -45,15 +50,93 @@ impl Default for ChannelConfiguration {
     }
 }
 
+/// Rate at which each enabled channel is sampled.
+///
+/// The ADC samples at 1 GS/s in total at most, so with two channels enabled, each is sampled at
+/// 500 MS/s at most, and with three or four channels, at 250 MS/s at most. Higher rates are
+/// reduced to these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleRate {
+    #[default]
+    MSps1000,
+    MSps500,
+    MSps250,
+    MSps125,
+}
+
+impl SampleRate {
+    pub const ALL: [SampleRate; 4] = [
+        SampleRate::MSps1000,
+        SampleRate::MSps500,
+        SampleRate::MSps250,
+        SampleRate::MSps125,
+    ];
+
+    /// Returns the rate, in samples per second.
+    pub fn samples_per_second(self) -> f32 {
+        match self {
+            SampleRate::MSps1000 => 1000e6,
+            SampleRate::MSps500  =>  500e6,
+            SampleRate::MSps250  =>  250e6,
+            SampleRate::MSps125  =>  125e6,
+        }
+    }
+}
+
+/// Resolution of the samples.
+///
+/// 8-bit samples appear in the stream as `i8`. Higher resolution samples appear in the stream as
+/// little endian `i16`, aligned to the most significant bit, so that e.g. the 12-bit code `0x7ff`
+/// becomes `0x7ff0`. The ADC can only output 12-bit samples at 640 MS/s in total at most,
+/// so higher sample rates are reduced to 500 MS/s with one channel enabled, and to half of
+/// the 8-bit sample rate otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolution {
+    #[default]
+    Bits8,
+    Bits12,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 2] = [
+        Resolution::Bits8,
+        Resolution::Bits12,
+    ];
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Resolution::Bits8  =>  8,
+            Resolution::Bits12 => 12,
+        }
+    }
+
+    /// Returns the size of one sample in the stream, in bytes.
+    pub fn sample_size(self) -> usize {
+        match self {
+            Resolution::Bits8  => 1,
+            Resolution::Bits12 => 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceConfiguration {
-    pub channels: [Option<ChannelConfiguration>; 4]
+    pub channels: [Option<ChannelConfiguration>; 4],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sample_rate: SampleRate,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub resolution: Resolution,
 }
 
 impl Default for DeviceConfiguration {
     fn default() -> Self {
         DeviceConfiguration {
-            channels: [Some(ChannelConfiguration::default()); 4]
+            channels: [Some(ChannelConfiguration::default()); 4],
+            sample_rate: SampleRate::default(),
+            resolution: Resolution::default(),
         }
     }
 }
@@ -0,0 +1,207 @@
+//! Counting of threshold crossings and pulses, and tracking of excursions, over a gate time;
+//! for frequency and event counting without displaying the waveform.
+
+use std::fmt;
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+
+/// Statistics of one channel over one gate time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CounterReading {
+    /// Length of the gate, in seconds.
+    pub gate_time: f32,
+    pub rising_edges: u64,
+    pub falling_edges: u64,
+    /// Amount of positive pulses (a rising edge followed by a falling edge) that ended within
+    /// the gate.
+    pub pulses: u64,
+    /// Smallest sample within the gate, in volts.
+    pub min: f32,
+    /// Largest sample within the gate, in volts.
+    pub max: f32,
+}
+
+impl CounterReading {
+    /// Returns the amount of threshold crossings in either direction.
+    pub fn crossings(&self) -> u64 {
+        self.rising_edges + self.falling_edges
+    }
+
+    /// Returns the rate of rising edges, in Hz.
+    pub fn frequency(&self) -> f32 {
+        self.rising_edges as f32 / self.gate_time
+    }
+}
+
+impl fmt::Display for CounterReading {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.6} s: {} rising, {} falling, {} pulses, {:.1} Hz, {:.4} V .. {:.4} V",
+            self.gate_time, self.rising_edges, self.falling_edges, self.pulses,
+            self.frequency(), self.min, self.max)
+    }
+}
+
+/// Counts the edges and pulses of one channel of the sample stream, and tracks its smallest and
+/// largest samples, over consecutive gate times.
+///
+/// The threshold has hysteresis, with the same meaning of `level` and `hysteresis` as for
+/// `Trigger`.
+#[derive(Debug, Clone)]
+pub struct EventCounter {
+    params: DeviceParameters,
+    channel_index: usize,
+    channels: usize,
+    lane: usize,
+    // index of the next sample in its frame
+    phase: usize,
+    below: i8,
+    above: i8,
+    level: i8,
+    // `None` until the first sample of the channel is processed
+    high: Option<bool>,
+    gate_samples: u64,
+    elapsed: u64,
+    rising_edges: u64,
+    falling_edges: u64,
+    pulses: u64,
+    in_pulse: bool,
+    min: i8,
+    max: i8,
+}
+
+impl EventCounter {
+    /// Create a new counter for faceplate channel `channel_index` in a sample stream acquired
+    /// with `params`, with the threshold at `level` and a gate time of `gate_time` seconds.
+    ///
+    /// Returns `None` if the channel is disabled.
+    pub fn new(params: &DeviceParameters, channel_index: usize, level: i8, hysteresis: u8,
+               gate_time: f32) -> Option<EventCounter> {
+        let channel_map = ChannelMap::from_params(params);
+        let lane = channel_map.lane(channel_index)?;
+        let gate_samples = ((gate_time * params.corrected_sample_rate()).round() as u64).max(1);
+        let mut counter = EventCounter {
+            params: *params,
+            channel_index,
+            channels: channel_map.stream_channels(),
+            lane,
+            phase: 0,
+            below: level.saturating_sub_unsigned(hysteresis).max(-127),
+            above: level.saturating_add_unsigned(hysteresis).min( 126),
+            level,
+            high: None,
+            gate_samples,
+            elapsed: 0,
+            rising_edges: 0,
+            falling_edges: 0,
+            pulses: 0,
+            in_pulse: false,
+            min: i8::MAX,
+            max: i8::MIN,
+        };
+        counter.start_gate();
+        Some(counter)
+    }
+
+    /// Reset the counter, discarding the gate in progress and the state of the threshold.
+    ///
+    /// After this method is called, the next sample must start a frame.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.high = None;
+        self.in_pulse = false;
+        self.start_gate();
+    }
+
+    fn start_gate(&mut self) {
+        self.elapsed = 0;
+        self.rising_edges = 0;
+        self.falling_edges = 0;
+        self.pulses = 0;
+        self.min = i8::MAX;
+        self.max = i8::MIN;
+    }
+
+    /// Returns the statistics of the gate in progress, or `None` if it has no samples yet.
+    pub fn partial(&self) -> Option<CounterReading> {
+        if self.elapsed == 0 {
+            return None
+        }
+        Some(CounterReading {
+            gate_time: self.elapsed as f32 / self.params.corrected_sample_rate(),
+            rising_edges: self.rising_edges,
+            falling_edges: self.falling_edges,
+            pulses: self.pulses,
+            min: self.params.code_to_volts(self.channel_index, self.min),
+            max: self.params.code_to_volts(self.channel_index, self.max),
+        })
+    }
+
+    /// Process incoming data (interleaved as in the sample stream), appending the statistics of
+    /// every gate that ends within it to `readings`.
+    pub fn process(&mut self, samples: &[i8], readings: &mut Vec<CounterReading>) {
+        for &sample in samples {
+            let lane = self.phase;
+            self.phase = (self.phase + 1) % self.channels;
+            if lane != self.lane { continue }
+            self.min = self.min.min(sample);
+            self.max = self.max.max(sample);
+            match self.high {
+                None => self.high = Some(sample >= self.level),
+                Some(false) if sample > self.above => {
+                    self.high = Some(true);
+                    self.rising_edges += 1;
+                    self.in_pulse = true;
+                }
+                Some(true) if sample < self.below => {
+                    self.high = Some(false);
+                    self.falling_edges += 1;
+                    if std::mem::take(&mut self.in_pulse) {
+                        self.pulses += 1;
+                    }
+                }
+                _ => ()
+            }
+            self.elapsed += 1;
+            if self.elapsed == self.gate_samples {
+                readings.extend(self.partial());
+                self.start_gate();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    #[test]
+    fn test_square_wave() {
+        // CH2 of two channels at 500 MS/s each; 10 samples per period (50 MHz), starting high
+        let params = DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [None, Some(ChannelConfiguration::default()),
+                       Some(ChannelConfiguration::default()), None],
+            ..Default::default()
+        });
+        assert!(EventCounter::new(&params, 0, 0, 2, 1e-6).is_none());
+        let mut counter = EventCounter::new(&params, 1, 0, 2, 1e-6).unwrap();
+        let samples = (0..1250).flat_map(|index| {
+            [if index % 10 < 5 { 50 } else { -50 }, 0]
+        }).collect::<Vec<_>>();
+        let mut readings = Vec::new();
+        counter.process(&samples, &mut readings);
+        // 1 us is 500 samples, or 50 periods
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].rising_edges, 49); // the first one precedes the gate
+        assert_eq!(readings[0].falling_edges, 50);
+        assert_eq!(readings[0].pulses, 49);
+        assert_eq!(readings[1].crossings(), 100);
+        assert!((readings[1].frequency() - 50e6).abs() < 1.0, "{}", readings[1]);
+        assert_eq!(readings[1].max, params.code_to_volts(1, 50));
+        assert_eq!(readings[1].min, params.code_to_volts(1, -50));
+        let partial = counter.partial().unwrap();
+        assert_eq!(partial.gate_time, 0.5e-6);
+        assert_eq!(partial.rising_edges, 25);
+    }
+}
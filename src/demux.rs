@@ -0,0 +1,194 @@
+//! De-interleaving of the sample stream into contiguous per-lane buffers.
+//!
+//! In two and four channel modes, the sample stream interleaves one sample per lane in each frame
+//! (see `Capture` for the layout, and `ChannelMap` for the assignment of faceplate channels to
+//! lanes, which follows the ADC input selection). Picking out every second or fourth byte one at
+//! a time is slow enough to limit multi-channel acquisition, so the frames are de-interleaved
+//! 16 bytes at a time with a byte shuffle, which gathers the samples of each lane in a block.
+//!
+//! The implementation is selected the same way as for the triggers, with `ScanVariant`.
+
+use wide::i8x16;
+
+use crate::channel_map::ChannelMap;
+use crate::trigger::ScanVariant;
+
+// shuffle masks that gather the samples of each lane of a 16 byte block into a contiguous run
+// of `16 / channels` bytes, in the order of lanes
+const SHUFFLE_2: [i8; 16] = [0, 2, 4, 6, 8, 10, 12, 14, 1, 3, 5, 7, 9, 11, 13, 15];
+const SHUFFLE_4: [i8; 16] = [0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15];
+
+/// De-interleave `data` with the channel mapping `channel_map` into the samples of each
+/// faceplate channel; `None` for the disabled ones. The data must start at a frame boundary.
+///
+/// A trailing partial frame is discarded.
+pub fn demux(channel_map: &ChannelMap, data: &[i8]) -> [Option<Vec<i8>>; 4] {
+    let stream_channels = channel_map.stream_channels();
+    let frames = data.len() / stream_channels;
+    let mut lanes = vec![vec![0; frames]; stream_channels];
+    demux_lanes(&data[..frames * stream_channels],
+        &mut lanes.iter_mut().map(|lane| &mut lane[..]).collect::<Vec<_>>());
+    let mut lanes = lanes.into_iter().map(Some).collect::<Vec<_>>();
+    std::array::from_fn(|channel_index| {
+        channel_map.lane(channel_index).and_then(|lane| lanes[lane].take())
+    })
+}
+
+/// De-interleave `data`, which interleaves `lanes.len()` lanes (1, 2, or 4; as in the sample
+/// stream) and starts at a frame boundary, into `lanes`.
+///
+/// Panics if `data` does not consist of whole frames, or if any of the lanes does not have
+/// the same amount of samples as there are frames.
+pub fn demux_lanes(data: &[i8], lanes: &mut [&mut [i8]]) {
+    let channels = lanes.len();
+    assert!(matches!(channels, 1 | 2 | 4), "cannot demultiplex {} lanes", channels);
+    let frames = data.len() / channels;
+    assert!(frames * channels == data.len(), "data does not consist of whole frames");
+    assert!(lanes.iter().all(|lane| lane.len() == frames),
+        "lanes do not have {} samples", frames);
+    if channels == 1 {
+        return lanes[0].copy_from_slice(data)
+    }
+    let variant = if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() };
+    // SAFETY: `ScanVariant::current()` only returns available variants.
+    unsafe { demux_variant(variant, data, lanes) }
+}
+
+/// # Safety
+///
+/// `variant` must be available.
+unsafe fn demux_variant(variant: ScanVariant, data: &[i8], lanes: &mut [&mut [i8]]) {
+    match variant {
+        ScanVariant::Generic => demux_generic(data, lanes),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        ScanVariant::Avx | ScanVariant::Avx2 => demux_ssse3(data, lanes),
+        #[cfg(target_arch = "aarch64")]
+        ScanVariant::Neon => demux_neon(data, lanes),
+        _ => unreachable!("{:?} demultiplexer is not available", variant)
+    }
+}
+
+macro_rules! demux_impl {
+    { $shuffle:ident $( $decl:tt )+ } => {
+        #[inline(never)] // makes assembly more readable; serves no other purpose
+        $( $decl )+(data: &[i8], lanes: &mut [&mut [i8]]) {
+            let channels = lanes.len();
+            let mask = if channels == 2 { &SHUFFLE_2 } else { &SHUFFLE_4 };
+            // frames per block, which is also the length of the run of each lane
+            let run = 16 / channels;
+            let mut blocks = data.chunks_exact(16);
+            for (index, block) in blocks.by_ref().enumerate() {
+                let shuffled = $shuffle(block.try_into().unwrap(), mask);
+                for (lane, samples) in lanes.iter_mut().enumerate() {
+                    samples[index * run..][..run].copy_from_slice(&shuffled[lane * run..][..run]);
+                }
+            }
+            let rest = blocks.remainder();
+            let first_frame = (data.len() - rest.len()) / channels;
+            for (frame, samples) in rest.chunks_exact(channels).enumerate() {
+                for (lane, &sample) in samples.iter().enumerate() {
+                    lanes[lane][first_frame + frame] = sample;
+                }
+            }
+        }
+    };
+}
+
+#[inline(always)]
+fn shuffle_generic(block: &[i8; 16], mask: &[i8; 16]) -> [i8; 16] {
+    i8x16::new(*block).swizzle_relaxed(i8x16::new(*mask)).to_array()
+}
+
+demux_impl! { shuffle_generic fn demux_generic }
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "ssse3")]
+unsafe fn shuffle_ssse3(block: &[i8; 16], mask: &[i8; 16]) -> [i8; 16] {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let block = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    let mask = _mm_loadu_si128(mask.as_ptr() as *const __m128i);
+    let mut shuffled = [0i8; 16];
+    _mm_storeu_si128(shuffled.as_mut_ptr() as *mut __m128i, _mm_shuffle_epi8(block, mask));
+    shuffled
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+demux_impl! { shuffle_ssse3 #[target_feature(enable = "ssse3")] unsafe fn demux_ssse3 }
+
+#[cfg(target_arch = "aarch64")]
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn shuffle_neon(block: &[i8; 16], mask: &[i8; 16]) -> [i8; 16] {
+    use std::arch::aarch64::*;
+
+    let shuffled = vqtbl1q_s8(vld1q_s8(block.as_ptr()), vld1q_u8(mask.as_ptr() as *const u8));
+    let mut result = [0i8; 16];
+    vst1q_s8(result.as_mut_ptr(), shuffled);
+    result
+}
+
+#[cfg(target_arch = "aarch64")]
+demux_impl! { shuffle_neon #[target_feature(enable = "neon")] unsafe fn demux_neon }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // a stream where sample `n` of lane `l` is `n * 4 + l`, wrapping around
+    fn stream(channels: usize, frames: usize) -> Vec<i8> {
+        (0..frames * channels).map(|index| {
+            let (frame, lane) = (index / channels, index % channels);
+            (frame * 4 + lane) as u8 as i8
+        }).collect()
+    }
+
+    fn check_variant(variant: ScanVariant) {
+        for channels in [2, 4] {
+            for frames in [0, 1, 3, 4, 7, 8, 9, 100, 1027] {
+                let data = stream(channels, frames);
+                let mut lanes = vec![vec![0i8; frames]; channels];
+                let mut lane_refs = lanes.iter_mut().map(|lane| &mut lane[..]).collect::<Vec<_>>();
+                // SAFETY: Only the available variants are tested.
+                unsafe { demux_variant(variant, &data, &mut lane_refs) };
+                for (lane, samples) in lanes.iter().enumerate() {
+                    let expected = (0..frames)
+                        .map(|frame| (frame * 4 + lane) as u8 as i8)
+                        .collect::<Vec<_>>();
+                    assert_eq!(samples, &expected,
+                        "{:?}: lane {} of {} over {} frames", variant, lane, channels, frames);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        for variant in ScanVariant::ALL.into_iter().filter(|variant| variant.is_available()) {
+            check_variant(variant);
+        }
+    }
+
+    #[test]
+    fn test_demux() {
+        let channel_map = ChannelMap::new([true, false, false, false]);
+        let channels = demux(&channel_map, &[1, 2, 3]);
+        assert_eq!(channels, [Some(vec![1, 2, 3]), None, None, None]);
+
+        let channel_map = ChannelMap::new([false, true, false, true]);
+        let channels = demux(&channel_map, &stream(2, 21)[..41]);
+        assert_eq!(channels[0], None);
+        assert_eq!(channels[1].as_deref(), Some(&stream(1, 20)[..]));
+        assert_eq!(channels[3].as_ref().unwrap().len(), 20);
+
+        // in four channel mode, the lane of a disabled channel is dropped
+        let channel_map = ChannelMap::new([true, true, false, true]);
+        let channels = demux(&channel_map, &stream(4, 10));
+        assert!(channels[2].is_none());
+        assert_eq!(channels[3].as_ref().unwrap()[..3], [3, 7, 11]);
+    }
+}
@@ -1,43 +1,314 @@
-use std::time::Duration;
+use std::fmt;
+use std::io::Read;
+use std::ops::{ControlFlow, Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use std::thread;
 
-use crate::Result;
-use crate::sys::Driver;
-use crate::regs::axi::{self, Control, FifoIsr, Status};
+use crate::{Error, Result};
+use crate::sys::{self, Driver, SimulatedSignal};
+use crate::bus;
+use crate::regs::axi::{self, Status};
 use crate::regs::adc;
-use crate::config::{Coupling, Termination};
+use crate::config::{Coupling, Resolution, Termination};
 use crate::params::{ChannelParameters, CoarseAttenuation, DeviceParameters};
+use crate::channel_map::ChannelMap;
+use crate::event::{EventKind, EventLog};
+use crate::interrupt;
+use crate::timestamp::Timestamp;
+use crate::buffer::RingBuffer;
 
 const SPI_BUS_ADC: u8 = 0;
 const SPI_BUS_PGA: [u8; 4] = [2, 3, 4, 5];
+const WIPER_ADDRESS: [u8; 4] = [0x6, 0x0, 0x1, 0x7];
+
+/// How long `DataStream::read()` waits for the data mover if no data is available. At 1 GS/s, this
+/// is 1 MB of samples, a small fraction of the device memory.
+const EVENT_TIMEOUT: Duration = Duration::from_millis(1);
+
+const PAGE_BITS: usize = 12; // 4 Ki
+const MEMORY_SIZE: usize = 1 << 16 << PAGE_BITS; // 64 Ki x (1 << PAGE_BITS) = 256 Mi
+
+/// Major version of the gateware that this driver is compatible with. Gateware with a different
+/// major version may have an incompatible register map.
+const GATEWARE_MAJOR_VERSION: u8 = 1;
+
+/// How long the ADC link training waits for the data mover to move a page of test patterns.
+const ADC_PATTERN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How often `Control::measure_clock_ppm()` reads the page counter of the data mover. At 1 GB/s,
+/// the counter wraps around every 268 ms.
+const CLOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Size of the ring buffer used by `Device::read_data()`.
+const READ_DATA_BUFFER_SIZE: usize = 16 << 20;
+
+/// Describes a device that is connected to the host, but not necessarily opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    /// Path to the XDMA device: on Linux, the device nodes without the `_user`/`_c2h_0`/etc
+    /// suffix; on Windows, the device interface, without the `\user`/`\c2h_0`/etc suffix.
+    pub path: String,
+    /// Serial number, if the device reports one.
+    pub serial: Option<String>,
+    /// Gateware revision, if the device reports one.
+    pub gateware_revision: Option<u8>,
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(serial) = &self.serial {
+            write!(f, " (serial {})", serial)?;
+        }
+        if let Some(revision) = self.gateware_revision {
+            write!(f, " (gateware revision {})", revision)?;
+        }
+        Ok(())
+    }
+}
+
+/// Version and capabilities of the gateware running on a device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceCapabilities {
+    /// Major and minor version of the gateware, or `None` if the gateware predates
+    /// the identification registers.
+    pub gateware_version: Option<(u8, u8)>,
+    /// Size of the DMA memory, in bytes.
+    pub memory_size: usize,
+    /// Amount of input channels.
+    pub channels: usize,
+    /// Largest sample rate, in samples per second, across all channels.
+    pub max_sample_rate: f32,
+    /// Whether samples can be acquired with `Resolution::Bits12`.
+    pub high_resolution: bool,
+    /// Whether the configuration can be read back from the device (see `Device::verify`).
+    pub bus_readback: bool,
+}
+
+impl DeviceCapabilities {
+    /// Returns the reason why this driver cannot work with the gateware, if it cannot.
+    fn incompatibility(&self) -> Option<String> {
+        match self.gateware_version {
+            Some((major, minor)) if major != GATEWARE_MAJOR_VERSION =>
+                return Some(format!("gateware version {}.{} is not supported (expected {}.x)",
+                    major, minor, GATEWARE_MAJOR_VERSION)),
+            _ => ()
+        }
+        if self.memory_size != MEMORY_SIZE {
+            return Some(format!("DMA memory size of {} MiB is not supported (expected {} MiB)",
+                self.memory_size >> 20, MEMORY_SIZE >> 20))
+        }
+        if self.channels != 4 {
+            return Some(format!("{} channels are not supported (expected 4)", self.channels))
+        }
+        None
+    }
+}
+
+impl fmt::Display for DeviceCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.gateware_version {
+            Some((major, minor)) => write!(f, "gateware {}.{}", major, minor)?,
+            None => write!(f, "gateware (unversioned)")?,
+        }
+        write!(f, ", {} MiB, {} channels, {} MS/s",
+            self.memory_size >> 20, self.channels, self.max_sample_rate / 1e6)?;
+        if self.high_resolution {
+            write!(f, ", 12-bit")?;
+        }
+        if self.bus_readback {
+            write!(f, ", readback")?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct Device {
-    driver: Driver,
+    descriptor: Descriptor,
+    control: Control,
+}
+
+/// The values most recently written to the write-only parts of the device, so that `configure()`
+/// only has to write the ones that change. Each SPI or I2C write takes tens to hundreds of
+/// microseconds, and resetting the data mover takes several milliseconds.
+#[derive(Debug, Default)]
+struct Shadow {
+    pga_commands: [Option<u16>; 4],
+    digipot_inputs: [Option<u16>; 4],
+    trimdac_inputs: [Option<u16>; 4],
+    channel_map: Option<ChannelMap>,
+    adc_clock_divisor: Option<usize>,
+    resolution: Option<Resolution>,
+    // the data mover is kept halted until acquisition is resumed
+    paused: bool,
+}
+
+/// A handle for controlling the device: starting it up, configuring it, and shutting it down.
+///
+/// Cloning the handle does not clone the device; all clones (and the `DataStream`s) refer to
+/// the same device. This makes it possible to reconfigure the device from one thread while
+/// another one streams data from it.
+#[derive(Debug, Clone)]
+pub struct Control {
+    driver: Arc<Driver>,
+    events: EventLog,
+    // serializes the register access sequences, which are not atomic
+    lock: Arc<Mutex<Shadow>>,
+    // incremented every time the data mover is reset, which invalidates the cursors of streams
+    generation: Arc<AtomicU64>,
+    // samples per second in the stream, and bytes per sample; change only while the data mover
+    // is reset
+    stream_sample_rate: Arc<AtomicU64>,
+    stream_sample_size: Arc<AtomicUsize>,
 }
 
 impl Device {
-    pub fn new() -> Result<Device> {
-        if cfg!(all(feature = "hardware", target_os = "linux")) {
-            // FIXME: do this better
-            Ok(Device { driver: Driver::new("/dev/xdma0")? })
+    /// List the devices connected to the host, in a stable order.
+    pub fn list() -> Result<Vec<Descriptor>> {
+        if cfg!(all(feature = "hardware", any(target_os = "linux", target_os = "windows"))) {
+            sys::enumerate()
         } else {
             log::error!("this platform does not implement a hardware driver");
             Err(crate::Error::Unsupported)
         }
     }
 
+    /// Open the device described by `descriptor`.
+    pub fn open(descriptor: &Descriptor) -> Result<Device> {
+        log::debug!("opening {}", descriptor);
+        Ok(Device {
+            descriptor: descriptor.clone(),
+            control: Control::new(Driver::new(&descriptor.path)?),
+        })
+    }
+
+    /// Open a simulated device whose sample stream contains `signal`, for exercising code that
+    /// uses the device without hardware. The simulated device is not listed by `Device::list()`.
+    pub fn simulated(signal: SimulatedSignal) -> Device {
+        let descriptor = Descriptor {
+            path: "sim".to_owned(),
+            serial: None,
+            gateware_revision: None,
+        };
+        log::debug!("opening {} (simulated)", descriptor);
+        Device { descriptor, control: Control::new(Driver::simulated(signal)) }
+    }
+
+    /// Open the first device connected to the host.
+    pub fn new() -> Result<Device> {
+        match Self::list()?.first() {
+            Some(descriptor) => Self::open(descriptor),
+            None => Err(Error::NotFound)
+        }
+    }
+
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    /// Start up the device and return a guard that shuts it down when dropped.
+    pub fn guard(self) -> Result<DeviceGuard> {
+        interrupt::install();
+        // the guard is created before startup so that the device is shut down even if
+        // the startup sequence fails midway
+        let guard = DeviceGuard { device: self, armed: true };
+        guard.device.startup()?;
+        Ok(guard)
+    }
+
+    /// Start up the device, call `f`, and shut the device down, even if `f` panics or
+    /// the process is interrupted with ^C (in which case streaming fails with
+    /// `Error::Interrupted`).
     pub fn with<F, R>(f: F) -> Result<R>
             where F: FnOnce(&mut Self) -> Result<R> {
-        let mut device = Self::new()?;
-        device.startup()?;
-        let result = f(&mut device);
-        device.shutdown()?;
-        result
+        let mut guard = Self::new()?.guard()?;
+        let result = f(&mut guard);
+        let shutdown = guard.shutdown();
+        result.and_then(|value| shutdown.map(|()| value))
+    }
+
+    /// Returns a handle for controlling the device that can be used concurrently with streaming.
+    pub fn control(&self) -> Control {
+        self.control.clone()
+    }
+
+    /// Returns a handle to the log of significant events that happened to this device.
+    pub fn event_log(&self) -> EventLog {
+        self.control.event_log()
+    }
+
+    pub fn configure(&self, params: &DeviceParameters) -> Result<()> {
+        self.control.configure(params)
+    }
+
+    pub fn verify(&self) -> Result<()> {
+        self.control.verify()
+    }
+
+    pub fn identify(&self) -> Result<DeviceCapabilities> {
+        self.control.identify()
+    }
+
+    pub fn startup(&self) -> Result<()> {
+        self.control.startup()
+    }
+
+    pub fn shutdown(&self) -> Result<()> {
+        self.control.shutdown()
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.control.pause()
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.control.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.is_paused()
+    }
+
+    pub fn measure_clock_ppm(&self, gate: Duration) -> Result<f32> {
+        self.control.measure_clock_ppm(gate)
     }
 }
 
-impl Device {
+impl Control {
+    fn new(driver: Driver) -> Control {
+        Control {
+            driver: Arc::new(driver),
+            events: EventLog::new(),
+            lock: Arc::new(Mutex::new(Shadow::default())),
+            generation: Arc::new(AtomicU64::new(0)),
+            stream_sample_rate: Arc::new(AtomicU64::new(
+                DeviceParameters::default().stream_sample_rate())),
+            stream_sample_size: Arc::new(AtomicUsize::new(1)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shadow> {
+        self.lock.lock().unwrap_or_else(|error| {
+            // a register access sequence was interrupted midway, so the shadow may be stale
+            self.lock.clear_poison();
+            let mut shadow = error.into_inner();
+            *shadow = Shadow::default();
+            shadow
+        })
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Returns a handle to the log of significant events that happened to this device.
+    pub fn event_log(&self) -> EventLog {
+        self.events.clone()
+    }
+
     fn read_user_u32(&self, addr: usize) -> Result<u32> {
         let mut bytes = [0u8; 4];
         self.driver.read_user(addr, &mut bytes[..])?;
@@ -53,18 +324,18 @@ impl Device {
         Ok(())
     }
 
-    fn read_control(&self) -> Result<Control> {
-        let value = Control::from_bits_retain(self.read_user_u32(axi::ADDR_CONTROL)?);
+    fn read_control(&self) -> Result<axi::Control> {
+        let value = axi::Control::from_bits_retain(self.read_user_u32(axi::ADDR_CONTROL)?);
         log::debug!("read_control() = {:?}", value);
         Ok(value)
     }
 
-    fn write_control(&self, value: Control) -> Result<()> {
+    fn write_control(&self, value: axi::Control) -> Result<()> {
         log::debug!("write_control({:?})", value);
         Ok(self.write_user_u32(axi::ADDR_CONTROL, value.bits())?)
     }
 
-    fn modify_control<F: FnOnce(&mut Control)>(&self, f: F) -> Result<()> {
+    fn modify_control<F: FnOnce(&mut axi::Control)>(&self, f: F) -> Result<()> {
         let mut value = self.read_control()?;
         f(&mut value);
         self.write_control(value)
@@ -76,56 +347,9 @@ impl Device {
         Ok(value)
     }
 
-    fn write_fifo(&self, data: &[u8]) -> Result<()> {
-        log::trace!("write_fifo({:02x?})", data);
-        // enqueue data into the FIFO
-        for &byte in data {
-            self.write_user_u32(axi::ADDR_FIFO_TDFD, byte as u32)?;
-        }
-        // start transmission; the FIFO is configured for 32-bit datapath length, but the top
-        // three bytes are ignored by the SPI/I2C gateware connected to it
-        self.write_user_u32(axi::ADDR_FIFO_TLR, data.len() as u32 * 4)?;
-        // clear transmit complete flag
-        self.write_user_u32(axi::ADDR_FIFO_ISR, FifoIsr::TC.bits())?;
-        // wait for the packet to be transmitted
-        loop {
-            let isr = FifoIsr::from_bits_retain(self.read_user_u32(axi::ADDR_FIFO_ISR)?);
-            assert!(!isr.contains(FifoIsr::TPOE), "Transmit FIFO overflow! ISR = {:?}", isr);
-            if isr.contains(FifoIsr::TC) { break } // done!
-        }
-        Ok(())
-    }
-
-    fn write_i2c(&self, i2c_addr: u8, data: &[u8]) -> Result<()> {
-        log::debug!("write_i2c({:#08b}, {:02x?})", i2c_addr, data);
-        let mut packet = Vec::<u8>::new();
-        packet.push(0xff);        // select I2C
-        packet.push(i2c_addr);
-        packet.extend_from_slice(data);
-        self.write_fifo(packet.as_ref())?;
-        // the I2C engine doesn't use TLAST to detect packet boundaries and runs at 400 kHz;
-        // make sure the engine is  done before releasing it. the delay has a 100% safety factor.
-        thread::sleep(Duration::from_micros((50 * data.len()) as u64));
-        Ok(())
-    }
-
-    // bus 0 (0xfd): ADC
-    // bus 2..5 (0xfb..0xf7): PGAn
-    fn write_spi(&self, spi_bus: u8, data: &[u8]) -> Result<()> {
-        log::debug!("write_spi({:?}, {:02x?})", spi_bus, data);
-        let mut packet = Vec::<u8>::new();
-        packet.push(0xfd - spi_bus);
-        packet.extend_from_slice(data);
-        self.write_fifo(packet.as_ref())?;
-        // the SPI engine doesn't use TLAST either, but it runs at 16 MHz. the delay is enough
-        // for 160 bytes.
-        thread::sleep(Duration::from_micros(10));
-        Ok(())
-    }
-
     fn write_pll_register(&self, reg_addr: u16, value: u8) -> Result<()> {
         log::debug!("write_pll_register({:#06x}, {:#04x})", reg_addr, value);
-        self.write_i2c(0b11101000, &[
+        bus::i2c_write(&self.driver, 0b11101000, &[
             0x02,                  // register write
             (reg_addr >> 8) as u8, // register address high
             (reg_addr >> 0) as u8, // register address low
@@ -142,7 +366,7 @@ impl Device {
 
     fn write_adc_register(&self, reg_addr: u8, value: u16) -> Result<()> {
         log::debug!("write_adc_register({:#04x}, {:#06x})", reg_addr, value);
-        self.write_spi(SPI_BUS_ADC, &[
+        bus::spi_write(&self.driver, SPI_BUS_ADC, &[
             reg_addr,
             (value >> 8) as u8,
             (value >> 0) as u8,
@@ -156,77 +380,84 @@ impl Device {
         Ok(())
     }
 
-    fn enable_adc_channels(&self, enabled: [bool; 4]) -> Result<()> {
-        log::debug!("enable_adc_channels({:?})", enabled);
-        // compute number of enabled ADC channels and ADC clock divisor
-        // channels CH1..CH4 on the faceplate are mapped to IN4..IN1 on the ADC, so this function
-        // has to perform a really annoying permutation
-        let clkdiv; // in ADC
+    fn enable_adc_channels(&self, channel_map: &ChannelMap, clock_divisor: usize,
+                           resolution: Resolution) -> Result<()> {
+        log::debug!("enable_adc_channels({:?}, {}, {:?})", channel_map, clock_divisor, resolution);
+        // compute number of enabled ADC channels and ADC clock divisor (encoded as its log2)
+        let clkdiv = clock_divisor.trailing_zeros() as u16; // in ADC
         let chnum;  // in ADC
         let chmux;  // in FPGA
-        match enabled.iter().map(|&en| en as u8).sum() {
-            1 => { clkdiv = 0; chnum = 1; chmux = Control::empty(); }
-            2 => { clkdiv = 1; chnum = 2; chmux = Control::ChannelMux0; }
-            3 => { clkdiv = 2; chnum = 4; chmux = Control::ChannelMux1; } // same as 4
-            4 => { clkdiv = 2; chnum = 4; chmux = Control::ChannelMux1; }
-            _ => panic!("unsupported channel configuration"),
-        };
-        // compute ADC input select permutation
-        let insel = match chnum {
-            1 => {
-                let ch1_index = enabled.iter().rev().position(|&en| en).unwrap();
-                [ch1_index, ch1_index, ch1_index, ch1_index]
-            }
-            2 => {
-                let ch1_index = enabled.iter().rev().position(|&en| en).unwrap();
-                let ch2_index = ch1_index + 1 +
-                    enabled.iter().rev().skip(ch1_index + 1).position(|&en| en).unwrap();
-                // this is permuted later again
-                // the (faceplate) channel order in the data is ch1,ch2,ch1,ch2
-                [ch2_index, ch2_index, ch1_index, ch1_index]
-            }
-            4 => {
-                // the (faceplate) channel order in the data is ch1,ch2,ch3,ch4
-                [3, 2, 1, 0]
-            }
+        match channel_map.stream_channels() {
+            1 => { chnum = 1; chmux = axi::Control::empty(); }
+            2 => { chnum = 2; chmux = axi::Control::ChannelMux0; }
+            4 => { chnum = 4; chmux = axi::Control::ChannelMux1; }
             _ => unreachable!()
         };
+        assert!(clock_divisor.is_power_of_two() && clock_divisor >= chnum as usize &&
+            clock_divisor <= 8);
+        // compute ADC input select permutation; channels CH1..CH4 on the faceplate are mapped
+        // to IN4..IN1 on the ADC, and the (faceplate) channel order in the data is
+        // ch1,ch2,ch1,ch2 or ch1,ch2,ch3,ch4
+        let insel = channel_map.adc_insel();
+        let (res_sel, high_resolution) = match resolution { // in ADC and FPGA
+            Resolution::Bits8  => (adc::HMCAD1520_RES_SEL_8BIT,  axi::Control::empty()),
+            Resolution::Bits12 => (adc::HMCAD1520_RES_SEL_12BIT, axi::Control::HighResolution),
+        };
         // reconfigure ADC
         self.init_adc_registers(&[
             // power down ADC
             (adc::ADDR_HMCAD1520_POWER, 0x0200),
             // configure clock divisor and channel count
             (adc::ADDR_HMCAD1520_CHNUM_CLKDIV, (clkdiv << 8) | chnum),
+            // configure output resolution
+            (adc::ADDR_HMCAD1520_RES_SEL, res_sel),
             // power up ADC
             (adc::ADDR_HMCAD1520_POWER, 0x0000),
             // configure channel mapping
             (adc::ADDR_HMCAD1520_INSEL12, 0x0200 << insel[1] | 0x0002 << insel[0]),
             (adc::ADDR_HMCAD1520_INSEL34, 0x0200 << insel[3] | 0x0002 << insel[2]),
         ])?;
-        // reconfigure channel mux in the FPGA
+        // reconfigure channel mux and sample width in the FPGA
         self.modify_control(|val| {
-            val.remove(Control::ChannelMux0 | Control::ChannelMux1);
-            val.insert(chmux);
+            val.remove(axi::Control::ChannelMux0 | axi::Control::ChannelMux1 |
+                axi::Control::HighResolution);
+            val.insert(chmux | high_resolution);
         })?;
         Ok(())
     }
 
     fn write_pga_command(&self, pga_bus: u8, command: u16) -> Result<()> {
         log::debug!("write_pga_command({:?}, {:#06x})", pga_bus, command);
-        self.write_spi(pga_bus, &[
+        bus::spi_write(&self.driver, pga_bus, &[
             0x00, // write command word
             (command >> 8) as u8,
             (command >> 0) as u8,
         ])
    }
 
-   fn configure_pga(&self, index: usize, params: &ChannelParameters) -> Result<()> {
-        self.write_pga_command(SPI_BUS_PGA[index],
+    fn read_pga_command(&self, pga_bus: u8) -> Result<u16> {
+        let data = bus::spi_transfer(&self.driver, pga_bus, &[
+            0x80, // read command word
+            0x00,
+            0x00,
+        ])?;
+        let command = (data[1] as u16) << 8 | (data[2] as u16);
+        log::debug!("read_pga_command({:?}) = {:#06x}", pga_bus, command);
+        Ok(command)
+    }
+
+   fn configure_pga(&self, shadow: &mut Shadow, index: usize,
+                    params: &ChannelParameters) -> Result<()> {
+        let command =
             (1 << 10) | // always turn off auxiliary output to save power
             params.filtering.lmh6518_code() |
             params.amplification.lmh6518_code() |
-            params.fine_attenuation.lmh6518_code()
-        )
+            params.fine_attenuation.lmh6518_code();
+        if shadow.pga_commands[index] != Some(command) {
+            self.write_pga_command(SPI_BUS_PGA[index], command)?;
+            shadow.pga_commands[index] = Some(command);
+        }
+        Ok(())
     }
 
    fn write_digipot_input(&self, addr: u8, input: u16) -> Result<()> {
@@ -234,114 +465,322 @@ impl Device {
             ((addr as u16) << 12) | // device address
             (0b00 << 10) | // write
             ((input & 0x3ff) << 0);
-        self.write_i2c(0b0101100, &[
+        bus::i2c_write(&self.driver, 0b0101100, &[
             (command_data >> 8) as u8,
             (command_data >> 0) as u8,
         ])
    }
 
+    fn read_digipot_input(&self, addr: u8) -> Result<u16> {
+        let data = bus::i2c_write_read(&self.driver, 0b0101100, &[
+            (addr << 4) | // device address
+            (0b11 << 2)   // read
+        ], 2)?;
+        let input = ((data[0] as u16) << 8 | (data[1] as u16)) & 0x3ff;
+        log::debug!("read_digipot_input({:?}) = {:#06x}", addr, input);
+        Ok(input)
+    }
+
    fn write_trimdac_input(&self, channel: u8, input: u16) -> Result<()> {
         log::debug!("write_trimdac_input({:?}, {:#06x})", channel, input);
-        self.write_i2c(0b1100000, &[
+        bus::i2c_write(&self.driver, 0b1100000, &[
             0b01011_00_0 | ((channel & 0b11) << 1),
             (input >> 8) as u8,
             (input >> 0) as u8,
         ])
    }
 
-   fn configure_digipot_trimdac(&self, index: usize, params: &ChannelParameters) -> Result<()> {
-        const WIPER_ADDRESS: [u8; 4] = [0x6, 0x0, 0x1, 0x7];
-        self.write_digipot_input(WIPER_ADDRESS[index],
-            params.offset_magnitude.mcp4432t_503e_code())?;
-        self.write_trimdac_input(index as u8,
+    fn read_trimdac_inputs(&self) -> Result<[u16; 4]> {
+        // for each channel, the DAC input register and then the EEPROM, 3 bytes each; the first
+        // byte of each is a status byte
+        let data = bus::i2c_write_read(&self.driver, 0b1100000, &[], 24)?;
+        let inputs = [0, 1, 2, 3].map(|channel|
+            (data[channel * 6 + 1] as u16) << 8 | (data[channel * 6 + 2] as u16));
+        log::debug!("read_trimdac_inputs() = {:#06x?}", inputs);
+        Ok(inputs)
+    }
+
+   fn configure_digipot_trimdac(&self, shadow: &mut Shadow, index: usize,
+                                params: &ChannelParameters) -> Result<()> {
+        let digipot_input = params.offset_magnitude.mcp4432t_503e_code();
+        if shadow.digipot_inputs[index] != Some(digipot_input) {
+            self.write_digipot_input(WIPER_ADDRESS[index], digipot_input)?;
+            shadow.digipot_inputs[index] = Some(digipot_input);
+        }
+        let trimdac_input =
             (1 << 15) | // always use Vref as reference
-            params.offset_value.mcp4728_code()
-        )?;
+            params.offset_value.mcp4728_code();
+        if shadow.trimdac_inputs[index] != Some(trimdac_input) {
+            self.write_trimdac_input(index as u8, trimdac_input)?;
+            shadow.trimdac_inputs[index] = Some(trimdac_input);
+        }
         Ok(())
     }
 
     fn enable_datamover(&self) -> Result<()> {
         // take the acquisition system out of reset
-        self.modify_control(|val| val.insert(Control::DatamoverHaltN | Control::FpgaAcqResetN))?;
+        self.modify_control(|val|
+            val.insert(axi::Control::DatamoverHaltN | axi::Control::FpgaAcqResetN))?;
         Ok(())
     }
 
     fn disable_datamover(&self) -> Result<()> {
+        // any data streamed from now on is discontinuous with the data streamed before
+        self.generation.fetch_add(1, Ordering::AcqRel);
         // halt the data mover
-        self.modify_control(|val| val.remove(Control::DatamoverHaltN))?;
+        self.modify_control(|val| val.remove(axi::Control::DatamoverHaltN))?;
         // wait for data mover to halt
         thread::sleep(Duration::from_millis(5));
         // reset the acquisition subsystem
-        self.modify_control(|val| val.remove(Control::FpgaAcqResetN))?;
+        self.modify_control(|val| val.remove(axi::Control::FpgaAcqResetN))?;
         Ok(())
     }
 
+    /// Reset the acquisition subsystem, and read the first page of samples acquired afterwards.
+    fn read_first_page(&self) -> Result<Vec<u8>> {
+        self.disable_datamover()?;
+        self.enable_datamover()?;
+        let started_at = Instant::now();
+        while self.read_status()?.pages_moved() == 0 {
+            if started_at.elapsed() > ADC_PATTERN_TIMEOUT {
+                return Err(Error::Timeout)
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+        let mut page = vec![0; 1 << PAGE_BITS];
+        self.driver.read_dma(0, &mut page)?;
+        Ok(page)
+    }
+
+    /// Check that the gateware frames the samples received from the ADC correctly, using
+    /// the deskew pattern (which detects a slip by an odd amount of bits) and the sync pattern
+    /// (which detects the rest). Returns a description of the misalignment, if any.
+    fn check_adc_alignment(&self) -> Result<Option<String>> {
+        for (name, pattern, expected) in [
+            ("deskew", adc::HMCAD1520_PAT_DESKEW, adc::HMCAD1520_DESKEW_PATTERN),
+            ("sync", adc::HMCAD1520_PAT_SYNC, adc::HMCAD1520_SYNC_PATTERN),
+        ] {
+            self.write_adc_register(adc::ADDR_HMCAD1520_PAT_DESKEW_SYNC, pattern)?;
+            let page = self.read_first_page()?;
+            if page.iter().all(|&sample| sample == expected) {
+                continue
+            }
+            let slip = (1..8).find(|&slip|
+                page.iter().all(|&sample| sample == expected.rotate_left(slip)));
+            return Ok(Some(match slip {
+                Some(slip) => format!("bit slip of {} in {} pattern", slip, name),
+                None => format!("{} pattern {:#04x} reads as {:02x?}", name, expected, &page[..8]),
+            }))
+        }
+        Ok(None)
+    }
+
+    /// Train the LVDS link between the ADC and the FPGA, trying each phase of the LVDS clock
+    /// until the test patterns are received intact. The ADC must be powered up.
+    fn train_adc_link(&self) -> Result<()> {
+        // the test patterns would be inverted as well
+        self.write_adc_register(adc::ADDR_HMCAD1520_INVERT, 0x0000)?;
+        let mut misalignment = None;
+        for lvds_phase in adc::HMCAD1520_LVDS_PHASES {
+            self.write_adc_register(adc::ADDR_HMCAD1520_LVDS_PHASE, lvds_phase)?;
+            misalignment = self.check_adc_alignment()?;
+            let Some(description) = misalignment.as_ref() else { break };
+            log::warn!("ADC link misaligned at LVDS phase {:#06x}: {}", lvds_phase, description);
+            self.events.record(EventKind::AdcLinkMisaligned { lvds_phase });
+        }
+        self.init_adc_registers(&[
+            // disable test patterns
+            (adc::ADDR_HMCAD1520_PAT_DESKEW_SYNC, 0x0000),
+            // invert channels
+            (adc::ADDR_HMCAD1520_INVERT, 0x007F),
+        ])?;
+        // discard the test patterns
+        self.disable_datamover()?;
+        self.enable_datamover()?;
+        match misalignment {
+            None => Ok(()),
+            Some(description) =>
+                Err(Error::Other(format!("ADC link training failed: {}", description).into())),
+        }
+    }
+
+    /// Configure the device. Only the parts of the configuration that have changed are written,
+    /// so e.g. changing the gain of a channel takes well under a millisecond. If the set of
+    /// enabled channels changes, streams that are open are restarted, as with
+    /// `DataStream::restart()`.
     pub fn configure(&self, params: &DeviceParameters) -> Result<()> {
+        let mut shadow = self.lock();
+        self.configure_locked(&mut shadow, params)
+    }
+
+    fn configure_locked(&self, shadow: &mut Shadow, params: &DeviceParameters) -> Result<()> {
         if *params == Default::default() {
             log::info!("configure(DeviceParameters::default())");
         } else {
             log::info!("configure({:#?})", params);
         }
+        if params.resolution != Resolution::Bits8 && !self.identify()?.high_resolution {
+            log::error!("configure(): gateware does not support {:?}", params.resolution);
+            return Err(Error::Unsupported)
+        }
         // configure the PGAs first; this keeps current consumption in check for the initial
         // `configure()` call from `startup()` by turning off the PGA aux outputs that (for all
         // PGAs together) consume almost 2W
         for (index, ch_params) in params.channels.iter().enumerate() {
             let ch_params = ch_params.unwrap_or_default();
-            self.configure_pga(index, &ch_params)?;
+            self.configure_pga(shadow, index, &ch_params)?;
         }
         // configure termination, coupling, and attenuator
-        for (index, ch_params) in params.channels.iter().enumerate() {
-            let ch_params = ch_params.unwrap_or_default();
-            self.modify_control(|val| {
+        self.modify_control(|val| {
+            for (index, ch_params) in params.channels.iter().enumerate() {
+                let ch_params = ch_params.unwrap_or_default();
                 match ch_params.termination {
-                    Termination::Ohm1M => val.remove(Control::ch_termination(index)),
-                    Termination::Ohm50 => val.insert(Control::ch_termination(index)),
+                    Termination::Ohm1M => val.remove(axi::Control::ch_termination(index)),
+                    Termination::Ohm50 => val.insert(axi::Control::ch_termination(index)),
                 }
                 match ch_params.coupling {
-                    Coupling::AC => val.remove(Control::ch_coupling(index)),
-                    Coupling::DC => val.insert(Control::ch_coupling(index)),
+                    Coupling::AC => val.remove(axi::Control::ch_coupling(index)),
+                    Coupling::DC => val.insert(axi::Control::ch_coupling(index)),
                 }
                 match ch_params.coarse_attenuation {
-                    CoarseAttenuation::X50 => val.remove(Control::ch_attenuator(index)),
-                    CoarseAttenuation::X1  => val.insert(Control::ch_attenuator(index)),
+                    CoarseAttenuation::X50 => val.remove(axi::Control::ch_attenuator(index)),
+                    CoarseAttenuation::X1  => val.insert(axi::Control::ch_attenuator(index)),
                 }
-            })?;
-        }
+            }
+        })?;
         // configure voltage offset
         for (index, ch_params) in params.channels.iter().enumerate() {
             let ch_params = ch_params.unwrap_or_default();
-            self.configure_digipot_trimdac(index, &ch_params)?;
+            self.configure_digipot_trimdac(shadow, index, &ch_params)?;
         }
-        // put data mover into reset (it cannot run without ADC clock or tolerate glitches on it)
-        self.disable_datamover()?;
-        // configure the ADC input selector, clock divisor, channel mapping, and FPGA data mux
-        // this disables data mover first and (re-)enables it after
-        self.enable_adc_channels([
-            params.channels[0].is_some(),
-            params.channels[1].is_some(),
-            params.channels[2].is_some(),
-            params.channels[3].is_some(),
-        ])?;
-        // take data mover out of reset now that ADC clock is available (again)
-        self.enable_datamover()?;
+        let channel_map = ChannelMap::from_params(params);
+        let clock_divisor = params.adc_clock_divisor();
+        if shadow.channel_map != Some(channel_map) ||
+                shadow.adc_clock_divisor != Some(clock_divisor) ||
+                shadow.resolution != Some(params.resolution) {
+            // streams pick up the new rate and sample size once they see that the data mover has
+            // been reset
+            self.stream_sample_rate.store(params.stream_sample_rate(), Ordering::Release);
+            self.stream_sample_size.store(params.resolution.sample_size(), Ordering::Release);
+            // put data mover into reset (it cannot run without ADC clock or tolerate glitches)
+            self.disable_datamover()?;
+            // configure the ADC input selector, clock divisor, channel mapping, resolution, and
+            // FPGA data mux
+            self.enable_adc_channels(&channel_map, clock_divisor, params.resolution)?;
+            // take data mover out of reset now that ADC clock is available (again), unless
+            // acquisition is paused
+            if !shadow.paused {
+                self.enable_datamover()?;
+            }
+            shadow.channel_map = Some(channel_map);
+            shadow.adc_clock_divisor = Some(clock_divisor);
+            shadow.resolution = Some(params.resolution);
+        }
+        self.events.record(EventKind::Configured(*params));
         Ok(())
     }
 
+    /// Read back the PGA, digipot, and trimdac registers, and check that they contain the values
+    /// most recently written by `configure()`. The ADC registers are write-only and are not
+    /// checked.
+    ///
+    /// Returns an error describing every mismatch if any are found, which indicates a problem
+    /// with the SPI or I2C bus, or a device that has been reset. Returns `Error::Unsupported` if
+    /// the gateware cannot read the registers back (see `DeviceCapabilities::bus_readback`).
+    pub fn verify(&self) -> Result<()> {
+        let shadow = self.lock();
+        log::info!("verify()");
+        if !self.identify()?.bus_readback {
+            return Err(Error::Unsupported)
+        }
+        let mut mismatches = Vec::new();
+        let mut check = |name: &str, index: usize, expected: Option<u16>, actual: u16| {
+            match expected {
+                Some(expected) if expected != actual =>
+                    mismatches.push(format!("CH{} {} is {:#06x} instead of {:#06x}",
+                        index + 1, name, actual, expected)),
+                _ => ()
+            }
+        };
+        let trimdac_inputs = self.read_trimdac_inputs()?;
+        for index in 0..4 {
+            check("PGA command", index, shadow.pga_commands[index],
+                self.read_pga_command(SPI_BUS_PGA[index])?);
+            check("digipot input", index, shadow.digipot_inputs[index],
+                self.read_digipot_input(WIPER_ADDRESS[index])?);
+            check("trimdac input", index, shadow.trimdac_inputs[index],
+                trimdac_inputs[index]);
+        }
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            log::error!("verify(): {}", mismatches.join(", "));
+            Err(Error::Other(format!("configuration read back incorrectly: {}",
+                mismatches.join(", ")).into()))
+        }
+    }
+
+    /// Read the version and capabilities of the gateware. This can be done whether or not
+    /// the device is started up.
+    pub fn identify(&self) -> Result<DeviceCapabilities> {
+        let id = self.read_user_u32(axi::ADDR_GATEWARE_ID)?;
+        let capabilities = if id == 0 {
+            // gateware that predates the identification registers always has these
+            DeviceCapabilities {
+                gateware_version: None,
+                memory_size: MEMORY_SIZE,
+                channels: 4,
+                max_sample_rate: 1e9,
+                high_resolution: false,
+                bus_readback: false,
+            }
+        } else if (id >> 16) as u16 != axi::GATEWARE_ID_MAGIC {
+            return Err(Error::IncompatibleGateware(
+                format!("unrecognized identification register value {:#010x}", id)))
+        } else {
+            let caps = axi::GatewareCaps(self.read_user_u32(axi::ADDR_GATEWARE_CAPS)?);
+            DeviceCapabilities {
+                gateware_version: Some(((id >> 8) as u8, id as u8)),
+                memory_size: 1 << caps.memory_size_log2(),
+                channels: caps.channels(),
+                max_sample_rate: caps.max_sample_rate() as f32 * 1e6,
+                high_resolution: caps.high_resolution(),
+                bus_readback: caps.bus_readback(),
+            }
+        };
+        log::debug!("identify() = {:?}", capabilities);
+        Ok(capabilities)
+    }
+
+    /// Start up the device. Returns `Error::IncompatibleGateware` without changing anything if
+    /// the gateware is not compatible with this driver.
     pub fn startup(&self) -> Result<()> {
+        let mut shadow = self.lock();
         log::info!("startup()");
+        let capabilities = self.identify()?;
+        log::info!("{}", capabilities);
+        if let Some(reason) = capabilities.incompatibility() {
+            log::error!("incompatible gateware: {}", reason);
+            return Err(Error::IncompatibleGateware(reason))
+        }
+        // the device is reset, so everything has to be written
+        *shadow = Shadow::default();
+        self.events.record(EventKind::Startup);
         // disable the data mover first and let it stop, in case it was running before
         // this prevents device crashes after unclean shutdowns (think ^C)
         self.disable_datamover()?;
         // enable the 3V3 rail and wait for it to stabilize
-        self.modify_control(|val| val.insert(Control::ClockGenResetN | Control::Rail3V3Enabled))?;
+        self.modify_control(|val|
+            val.insert(axi::Control::ClockGenResetN | axi::Control::Rail3V3Enabled))?;
+        self.events.record(EventKind::Rail3V3Enabled);
         thread::sleep(Duration::from_millis(10));
         // The RSTN pin must be asserted once after power-up.
         // Reset should be asserted for at least 1μs.
-        self.modify_control(|val| val.remove(Control::ClockGenResetN))?;
+        self.modify_control(|val| val.remove(axi::Control::ClockGenResetN))?;
         thread::sleep(Duration::from_micros(100));
         // System software must wait at least 100μs after RSTN is deasserted
         // and wait for GLOBISR.BCDONE=1 before configuring the device.
-        self.modify_control(|val| val.insert(Control::ClockGenResetN))?;
+        self.modify_control(|val| val.insert(axi::Control::ClockGenResetN))?;
         thread::sleep(Duration::from_millis(1));
         // configure the PLL using the Rev4 blob
         self.init_pll_registers(&[
@@ -388,52 +827,321 @@ impl Device {
         // this causes a current spike due to PGA aux output being enabled by default, and *must*
         // be quickly followed by a call to `configure()` (with any parameters) to disable that
         // output as soon as possible, or risk an overcurrent condition
-        self.modify_control(|val| val.insert(Control::Rail5VEnabled))?;
+        self.modify_control(|val| val.insert(axi::Control::Rail5VEnabled))?;
+        self.events.record(EventKind::Rail5VEnabled);
         thread::sleep(Duration::from_millis(5));
         // configure to a known (default) state
         // this also enables the data mover
-        self.configure(&DeviceParameters::default())?;
+        self.configure_locked(&mut shadow, &DeviceParameters::default())?;
+        // check that the samples are framed correctly instead of assuming it
+        self.train_adc_link()?;
         // done!
         Ok(())
     }
 
+    /// Pause acquisition by halting the data mover and holding the acquisition subsystem in
+    /// reset. The configuration of the device is retained, and so is any data that has already
+    /// been read from streams; streams return no data until acquisition is resumed.
+    ///
+    /// As with `DataStream::restart()`, the data acquired after resuming is discontinuous with
+    /// the data acquired before pausing, and the stream position is advanced to a frame boundary.
+    /// If the device is reconfigured while acquisition is paused, it stays paused.
+    pub fn pause(&self) -> Result<()> {
+        let mut shadow = self.lock();
+        log::info!("pause()");
+        if !shadow.paused {
+            self.disable_datamover()?;
+            shadow.paused = true;
+            self.events.record(EventKind::AcquisitionPaused);
+        }
+        Ok(())
+    }
+
+    /// Resume acquisition after `pause()`.
+    pub fn resume(&self) -> Result<()> {
+        let mut shadow = self.lock();
+        log::info!("resume()");
+        if shadow.paused {
+            // streams may have read the position of the data mover while it was in reset
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            self.enable_datamover()?;
+            shadow.paused = false;
+            self.events.record(EventKind::AcquisitionResumed);
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// Measure the deviation of the sample clock from its nominal frequency, in parts per
+    /// million, by comparing the rate at which the data mover moves pages against the host
+    /// monotonic clock over `gate`. The result is meant for `DeviceCalibration::clock_ppm`.
+    ///
+    /// The device must be acquiring. No samples are read, so this can be done while streaming,
+    /// but not while the device is being reconfigured. The measurement starts and ends when
+    /// a page has just been moved, so its uncertainty is roughly the time it takes to read
+    /// a register (a few microseconds) divided by `gate`; a gate of several seconds is needed
+    /// for a useful result.
+    pub fn measure_clock_ppm(&self, gate: Duration) -> Result<f32> {
+        let generation = self.generation();
+        let bytes_per_second = self.stream_sample_rate.load(Ordering::Acquire) *
+            self.stream_sample_size.load(Ordering::Acquire) as u64;
+        let (mut pages, started_at) = self.wait_for_page()?;
+        let mut moved = 0;
+        let mut advance = |next_pages: usize| {
+            moved += (next_pages + (MEMORY_SIZE >> PAGE_BITS) - pages) % (MEMORY_SIZE >> PAGE_BITS);
+            pages = next_pages;
+        };
+        while started_at.elapsed() < gate {
+            thread::sleep(CLOCK_POLL_INTERVAL);
+            advance(self.read_pages_moved()?);
+        }
+        let (next_pages, ended_at) = self.wait_for_page()?;
+        advance(next_pages);
+        if self.generation() != generation {
+            return Err(Error::Other("device was reconfigured while measuring clock".into()))
+        }
+        let expected = (ended_at - started_at).as_secs_f64() * bytes_per_second as f64 /
+            (1 << PAGE_BITS) as f64;
+        let ppm = ((moved as f64 / expected - 1.0) * 1e6) as f32;
+        log::info!("measure_clock_ppm(): {} pages in {:?}, {:+.2} ppm",
+            moved, ended_at - started_at, ppm);
+        Ok(ppm)
+    }
+
+    fn read_pages_moved(&self) -> Result<usize> {
+        let status = self.read_status()?;
+        if status.intersects(Status::FifoOverflow | Status::DatamoverError) {
+            return Err(Error::Other(format!("data mover failure: {:?}", status).into()))
+        }
+        Ok(status.pages_moved())
+    }
+
+    /// Returns the page counter of the data mover right after it changes, and when that was.
+    fn wait_for_page(&self) -> Result<(usize, Instant)> {
+        let started_at = Instant::now();
+        let initial_pages = self.read_pages_moved()?;
+        loop {
+            let pages = self.read_pages_moved()?;
+            let now = Instant::now();
+            if pages != initial_pages {
+                return Ok((pages, now))
+            } else if now - started_at > ADC_PATTERN_TIMEOUT {
+                return Err(Error::Timeout)
+            }
+        }
+    }
+
     pub fn shutdown(&self) -> Result<()> {
+        let mut shadow = self.lock();
         log::info!("shutdown()");
+        *shadow = Shadow::default();
+        self.events.record(EventKind::Shutdown);
         // disable the data mover first and let it stop, since it runs on ADC clock
         self.disable_datamover()?;
         // power down the frontend 5V0 and board 3V3
-        self.write_control(Control::empty())?;
+        self.write_control(axi::Control::empty())?;
+        self.events.record(EventKind::RailsDisabled);
         Ok(())
     }
+}
+
+impl Device {
+    /// Returns a handle for streaming data from the device, which may be sent to another thread.
+    pub fn stream_data(&self) -> DataStream {
+        let control = self.control.clone();
+        let generation = control.generation();
+        DataStream { control, generation, cursor: None, position: 0, timestamp: None }
+    }
+
+    /// Stream data, calling `callback` with each chunk of newly acquired samples until it returns
+    /// `ControlFlow::Break` or an error.
+    ///
+    /// The samples are read into an internal ring buffer; the chunk passed to `callback` is
+    /// overwritten once it returns. Only 8-bit samples can be read this way; for 16-bit ones,
+    /// use `stream_data()` with a `RingBuffer` and `RingBuffer::read_i16()`.
+    pub fn read_data<F, R>(&self, mut callback: F) -> Result<R>
+            where F: FnMut(&[i8]) -> Result<ControlFlow<R>> {
+        let mut buffer = RingBuffer::new(READ_DATA_BUFFER_SIZE)?;
+        let mut stream = self.stream_data();
+        loop {
+            let cursor = buffer.cursor();
+            let length = buffer.append(buffer.len(), |chunk| stream.read(chunk))?;
+            if length == 0 {
+                // no new data yet; if events are not supported, avoid pegging a core
+                thread::sleep(Duration::from_micros(100));
+                continue
+            }
+            if let ControlFlow::Break(result) = callback(buffer.read(cursor, length))? {
+                return Ok(result)
+            }
+        }
+    }
+
+    /// Like `stream_data()`, but returns a stream that can be read without blocking a thread.
+    /// Must be used within a Tokio runtime with the timer enabled.
+    #[cfg(feature = "tokio")]
+    pub fn stream_data_async(&self) -> crate::AsyncStreamer {
+        crate::AsyncStreamer::new(self.stream_data())
+    }
+}
+
+/// A device that has been started up, and that will be shut down when the guard is dropped.
+///
+/// Shutting the device down halts the data mover and powers down the frontend, which protects
+/// the hardware if the code using the device panics. This requires `panic = "unwind"`.
+#[derive(Debug)]
+pub struct DeviceGuard {
+    device: Device,
+    armed: bool,
+}
+
+impl DeviceGuard {
+    /// Shut down the device, reporting any errors (unlike dropping the guard).
+    pub fn shutdown(mut self) -> Result<()> {
+        self.armed = false;
+        self.device.shutdown()
+    }
+}
+
+impl Deref for DeviceGuard {
+    type Target = Device;
+
+    fn deref(&self) -> &Device {
+        &self.device
+    }
+}
 
-    pub fn stream_data<'a>(&'a self) -> Streamer<'a> {
-        Streamer { device: self, cursor: None }
+impl DerefMut for DeviceGuard {
+    fn deref_mut(&mut self) -> &mut Device {
+        &mut self.device
     }
 }
 
+impl Drop for DeviceGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            if thread::panicking() {
+                log::warn!("shutting down device after a panic");
+            }
+            if let Err(error) = self.device.shutdown() {
+                log::error!("failed to shut down device: {}", error);
+            }
+        }
+        interrupt::uninstall();
+    }
+}
+
+/// A handle for streaming data from the device.
+///
+/// If the device is reconfigured (which resets the data mover) while data is being streamed,
+/// the stream continues with the data acquired after the reconfiguration; the stream position
+/// is advanced as for `DataStream::restart()`.
 #[derive(Debug)]
-pub struct Streamer<'a> {
-    device: &'a Device,
+pub struct DataStream {
+    control: Control,
+    // the generation of `control` that `cursor` is valid for
+    generation: u64,
     cursor: Option<usize>,
+    position: u64,
+    timestamp: Option<Timestamp>,
 }
 
-impl<'a> std::io::Read for Streamer<'a> {
-    fn read(&mut self, mut buffer: &mut [u8]) -> std::io::Result<usize> {
-        const PAGE_BITS: usize = 12; // 4 Ki
-        const MEMORY_SIZE: usize = 1 << 16 << PAGE_BITS; // 64 Ki x (1 << PAGE_BITS) = 256 Mi
+impl DataStream {
+    /// Returns the offset of the next byte that will be read from the stream. With 8-bit samples,
+    /// this is the index of the next sample.
+    ///
+    /// The first byte read from the stream has offset 0.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the most recent correspondence between a stream position and host time, or
+    /// `None` if no data has been acquired yet.
+    ///
+    /// The timestamp is taken when the data mover reports that new data is available, which
+    /// bounds its error by the latency of a status register read and a page transfer.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    pub(crate) fn control(&self) -> &Control {
+        &self.control
+    }
+
+    /// Restart acquisition by halting the data mover and resetting the acquisition subsystem.
+    /// This is done automatically when the data mover fails (see `Error::Overflow`).
+    ///
+    /// Samples acquired around the time of the restart are lost. To keep the stream position
+    /// aligned to frames (see `Capture`), it is advanced to the next multiple of four samples.
+    pub fn restart(&mut self) -> Result<()> {
+        log::info!("restarting acquisition");
+        {
+            let shadow = self.control.lock();
+            self.control.disable_datamover()?;
+            if !shadow.paused {
+                self.control.enable_datamover()?;
+            }
+        }
+        self.control.events.record(EventKind::AcquisitionRestarted);
+        self.resynchronize();
+        Ok(())
+    }
+
+    /// Discard the cursor after the data mover has been reset.
+    fn resynchronize(&mut self) {
+        self.generation = self.control.generation();
+        self.cursor = None;
+        let sample_size = self.control.stream_sample_size.load(Ordering::Acquire);
+        self.position = self.position.next_multiple_of(4 * sample_size as u64);
+    }
 
+    /// Read the data that is already available, without waiting for the data mover.
+    pub(crate) fn read_available(&mut self, mut buffer: &mut [u8]) -> std::io::Result<usize> {
+        if interrupt::interrupted() {
+            return Err(Error::Interrupted.into())
+        }
         let mut written = 0;
         while buffer.len() > 0 {
+            if self.control.generation() != self.generation {
+                log::debug!("data mover has been reset, resynchronizing");
+                self.resynchronize();
+            }
             // check if there is an error condition set
             // these should never appear so long as the FPGA is functioning correctly
-            let status = self.device.read_status()?;
+            let status = self.control.read_status()?;
+            let next_cursor = status.pages_moved() << PAGE_BITS;
             if status.intersects(Status::FifoOverflow | Status::DatamoverError) {
-                log::error!("data mover failure, power cycle the device");
-                panic!("data mover failure: {:?} (overflow by {} cycles)",
+                if written > 0 {
+                    // the error bits are sticky, so the failure is reported by the next read
+                    break
+                }
+                log::error!("data mover failure: {:?} (overflow by {} cycles)",
                     status, status.overflow_cycles());
+                self.control.events.record(EventKind::DatamoverFailure {
+                    fifo_overflow: status.contains(Status::FifoOverflow),
+                    datamover_error: status.contains(Status::DatamoverError),
+                    overflow_cycles: status.overflow_cycles(),
+                });
+                let lost_pages = self.cursor.map_or(0, |prev_cursor|
+                    ((next_cursor + MEMORY_SIZE - prev_cursor) % MEMORY_SIZE)
+                        .div_ceil(1 << PAGE_BITS));
+                self.restart()?;
+                return Err(Error::Overflow { lost_pages }.into())
             }
             // read any newly available data
-            let next_cursor = status.pages_moved() << PAGE_BITS;
+            if let Some(prev_cursor) = self.cursor {
+                // the last sample before `next_cursor` has just been acquired
+                let pending = (next_cursor + MEMORY_SIZE - prev_cursor) % MEMORY_SIZE;
+                if pending > 0 {
+                    let sample_size = self.control.stream_sample_size.load(Ordering::Acquire);
+                    self.timestamp = Some(Timestamp::now(
+                        (self.position + pending as u64) / sample_size as u64 - 1,
+                        self.control.stream_sample_rate.load(Ordering::Acquire)));
+                }
+            }
             let (prev_cursor, length) = match self.cursor {
                 None => { // first ever read
                     self.cursor = Some(next_cursor);
@@ -445,11 +1153,17 @@ impl<'a> std::io::Read for Streamer<'a> {
                     (prev_cursor, buffer.len().min(next_cursor - prev_cursor)),
             };
             if length > 0 {
-                let (chunk, rest) = buffer.split_at_mut(length);
+                let chunk = &mut buffer[..length];
                 log::debug!("streaming {:#010x?}+{:#x?} to {:#x?}+{:#x?}",
                     prev_cursor, length, chunk.as_ptr(), chunk.len());
-                self.device.driver.read_dma(prev_cursor, chunk)?;
+                self.control.driver.read_dma(prev_cursor, chunk)?;
+                if self.control.generation() != self.generation {
+                    // the data mover has been reset while reading, so the chunk may be torn
+                    continue
+                }
+                let (_, rest) = buffer.split_at_mut(length);
                 self.cursor = Some((prev_cursor + length) % MEMORY_SIZE);
+                self.position += length as u64;
                 written += length;
                 buffer = rest;
             } else {
@@ -458,5 +1172,277 @@ impl<'a> std::io::Read for Streamer<'a> {
         }
         Ok(written)
     }
+}
+
+/// Reads the newly acquired data. If none is available, waits up to 1 ms for the data mover to
+/// signal that it has moved more data (where supported by the driver), and returns 0 if it
+/// has not.
+///
+/// If the data mover fails, acquisition is restarted and `Error::Overflow` is returned; reading
+/// may continue afterwards.
+impl std::io::Read for DataStream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.read_available(buffer)?;
+        if length > 0 || buffer.is_empty() {
+            return Ok(length)
+        }
+        if self.control.driver.wait_event(EVENT_TIMEOUT)? {
+            self.read_available(buffer)
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::params::Amplification;
+
+    #[test]
+    fn test_simulated_stream() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        device.configure(&DeviceParameters::default()).unwrap();
+        let mut stream = device.stream_data();
+        let mut samples = vec![0u8; 1 << 20];
+        let mut length = 0;
+        while length < samples.len() {
+            length += stream.read(&mut samples[length..]).unwrap();
+        }
+        assert_eq!(stream.position(), samples.len() as u64);
+        assert!(samples.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_reconfigure_while_streaming() {
+        fn read_exact(stream: &mut DataStream, samples: &mut [u8]) {
+            let mut length = 0;
+            while length < samples.len() {
+                length += stream.read(&mut samples[length..]).unwrap();
+            }
+        }
+
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let mut stream = device.stream_data();
+        let (reconfigure_tx, reconfigure_rx) = std::sync::mpsc::channel();
+        let (reconfigured_tx, reconfigured_rx) = std::sync::mpsc::channel();
+        let reader = thread::spawn(move || {
+            let mut samples = vec![0u8; 1001];
+            read_exact(&mut stream, &mut samples);
+            reconfigure_tx.send(()).unwrap();
+            reconfigured_rx.recv().unwrap();
+            let mut samples = vec![0u8; 1 << 16];
+            read_exact(&mut stream, &mut samples);
+            assert_eq!(stream.position(), 1004 + samples.len() as u64);
+            assert!(samples.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        });
+        let control = device.control();
+        reconfigure_rx.recv().unwrap();
+        let mut params = DeviceParameters::default();
+        params.channels[3] = None;
+        control.configure(&params).unwrap();
+        reconfigured_tx.send(()).unwrap();
+        reader.join().unwrap();
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let mut stream = device.stream_data();
+        let mut samples = vec![0u8; 1001];
+        let mut length = 0;
+        while length < samples.len() {
+            length += stream.read(&mut samples[length..]).unwrap();
+        }
+        device.pause().unwrap();
+        assert!(device.is_paused());
+        // reconfiguring does not resume acquisition
+        let mut params = DeviceParameters::default();
+        params.channels[3] = None;
+        device.configure(&params).unwrap();
+        thread::sleep(Duration::from_millis(1));
+        assert_eq!(stream.read(&mut samples).unwrap(), 0);
+        assert_eq!(stream.read(&mut samples).unwrap(), 0);
+        device.resume().unwrap();
+        assert!(!device.is_paused());
+        let mut length = 0;
+        while length < samples.len() {
+            length += stream.read(&mut samples[length..]).unwrap();
+        }
+        assert_eq!(stream.position(), 1004 + samples.len() as u64);
+        assert!(samples.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        let kinds = device.event_log().events().into_iter().map(|event| event.kind)
+            .filter(|kind| matches!(kind, EventKind::AcquisitionPaused |
+                                          EventKind::AcquisitionResumed))
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, [EventKind::AcquisitionPaused, EventKind::AcquisitionResumed]);
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_configure_changes_only() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let generation = device.control.generation();
+        let packets = device.control.driver.simulated_packets().len();
+        let mut params = DeviceParameters::default();
+        device.configure(&params).unwrap();
+        assert_eq!(device.control.driver.simulated_packets().len(), packets);
+        params.channels[1].as_mut().unwrap().amplification = Amplification::dB10;
+        device.configure(&params).unwrap();
+        let new_packets = device.control.driver.simulated_packets().split_off(packets);
+        assert_eq!(new_packets.len(), 1);
+        assert_eq!(new_packets[0][0], 0xfd - SPI_BUS_PGA[1]);
+        assert_eq!(device.control.generation(), generation);
+        device.shutdown().unwrap();
+    }
 
+    #[test]
+    fn test_verify() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let mut params = DeviceParameters::default();
+        params.channels[2].as_mut().unwrap().amplification = Amplification::dB30;
+        device.configure(&params).unwrap();
+        device.verify().unwrap();
+        // bypass the shadow, as if the PGA had been reset
+        device.control.write_pga_command(SPI_BUS_PGA[2], 0x0000).unwrap();
+        let error = device.verify().unwrap_err().to_string();
+        assert!(error.contains("CH3 PGA command is 0x0000"), "{}", error);
+        // gateware without bus readback
+        device.control.driver.simulate_gateware(0x5453_0100, 0x03e8_041c);
+        assert!(matches!(device.verify(), Err(Error::Unsupported)));
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_identify() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        let capabilities = device.identify().unwrap();
+        assert_eq!(capabilities.gateware_version, Some((GATEWARE_MAJOR_VERSION, 0)));
+        assert_eq!(capabilities.memory_size, MEMORY_SIZE);
+        assert_eq!(capabilities.channels, 4);
+        assert_eq!(capabilities.max_sample_rate, 1e9);
+        assert!(capabilities.bus_readback);
+        // gateware that predates the identification registers
+        device.control.driver.simulate_gateware(0, 0);
+        assert_eq!(device.identify().unwrap().gateware_version, None);
+        device.startup().unwrap();
+        device.shutdown().unwrap();
+        // gateware with a newer register map
+        let events = device.event_log().events().len();
+        device.control.driver.simulate_gateware(0x5453_0200, 0x03e8_041c);
+        let error = device.startup().unwrap_err();
+        assert!(matches!(error, Error::IncompatibleGateware(_)), "{}", error);
+        assert_eq!(device.event_log().events().len(), events);
+    }
+
+    #[test]
+    fn test_high_resolution() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let mut params = DeviceParameters { resolution: Resolution::Bits12, ..Default::default() };
+        assert!(matches!(device.configure(&params), Err(Error::Unsupported)));
+        device.control.driver.simulate_gateware(0x5453_0100, 0x03e8_141c);
+        assert!(device.identify().unwrap().high_resolution);
+        let generation = device.control.generation();
+        device.configure(&params).unwrap();
+        assert_eq!(device.control.generation(), generation + 1);
+        assert!(device.control.read_user_u32(axi::ADDR_CONTROL).unwrap() &
+            axi::Control::HighResolution.bits() != 0);
+        params.resolution = Resolution::Bits8;
+        device.configure(&params).unwrap();
+        assert_eq!(device.control.generation(), generation + 2);
+        assert!(device.control.read_user_u32(axi::ADDR_CONTROL).unwrap() &
+            axi::Control::HighResolution.bits() == 0);
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_adc_link_training() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.control.driver.simulate_bit_slip(&adc::HMCAD1520_LVDS_PHASES[..2]);
+        device.startup().unwrap();
+        let events = device.event_log().events().into_iter().map(|event| event.kind)
+            .collect::<Vec<_>>();
+        assert!(events.contains(&EventKind::AdcLinkMisaligned { lvds_phase: 0x0060 }));
+        assert!(events.contains(&EventKind::AdcLinkMisaligned { lvds_phase: 0x0040 }));
+        assert!(!events.contains(&EventKind::AdcLinkMisaligned { lvds_phase: 0x0020 }));
+        // the test patterns are disabled afterwards
+        let mut stream = device.stream_data();
+        let mut samples = vec![0u8; 1 << 16];
+        let mut length = 0;
+        while length < samples.len() {
+            length += stream.read(&mut samples[length..]).unwrap();
+        }
+        assert!(samples.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        device.shutdown().unwrap();
+        // misaligned at every phase
+        device.control.driver.simulate_bit_slip(&adc::HMCAD1520_LVDS_PHASES);
+        let error = device.startup().unwrap_err().to_string();
+        assert!(error.contains("bit slip of 1 in deskew pattern"), "{}", error);
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_measure_clock() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        assert!(matches!(device.measure_clock_ppm(Duration::from_millis(1)), Err(Error::Timeout)));
+        device.startup().unwrap();
+        device.configure(&DeviceParameters::default()).unwrap();
+        // the simulated data mover moves 1 byte per nanosecond of the host clock, so any error
+        // comes from the host; the bound allows for a preempted register read
+        let ppm = device.measure_clock_ppm(Duration::from_millis(100)).unwrap();
+        assert!(ppm.abs() < 1e4, "{} ppm", ppm);
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_fifo_timeout() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        device.control.driver.simulate_wedged_fifo();
+        let mut params = DeviceParameters::default();
+        params.channels[0].as_mut().unwrap().amplification = Amplification::dB10;
+        assert!(matches!(device.configure(&params), Err(Error::Timeout)));
+        device.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_overflow_recovery() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        device.startup().unwrap();
+        let mut stream = device.stream_data();
+        let mut samples = vec![0u8; 1 << 16];
+        let mut length = 0;
+        while length < 1001 {
+            length += stream.read(&mut samples[length..1001]).unwrap();
+        }
+        thread::sleep(Duration::from_millis(1));
+        device.control.driver.simulate_overflow();
+        let error = Error::from(stream.read(&mut samples).unwrap_err());
+        assert!(matches!(error, Error::Overflow { lost_pages } if lost_pages > 0), "{}", error);
+        length = 0;
+        while length < samples.len() {
+            length += stream.read(&mut samples[length..]).unwrap();
+        }
+        assert_eq!(stream.position(), 1004 + samples.len() as u64);
+        assert!(samples.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)));
+        let events = device.event_log().events().into_iter().map(|event| event.kind)
+            .collect::<Vec<_>>();
+        assert!(events.ends_with(&[
+            EventKind::DatamoverFailure {
+                fifo_overflow: true,
+                datamover_error: false,
+                overflow_cycles: 0,
+            },
+            EventKind::AcquisitionRestarted,
+        ]), "{:?}", events);
+        device.shutdown().unwrap();
+    }
 }
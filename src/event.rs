@@ -0,0 +1,122 @@
+//! Log of significant events that happened to a device, kept to make bug reports actionable.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::params::DeviceParameters;
+
+/// Amount of events retained; older events are discarded.
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    Startup,
+    Shutdown,
+    Rail3V3Enabled,
+    Rail5VEnabled,
+    RailsDisabled,
+    Configured(DeviceParameters),
+    DatamoverFailure { fifo_overflow: bool, datamover_error: bool, overflow_cycles: u32 },
+    AcquisitionRestarted,
+    AcquisitionPaused,
+    AcquisitionResumed,
+    AdcLinkMisaligned { lvds_phase: u16 },
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Startup =>
+                write!(f, "device startup"),
+            Self::Shutdown =>
+                write!(f, "device shutdown"),
+            Self::Rail3V3Enabled =>
+                write!(f, "3V3 rail enabled"),
+            Self::Rail5VEnabled =>
+                write!(f, "5V0 rail enabled"),
+            Self::RailsDisabled =>
+                write!(f, "all rails disabled"),
+            Self::Configured(params) => {
+                let enabled = params.channels.iter().enumerate()
+                    .filter(|(_, ch)| ch.is_some())
+                    .map(|(index, _)| format!("CH{}", index + 1))
+                    .collect::<Vec<_>>();
+                write!(f, "configured with {} enabled", enabled.join(", "))
+            }
+            Self::DatamoverFailure { fifo_overflow, datamover_error, overflow_cycles } =>
+                write!(f, "data mover failure (FIFO overflow: {}, data mover error: {}, \
+                           overflow by {} cycles)",
+                    fifo_overflow, datamover_error, overflow_cycles),
+            Self::AcquisitionRestarted =>
+                write!(f, "acquisition restarted"),
+            Self::AcquisitionPaused =>
+                write!(f, "acquisition paused"),
+            Self::AcquisitionResumed =>
+                write!(f, "acquisition resumed"),
+            Self::AdcLinkMisaligned { lvds_phase } =>
+                write!(f, "ADC link misaligned at LVDS phase {:#06x}", lvds_phase),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: SystemTime,
+    pub kind: EventKind,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since_epoch = self.time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        let (secs, millis) = (since_epoch.as_secs(), since_epoch.subsec_millis());
+        write!(f, "{:02}:{:02}:{:02}.{:03} UTC {}",
+            secs / 3600 % 24, secs / 60 % 60, secs % 60, millis, self.kind)
+    }
+}
+
+/// A handle to the event log of a device.
+///
+/// Cloning the handle does not clone the log; all clones refer to the same log, which makes it
+/// possible to inspect the log from a thread other than the one using the device.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog(Arc<Mutex<VecDeque<Event>>>);
+
+impl EventLog {
+    pub fn new() -> EventLog {
+        Default::default()
+    }
+
+    pub(crate) fn record(&self, kind: EventKind) {
+        log::trace!("event: {}", kind);
+        let mut events = self.0.lock().unwrap();
+        if events.len() == CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(Event { time: SystemTime::now(), kind });
+    }
+
+    /// Returns the retained events, oldest first.
+    pub fn events(&self) -> Vec<Event> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capacity() {
+        let log = EventLog::new();
+        for _ in 0..CAPACITY {
+            log.record(EventKind::Startup);
+        }
+        log.record(EventKind::Shutdown);
+        let events = log.events();
+        assert_eq!(events.len(), CAPACITY);
+        assert_eq!(events[0].kind, EventKind::Startup);
+        assert_eq!(events[CAPACITY - 1].kind, EventKind::Shutdown);
+    }
+}
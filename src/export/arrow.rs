@@ -0,0 +1,122 @@
+//! Apache Arrow IPC and Parquet exporters.
+//!
+//! A capture is exported as a single record batch with one `Float32` column per enabled channel
+//! (named `CH1` to `CH4`), in volts. The schema carries the sample rate, and each field carries
+//! the gain, full scale, and calibration status of its channel, so that the data can be loaded
+//! into pandas or polars without any additional context.
+//!
+//! Markers are stored in the `markers` key of the schema metadata, one per line, as the sample
+//! position and the label separated by a tab.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use ::arrow::array::{ArrayRef, Float32Array};
+use ::arrow::datatypes::{DataType, Field, Schema};
+use ::arrow::error::ArrowError;
+use ::arrow::ipc::writer::FileWriter;
+use ::arrow::record_batch::RecordBatch;
+
+use crate::{Error, Result};
+use crate::params::DeviceParameters;
+use super::Marker;
+
+impl From<ArrowError> for Error {
+    fn from(error: ArrowError) -> Self {
+        Error::Other(error.into())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<::parquet::errors::ParquetError> for Error {
+    fn from(error: ::parquet::errors::ParquetError) -> Self {
+        Error::Other(error.into())
+    }
+}
+
+fn record_batch(params: &DeviceParameters, channels: &[Option<&[i8]>; 4],
+        markers: &[Marker]) -> Result<RecordBatch> {
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (index, samples) in channels.iter().enumerate() {
+        let Some(samples) = samples else { continue };
+        let metadata = HashMap::from([
+            ("gain_db".to_owned(), params.gain(index).to_string()),
+            ("full_scale_v".to_owned(), params.full_scale(index).to_string()),
+            ("calibrated".to_owned(), params.is_calibrated(index).to_string()),
+        ]);
+        fields.push(Field::new(format!("CH{}", index + 1), DataType::Float32, false)
+            .with_metadata(metadata));
+        let volts_table = params.volts_table(index);
+        columns.push(Arc::new(samples.iter()
+            .map(|&code| volts_table[code as u8 as usize])
+            .collect::<Float32Array>()) as ArrayRef);
+    }
+    let mut metadata = HashMap::from([
+        ("sample_rate_hz".to_owned(), params.sample_rate().to_string()),
+    ]);
+    if !markers.is_empty() {
+        metadata.insert("markers".to_owned(), markers.iter()
+            .map(|marker| format!("{}\t{}",
+                marker.position, marker.label.replace(['\t', '\n'], " ")))
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+    let schema = Schema::new_with_metadata(fields, metadata);
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Writes a capture as an Arrow IPC file (also known as Feather V2).
+///
+/// All of the enabled channels must have the same amount of samples.
+pub fn write_ipc<W: Write>(writer: W, params: &DeviceParameters,
+        channels: &[Option<&[i8]>; 4], markers: &[Marker]) -> Result<()> {
+    let batch = record_batch(params, channels, markers)?;
+    let mut writer = FileWriter::try_new(writer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes a capture as a Parquet file.
+///
+/// All of the enabled channels must have the same amount of samples.
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: Write + Send>(writer: W, params: &DeviceParameters,
+        channels: &[Option<&[i8]>; 4], markers: &[Marker]) -> Result<()> {
+    let batch = record_batch(params, channels, markers)?;
+    let mut writer = ::parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DeviceCalibration, DeviceConfiguration};
+
+    #[test]
+    fn test_record_batch() {
+        let params = DeviceParameters::derive(&DeviceCalibration::default(),
+            &DeviceConfiguration { channels: [None, Some(Default::default()), None, None],
+                                   ..Default::default() });
+        let markers = [
+            Marker { position: 1, label: "reset".to_owned() },
+            Marker { position: 2, label: "boot\tok".to_owned() },
+        ];
+        let batch = record_batch(&params, &[None, Some(&[0, 64, -64]), None, None],
+            &markers).unwrap();
+        assert_eq!(batch.num_columns(), 1);
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.schema().field(0).name(), "CH2");
+        assert_eq!(batch.schema().metadata()["sample_rate_hz"], "1000000000");
+        assert_eq!(batch.schema().field(0).metadata()["calibrated"], "false");
+        assert_eq!(batch.schema().metadata()["markers"], "1\treset\n2\tboot ok");
+        let column = batch.column(0).as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(column.value(0), 0.0);
+        assert_eq!(column.value(1), params.full_scale(1) / 4.0);
+        assert_eq!(column.value(2), -params.full_scale(1) / 4.0);
+    }
+}
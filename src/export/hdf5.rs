@@ -0,0 +1,70 @@
+//! HDF5 exporter.
+//!
+//! A capture is exported as one `f32` dataset per enabled channel (named `CH1` to `CH4`),
+//! in volts. The root group carries the sample rate and trigger information as attributes,
+//! and each dataset carries the gain, full scale, and calibration status of its channel.
+//! Markers are stored as the `marker_positions` and `marker_labels` attributes of the root group.
+
+use std::path::Path;
+
+use ::hdf5::types::VarLenUnicode;
+
+use crate::{Error, Result};
+use crate::params::DeviceParameters;
+use crate::trigger::EdgeFilter;
+use super::{Marker, TriggerInfo};
+
+impl From<::hdf5::Error> for Error {
+    fn from(error: ::hdf5::Error) -> Self {
+        Error::Other(error.into())
+    }
+}
+
+fn write_scalar_attr<T: ::hdf5::H5Type>(location: &::hdf5::Location, name: &str,
+        value: T) -> Result<()> {
+    location.new_attr::<T>().shape(()).create(name)?.write_scalar(&value)?;
+    Ok(())
+}
+
+/// Writes a capture into a newly created HDF5 file at `path`, overwriting it if it exists.
+pub fn write(path: impl AsRef<Path>, params: &DeviceParameters, channels: &[Option<&[i8]>; 4],
+        trigger: Option<&TriggerInfo>, markers: &[Marker]) -> Result<()> {
+    let file = ::hdf5::File::create(path)?;
+    write_scalar_attr(&file, "sample_rate_hz", params.sample_rate())?;
+    if let Some(trigger) = trigger {
+        let edge = match trigger.edge {
+            EdgeFilter::Rising  => "rising",
+            EdgeFilter::Falling => "falling",
+            EdgeFilter::Both    => "both",
+        };
+        write_scalar_attr(&file, "trigger_channel", trigger.channel as u32 + 1)?;
+        write_scalar_attr(&file, "trigger_level_v", trigger.level)?;
+        write_scalar_attr(&file, "trigger_edge", edge.parse::<VarLenUnicode>().unwrap())?;
+        write_scalar_attr(&file, "trigger_position", trigger.position as u64)?;
+    }
+    if !markers.is_empty() {
+        let positions = markers.iter()
+            .map(|marker| marker.position as u64)
+            .collect::<Vec<_>>();
+        let labels = markers.iter()
+            .map(|marker| marker.label.replace('\0', "").parse::<VarLenUnicode>().unwrap())
+            .collect::<Vec<_>>();
+        file.new_attr_builder().with_data(&positions[..]).create("marker_positions")?;
+        file.new_attr_builder().with_data(&labels[..]).create("marker_labels")?;
+    }
+    for (index, samples) in channels.iter().enumerate() {
+        let Some(samples) = samples else { continue };
+        let volts_table = params.volts_table(index);
+        let volts = samples.iter()
+            .map(|&code| volts_table[code as u8 as usize])
+            .collect::<Vec<f32>>();
+        let dataset = file.new_dataset_builder()
+            .with_data(&volts[..])
+            .create(format!("CH{}", index + 1).as_str())?;
+        write_scalar_attr(&dataset, "gain_db", params.gain(index))?;
+        write_scalar_attr(&dataset, "full_scale_v", params.full_scale(index))?;
+        write_scalar_attr(&dataset, "calibrated", params.is_calibrated(index))?;
+    }
+    file.close()?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Exporters that write captured waveforms into file formats understood by external tools.
+//!
+//! Each exporter is gated behind a Cargo feature of the same name, since the libraries
+//! implementing these formats are fairly heavy. All of them accept per-channel sample codes
+//! (with `None` for disabled channels, as returned by `Capture::channels()`) and
+//! the `DeviceParameters` that the codes were captured with, and store samples converted to volts
+//! as measured at the probe. The Arrow and HDF5 exporters also store annotations placed on
+//! the capture (see `AnnotationFeed::markers()`); the sigrok format has no place for them.
+
+use crate::trigger::EdgeFilter;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "hdf5")]
+pub mod hdf5;
+
+#[cfg(feature = "sigrok")]
+pub mod sigrok;
+
+/// Describes the trigger condition that caused a capture to be acquired.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerInfo {
+    /// Index of the channel the trigger was evaluated on.
+    pub channel: usize,
+    /// Trigger level, in volts.
+    pub level: f32,
+    /// Edge (or edges) the trigger was looking for.
+    pub edge: EdgeFilter,
+    /// Index of the sample at which the edge was detected.
+    pub position: usize,
+}
+
+/// An annotation placed on a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// Index of the sample (within each channel) at which the annotated event happened.
+    pub position: usize,
+    pub label: String,
+}
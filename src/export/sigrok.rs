@@ -0,0 +1,125 @@
+//! sigrok session file exporter.
+//!
+//! A capture is exported as an `.sr` file (a ZIP archive in the "srzip" version 2 format) with
+//! one analog channel per enabled channel (named `CH1` to `CH4`), in volts. The analog channels of
+//! the session are numbered from 1 in the order the channels are enabled, since sigrok requires
+//! them to be contiguous. Such files can be opened in PulseView and processed with `sigrok-cli`.
+
+use std::io::{Seek, Write};
+
+use zip::ZipWriter;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+
+use crate::{Error, Result};
+use crate::params::DeviceParameters;
+
+impl From<ZipError> for Error {
+    fn from(error: ZipError) -> Self {
+        match error {
+            ZipError::Io(error) => error.into(),
+            error => Error::Other(error.into())
+        }
+    }
+}
+
+fn metadata(params: &DeviceParameters, channels: &[Option<&[i8]>; 4]) -> String {
+    let mut metadata = String::new();
+    metadata.push_str("[global]\n");
+    metadata.push_str("sigrok version=0.5.2\n");
+    metadata.push('\n');
+    metadata.push_str("[device 1]\n");
+    metadata.push_str(&format!("samplerate={}\n", params.sample_rate() as u64));
+    metadata.push_str("total probes=0\n");
+    metadata.push_str(&format!("total analog={}\n",
+        channels.iter().filter(|ch| ch.is_some()).count()));
+    let enabled = channels.iter().enumerate().filter(|(_, samples)| samples.is_some());
+    for (number, (index, _)) in (1..).zip(enabled) {
+        metadata.push_str(&format!("analog{}=CH{}\n", number, index + 1));
+    }
+    metadata
+}
+
+/// Writes a capture as a sigrok session file.
+pub fn write<W: Write + Seek>(writer: W, params: &DeviceParameters,
+        channels: &[Option<&[i8]>; 4]) -> Result<()> {
+    let options = SimpleFileOptions::default();
+    let mut archive = ZipWriter::new(writer);
+    archive.start_file("version", options)?;
+    archive.write_all(b"2")?;
+    archive.start_file("metadata", options)?;
+    archive.write_all(metadata(params, channels).as_bytes())?;
+    let enabled = channels.iter().enumerate()
+        .filter_map(|(index, samples)| Some((index, (*samples)?)));
+    for (number, (index, samples)) in (1..).zip(enabled) {
+        let volts_table = params.volts_table(index);
+        // analog data is stored as native-endian floats; all hosts we support are little-endian
+        let volts = samples.iter()
+            .flat_map(|&code| volts_table[code as u8 as usize].to_le_bytes())
+            .collect::<Vec<u8>>();
+        archive.start_file(format!("analog-1-{}-1", number), options)?;
+        archive.write_all(&volts)?;
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::{DeviceCalibration, DeviceConfiguration};
+
+    fn params() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(),
+            &DeviceConfiguration { channels: [Some(Default::default()), None,
+                                              Some(Default::default()), None],
+                                   ..Default::default() })
+    }
+
+    #[test]
+    fn test_metadata() {
+        let params = params();
+        assert_eq!(metadata(&params, &[Some(&[]), None, Some(&[]), None]), "\
+            [global]\n\
+            sigrok version=0.5.2\n\
+            \n\
+            [device 1]\n\
+            samplerate=500000000\n\
+            total probes=0\n\
+            total analog=2\n\
+            analog1=CH1\n\
+            analog2=CH3\n");
+    }
+
+    #[test]
+    fn test_write() {
+        let mut params = params();
+        params.channels[2].as_mut().unwrap().drift_correction = 0.25;
+        let ch1 = [-128, -1, 0, 1, 127];
+        let ch3 = [10, 20, -30, 40, -50];
+        let mut file = Cursor::new(Vec::new());
+        write(&mut file, &params, &[Some(&ch1), None, Some(&ch3), None]).unwrap();
+        let mut archive = ZipArchive::new(file).unwrap();
+        let mut read_file = |name: &str| {
+            let mut data = Vec::new();
+            archive.by_name(name).unwrap().read_to_end(&mut data).unwrap();
+            data
+        };
+        assert_eq!(read_file("version"), b"2");
+        // the analog channels are numbered contiguously, and hold the samples in volts
+        for (number, index, samples) in [(1, 0, &ch1), (2, 2, &ch3)] {
+            let volts = read_file(&format!("analog-1-{}-1", number)).chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect::<Vec<f32>>();
+            let expected = samples.iter()
+                .map(|&code| params.code_to_volts(index, code))
+                .collect::<Vec<f32>>();
+            assert_eq!(volts, expected);
+        }
+        assert!(archive.by_name("analog-1-3-1").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Adapter publishing the sample stream for consumption by GNU Radio flowgraphs.
+//!
+//! Samples are converted to volts, decimated by averaging, and published on a ZeroMQ PUB socket
+//! as 32-bit native-endian floats without any framing or tags. This is the format expected by
+//! the GNU Radio "ZMQ SUB Source" block configured with a `float` item type and "Pass Tags"
+//! disabled.
+
+use crate::{Error, Result};
+
+impl From<zmq::Error> for Error {
+    fn from(error: zmq::Error) -> Self {
+        Error::Other(error.into())
+    }
+}
+
+pub struct Publisher {
+    socket: zmq::Socket,
+    decimation: usize,
+    accumulator: f32,
+    accumulated: usize,
+}
+
+impl std::fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Publisher")
+            .field("decimation", &self.decimation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Publisher {
+    /// Create a publisher listening on `endpoint` (e.g. `tcp://*:5555`), which will average every
+    /// `decimation` consecutive samples into one published sample.
+    pub fn bind(endpoint: &str, decimation: usize) -> Result<Publisher> {
+        assert!(decimation > 0);
+        let socket = zmq::Context::new().socket(zmq::PUB)?;
+        socket.bind(endpoint)?;
+        log::info!("publishing samples for GNU Radio at {}", endpoint);
+        Ok(Publisher { socket, decimation, accumulator: 0.0, accumulated: 0 })
+    }
+
+    /// Decimate and publish `samples`, converted to volts with `volts_table` (see
+    /// `DeviceParameters::volts_table`).
+    ///
+    /// Samples that do not fill a complete decimation group are retained until the next call.
+    pub fn publish(&mut self, volts_table: &[f32; 256], samples: &[i8]) -> Result<()> {
+        let mut message = Vec::with_capacity(samples.len() / self.decimation * 4 + 4);
+        for &code in samples {
+            self.accumulator += volts_table[code as u8 as usize];
+            self.accumulated += 1;
+            if self.accumulated == self.decimation {
+                let volts = self.accumulator / self.decimation as f32;
+                message.extend_from_slice(&volts.to_ne_bytes());
+                self.accumulator = 0.0;
+                self.accumulated = 0;
+            }
+        }
+        if !message.is_empty() {
+            // a PUB socket never blocks; if no subscribers are connected, data is discarded
+            self.socket.send(message, 0)?;
+        }
+        Ok(())
+    }
+}
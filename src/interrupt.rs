@@ -0,0 +1,65 @@
+//! Handling of SIGINT (^C) while a device is powered up.
+//!
+//! The default action for SIGINT terminates the process without running destructors, leaving
+//! the frontend rails on and the data mover running. While a handler is installed, the first
+//! SIGINT only sets a flag, which makes `DataStream::read` fail with `Error::Interrupted` so that
+//! the device can be shut down by the usual means; a second SIGINT terminates the process.
+
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+static INSTALLED: Mutex<Option<(usize, libc::sighandler_t)>> = Mutex::new(None);
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // interrupted twice; the user really wants the process gone
+        unsafe { libc::_exit(128 + libc::SIGINT) }
+    }
+}
+
+/// Install the SIGINT handler, or increment its use count if already installed.
+pub fn install() {
+    #[cfg(unix)]
+    {
+        let mut installed = INSTALLED.lock().unwrap();
+        match installed.as_mut() {
+            Some((count, _)) => *count += 1,
+            None => {
+                log::debug!("installing SIGINT handler");
+                // a SIGINT received while a previous device was in use has been handled already
+                INTERRUPTED.store(false, Ordering::SeqCst);
+                let handler = handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                let previous = unsafe { libc::signal(libc::SIGINT, handler) };
+                *installed = Some((1, previous));
+            }
+        }
+    }
+}
+
+/// Decrement the use count of the SIGINT handler, and restore the previous handler when it
+/// is no longer used.
+pub fn uninstall() {
+    #[cfg(unix)]
+    {
+        let mut installed = INSTALLED.lock().unwrap();
+        match installed.as_mut() {
+            Some((count, _)) if *count > 1 => *count -= 1,
+            Some((_, previous)) => {
+                log::debug!("restoring SIGINT handler");
+                unsafe { libc::signal(libc::SIGINT, *previous) };
+                *installed = None;
+            }
+            None => unreachable!(),
+        }
+    }
+}
+
+/// Returns `true` if SIGINT has been received while the handler was installed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
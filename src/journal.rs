@@ -0,0 +1,172 @@
+//! Crash-safe journaling of records, e.g. of the sample stream being recorded.
+//!
+//! Each record is written as a 32-bit little-endian length, the data, a commit marker, and
+//! the CRC-32 of the data. After a crash or power loss, the end of the file may be truncated, or
+//! (since the filesystem may write blocks out of order) contain garbage; a record only counts as
+//! written if it is complete and its checksum matches, so a journal can always be read up to
+//! the last record that was committed.
+
+use std::io::{Read, Write};
+
+const COMMIT_MARKER: [u8; 4] = *b"TSCM";
+
+/// Limit on the length of a record, so that a corrupted length does not cause an allocation of
+/// up to 4 GiB.
+const MAX_RECORD_LENGTH: usize = 1 << 28;
+
+/// Returns the encoding of a record with `data`, e.g. to write it by other means than
+/// a `JournalWriter`.
+pub fn journal_record(data: &[u8]) -> Vec<u8> {
+    assert!(data.len() <= MAX_RECORD_LENGTH);
+    let mut record = Vec::with_capacity(data.len() + 12);
+    record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    record.extend_from_slice(data);
+    record.extend_from_slice(&COMMIT_MARKER);
+    record.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    record
+}
+
+#[derive(Debug)]
+pub struct JournalWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> JournalWriter<W> {
+    pub fn new(inner: W) -> JournalWriter<W> {
+        JournalWriter { inner }
+    }
+
+    /// Append a record with `data`.
+    pub fn append(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.inner.write_all(&journal_record(data))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[derive(Debug)]
+pub struct JournalReader<R: Read> {
+    inner: R,
+    committed: u64,
+    torn: bool,
+}
+
+impl<R: Read> JournalReader<R> {
+    pub fn new(inner: R) -> JournalReader<R> {
+        JournalReader { inner, committed: 0, torn: false }
+    }
+
+    /// Returns the data of the next committed record, or `None` once there are no more.
+    ///
+    /// Only I/O errors are reported as errors; an incomplete or corrupted record ends
+    /// the journal, which can be checked with `is_torn()`.
+    pub fn read_record(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        if self.torn {
+            return Ok(None)
+        }
+        let mut length = [0; 4];
+        match self.read_exact_or_end(&mut length)? {
+            // the journal ends cleanly after a record
+            0 => return Ok(None),
+            4 => (),
+            _ => return self.tear(),
+        }
+        let length = u32::from_le_bytes(length) as usize;
+        if length > MAX_RECORD_LENGTH {
+            return self.tear()
+        }
+        let mut data = vec![0; length];
+        let mut trailer = [0; 8];
+        if self.read_exact_or_end(&mut data)? < length ||
+                self.read_exact_or_end(&mut trailer)? < trailer.len() {
+            return self.tear()
+        }
+        if trailer[..4] != COMMIT_MARKER || trailer[4..] != crc32fast::hash(&data).to_le_bytes() {
+            return self.tear()
+        }
+        self.committed += 4 + length as u64 + 8;
+        Ok(Some(data))
+    }
+
+    /// Returns `true` if the journal ends with an incomplete or corrupted record, e.g. one that
+    /// was being written during a crash.
+    pub fn is_torn(&self) -> bool {
+        self.torn
+    }
+
+    /// Returns the length of the records read so far, in bytes. If the journal is torn, it can
+    /// be truncated to this length before appending more records.
+    pub fn committed_len(&self) -> u64 {
+        self.committed
+    }
+
+    fn tear(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        log::warn!("journal: incomplete record after {} bytes", self.committed);
+        self.torn = true;
+        Ok(None)
+    }
+
+    // like `read_exact`, but returns the amount of bytes read before the end of the journal
+    fn read_exact_or_end(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.inner.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(length) => filled += length,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(filled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn journal(records: &[&[u8]]) -> Vec<u8> {
+        let mut writer = JournalWriter::new(Vec::new());
+        for record in records {
+            writer.append(record).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn read_all(data: &[u8]) -> (Vec<Vec<u8>>, bool, u64) {
+        let mut reader = JournalReader::new(data);
+        let mut records = Vec::new();
+        while let Some(record) = reader.read_record().unwrap() {
+            records.push(record);
+        }
+        (records, reader.is_torn(), reader.committed_len())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = journal(&[b"first", b"", b"third"]);
+        assert_eq!(read_all(&data),
+            (vec![b"first".to_vec(), vec![], b"third".to_vec()], false, data.len() as u64));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let data = journal(&[b"first", b"second"]);
+        let first_len = 4 + 5 + 8;
+        // truncated anywhere in the second record
+        for length in first_len + 1..data.len() {
+            assert_eq!(read_all(&data[..length]),
+                (vec![b"first".to_vec()], true, first_len as u64));
+        }
+        // garbage in the data of the second record
+        let mut corrupted = data.clone();
+        corrupted[first_len + 4] ^= 0xff;
+        assert_eq!(read_all(&corrupted), (vec![b"first".to_vec()], true, first_len as u64));
+        // garbage instead of the length of the second record
+        let mut corrupted = data.clone();
+        corrupted[first_len + 3] = 0xff;
+        assert_eq!(read_all(&corrupted), (vec![b"first".to_vec()], true, first_len as u64));
+    }
+}
@@ -2,16 +2,56 @@
 
 mod sys;
 mod regs;
+mod bus;
 mod config;
 mod params;
 mod device;
 mod buffer;
 mod trigger;
+mod band_trigger;
+mod window_trigger;
+mod pattern_trigger;
+mod trigger_coupling;
+mod uart_trigger;
+mod burst;
+mod event;
+mod interrupt;
+mod timestamp;
+mod counter;
+mod annotation;
+mod capture;
+mod demux;
+mod peak_detect;
+mod channel_map;
+mod sched;
+mod watch;
+mod journal;
+mod recorder;
+mod replay;
+mod capabilities;
+#[cfg(feature = "tokio")]
+mod async_stream;
+
+pub mod acquire;
+pub mod export;
+#[cfg(feature = "gnuradio")]
+pub mod gnuradio;
 
 #[derive(Debug)]
 pub enum Error {
     Unsupported,
     NotFound,
+    Interrupted,
+    /// The device did not respond in time.
+    Timeout,
+    /// The data mover has failed, and acquisition has been restarted; the data that had been
+    /// acquired but not yet read (`lost_pages` pages of 4 KiB), as well as the data acquired
+    /// during the restart, is lost.
+    Overflow { lost_pages: usize },
+    /// Data was corrupted in host memory after it was acquired.
+    Corrupted,
+    /// The gateware is not compatible with this version of the driver.
+    IncompatibleGateware(String),
     Xdma(std::io::Error),
     Vmap(vmap::Error),
     Other(Box<dyn std::error::Error + Sync + Send + 'static>),
@@ -24,6 +64,16 @@ impl std::fmt::Display for Error {
                 write!(f, "platform not supported"),
             Self::NotFound =>
                 write!(f, "device not connected"),
+            Self::Interrupted =>
+                write!(f, "interrupted"),
+            Self::Timeout =>
+                write!(f, "timed out"),
+            Self::Overflow { lost_pages } =>
+                write!(f, "data mover failure, {} pages lost", lost_pages),
+            Self::Corrupted =>
+                write!(f, "data corrupted after acquisition"),
+            Self::IncompatibleGateware(reason) =>
+                write!(f, "incompatible gateware: {}", reason),
             Self::Xdma(error) =>
                 write!(f, "XDMA error: {}", error),
             Self::Vmap(error) =>
@@ -66,6 +116,17 @@ impl From<Error> for std::io::Error {
                 Self::new(std::io::ErrorKind::Unsupported, error),
             Error::NotFound => // converted from std::io::Error in first place
                 Self::new(std::io::ErrorKind::NotFound, error),
+            // not `ErrorKind::Interrupted`, since `read_exact()` and similar retry on it
+            Error::Interrupted =>
+                Self::other(error),
+            Error::Timeout =>
+                Self::new(std::io::ErrorKind::TimedOut, error),
+            Error::Overflow { .. } =>
+                Self::other(error),
+            Error::Corrupted =>
+                Self::new(std::io::ErrorKind::InvalidData, error),
+            Error::IncompatibleGateware(_) =>
+                Self::new(std::io::ErrorKind::Unsupported, error),
             Error::Xdma(error) => error,
             Error::Vmap(error) => error.into(),
             Error::Other(error) => {
@@ -85,6 +146,8 @@ pub use config::{
     Termination,
     Coupling,
     Bandwidth,
+    SampleRate,
+    Resolution,
     ChannelConfiguration,
     DeviceConfiguration,
 };
@@ -98,16 +161,81 @@ pub use params::{
     OffsetValue,
     ChannelParameters,
     DeviceParameters,
+    CalibrationStatus,
     ChannelCalibration,
     DeviceCalibration,
 };
 
-pub use device::Device;
+pub use device::{Control, DataStream, Descriptor, Device, DeviceCapabilities, DeviceGuard};
+
+pub use sys::SimulatedSignal;
+
+#[cfg(feature = "tokio")]
+pub use async_stream::AsyncStreamer;
 
 pub use trigger::{
     EdgeFilter,
     Edge,
     Trigger,
+    ScanVariant,
+};
+
+pub use window_trigger::{
+    WindowFilter,
+    WindowCrossing,
+    WindowTrigger,
+};
+
+pub use pattern_trigger::{
+    PatternLevel,
+    PatternLogic,
+    PatternCondition,
+    PatternTrigger,
+};
+
+pub use band_trigger::BandTrigger;
+
+pub use uart_trigger::{
+    UartParity,
+    UartFormat,
+    UartTrigger,
+};
+
+pub use trigger_coupling::{TriggerConditioner, TriggerCoupling};
+
+pub use timestamp::Timestamp;
+
+pub use annotation::{Annotation, AnnotationFeed};
+
+pub use channel_map::ChannelMap;
+
+pub use sched::ThreadScheduling;
+
+pub use capture::Capture;
+
+pub use demux::{demux, demux_lanes};
+
+pub use peak_detect::PeakDetector;
+
+pub use counter::{CounterReading, EventCounter};
+
+pub use burst::{BurstCapture, BurstSegment};
+
+pub use watch::FileWatcher;
+
+pub use capabilities::{capabilities, Capabilities};
+
+pub use journal::{journal_record, JournalReader, JournalWriter};
+
+pub use recorder::{recording_path, Backpressure, RecordingHeader, StreamRecorder};
+pub use recorder::{RecorderOptions, RecorderStats};
+
+pub use replay::ReplaySource;
+
+pub use event::{
+    EventKind,
+    Event,
+    EventLog,
 };
 
 pub use buffer::{
@@ -5,9 +5,14 @@
 
 use std::fmt;
 
-use crate::{config::{Bandwidth, Coupling, DeviceConfiguration, Termination}, ChannelConfiguration};
+use crate::channel_map::ChannelMap;
+
+use crate::{config::{Bandwidth, Coupling, DeviceConfiguration, Resolution, SampleRate,
+                     Termination},
+            ChannelConfiguration};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoarseAttenuation {
     X1,
     #[default]
@@ -26,6 +31,7 @@ impl CoarseAttenuation {
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Amplification {
     dB10,
     #[default]
@@ -51,6 +57,7 @@ impl Amplification {
 
 #[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FineAttenuation {
     #[default]
     dB0,
@@ -102,6 +109,7 @@ impl FineAttenuation {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Filtering {
     MHz20,
     #[default]
@@ -124,6 +132,7 @@ impl Filtering {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OffsetMagnitude {
     code: u16,
 }
@@ -158,6 +167,7 @@ impl OffsetMagnitude {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OffsetValue {
     code: u16, // 12 bit DAC
 }
@@ -174,7 +184,19 @@ impl OffsetValue {
     }
 }
 
+/// Whether the conversion between codes and volts uses calibration data measured for the specific
+/// instrument, or nominal component values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CalibrationStatus {
+    /// No calibration data is available; absolute voltages may be inaccurate by several percent.
+    #[default]
+    Nominal,
+    Calibrated,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelParameters {
     pub probe_attenuation: f32, // in dB
     pub termination: Termination,
@@ -185,6 +207,11 @@ pub struct ChannelParameters {
     pub filtering: Filtering,
     pub offset_magnitude: OffsetMagnitude,
     pub offset_value: OffsetValue,
+    pub calibration: CalibrationStatus, // not written to the device
+    /// Baseline drift of the frontend since it was measured, e.g. by `DriftTracker`, in volts.
+    /// It is subtracted when converting codes to volts, and is not written to the device.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub drift_correction: f32,
 }
 
 impl ChannelParameters {
@@ -201,14 +228,28 @@ impl ChannelParameters {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceParameters {
     pub channels: [Option<ChannelParameters>; 4],
+    /// Requested rate at which each channel is sampled; see `sample_rate()` for the rate at which
+    /// it actually is.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_sample_rate: SampleRate,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub resolution: Resolution,
+    /// Deviation of the sample clock from its nominal frequency, in parts per million; see
+    /// `DeviceCalibration::clock_ppm`. It is not written to the device.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub clock_ppm: f32,
 }
 
 impl Default for DeviceParameters {
     fn default() -> Self {
         DeviceParameters {
-            channels: [Some(ChannelParameters::default()); 4]
+            channels: [Some(ChannelParameters::default()); 4],
+            max_sample_rate: SampleRate::default(),
+            resolution: Resolution::default(),
+            clock_ppm: 0.0,
         }
     }
 }
@@ -228,6 +269,48 @@ impl DeviceParameters {
         self.channels[channel_index].unwrap().gain(adc_coarse_gain)
     }
 
+    /// Returns `true` if the given channel is enabled and its conversion between codes and volts
+    /// uses calibration data measured for the specific instrument.
+    pub fn is_calibrated(&self, channel_index: usize) -> bool {
+        self.channels[channel_index].map(|channel| channel.calibration) ==
+            Some(CalibrationStatus::Calibrated)
+    }
+
+    /// Returns the amount of channels interleaved in the sample stream.
+    pub fn stream_channels(&self) -> usize {
+        ChannelMap::from_params(self).stream_channels()
+    }
+
+    /// Returns the amount by which the HMCAD1520 divides its 1 GHz clock; 1, 2, 4, or 8.
+    pub(crate) fn adc_clock_divisor(&self) -> usize {
+        // the enabled channels take turns sampling on the ADC clock, so it is divided at least
+        // by their amount; in 12-bit mode, the ADC outputs at most 640 MS/s
+        let requested = (1e9 / self.max_sample_rate.samples_per_second()) as usize;
+        let minimum = match self.resolution {
+            Resolution::Bits8  => self.stream_channels(),
+            Resolution::Bits12 => self.stream_channels() * 2,
+        };
+        requested.max(minimum)
+    }
+
+    /// Returns the rate at which each enabled channel is sampled, in samples per second.
+    pub fn sample_rate(&self) -> f32 {
+        1e9 / self.adc_clock_divisor() as f32
+    }
+
+    /// Returns the rate at which each enabled channel is actually sampled, i.e. `sample_rate()`
+    /// corrected for the deviation of the sample clock, in samples per second. Time measurements
+    /// should use this rate.
+    pub fn corrected_sample_rate(&self) -> f32 {
+        (self.sample_rate() as f64 * (1.0 + self.clock_ppm as f64 * 1e-6)) as f32
+    }
+
+    /// Returns the rate at which samples appear in the stream (of all channels, interleaved), in
+    /// samples per second.
+    pub fn stream_sample_rate(&self) -> u64 {
+        (1_000_000_000 / self.adc_clock_divisor() * self.stream_channels()) as u64
+    }
+
     /// Returns the voltage difference (as measured at the probe) between the most negative and
     /// most positive ADC code for the given channel, in volts.
     pub fn full_scale(&self, channel_index: usize) -> f32 {
@@ -238,6 +321,7 @@ impl DeviceParameters {
     /// negative or most positive code for out of range values.
     pub fn volts_to_code(&self, channel_index: usize, volts: f32) -> i8 {
         let full_scale = self.full_scale(channel_index);
+        let volts = volts + self.drift_correction(channel_index);
         // Since Rust 1.45 this performs a saturating cast. Nice!
         (256.0 * (volts / full_scale)) as i8
     }
@@ -245,22 +329,175 @@ impl DeviceParameters {
     /// Converts an ADC code to voltage (as measured at the probe).
     pub fn code_to_volts(&self, channel_index: usize, code: i8) -> f32 {
         let full_scale = self.full_scale(channel_index);
-        code as f32 / 256.0 * full_scale
+        code as f32 / 256.0 * full_scale - self.drift_correction(channel_index)
+    }
+
+    /// Returns the voltage (as in `code_to_volts`) of every ADC code, indexed by the code cast
+    /// to `u8`, for converting many samples at once.
+    pub fn volts_table(&self, channel_index: usize) -> [f32; 256] {
+        let full_scale = self.full_scale(channel_index);
+        let drift_correction = self.drift_correction(channel_index);
+        std::array::from_fn(|index| {
+            let code = index as u8 as i8;
+            code as f32 / 256.0 * full_scale - drift_correction
+        })
+    }
+
+    /// Like `volts_to_code`, but for 16-bit samples (see `Resolution`). The least significant
+    /// bits that the ADC does not output are cleared.
+    pub fn volts_to_code_i16(&self, channel_index: usize, volts: f32) -> i16 {
+        let full_scale = self.full_scale(channel_index);
+        let volts = volts + self.drift_correction(channel_index);
+        let unused_bits = 16 - self.resolution.bits();
+        ((65536.0 * (volts / full_scale)) as i16) & !((1 << unused_bits) - 1)
+    }
+
+    /// Like `code_to_volts`, but for 16-bit samples (see `Resolution`).
+    pub fn code_i16_to_volts(&self, channel_index: usize, code: i16) -> f32 {
+        let full_scale = self.full_scale(channel_index);
+        code as f32 / 65536.0 * full_scale - self.drift_correction(channel_index)
+    }
+
+    fn drift_correction(&self, channel_index: usize) -> f32 {
+        self.channels[channel_index].map_or(0.0, |channel| channel.drift_correction)
+    }
+
+    /// Returns a compact binary encoding of the parameters, e.g. to store them alongside
+    /// recorded samples, which does not require the `serde` feature; see `decode()`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for channel in self.channels.iter() {
+            let Some(channel) = channel else { data.push(0); continue };
+            data.push(1);
+            data.extend_from_slice(&channel.probe_attenuation.to_le_bytes());
+            data.extend_from_slice(&[
+                encode_enum(channel.termination),
+                encode_enum(channel.coupling),
+                encode_enum(channel.coarse_attenuation),
+                encode_enum(channel.amplification),
+                encode_enum(channel.fine_attenuation),
+                encode_enum(channel.filtering),
+            ]);
+            data.extend_from_slice(&channel.offset_magnitude.code.to_le_bytes());
+            data.extend_from_slice(&channel.offset_value.code.to_le_bytes());
+            data.push(encode_enum(channel.calibration));
+            data.extend_from_slice(&channel.drift_correction.to_le_bytes());
+        }
+        data.push(encode_enum(self.max_sample_rate));
+        data.push(encode_enum(self.resolution));
+        data.extend_from_slice(&self.clock_ppm.to_le_bytes());
+        data
+    }
+
+    /// Decodes parameters encoded by `encode()`. Returns `None` if `data` is not a valid encoding.
+    pub fn decode(data: &[u8]) -> Option<DeviceParameters> {
+        let mut data = data.iter().copied();
+        let mut bytes = |count: usize| -> Option<Vec<u8>> {
+            let bytes = data.by_ref().take(count).collect::<Vec<_>>();
+            (bytes.len() == count).then_some(bytes)
+        };
+        let f32 = |bytes: Vec<u8>| f32::from_le_bytes(bytes.try_into().unwrap());
+        let u16 = |bytes: Vec<u8>| u16::from_le_bytes(bytes.try_into().unwrap());
+        let mut channels = [None; 4];
+        for channel in channels.iter_mut() {
+            match bytes(1)?[0] {
+                0 => continue,
+                1 => (),
+                _ => return None,
+            }
+            let probe_attenuation = f32(bytes(4)?);
+            let codes = bytes(6)?;
+            *channel = Some(ChannelParameters {
+                probe_attenuation,
+                termination: decode_enum(codes[0])?,
+                coupling: decode_enum(codes[1])?,
+                coarse_attenuation: decode_enum(codes[2])?,
+                amplification: decode_enum(codes[3])?,
+                fine_attenuation: decode_enum(codes[4])?,
+                filtering: decode_enum(codes[5])?,
+                offset_magnitude: OffsetMagnitude { code: u16(bytes(2)?) },
+                offset_value: OffsetValue { code: u16(bytes(2)?) },
+                calibration: decode_enum(bytes(1)?[0])?,
+                drift_correction: f32(bytes(4)?),
+            });
+        }
+        let codes = bytes(2)?;
+        let params = DeviceParameters {
+            channels,
+            max_sample_rate: decode_enum(codes[0])?,
+            resolution: decode_enum(codes[1])?,
+            clock_ppm: f32(bytes(4)?),
+        };
+        // trailing data is not valid either
+        bytes(1).is_none().then_some(params)
     }
 }
 
+/// Enumerations that are encoded by `DeviceParameters::encode()` as their index in `ALL`.
+trait EnumCode: Copy + PartialEq + 'static {
+    const ALL: &'static [Self];
+}
+
+macro_rules! enum_code {
+    ($($ty:ident => [$($variant:ident),+]),+ $(,)?) => {
+        $(impl EnumCode for $ty {
+            const ALL: &'static [Self] = &[$($ty::$variant),+];
+        })+
+    };
+}
+
+enum_code! {
+    Termination => [Ohm1M, Ohm50],
+    Coupling => [DC, AC],
+    CoarseAttenuation => [X1, X50],
+    Amplification => [dB10, dB30],
+    FineAttenuation => [dB0, dB2, dB4, dB6, dB8, dB10, dB12, dB14, dB16, dB18, dB20],
+    Filtering => [MHz20, MHz100, MHz200, MHz350, Off],
+    CalibrationStatus => [Nominal, Calibrated],
+}
+
+impl EnumCode for SampleRate {
+    const ALL: &'static [Self] = &SampleRate::ALL;
+}
+
+impl EnumCode for Resolution {
+    const ALL: &'static [Self] = &Resolution::ALL;
+}
+
+fn encode_enum<T: EnumCode>(value: T) -> u8 {
+    T::ALL.iter().position(|&item| item == value).unwrap() as u8
+}
+
+fn decode_enum<T: EnumCode>(code: u8) -> Option<T> {
+    T::ALL.get(code as usize).copied()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChannelCalibration {
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+impl ChannelCalibration {
+    /// Returns `true` if this is the nominal calibration (`ChannelCalibration::default()`) rather
+    /// than data measured for a specific instrument.
+    pub fn is_nominal(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceCalibration {
     pub channels: [ChannelCalibration; 4],
+    /// Deviation of the sample clock from its nominal frequency, in parts per million, as
+    /// measured by `Device::measure_clock_ppm()`; positive if the clock is fast.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub clock_ppm: f32,
 }
 
 impl DeviceParameters {
     pub fn derive(calibration: &DeviceCalibration, configuration: &DeviceConfiguration) -> Self {
-        fn derive_channel(_calibration: &ChannelCalibration,
+        fn derive_channel(calibration: &ChannelCalibration,
                 configuration: &ChannelConfiguration) -> ChannelParameters {
             ChannelParameters {
                 probe_attenuation: configuration.probe_attenuation,
@@ -276,13 +513,105 @@ impl DeviceParameters {
                 },
                 offset_magnitude: Default::default(), // FIXME
                 offset_value: Default::default(), // FIXME
+                calibration: match calibration.is_nominal() {
+                    true  => CalibrationStatus::Nominal,
+                    false => CalibrationStatus::Calibrated,
+                },
+                drift_correction: 0.0,
             }
         }
 
         DeviceParameters {
             channels: std::array::from_fn(|index|
                 configuration.channels[index].map(|channel|
-                    derive_channel(&calibration.channels[index], &channel)))
+                    derive_channel(&calibration.channels[index], &channel))),
+            max_sample_rate: configuration.sample_rate,
+            resolution: configuration.resolution,
+            clock_ppm: calibration.clock_ppm,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn derive(channels: usize, sample_rate: SampleRate) -> DeviceParameters {
+        let mut configuration = DeviceConfiguration { sample_rate, ..Default::default() };
+        configuration.channels[channels..].fill(None);
+        DeviceParameters::derive(&DeviceCalibration::default(), &configuration)
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let params = derive(1, SampleRate::MSps1000);
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (1, 1e9));
+        let params = derive(1, SampleRate::MSps125);
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (8, 125e6));
+        assert_eq!(params.stream_sample_rate(), 125_000_000);
+        // the requested rate is higher than what is possible with this many channels
+        let params = derive(3, SampleRate::MSps1000);
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (4, 250e6));
+        assert_eq!(params.stream_sample_rate(), 1_000_000_000);
+        let params = derive(2, SampleRate::MSps250);
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (4, 250e6));
+        assert_eq!(params.stream_sample_rate(), 500_000_000);
+        // 12-bit samples are limited to 640 MS/s in total
+        let params = DeviceParameters {
+            resolution: Resolution::Bits12, ..derive(1, SampleRate::MSps1000) };
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (2, 500e6));
+        let params = DeviceParameters {
+            resolution: Resolution::Bits12, ..derive(4, SampleRate::MSps1000) };
+        assert_eq!((params.adc_clock_divisor(), params.sample_rate()), (8, 125e6));
+    }
+
+    #[test]
+    fn test_corrected_sample_rate() {
+        let params = derive(1, SampleRate::MSps1000);
+        assert_eq!(params.corrected_sample_rate(), 1e9);
+        let calibration = DeviceCalibration { clock_ppm: -25.0, ..Default::default() };
+        let params = DeviceParameters::derive(&calibration, &DeviceConfiguration {
+            sample_rate: SampleRate::MSps125, ..Default::default() });
+        assert_eq!(params.sample_rate(), 125e6);
+        assert_eq!(params.corrected_sample_rate(), 125e6 - 3125.0);
+    }
+
+    #[test]
+    fn test_code_i16() {
+        let params = DeviceParameters { resolution: Resolution::Bits12, ..Default::default() };
+        let volts = params.code_to_volts(0, 50);
+        assert_eq!(params.code_i16_to_volts(0, 50 << 8), volts);
+        assert_eq!(params.volts_to_code_i16(0, volts), 50 << 8);
+        // the bottom 4 bits are always zero
+        assert_eq!(params.volts_to_code_i16(0, params.code_i16_to_volts(0, 0x123f)), 0x1230);
+        assert_eq!(params.volts_to_code_i16(0, 1e3), 0x7ff0);
+    }
+
+    #[test]
+    fn test_volts_table() {
+        let mut params = derive(2, SampleRate::MSps500);
+        params.channels[1].as_mut().unwrap().drift_correction = 0.125;
+        for channel_index in 0..2 {
+            let table = params.volts_table(channel_index);
+            for code in i8::MIN..=i8::MAX {
+                assert_eq!(table[code as u8 as usize], params.code_to_volts(channel_index, code));
+            }
         }
     }
+
+    #[test]
+    fn test_encode() {
+        let mut params = derive(3, SampleRate::MSps250);
+        params.resolution = Resolution::Bits12;
+        params.clock_ppm = -12.5;
+        let channel = params.channels[1].as_mut().unwrap();
+        channel.filtering = Filtering::Off;
+        channel.offset_magnitude = OffsetMagnitude::from_ohms(1000);
+        channel.drift_correction = 0.25;
+        let data = params.encode();
+        assert_eq!(DeviceParameters::decode(&data), Some(params));
+        assert_eq!(DeviceParameters::decode(&data[..data.len() - 1]), None);
+        assert_eq!(DeviceParameters::decode(&[&data[..], &[0]].concat()), None);
+        assert_eq!(DeviceParameters::decode(&[2; 64]), None);
+    }
 }
@@ -0,0 +1,181 @@
+//! Implements a pattern trigger, which detects a boolean combination of level conditions on
+//! several channels becoming true; e.g. a chip select going low while a clock is high.
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatternLevel {
+    High,
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PatternLogic {
+    /// The pattern matches if every condition is true.
+    And,
+    /// The pattern matches if any condition is true.
+    Or,
+}
+
+/// The condition on the signal of one channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PatternCondition {
+    pub channel: usize,
+    pub level: PatternLevel,
+    /// Threshold between low and high, in ADC codes.
+    pub threshold: i8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LaneCondition {
+    level: PatternLevel,
+    below: i8,
+    above: i8,
+    threshold: i8,
+    // `None` until the first sample of the lane is processed
+    high: Option<bool>,
+}
+
+impl LaneCondition {
+    fn update(&mut self, sample: i8) {
+        self.high = match self.high {
+            None => Some(sample >= self.threshold),
+            Some(false) if sample > self.above => Some(true),
+            Some(true) if sample < self.below => Some(false),
+            high => high
+        }
+    }
+
+    fn is_true(&self) -> Option<bool> {
+        self.high.map(|high| high == (self.level == PatternLevel::High))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternTrigger {
+    channels: usize,
+    lanes: Vec<Option<LaneCondition>>,
+    logic: PatternLogic,
+    // index of the next sample in its frame
+    phase: usize,
+    // whether the pattern matched in the previous frame; `None` until every condition is known
+    matched: Option<bool>,
+}
+
+impl PatternTrigger {
+    /// Create a new trigger on `conditions` combined with `logic`, in a sample stream acquired
+    /// with `params`. The threshold of each condition has hysteresis, with the same meaning of
+    /// `hysteresis` as for `Trigger`.
+    ///
+    /// Returns `None` if there are no conditions, or if a condition is on a disabled channel.
+    pub fn new(params: &DeviceParameters, conditions: &[PatternCondition], logic: PatternLogic,
+               hysteresis: u8) -> Option<PatternTrigger> {
+        if conditions.is_empty() {
+            return None
+        }
+        let channel_map = ChannelMap::from_params(params);
+        let mut lanes = vec![None; channel_map.stream_channels()];
+        for condition in conditions {
+            let lane = channel_map.lane(condition.channel)?;
+            lanes[lane] = Some(LaneCondition {
+                level: condition.level,
+                below: condition.threshold.saturating_sub_unsigned(hysteresis).max(-127),
+                above: condition.threshold.saturating_add_unsigned(hysteresis).min( 126),
+                threshold: condition.threshold,
+                high: None,
+            });
+        }
+        Some(PatternTrigger {
+            channels: channel_map.stream_channels(),
+            lanes,
+            logic,
+            phase: 0,
+            matched: None,
+        })
+    }
+
+    /// Reset the trigger, as if it was re-created with the same parameters.
+    ///
+    /// After this method is called, the next sample must start a frame.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.matched = None;
+        for condition in self.lanes.iter_mut().flatten() {
+            condition.high = None;
+        }
+    }
+
+    fn evaluate(&self) -> Option<bool> {
+        let mut values = self.lanes.iter().flatten().map(|condition| condition.is_true());
+        match self.logic {
+            PatternLogic::And => values.try_fold(true, |all, value| Some(all && value?)),
+            PatternLogic::Or  => values.try_fold(false, |any, value| Some(any || value?)),
+        }
+    }
+
+    /// Scan incoming data (interleaved as in the sample stream) for a frame where the pattern
+    /// starts matching.
+    ///
+    /// Returns the amount of consumed samples, and whether the trigger has fired. If it has,
+    /// the last consumed sample is the last one of the frame where the pattern started matching.
+    pub fn find(&mut self, samples: &[i8]) -> (usize, bool) {
+        for (index, &sample) in samples.iter().enumerate() {
+            let lane = self.phase;
+            self.phase = (self.phase + 1) % self.channels;
+            if let Some(condition) = self.lanes[lane].as_mut() {
+                condition.update(sample);
+            }
+            if self.phase != 0 { continue }
+            let Some(matched) = self.evaluate() else { continue };
+            if self.matched.replace(matched) == Some(false) && matched {
+                return (index + 1, true)
+            }
+        }
+        (samples.len(), false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn two_channels() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None,
+                       Some(ChannelConfiguration::default()), None],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_pattern() {
+        let params = two_channels();
+        let clock_high = PatternCondition { channel: 0, level: PatternLevel::High, threshold: 0 };
+        let select_low = PatternCondition { channel: 2, level: PatternLevel::Low, threshold: 0 };
+        assert!(PatternTrigger::new(&params, &[], PatternLogic::And, 2).is_none());
+        assert!(PatternTrigger::new(&params,
+            &[PatternCondition { channel: 1, ..clock_high }], PatternLogic::And, 2).is_none());
+        // CH1 is a clock with a period of 10 samples, starting high; CH3 goes low at sample 23
+        let samples = (0..50).flat_map(|index| {
+            [if index % 10 < 5 { 50 } else { -50 }, if index < 23 { 50 } else { -50 }]
+        }).collect::<Vec<_>>();
+        let mut trigger = PatternTrigger::new(&params, &[clock_high, select_low],
+            PatternLogic::And, 2).unwrap();
+        assert_eq!(trigger.find(&samples), (24 * 2, true));
+        assert_eq!(trigger.find(&samples[24 * 2..]), ((31 - 24) * 2, true));
+        // a pattern matching from the start does not fire the trigger
+        trigger.reset();
+        assert_eq!(trigger.find(&samples[30 * 2..]), ((41 - 30) * 2, true));
+        let mut trigger = PatternTrigger::new(&params, &[clock_high, select_low],
+            PatternLogic::Or, 2).unwrap();
+        assert_eq!(trigger.find(&samples), (11 * 2, true));
+        assert_eq!(trigger.find(&samples[11 * 2..]), ((21 - 11) * 2, true));
+        // once CH3 is low, the pattern keeps matching
+        assert_eq!(trigger.find(&samples[21 * 2..]), ((50 - 21) * 2, false));
+    }
+}
@@ -0,0 +1,201 @@
+//! Peak detect decimation, which reduces each group of consecutive samples to their minimum and
+//! maximum, so that long captures can be displayed or stored at a lower rate without aliasing
+//! away glitches narrower than a group.
+//!
+//! The minimum and maximum are accumulated over 16 or 32 samples at a time in SIMD registers.
+//! Since groups start at a frame boundary and the amount of lanes is a multiple of the amount of
+//! channels, each lane only ever holds samples of one channel, and the lanes are only combined
+//! once per group. The implementation is selected the same way as for the triggers, with
+//! `ScanVariant`.
+
+use crate::trigger::ScanVariant;
+
+/// Reduces every `factor` consecutive frames of a stream of interleaved channels to
+/// the minimum and the maximum of each channel, output as two consecutive frames.
+#[derive(Debug, Clone)]
+pub struct PeakDetector {
+    variant: ScanVariant,
+    factor: usize,
+    // minimum and maximum of each channel over the frames of the group being accumulated
+    min: Vec<i8>,
+    max: Vec<i8>,
+    // amount of frames accumulated
+    frames: usize,
+}
+
+impl PeakDetector {
+    /// Create a peak detector for a stream of `channels` interleaved channels (1, 2, or 4; as
+    /// in the sample stream), reducing each `factor` frames to two.
+    pub fn new(factor: usize, channels: usize) -> PeakDetector {
+        assert!(factor > 0, "cannot decimate by 0");
+        assert!(matches!(channels, 1 | 2 | 4), "cannot peak detect {} channels", channels);
+        PeakDetector {
+            variant: if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() },
+            factor,
+            min: vec![i8::MAX; channels],
+            max: vec![i8::MIN; channels],
+            frames: 0,
+        }
+    }
+
+    /// Discard the frames accumulated towards the next pair of output frames.
+    pub fn reset(&mut self) {
+        self.min.fill(i8::MAX);
+        self.max.fill(i8::MIN);
+        self.frames = 0;
+    }
+
+    /// Process `samples`, which must consist of whole frames, appending the minimum and
+    /// the maximum of each complete group to `output`. Frames of an incomplete group at the end
+    /// are accumulated until the next call.
+    pub fn process(&mut self, mut samples: &[i8], output: &mut Vec<i8>) {
+        let channels = self.min.len();
+        assert!(samples.len() / channels * channels == samples.len(),
+            "samples do not consist of whole frames");
+        output.reserve((self.frames + samples.len() / channels) / self.factor * channels * 2);
+        while !samples.is_empty() {
+            let length = ((self.factor - self.frames) * channels).min(samples.len());
+            // SAFETY: `ScanVariant::current()` only returns available variants.
+            unsafe { fold_variant(self.variant, &samples[..length], &mut self.min, &mut self.max) }
+            self.frames += length / channels;
+            samples = &samples[length..];
+            if self.frames == self.factor {
+                output.extend_from_slice(&self.min);
+                output.extend_from_slice(&self.max);
+                self.reset();
+            }
+        }
+    }
+}
+
+/// # Safety
+///
+/// `variant` must be available.
+unsafe fn fold_variant(variant: ScanVariant, data: &[i8], min: &mut [i8], max: &mut [i8]) {
+    match variant {
+        ScanVariant::Generic => fold_generic(data, min, max),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        ScanVariant::Avx => fold_avx(data, min, max),
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        ScanVariant::Avx2 => fold_avx2(data, min, max),
+        #[cfg(target_arch = "aarch64")]
+        ScanVariant::Neon => fold_neon(data, min, max),
+        _ => unreachable!("{:?} peak detector is not available", variant)
+    }
+}
+
+macro_rules! fold_impl {
+    { $simd_ty:ident $( $decl:tt )+ } => {
+        #[inline(never)] // makes assembly more readable; serves no other purpose
+        $( $decl )+(data: &[i8], min: &mut [i8], max: &mut [i8]) {
+            use wide::$simd_ty;
+            const LANES: usize = $simd_ty::LANES as usize;
+
+            let channels = min.len();
+            let mut groups = data.chunks_exact(LANES);
+            let mut group_min = $simd_ty::splat(i8::MAX);
+            let mut group_max = $simd_ty::splat(i8::MIN);
+            for group in groups.by_ref() {
+                let group = $simd_ty::new(group.try_into().unwrap());
+                group_min = group_min.min(group);
+                group_max = group_max.max(group);
+            }
+            // lane `index` holds the samples of channel `index % channels`, and so do
+            // the remaining samples, which start at a frame boundary
+            let lanes = group_min.to_array().into_iter().zip(group_max.to_array());
+            let rest = groups.remainder().iter().map(|&sample| (sample, sample));
+            for (index, (sample_min, sample_max)) in lanes.chain(rest).enumerate() {
+                let channel = index % channels;
+                min[channel] = min[channel].min(sample_min);
+                max[channel] = max[channel].max(sample_max);
+            }
+        }
+    };
+}
+
+fold_impl! { i8x16 fn fold_generic }
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fold_impl! { i8x32 #[target_feature(enable = "avx")]  unsafe fn fold_avx  }
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fold_impl! { i8x32 #[target_feature(enable = "avx2")] unsafe fn fold_avx2 }
+
+#[cfg(target_arch = "aarch64")]
+fold_impl! { i8x16 #[target_feature(enable = "neon")] unsafe fn fold_neon }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // a noisy signal with a narrow glitch in each channel
+    fn stream(channels: usize, frames: usize) -> Vec<i8> {
+        let mut data = (0..frames * channels)
+            .map(|index| ((index * 37 + index / channels * 11) % 61) as i8 - 30)
+            .collect::<Vec<_>>();
+        for channel in 0..channels.min(frames) {
+            data[(frames / 2 + channel) / channels * channels + channel] =
+                if channel % 2 == 0 { 127 } else { -128 };
+        }
+        data
+    }
+
+    fn reference(factor: usize, channels: usize, data: &[i8]) -> Vec<i8> {
+        let mut output = Vec::new();
+        for group in data.chunks_exact(factor * channels) {
+            let lane = |index| group.iter().skip(index).step_by(channels).copied();
+            output.extend((0..channels).map(|index| lane(index).min().unwrap()));
+            output.extend((0..channels).map(|index| lane(index).max().unwrap()));
+        }
+        output
+    }
+
+    #[test]
+    fn test_variants() {
+        for variant in ScanVariant::ALL.into_iter().filter(|variant| variant.is_available()) {
+            for channels in [1, 2, 4] {
+                for frames in [0, 1, 7, 8, 9, 33, 100, 1027] {
+                    let data = stream(channels, frames);
+                    let (mut min, mut max) = (vec![i8::MAX; channels], vec![i8::MIN; channels]);
+                    // SAFETY: Only the available variants are tested.
+                    unsafe { fold_variant(variant, &data, &mut min, &mut max) };
+                    let expected = reference(frames.max(1), channels, &data);
+                    if frames > 0 {
+                        assert_eq!([min, max].concat(), expected,
+                            "{:?}: {} channels over {} frames", variant, channels, frames);
+                    } else {
+                        assert_eq!((min, max), (vec![i8::MAX; channels], vec![i8::MIN; channels]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_process() {
+        for channels in [1, 2, 4] {
+            let data = stream(channels, 1000);
+            for factor in [1, 3, 16, 100, 999] {
+                for chunk_frames in [1, 5, 64, 1000] {
+                    let mut detector = PeakDetector::new(factor, channels);
+                    let mut output = Vec::new();
+                    for chunk in data.chunks(chunk_frames * channels) {
+                        detector.process(chunk, &mut output);
+                    }
+                    assert_eq!(output, reference(factor, channels, &data),
+                        "{} channels, by {} in chunks of {}", channels, factor, chunk_frames);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut detector = PeakDetector::new(4, 1);
+        let mut output = Vec::new();
+        detector.process(&[100, -100, 1], &mut output);
+        assert!(output.is_empty());
+        detector.reset();
+        detector.process(&[1, 2, 3, 4, 5], &mut output);
+        assert_eq!(output, [1, 4]);
+    }
+}
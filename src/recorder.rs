@@ -0,0 +1,475 @@
+//! Recording of the raw sample stream to disk, for long gap-free recordings.
+//!
+//! Each recording file starts with a header (see `RecordingHeader`) describing the parameters
+//! the samples were acquired with and when the first of them was acquired, which is followed by
+//! the samples exactly as they appear in the stream. A recording may be split across several
+//! files (see `recording_path`), each of which is self-contained: a new file is started once
+//! the current one reaches the rotation size, and whenever samples had to be dropped, so that
+//! the samples within any one file are always contiguous.
+//!
+//! The files are written on a thread of their own, in large chunks, which are handed over through
+//! a fixed pool of buffers; if the disk cannot keep up and the pool runs out, the recorder either
+//! waits for a buffer to be written (which eventually causes the device FIFO to overflow) or drops
+//! samples, according to its `Backpressure` policy.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use crate::Result;
+use crate::params::DeviceParameters;
+use crate::timestamp::Timestamp;
+
+const MAGIC: [u8; 8] = *b"TSRECORD";
+
+const VERSION: u16 = 1;
+
+/// The header at the start of every recording file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingHeader {
+    pub params: DeviceParameters,
+    /// Stream position of the first sample in the file, and the host time at which it was
+    /// acquired.
+    pub start: Timestamp,
+}
+
+impl RecordingHeader {
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let params = self.params.encode();
+        let since_epoch = self.start.time.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(params.len() as u16).to_le_bytes())?;
+        writer.write_all(&params)?;
+        writer.write_all(&self.start.sample.to_le_bytes())?;
+        writer.write_all(&since_epoch.as_secs().to_le_bytes())?;
+        writer.write_all(&since_epoch.subsec_nanos().to_le_bytes())?;
+        writer.write_all(&self.start.sample_rate.to_le_bytes())
+    }
+
+    /// Read the header, leaving `reader` at the first sample.
+    pub fn read_from(reader: &mut impl Read) -> std::io::Result<RecordingHeader> {
+        fn read<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+            let mut bytes = [0; N];
+            reader.read_exact(&mut bytes)?;
+            Ok(bytes)
+        }
+        let invalid = |message| std::io::Error::new(ErrorKind::InvalidData, message);
+        if read::<8>(reader)? != MAGIC {
+            return Err(invalid("not a recording"))
+        }
+        if u16::from_le_bytes(read(reader)?) != VERSION {
+            return Err(invalid("unsupported recording version"))
+        }
+        let mut params = vec![0; u16::from_le_bytes(read(reader)?) as usize];
+        reader.read_exact(&mut params)?;
+        let params = DeviceParameters::decode(&params)
+            .ok_or_else(|| invalid("malformed recording parameters"))?;
+        let sample = u64::from_le_bytes(read(reader)?);
+        let time = SystemTime::UNIX_EPOCH + Duration::new(
+            u64::from_le_bytes(read(reader)?), u32::from_le_bytes(read(reader)?));
+        let sample_rate = u64::from_le_bytes(read(reader)?);
+        Ok(RecordingHeader { params, start: Timestamp { sample, time, sample_rate } })
+    }
+}
+
+/// Returns the path of file `index` of the recording at `path`. The first file is at `path`
+/// itself, and the rest have the index appended to the file stem, e.g. `capture-0001.tsr`.
+pub fn recording_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_owned()
+    }
+    let mut file_name = path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(format!("-{:04}", index));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name)
+}
+
+/// What the recorder does when the disk falls behind the sample stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Wait until there is a free buffer. The samples that are not read in the meantime are lost
+    /// when the device FIFO overflows; the recording itself has no gaps, but the stream does.
+    #[default]
+    Block,
+    /// Drop the samples that do not fit into a free buffer, and start a new file once there is
+    /// one again.
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecorderOptions {
+    /// Size of each file after which a new one is started, in bytes; the files are rotated at
+    /// a chunk boundary, so they may be larger by up to `chunk_size`. If `None`, there is no
+    /// limit.
+    pub rotate_after: Option<u64>,
+    pub backpressure: Backpressure,
+    /// Amount of samples written to disk at once.
+    pub chunk_size: usize,
+    /// Amount of chunks that may be waiting to be written.
+    pub queue_depth: usize,
+}
+
+impl Default for RecorderOptions {
+    fn default() -> Self {
+        RecorderOptions {
+            rotate_after: None,
+            backpressure: Backpressure::Block,
+            chunk_size: 4 << 20,
+            queue_depth: 16,
+        }
+    }
+}
+
+/// Statistics of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RecorderStats {
+    /// Amount of samples written to disk so far.
+    pub written: u64,
+    /// Amount of samples dropped because the disk could not keep up.
+    pub dropped: u64,
+    /// Amount of files started so far.
+    pub files: usize,
+}
+
+#[derive(Debug)]
+struct Chunk {
+    data: Vec<u8>,
+    // stream position of the first sample
+    position: u64,
+    // timestamp of the last sample, taken when the chunk is submitted
+    timestamp: Option<Timestamp>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    written: AtomicU64,
+    files: AtomicUsize,
+}
+
+/// Records the sample stream written into it to disk.
+#[derive(Debug)]
+pub struct StreamRecorder {
+    options: RecorderOptions,
+    sample_rate: u64,
+    current: Option<Chunk>,
+    // position of the next sample
+    position: u64,
+    dropped: u64,
+    // Like the acquisition engine, the recorder relies on a pair of channels acting like a bucket
+    // brigade: filled chunks are sent to the writer thread, and return empty once written.
+    chunk_send: Option<Sender<Chunk>>,
+    free_recv: Receiver<Vec<u8>>,
+    counters: Arc<Counters>,
+    thread: Option<JoinHandle<std::io::Result<()>>>,
+}
+
+impl StreamRecorder {
+    /// Start recording samples acquired with `params` into the files at `path` (see
+    /// `recording_path`). Stream positions are counted from the first sample recorded.
+    pub fn create(path: &Path, params: &DeviceParameters, options: RecorderOptions)
+            -> Result<StreamRecorder> {
+        assert!(options.chunk_size > 0 && options.queue_depth > 0);
+        let (chunk_send, chunk_recv) = channel();
+        let (free_send, free_recv) = channel();
+        for _ in 0..options.queue_depth {
+            free_send.send(Vec::with_capacity(options.chunk_size)).unwrap();
+        }
+        let counters = Arc::new(Counters::default());
+        let writer = Writer {
+            path: path.to_owned(),
+            params: *params,
+            rotate_after: options.rotate_after,
+            // fail early if the first file cannot be created
+            first: Some(File::create(path)?),
+            file: None,
+            index: 0,
+            counters: counters.clone(),
+        };
+        let thread = std::thread::Builder::new()
+            .name("recorder".to_owned())
+            .spawn(move || writer.run(chunk_recv, free_send))?;
+        Ok(StreamRecorder {
+            options,
+            sample_rate: params.stream_sample_rate(),
+            current: None,
+            position: 0,
+            dropped: 0,
+            chunk_send: Some(chunk_send),
+            free_recv,
+            counters,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn stats(&self) -> RecorderStats {
+        RecorderStats {
+            written: self.counters.written.load(Ordering::Relaxed),
+            dropped: self.dropped,
+            files: self.counters.files.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the stream position of the next sample to be written.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Record `samples`, which follow the ones written before.
+    ///
+    /// Returns an error if writing to disk has failed; the recording cannot continue after that.
+    pub fn record(&mut self, mut samples: &[u8]) -> Result<()> {
+        while !samples.is_empty() {
+            if self.current.is_none() {
+                let data = match self.options.backpressure {
+                    Backpressure::Block => match self.free_recv.recv() {
+                        Ok(data) => data,
+                        Err(_) => return Err(self.join_writer()),
+                    }
+                    Backpressure::Drop => match self.free_recv.try_recv() {
+                        Ok(data) => data,
+                        Err(TryRecvError::Empty) => {
+                            log::debug!("recorder: dropping {} bytes", samples.len());
+                            self.position += samples.len() as u64;
+                            self.dropped += samples.len() as u64;
+                            return Ok(())
+                        }
+                        Err(TryRecvError::Disconnected) => return Err(self.join_writer()),
+                    }
+                };
+                self.current = Some(Chunk { data, position: self.position, timestamp: None });
+            }
+            let chunk = self.current.as_mut().unwrap();
+            let length = (self.options.chunk_size - chunk.data.len()).min(samples.len());
+            chunk.data.extend_from_slice(&samples[..length]);
+            self.position += length as u64;
+            samples = &samples[length..];
+            if chunk.data.len() == self.options.chunk_size {
+                self.submit()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self) -> Result<()> {
+        let Some(mut chunk) = self.current.take() else { return Ok(()) };
+        if chunk.data.is_empty() {
+            return Ok(())
+        }
+        chunk.timestamp = Some(Timestamp::now(self.position - 1, self.sample_rate));
+        let sent = self.chunk_send.as_ref()
+            .is_some_and(|chunk_send| chunk_send.send(chunk).is_ok());
+        if !sent {
+            return Err(self.join_writer())
+        }
+        Ok(())
+    }
+
+    // returns the error that has stopped the writer thread
+    fn join_writer(&mut self) -> crate::Error {
+        self.chunk_send = None;
+        match self.thread.take().map(|thread| thread.join().expect("recorder thread panicked")) {
+            Some(Err(error)) => error.into(),
+            _ => std::io::Error::new(ErrorKind::BrokenPipe, "recorder has stopped").into(),
+        }
+    }
+
+    /// Write out the samples that have been recorded, and finish the recording.
+    pub fn finish(mut self) -> Result<RecorderStats> {
+        self.submit()?;
+        self.chunk_send = None;
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("recorder thread panicked")?;
+        }
+        Ok(self.stats())
+    }
+}
+
+impl Write for StreamRecorder {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.record(data)?;
+        Ok(data.len())
+    }
+
+    /// Submits the partially filled chunk for writing. This does not wait until it is written.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(self.submit()?)
+    }
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        // the writer thread writes out the submitted chunks and exits
+        self.chunk_send = None;
+    }
+}
+
+struct Writer {
+    path: PathBuf,
+    params: DeviceParameters,
+    rotate_after: Option<u64>,
+    // the first file, which is created up front, until it is used
+    first: Option<File>,
+    // the current file, the amount of samples in it, and the position of the next sample
+    file: Option<(File, u64, u64)>,
+    index: usize,
+    counters: Arc<Counters>,
+}
+
+impl Writer {
+    fn run(mut self, chunk_recv: Receiver<Chunk>, free_send: Sender<Vec<u8>>)
+            -> std::io::Result<()> {
+        for mut chunk in chunk_recv {
+            let contiguous = matches!(self.file, Some((_, length, next)) if
+                next == chunk.position &&
+                    self.rotate_after.is_none_or(|limit| length < limit));
+            if !contiguous {
+                let mut file = match self.first.take() {
+                    Some(file) => file,
+                    None => {
+                        self.index += 1;
+                        File::create(recording_path(&self.path, self.index))?
+                    }
+                };
+                let timestamp = chunk.timestamp.expect("chunk has no timestamp");
+                let header = RecordingHeader {
+                    params: self.params,
+                    start: Timestamp {
+                        sample: chunk.position,
+                        time: timestamp.sample_to_time(chunk.position),
+                        ..timestamp
+                    },
+                };
+                header.write_to(&mut file)?;
+                log::debug!("recorder: writing to {} from sample {}",
+                    recording_path(&self.path, self.index).display(), chunk.position);
+                self.counters.files.fetch_add(1, Ordering::Relaxed);
+                self.file = Some((file, 0, chunk.position));
+            }
+            let (file, length, next) = self.file.as_mut().unwrap();
+            file.write_all(&chunk.data)?;
+            *length += chunk.data.len() as u64;
+            *next += chunk.data.len() as u64;
+            self.counters.written.fetch_add(chunk.data.len() as u64, Ordering::Relaxed);
+            chunk.data.clear();
+            let _ = free_send.send(chunk.data);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn params() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None, None, None],
+            ..Default::default()
+        })
+    }
+
+    fn read_recording(path: &Path, index: usize) -> (RecordingHeader, Vec<u8>) {
+        let mut file = File::open(recording_path(path, index)).unwrap();
+        let header = RecordingHeader::read_from(&mut file).unwrap();
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        (header, data)
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("recorder-{}-{}.tsr", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_recording_path() {
+        assert_eq!(recording_path(Path::new("/a/b.tsr"), 0), Path::new("/a/b.tsr"));
+        assert_eq!(recording_path(Path::new("/a/b.tsr"), 12), Path::new("/a/b-0012.tsr"));
+        assert_eq!(recording_path(Path::new("b"), 1), Path::new("b-0001"));
+    }
+
+    #[test]
+    fn test_header() {
+        let header = RecordingHeader {
+            params: params(),
+            start: Timestamp {
+                sample: 1234,
+                time: SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 5),
+                sample_rate: 1_000_000_000,
+            },
+        };
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        assert_eq!(RecordingHeader::read_from(&mut &data[..]).unwrap(), header);
+        data[0] = b'X';
+        assert!(RecordingHeader::read_from(&mut &data[..]).is_err());
+    }
+
+    #[test]
+    fn test_rotate() {
+        let path = temp_path("rotate");
+        let samples = (0..1000).map(|index| index as u8).collect::<Vec<_>>();
+        let options = RecorderOptions {
+            rotate_after: Some(300),
+            chunk_size: 100,
+            ..Default::default()
+        };
+        let mut recorder = StreamRecorder::create(&path, &params(), options).unwrap();
+        for chunk in samples.chunks(37) {
+            recorder.record(chunk).unwrap();
+        }
+        let stats = recorder.finish().unwrap();
+        assert_eq!(stats, RecorderStats { written: 1000, dropped: 0, files: 4 });
+        let mut recorded = Vec::new();
+        for index in 0..4 {
+            let (header, data) = read_recording(&path, index);
+            assert_eq!(header.start.sample, recorded.len() as u64);
+            assert_eq!(header.params, params());
+            assert_eq!(data.len(), if index < 3 { 300 } else { 100 });
+            recorded.extend(data);
+            std::fs::remove_file(recording_path(&path, index)).unwrap();
+        }
+        assert_eq!(recorded, samples);
+    }
+
+    #[test]
+    fn test_gap() {
+        let path = temp_path("gap");
+        let options = RecorderOptions {
+            backpressure: Backpressure::Drop,
+            chunk_size: 10,
+            queue_depth: 1,
+            ..Default::default()
+        };
+        let mut recorder = StreamRecorder::create(&path, &params(), options).unwrap();
+        recorder.record(&[1; 10]).unwrap();
+        // make the next samples be dropped, as if the disk could not keep up
+        let data = recorder.free_recv.recv().unwrap();
+        recorder.record(&[2; 10]).unwrap();
+        assert_eq!(recorder.stats().dropped, 10);
+        recorder.free_recv = {
+            let (free_send, free_recv) = channel();
+            free_send.send(data).unwrap();
+            free_recv
+        };
+        recorder.record(&[3; 5]).unwrap();
+        let stats = recorder.finish().unwrap();
+        assert_eq!(stats, RecorderStats { written: 15, dropped: 10, files: 2 });
+        assert_eq!(read_recording(&path, 0).1, [1; 10]);
+        let (header, data) = read_recording(&path, 1);
+        assert_eq!((header.start.sample, data), (20, vec![3; 5]));
+        for index in 0..2 {
+            std::fs::remove_file(recording_path(&path, index)).unwrap();
+        }
+    }
+}
@@ -11,6 +11,16 @@ pub const ADDR_HMCAD1520_INSEL12: u8 = 0x3A;
 pub const ADDR_HMCAD1520_INSEL34: u8 = 0x3B;
 pub const ADDR_HMCAD1520_FS_CNTRL: u8 = 0x55;
 pub const ADDR_HMCAD1520_RES_SEL: u8 = 0x53;
+pub const HMCAD1520_RES_SEL_8BIT: u16 = 0x0000;
+pub const HMCAD1520_RES_SEL_12BIT: u16 = 0x0001;
 pub const ADDR_HMCAD1520_LVDS_PHASE: u8 = 0x42;
 pub const ADDR_HMCAD1520_LVDS_DRIVE: u8 = 0x11;
 pub const ADDR_HMCAD1520_LVDS_PATTERN: u8 = 0x25;
+pub const ADDR_HMCAD1520_PAT_DESKEW_SYNC: u8 = 0x45;
+pub const HMCAD1520_PAT_DESKEW: u16 = 0x0001;
+pub const HMCAD1520_PAT_SYNC: u16 = 0x0002;
+// in 8-bit mode
+pub const HMCAD1520_DESKEW_PATTERN: u8 = 0b1010_1010;
+pub const HMCAD1520_SYNC_PATTERN: u8 = 0b1111_0000;
+// 0, 90, 180, and 270 degrees; the first one is the default
+pub const HMCAD1520_LVDS_PHASES: [u16; 4] = [0x0060, 0x0040, 0x0020, 0x0000];
@@ -14,6 +14,7 @@ bitflags! {
 
         const ChannelMux0       = 1<<4;
         const ChannelMux1       = 1<<5;
+        const HighResolution    = 1<<6;
 
         const Ch1Termination    = 1<<12;
         const Ch2Termination    = 1<<13;
@@ -90,6 +91,48 @@ impl Status {
     }
 }
 
+/// Gateware Identification Register; reads as zero in gateware that predates it.
+pub const ADDR_GATEWARE_ID: usize = 0x10;
+
+/// Value of the top half of `ADDR_GATEWARE_ID`, "TS" in ASCII. The bottom half contains
+/// the major and the minor version of the gateware, in that order.
+pub const GATEWARE_ID_MAGIC: u16 = 0x5453;
+
+/// Gateware Capability Register; reads as zero in gateware that predates it.
+pub const ADDR_GATEWARE_CAPS: usize = 0x14;
+
+/// Contents of `ADDR_GATEWARE_CAPS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GatewareCaps(pub u32);
+
+impl GatewareCaps {
+    /// Size of the DMA memory, as a base 2 logarithm of the size in bytes.
+    pub fn memory_size_log2(self) -> u32 {
+        self.0 & 0x3F
+    }
+
+    /// Amount of input channels.
+    pub fn channels(self) -> usize {
+        ((self.0 >> 8) & 0xF) as usize
+    }
+
+    /// Whether the ADC interface and the data mover support 12-bit samples.
+    pub fn high_resolution(self) -> bool {
+        self.0 & (1 << 12) != 0
+    }
+
+    /// Whether the SPI/I2C engine returns the data it reads through the receive side of
+    /// the FIFO (see `bus`).
+    pub fn bus_readback(self) -> bool {
+        self.0 & (1 << 13) != 0
+    }
+
+    /// Largest sample rate, in MS/s.
+    pub fn max_sample_rate(self) -> u32 {
+        self.0 >> 16
+    }
+}
+
 // For the FIFO registers, see the documentation for the Xilinx AXI4-Stream FIFO v4.1 core.
 // /https://confluence.slac.stanford.edu/download/attachments/240276688/pg080-axi-fifo-mm-s.pdf
 
@@ -173,3 +216,22 @@ pub const ADDR_FIFO_TLR: usize = 0x00020014;
 
 /// FIFO Transmit Destination Register
 pub const ADDR_FIFO_TDR: usize = 0x0002002C;
+
+/// FIFO Receive Reset Register
+pub const ADDR_FIFO_RDFR: usize = 0x00020018;
+
+/// Value that resets the receive logic when written to `ADDR_FIFO_RDFR` (or the transmit logic
+/// when written to `ADDR_FIFO_TDFR`).
+pub const FIFO_RESET_KEY: u32 = 0xa5;
+
+/// FIFO Receive Occupancy Register
+pub const ADDR_FIFO_RDFO: usize = 0x0002001c;
+
+/// FIFO Receive Data Register
+pub const ADDR_FIFO_RDFD: usize = 0x00020020;
+
+/// FIFO Receive Length Register
+pub const ADDR_FIFO_RLR: usize = 0x00020024;
+
+/// FIFO Receive Destination Register
+pub const ADDR_FIFO_RDR: usize = 0x00020030;
@@ -0,0 +1,265 @@
+//! Replay of sample streams recorded earlier, so that triggering, decoding, and frontends can be
+//! developed and tested against real signals without a device.
+//!
+//! Both recordings made by `StreamRecorder` (which know the parameters the samples were acquired
+//! with, and may span several files) and raw dumps of the sample stream (e.g. those saved by
+//! the `test` binary, for which the parameters must be provided) can be replayed. The samples are
+//! read as fast as they are requested, or, if throttling is enabled, no faster than they were
+//! originally acquired.
+
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+use crate::acquire::SampleSource;
+use crate::params::DeviceParameters;
+use crate::recorder::{recording_path, RecordingHeader};
+use crate::timestamp::Timestamp;
+
+/// Reads a recorded sample stream, in the same way as `DataStream` reads the stream of a device.
+///
+/// Where the recording has a gap, reading returns `Error::Overflow` once, as `DataStream` does
+/// when samples are lost, so that the acquisition restarts. Once all of the samples are read,
+/// reading returns an `UnexpectedEof` error.
+#[derive(Debug)]
+pub struct ReplaySource {
+    path: PathBuf,
+    params: DeviceParameters,
+    // index of the file of the recording being read, or `None` for a raw dump
+    index: Option<usize>,
+    reader: BufReader<File>,
+    position: u64,
+    timestamp: Option<Timestamp>,
+    throttled: bool,
+    // host time at which the first sample was read, and its stream position
+    started: Option<(Instant, u64)>,
+}
+
+impl ReplaySource {
+    /// Open the recording at `path` (see `recording_path`). The files following the first one
+    /// are opened as it is read.
+    pub fn open(path: &Path) -> Result<ReplaySource> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = RecordingHeader::read_from(&mut reader)?;
+        Ok(ReplaySource {
+            path: path.to_owned(),
+            params: header.params,
+            index: Some(0),
+            reader,
+            position: header.start.sample,
+            timestamp: Some(header.start),
+            throttled: false,
+            started: None,
+        })
+    }
+
+    /// Open the raw dump of a sample stream acquired with `params` at `path`.
+    pub fn open_raw(path: &Path, params: &DeviceParameters) -> Result<ReplaySource> {
+        Ok(ReplaySource {
+            path: path.to_owned(),
+            params: *params,
+            index: None,
+            reader: BufReader::new(File::open(path)?),
+            position: 0,
+            timestamp: None,
+            throttled: false,
+            started: None,
+        })
+    }
+
+    /// Read samples no faster than they were acquired (at `stream_sample_rate()` of
+    /// the recorded parameters), or as fast as possible.
+    pub fn with_throttling(self, throttled: bool) -> ReplaySource {
+        ReplaySource { throttled, ..self }
+    }
+
+    /// Returns the parameters the samples were acquired with; the acquisition should be
+    /// configured with them, since the recorded samples cannot be reconfigured.
+    pub fn params(&self) -> &DeviceParameters {
+        &self.params
+    }
+
+    /// Returns the stream position of the next sample, as in `DataStream::position()`. Where
+    /// the recording has a gap, the position advances by the amount of missing samples.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the stream position and host time of the first sample of the file being read,
+    /// or `None` for a raw dump.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    // returns the amount of samples missing before the next file, or `None` if there are no more
+    // files
+    fn open_next_file(&mut self) -> std::io::Result<Option<u64>> {
+        let Some(index) = self.index else { return Ok(None) };
+        let path = recording_path(&self.path, index + 1);
+        let mut reader = match File::open(&path) {
+            Ok(file) => BufReader::new(file),
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let header = RecordingHeader::read_from(&mut reader)?;
+        let invalid = |message| std::io::Error::new(ErrorKind::InvalidData, message);
+        if header.params != self.params {
+            return Err(invalid("parameters differ between the files of the recording"))
+        }
+        if header.start.sample < self.position {
+            return Err(invalid("files of the recording overlap"))
+        }
+        let missing = header.start.sample - self.position;
+        if missing > 0 {
+            log::debug!("replay: {} samples missing before {}", missing, path.display());
+        }
+        self.index = Some(index + 1);
+        self.reader = reader;
+        self.position = header.start.sample;
+        self.timestamp = Some(header.start);
+        Ok(Some(missing))
+    }
+
+    fn throttle(&self) {
+        let Some((started_at, start)) = self.started else { return };
+        let elapsed = Duration::from_secs_f64(
+            (self.position - start) as f64 / self.params.stream_sample_rate() as f64);
+        if let Some(delay) = elapsed.checked_sub(started_at.elapsed()) {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+impl Read for ReplaySource {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0)
+        }
+        if self.throttled && self.started.is_none() {
+            self.started = Some((Instant::now(), self.position));
+        }
+        let length = loop {
+            match self.reader.read(data)? {
+                0 => match self.open_next_file()? {
+                    None =>
+                        return Err(std::io::Error::new(ErrorKind::UnexpectedEof,
+                            "end of recorded samples")),
+                    Some(0) => continue,
+                    // the samples that follow are not contiguous with those read before, so
+                    // the gap is reported as the data mover failure would be, in pages of 4 KiB
+                    Some(missing) =>
+                        return Err(Error::Overflow {
+                            lost_pages: (missing as usize).div_ceil(4096) }.into()),
+                },
+                length => break length,
+            }
+        };
+        self.position += length as u64;
+        if self.throttled {
+            self.throttle();
+        }
+        Ok(length)
+    }
+}
+
+impl SampleSource for ReplaySource {}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+    use crate::{RecorderOptions, StreamRecorder};
+
+    fn params() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None, None, None],
+            ..Default::default()
+        })
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("replay-{}-{}.tsr", name, std::process::id()))
+    }
+
+    fn assert_end(source: &mut ReplaySource) {
+        let error = source.read(&mut [0; 1]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_recording() {
+        let path = temp_path("recording");
+        let samples = (0..1000).map(|index| index as u8).collect::<Vec<_>>();
+        let options = RecorderOptions {
+            rotate_after: Some(300),
+            chunk_size: 100,
+            ..Default::default()
+        };
+        let mut recorder = StreamRecorder::create(&path, &params(), options).unwrap();
+        recorder.record(&samples).unwrap();
+        assert_eq!(recorder.finish().unwrap().files, 4);
+        let mut source = ReplaySource::open(&path).unwrap();
+        assert_eq!(source.params(), &params());
+        let mut replayed = vec![0; samples.len()];
+        source.read_exact(&mut replayed).unwrap();
+        assert_eq!(replayed, samples);
+        assert_eq!(source.position(), 1000);
+        assert_eq!(source.timestamp().unwrap().sample, 900);
+        assert_end(&mut source);
+        for index in 0..4 {
+            std::fs::remove_file(recording_path(&path, index)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gap() {
+        let path = temp_path("gap");
+        for (index, start, data) in [(0, 0, [1; 10]), (1, 20, [2; 10])] {
+            let mut file = File::create(recording_path(&path, index)).unwrap();
+            let header = RecordingHeader {
+                params: params(),
+                start: Timestamp::now(start, params().stream_sample_rate()),
+            };
+            header.write_to(&mut file).unwrap();
+            file.write_all(&data).unwrap();
+        }
+        let mut source = ReplaySource::open(&path).unwrap();
+        let mut data = [0; 16];
+        assert_eq!(source.read(&mut data).unwrap(), 10);
+        assert_eq!(source.position(), 10);
+        let error = Error::from(source.read(&mut data).unwrap_err());
+        assert!(matches!(error, Error::Overflow { lost_pages: 1 }), "{}", error);
+        assert_eq!(source.position(), 20);
+        assert_eq!(source.read(&mut data).unwrap(), 10);
+        assert_eq!(data[..10], [2; 10]);
+        assert_eq!(source.position(), 30);
+        assert_end(&mut source);
+        for index in 0..2 {
+            std::fs::remove_file(recording_path(&path, index)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_raw() {
+        let path = temp_path("raw");
+        std::fs::write(&path, [5; 100]).unwrap();
+        let mut source = ReplaySource::open_raw(&path, &params()).unwrap()
+            .with_throttling(true);
+        assert!(source.timestamp().is_none());
+        let started_at = SystemTime::now();
+        let mut data = [0; 100];
+        source.read_exact(&mut data).unwrap();
+        assert_eq!(data, [5; 100]);
+        assert_eq!(source.position(), 100);
+        assert_end(&mut source);
+        // 100 samples take well under a millisecond to acquire
+        assert!(started_at.elapsed().unwrap() < Duration::from_secs(1));
+        std::fs::remove_file(&path).unwrap();
+        assert!(ReplaySource::open(&path).is_err());
+    }
+}
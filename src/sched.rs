@@ -0,0 +1,84 @@
+//! Scheduling of the threads that handle acquisition.
+//!
+//! On a busy desktop, the thread reading samples may be descheduled for long enough that the FIFO
+//! in the gateware overflows. Running it with a real-time priority, or pinning it to cores that
+//! are not used by anything else (e.g. ones excluded from scheduling with `isolcpus=`), avoids
+//! that.
+
+use crate::{Error, Result};
+
+/// How a thread is scheduled. The default leaves the scheduling of the thread unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ThreadScheduling {
+    /// Priority for the real-time FIFO policy, from 1 to 99. If `None`, the policy is unchanged.
+    pub realtime_priority: Option<u8>,
+    /// Indices of the CPU cores the thread may run on. If empty, it may run on any core.
+    pub cpu_affinity: Vec<usize>,
+}
+
+impl ThreadScheduling {
+    /// Applies the scheduling to the calling thread.
+    ///
+    /// On Linux, a real-time priority requires either the `CAP_SYS_NICE` capability or
+    /// an appropriate `RLIMIT_RTPRIO` limit. On other platforms, returns `Error::Unsupported`
+    /// unless the scheduling is the default one.
+    pub fn apply(&self) -> Result<()> {
+        if *self == Self::default() {
+            return Ok(())
+        }
+        self.apply_impl()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn apply_impl(&self) -> Result<()> {
+        if !self.cpu_affinity.is_empty() {
+            let mut cpu_set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+            for &cpu in self.cpu_affinity.iter() {
+                if cpu >= libc::CPU_SETSIZE as usize {
+                    return Err(Error::Other(format!("CPU core {} does not exist", cpu).into()))
+                }
+                unsafe { libc::CPU_SET(cpu, &mut cpu_set) }
+            }
+            // thread ID 0 is the calling thread
+            let result = unsafe {
+                libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set)
+            };
+            if result == -1 {
+                return Err(std::io::Error::last_os_error().into())
+            }
+        }
+        if let Some(priority) = self.realtime_priority {
+            let param = libc::sched_param { sched_priority: priority as libc::c_int };
+            match unsafe {
+                libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param)
+            } {
+                0 => (),
+                errno => return Err(std::io::Error::from_raw_os_error(errno).into())
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply_impl(&self) -> Result<()> {
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        assert!(ThreadScheduling::default().apply().is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_nonexistent_core() {
+        let scheduling = ThreadScheduling { cpu_affinity: vec![1 << 20], ..Default::default() };
+        assert!(matches!(scheduling.apply(), Err(Error::Other(_))));
+    }
+}
@@ -1,15 +1,20 @@
 use std::ffi::{CStr, CString};
+use std::time::Duration;
 use std::{fs, io};
 use libc::{c_int, c_void};
 use crate::Result;
+use crate::device::Descriptor;
+
+// user interrupt requested by the gateware when the data mover has moved pages
+const EVENT_INDEX: usize = 0;
 
 #[derive(Debug)]
 struct Fd(c_int);
 
 impl Fd {
-    fn open(path: &CStr) -> io::Result<Fd> {
+    fn open(path: &CStr, flags: c_int) -> io::Result<Fd> {
         unsafe {
-            let fd = libc::open(path.as_ptr(), libc::O_RDWR);
+            let fd = libc::open(path.as_ptr(), flags);
             if fd == -1 {
                 Err(io::Error::last_os_error())
             } else {
@@ -30,6 +35,32 @@ impl Fd {
         }
     }
 
+    fn read(&self, data: &mut [u8]) -> io::Result<usize> {
+        unsafe {
+            match libc::read(self.0, data.as_mut_ptr() as *mut c_void, data.len()) {
+                -1 => Err(io::Error::last_os_error()),
+                bytes_read => Ok(bytes_read as usize)
+            }
+        }
+    }
+
+    // returns `false` if the timeout expires, or if a signal is delivered while waiting
+    fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd { fd: self.0, events: libc::POLLIN, revents: 0 };
+        let timeout_ms = timeout.as_millis().try_into().unwrap_or(c_int::MAX);
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            -1 => {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(false),
+                    _ => Err(error)
+                }
+            }
+            0 => Ok(false),
+            _ => Ok(true)
+        }
+    }
+
     fn write_at(&self, offset: usize, data: &[u8]) -> io::Result<()> {
         unsafe {
             let bytes_written = libc::pwrite(
@@ -57,6 +88,38 @@ impl Drop for Fd {
 pub struct DriverData {
     user_fd: Fd,
     c2h_fd: Fd,
+    events_fd: Option<Fd>,
+}
+
+// matches `xdmaN_control`, returning `N`
+fn parse_control_node(name: &str) -> Option<u32> {
+    let index = name.strip_prefix("xdma")?.strip_suffix("_control")?;
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) { return None }
+    index.parse().ok()
+}
+
+// the gateware revision is reported as the PCI revision ID
+fn read_pci_revision(index: u32) -> Option<u8> {
+    let path = format!("/sys/class/xdma/xdma{}_control/device/revision", index);
+    let text = fs::read_to_string(path).ok()?;
+    u8::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+pub fn enumerate() -> Result<Vec<Descriptor>> {
+    let mut indices = Vec::new();
+    for entry in fs::read_dir("/dev")? {
+        let entry = entry?;
+        if let Some(index) = entry.file_name().to_str().and_then(parse_control_node) {
+            indices.push(index);
+        }
+    }
+    indices.sort();
+    Ok(indices.into_iter().map(|index| Descriptor {
+        path: format!("/dev/xdma{}", index),
+        // the gateware does not expose a serial number yet
+        serial: None,
+        gateware_revision: read_pci_revision(index),
+    }).collect())
 }
 
 pub fn open(device_path: &str) -> Result<DriverData> {
@@ -64,9 +127,20 @@ pub fn open(device_path: &str) -> Result<DriverData> {
     if fs::metadata(control_path).is_ok() {
         let user_path = CString::new(device_path.to_owned() + "_user").unwrap();
         let d2h_path = CString::new(device_path.to_owned() + "_c2h_0").unwrap();
+        let events_path = CString::new(format!("{}_events_{}", device_path, EVENT_INDEX))
+            .unwrap();
         Ok(DriverData {
-            user_fd: Fd::open(user_path.as_ref())?,
-            c2h_fd: Fd::open(d2h_path.as_ref())?,
+            user_fd: Fd::open(user_path.as_ref(), libc::O_RDWR)?,
+            c2h_fd: Fd::open(d2h_path.as_ref(), libc::O_RDWR)?,
+            // older drivers may not provide event nodes; the status register is polled then
+            events_fd: match Fd::open(events_path.as_ref(), libc::O_RDONLY) {
+                Ok(fd) => Some(fd),
+                Err(error) => {
+                    log::warn!("cannot open {:?}, polling for data instead: {}",
+                        events_path, error);
+                    None
+                }
+            },
         })
     } else {
         Err(crate::Error::NotFound)
@@ -84,3 +158,29 @@ pub fn write_user(driver_data: &DriverData, addr: usize, data: &[u8]) -> Result<
 pub fn read_dma(driver_data: &DriverData, addr: usize, data: &mut [u8]) -> Result<()> {
     Ok(driver_data.c2h_fd.read_at(addr, data)?)
 }
+
+pub fn wait_event(driver_data: &DriverData, timeout: Duration) -> Result<bool> {
+    let Some(events_fd) = driver_data.events_fd.as_ref() else { return Ok(false) };
+    if !events_fd.wait_readable(timeout)? {
+        return Ok(false)
+    }
+    // acknowledge the interrupt; the driver returns the amount of events since the last read
+    let mut events = [0u8; 4];
+    events_fd.read(&mut events)?;
+    log::trace!("wait_event() = {}", u32::from_ne_bytes(events));
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_control_node() {
+        assert_eq!(parse_control_node("xdma0_control"), Some(0));
+        assert_eq!(parse_control_node("xdma12_control"), Some(12));
+        assert_eq!(parse_control_node("xdma0_user"), None);
+        assert_eq!(parse_control_node("xdma_control"), None);
+        assert_eq!(parse_control_node("xdma+1_control"), None);
+    }
+}
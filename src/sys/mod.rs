@@ -1,30 +1,119 @@
+use std::time::Duration;
+
 use crate::Result;
+use crate::device::Descriptor;
 
-#[cfg(all(feature = "hardware", any(target_os = "linux")))]
+#[cfg(all(feature = "hardware", target_os = "linux"))]
 #[path = "linux.rs"]
 mod imp;
 
-#[cfg(not(all(feature = "hardware", any(target_os = "linux"))))]
+#[cfg(all(feature = "hardware", target_os = "windows"))]
+#[path = "windows.rs"]
+mod imp;
+
+#[cfg(not(all(feature = "hardware", any(target_os = "linux", target_os = "windows"))))]
 #[path = "stub.rs"]
 mod imp;
 
+mod sim;
+
+pub use sim::SimulatedSignal;
+
+#[derive(Debug)]
+enum Backend {
+    Hardware(imp::DriverData),
+    Simulated(Box<sim::DriverData>),
+}
+
 #[derive(Debug)]
-pub struct Driver(imp::DriverData);
+pub struct Driver(Backend);
+
+pub fn enumerate() -> Result<Vec<Descriptor>> {
+    imp::enumerate()
+}
 
 impl Driver {
     pub fn new(device_path: &str) -> Result<Self> {
-        Ok(Self(imp::open(device_path)?))
+        Ok(Self(Backend::Hardware(imp::open(device_path)?)))
+    }
+
+    pub fn simulated(signal: SimulatedSignal) -> Self {
+        Self(Backend::Simulated(Box::new(sim::DriverData::new(signal))))
+    }
+
+    /// Returns the packets sent through the FIFO of the simulated device so far.
+    #[cfg(test)]
+    pub fn simulated_packets(&self) -> Vec<Vec<u8>> {
+        match &self.0 {
+            Backend::Hardware(_) => unimplemented!(),
+            Backend::Simulated(driver_data) => driver_data.packets(),
+        }
+    }
+
+    /// Make the identification registers of the simulated device read as `id` and `caps`.
+    #[cfg(test)]
+    pub fn simulate_gateware(&self, id: u32, caps: u32) {
+        match &self.0 {
+            Backend::Hardware(_) => unimplemented!(),
+            Backend::Simulated(driver_data) => driver_data.set_gateware(id, caps),
+        }
+    }
+
+    /// Make the simulated FIFO never complete a transmission.
+    #[cfg(test)]
+    pub fn simulate_wedged_fifo(&self) {
+        match &self.0 {
+            Backend::Hardware(_) => unimplemented!(),
+            Backend::Simulated(driver_data) => driver_data.wedge_fifo(),
+        }
+    }
+
+    /// Make the simulated data mover report a FIFO overflow until the acquisition is reset.
+    #[cfg(test)]
+    pub fn simulate_overflow(&self) {
+        match &self.0 {
+            Backend::Hardware(_) => unimplemented!(),
+            Backend::Simulated(driver_data) => driver_data.overflow(),
+        }
+    }
+
+    /// Make the samples of the simulated device misframed by a bit while the LVDS phase of
+    /// the ADC is one of `lvds_phases`.
+    #[cfg(test)]
+    pub fn simulate_bit_slip(&self, lvds_phases: &[u16]) {
+        match &self.0 {
+            Backend::Hardware(_) => unimplemented!(),
+            Backend::Simulated(driver_data) => driver_data.slip_bits(lvds_phases),
+        }
     }
 
     pub fn read_user(&self, addr: usize, data: &mut [u8]) -> Result<()> {
-        imp::read_user(&self.0, addr, data)
+        match &self.0 {
+            Backend::Hardware(driver_data) => imp::read_user(driver_data, addr, data),
+            Backend::Simulated(driver_data) => sim::read_user(driver_data, addr, data),
+        }
     }
 
     pub fn write_user(&self, addr: usize, data: &[u8]) -> Result<()> {
-        imp::write_user(&self.0, addr, data)
+        match &self.0 {
+            Backend::Hardware(driver_data) => imp::write_user(driver_data, addr, data),
+            Backend::Simulated(driver_data) => sim::write_user(driver_data, addr, data),
+        }
     }
 
     pub fn read_dma(&self, addr: usize, data: &mut [u8]) -> Result<()> {
-        imp::read_dma(&self.0, addr, data)
+        match &self.0 {
+            Backend::Hardware(driver_data) => imp::read_dma(driver_data, addr, data),
+            Backend::Simulated(driver_data) => sim::read_dma(driver_data, addr, data),
+        }
+    }
+
+    /// Wait for the gateware to signal an event, for at most `timeout`. Returns `false` if
+    /// the timeout expires, or if events are not supported (in which case it returns at once).
+    pub fn wait_event(&self, timeout: Duration) -> Result<bool> {
+        match &self.0 {
+            Backend::Hardware(driver_data) => imp::wait_event(driver_data, timeout),
+            Backend::Simulated(driver_data) => sim::wait_event(driver_data, timeout),
+        }
     }
 }
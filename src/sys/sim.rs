@@ -0,0 +1,403 @@
+//! Simulated device, for exercising the driver without hardware.
+//!
+//! The control, status, and FIFO registers are modelled closely enough for the startup,
+//! configuration, and streaming sequences to run unmodified. Packets sent through the FIFO
+//! (to the SPI and I2C buses) are recorded; the PGA, digipot, and trimdac registers are modelled
+//! so that they can be read back, and the rest of the packets are ignored. The data mover runs in
+//! real time at 1 GS/s while enabled, and the DMA memory contains a synthetic waveform, or
+//! the test pattern of the ADC if one is enabled.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Result;
+use crate::regs::adc;
+use crate::regs::axi::{self, Control, FifoIsr, Status};
+
+const PAGE_BITS: usize = 12;
+const SPI_BUS_PGA: std::ops::RangeInclusive<u8> = 2..=5;
+const I2C_ADDR_DIGIPOT: u8 = 0b0101100;
+const I2C_ADDR_TRIMDAC: u8 = 0b1100000;
+const MEMORY_SIZE: u64 = 1 << 16 << PAGE_BITS;
+
+/// Waveform in the sample stream of the simulated device. It is the same on every channel, except
+/// for `Ramp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimulatedSignal {
+    /// Each sample in the stream (regardless of the channel) is one greater than the previous one,
+    /// wrapping around; useful to check that no samples are lost or duplicated.
+    Ramp,
+    /// Sine wave with `period` samples (per channel) and `amplitude` ADC codes.
+    Sine { period: f32, amplitude: i8 },
+    /// Square wave with `period` samples (per channel) and `amplitude` ADC codes.
+    Square { period: f32, amplitude: i8 },
+}
+
+impl SimulatedSignal {
+    fn sample(self, channels: usize, position: u64) -> i8 {
+        let phase = || ((position / channels as u64) as f64 % self.period()) / self.period();
+        match self {
+            SimulatedSignal::Ramp => position as u8 as i8,
+            SimulatedSignal::Sine { amplitude, .. } =>
+                (amplitude as f64 * (2.0 * std::f64::consts::PI * phase()).sin()) as i8,
+            SimulatedSignal::Square { amplitude, .. } =>
+                if phase() < 0.5 { amplitude } else { -amplitude },
+        }
+    }
+
+    fn period(self) -> f64 {
+        match self {
+            SimulatedSignal::Ramp => 1.0,
+            SimulatedSignal::Sine { period, .. } |
+            SimulatedSignal::Square { period, .. } => (period as f64).max(1.0),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    gateware_id: u32,
+    gateware_caps: u32,
+    control: Control,
+    fifo_isr: FifoIsr,
+    fifo_data: Vec<u8>,
+    // set when a transmission starts; it completes (instantly) once the status is polled
+    transmitting: bool,
+    wedged: bool,
+    packets: Vec<Vec<u8>>,
+    // packets waiting in the receive FIFO, and the rest of the one whose length has been read
+    received: VecDeque<Vec<u8>>,
+    receiving: VecDeque<u8>,
+    pga_commands: [u16; 4],
+    digipot_inputs: [u16; 16],
+    trimdac_inputs: [u16; 4],
+    adc_pattern: u16,
+    lvds_phase: u16,
+    // the LVDS phases at which the samples are misframed by a bit
+    slipped_phases: Vec<u16>,
+    // bytes moved before `running_since`, or in total if the data mover is not running
+    moved: u64,
+    running_since: Option<Instant>,
+    // sticky until the acquisition subsystem is reset
+    overflow: bool,
+}
+
+impl State {
+    fn moved(&self) -> u64 {
+        // 1 byte per nanosecond
+        self.moved + self.running_since.map_or(0, |since| since.elapsed().as_nanos() as u64)
+    }
+
+    fn channels(&self) -> usize {
+        if self.control.contains(Control::ChannelMux1) {
+            4
+        } else if self.control.contains(Control::ChannelMux0) {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn write_control(&mut self, control: Control) {
+        if !control.contains(Control::FpgaAcqResetN) {
+            self.moved = 0;
+            self.running_since = None;
+            self.overflow = false;
+        } else if !control.contains(Control::DatamoverHaltN) {
+            self.moved = self.moved();
+            self.running_since = None;
+        } else if self.running_since.is_none() {
+            self.running_since = Some(Instant::now());
+        }
+        self.control = control;
+    }
+
+    fn adc_pattern(&self) -> Option<u8> {
+        if self.adc_pattern & adc::HMCAD1520_PAT_DESKEW != 0 {
+            Some(adc::HMCAD1520_DESKEW_PATTERN)
+        } else if self.adc_pattern & adc::HMCAD1520_PAT_SYNC != 0 {
+            Some(adc::HMCAD1520_SYNC_PATTERN)
+        } else {
+            None
+        }
+    }
+
+    /// Execute a transaction on the SPI or I2C bus, returning the received packet, if any.
+    fn execute(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+        match *packet {
+            // PGA; the LMH6518 has a single register, the command word
+            [header, command, high, low]
+                    if header <= 0xfd && SPI_BUS_PGA.contains(&(0xfd - header)) => {
+                let index = (0xfd - header - SPI_BUS_PGA.start()) as usize;
+                let value = self.pga_commands[index];
+                if command & 0x80 == 0 {
+                    self.pga_commands[index] = (high as u16) << 8 | low as u16;
+                }
+                Some(vec![0, (value >> 8) as u8, value as u8])
+            }
+            // ADC; only the registers that affect the sample stream are modelled
+            [0xfd, address, high, low] => {
+                let value = (high as u16) << 8 | low as u16;
+                match address {
+                    adc::ADDR_HMCAD1520_PAT_DESKEW_SYNC => self.adc_pattern = value,
+                    adc::ADDR_HMCAD1520_LVDS_PHASE => self.lvds_phase = value,
+                    _ => ()
+                }
+                None
+            }
+            // digipot, write command
+            [0xff, I2C_ADDR_DIGIPOT, command, low] if command & 0b1100 == 0b0000 => {
+                self.digipot_inputs[(command >> 4) as usize] =
+                    ((command & 0b11) as u16) << 8 | low as u16;
+                None
+            }
+            // digipot, read command
+            [0xfe, I2C_ADDR_DIGIPOT, 2, command] if command & 0b1100 == 0b1100 => {
+                let value = self.digipot_inputs[(command >> 4) as usize];
+                Some(vec![(value >> 8) as u8, value as u8])
+            }
+            // trimdac, single write command
+            [0xff, I2C_ADDR_TRIMDAC, command, high, low] if command >> 3 == 0b01011 => {
+                self.trimdac_inputs[((command >> 1) & 0b11) as usize] =
+                    (high as u16) << 8 | low as u16;
+                None
+            }
+            // trimdac, read of the DAC input registers and the EEPROM of every channel
+            [0xfe, I2C_ADDR_TRIMDAC, 24] => {
+                Some(self.trimdac_inputs.iter().flat_map(|&value| {
+                    let status = 0b1100_0000; // ready, powered on
+                    let (high, low) = ((value >> 8) as u8, value as u8);
+                    [status, high, low, status, high, low]
+                }).collect())
+            }
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DriverData {
+    signal: SimulatedSignal,
+    state: Mutex<State>,
+}
+
+impl DriverData {
+    pub fn new(signal: SimulatedSignal) -> DriverData {
+        DriverData {
+            signal,
+            state: Mutex::new(State {
+                // version 1.0; 256 MiB, 4 channels, 1000 MS/s, bus readback
+                gateware_id: 0x5453_0100,
+                gateware_caps: 0x03e8_241c,
+                control: Control::empty(),
+                fifo_isr: FifoIsr::empty(),
+                fifo_data: Vec::new(),
+                transmitting: false,
+                wedged: false,
+                packets: Vec::new(),
+                received: VecDeque::new(),
+                receiving: VecDeque::new(),
+                pga_commands: [0; 4],
+                digipot_inputs: [0; 16],
+                trimdac_inputs: [0; 4],
+                adc_pattern: 0,
+                lvds_phase: 0,
+                slipped_phases: Vec::new(),
+                moved: 0,
+                running_since: None,
+                overflow: false,
+            }),
+        }
+    }
+
+    /// Returns the packets sent through the FIFO so far.
+    #[cfg(test)]
+    pub fn packets(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().packets.clone()
+    }
+
+    /// Makes the identification registers read as `id` and `caps`.
+    #[cfg(test)]
+    pub fn set_gateware(&self, id: u32, caps: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.gateware_id = id;
+        state.gateware_caps = caps;
+    }
+
+    /// Makes the FIFO never complete a transmission, as if the SPI/I2C engine were wedged.
+    #[cfg(test)]
+    pub fn wedge_fifo(&self) {
+        self.state.lock().unwrap().wedged = true;
+    }
+
+    /// Makes the data mover report a FIFO overflow until the acquisition is reset.
+    #[cfg(test)]
+    pub fn overflow(&self) {
+        self.state.lock().unwrap().overflow = true;
+    }
+
+    /// Makes the samples misframed by a bit while the LVDS phase is one of `lvds_phases`.
+    #[cfg(test)]
+    pub fn slip_bits(&self, lvds_phases: &[u16]) {
+        self.state.lock().unwrap().slipped_phases = lvds_phases.to_vec();
+    }
+}
+
+pub fn read_user(driver_data: &DriverData, addr: usize, data: &mut [u8]) -> Result<()> {
+    let mut state = driver_data.state.lock().unwrap();
+    let value = match addr {
+        axi::ADDR_CONTROL => state.control.bits(),
+        axi::ADDR_GATEWARE_ID => state.gateware_id,
+        axi::ADDR_GATEWARE_CAPS => state.gateware_caps,
+        axi::ADDR_STATUS => {
+            let pages_moved = ((state.moved() >> PAGE_BITS) & 0xFFFF) as u32;
+            let mut status = Status::from_bits_retain(pages_moved);
+            status.set(Status::FifoOverflow, state.overflow);
+            status.bits()
+        }
+        axi::ADDR_FIFO_ISR => {
+            if !state.wedged && std::mem::take(&mut state.transmitting) {
+                state.fifo_isr.insert(FifoIsr::TC);
+                if !state.received.is_empty() {
+                    state.fifo_isr.insert(FifoIsr::RC);
+                }
+            }
+            state.fifo_isr.bits()
+        }
+        axi::ADDR_FIFO_RDFO =>
+            state.received.iter().map(|packet| packet.len() as u32).sum::<u32>() +
+                state.receiving.len() as u32,
+        axi::ADDR_FIFO_RLR => match state.received.pop_front() {
+            Some(packet) => {
+                let length = packet.len() as u32 * 4;
+                state.receiving = packet.into();
+                length
+            }
+            None => {
+                state.fifo_isr.insert(FifoIsr::RPURE);
+                0
+            }
+        }
+        axi::ADDR_FIFO_RDFD => match state.receiving.pop_front() {
+            Some(byte) => byte as u32,
+            None => {
+                state.fifo_isr.insert(FifoIsr::RPUE);
+                0
+            }
+        }
+        _ => 0,
+    };
+    data.copy_from_slice(&value.to_le_bytes()[..data.len()]);
+    Ok(())
+}
+
+pub fn write_user(driver_data: &DriverData, addr: usize, data: &[u8]) -> Result<()> {
+    let mut state = driver_data.state.lock().unwrap();
+    let mut bytes = [0u8; 4];
+    bytes[..data.len()].copy_from_slice(data);
+    let value = u32::from_le_bytes(bytes);
+    match addr {
+        axi::ADDR_CONTROL => state.write_control(Control::from_bits_retain(value)),
+        // write 1 to clear
+        axi::ADDR_FIFO_ISR => state.fifo_isr.remove(FifoIsr::from_bits_retain(value)),
+        axi::ADDR_FIFO_TDFD => state.fifo_data.push(value as u8),
+        axi::ADDR_FIFO_TLR => {
+            let packet = std::mem::take(&mut state.fifo_data);
+            if !state.wedged {
+                if let Some(received) = state.execute(&packet) {
+                    state.received.push_back(received);
+                }
+            }
+            state.packets.push(packet);
+            state.transmitting = true;
+        }
+        axi::ADDR_FIFO_RDFR if value == axi::FIFO_RESET_KEY => {
+            state.received.clear();
+            state.receiving.clear();
+            state.fifo_isr.insert(FifoIsr::RRC);
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+pub fn read_dma(driver_data: &DriverData, addr: usize, data: &mut [u8]) -> Result<()> {
+    let (moved, channels, pattern, slipped) = {
+        let state = driver_data.state.lock().unwrap();
+        (state.moved(), state.channels(), state.adc_pattern(),
+            state.slipped_phases.contains(&state.lvds_phase))
+    };
+    // the stream position of the sample most recently written to `addr`; the caller only reads
+    // memory that has been written already
+    let mut position = moved - moved % MEMORY_SIZE + addr as u64;
+    if position >= moved {
+        position = position.saturating_sub(MEMORY_SIZE);
+    }
+    for (offset, byte) in data.iter_mut().enumerate() {
+        let sample = pattern.unwrap_or_else(||
+            driver_data.signal.sample(channels, position + offset as u64) as u8);
+        *byte = if slipped { sample.rotate_left(1) } else { sample };
+    }
+    Ok(())
+}
+
+pub fn wait_event(driver_data: &DriverData, timeout: Duration) -> Result<bool> {
+    let (running, moved) = {
+        let state = driver_data.state.lock().unwrap();
+        (state.running_since.is_some(), state.moved())
+    };
+    if !running {
+        std::thread::sleep(timeout);
+        return Ok(false)
+    }
+    // wait until the next page is moved
+    let until_next_page = (1 << PAGE_BITS) - moved % (1 << PAGE_BITS);
+    let delay = Duration::from_nanos(until_next_page);
+    std::thread::sleep(delay.min(timeout));
+    Ok(delay <= timeout)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fifo() {
+        let driver_data = DriverData::new(SimulatedSignal::Ramp);
+        for byte in [0xfd, 0x01, 0x02] {
+            write_user(&driver_data, axi::ADDR_FIFO_TDFD, &(byte as u32).to_le_bytes()).unwrap();
+        }
+        write_user(&driver_data, axi::ADDR_FIFO_TLR, &12u32.to_le_bytes()).unwrap();
+        write_user(&driver_data, axi::ADDR_FIFO_ISR, &FifoIsr::TC.bits().to_le_bytes()).unwrap();
+        let mut isr = [0; 4];
+        read_user(&driver_data, axi::ADDR_FIFO_ISR, &mut isr).unwrap();
+        assert!(FifoIsr::from_bits_retain(u32::from_le_bytes(isr)).contains(FifoIsr::TC));
+        write_user(&driver_data, axi::ADDR_FIFO_ISR, &FifoIsr::TC.bits().to_le_bytes()).unwrap();
+        read_user(&driver_data, axi::ADDR_FIFO_ISR, &mut isr).unwrap();
+        assert_eq!(u32::from_le_bytes(isr), 0);
+        assert_eq!(driver_data.packets(), vec![vec![0xfd, 0x01, 0x02]]);
+    }
+
+    #[test]
+    fn test_datamover() {
+        let driver_data = DriverData::new(SimulatedSignal::Ramp);
+        let mut status = [0; 4];
+        let control = Control::DatamoverHaltN | Control::FpgaAcqResetN;
+        write_user(&driver_data, axi::ADDR_CONTROL, &control.bits().to_le_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        read_user(&driver_data, axi::ADDR_STATUS, &mut status).unwrap();
+        assert!(u32::from_le_bytes(status) > 0);
+        write_user(&driver_data, axi::ADDR_CONTROL, &0u32.to_le_bytes()).unwrap();
+        read_user(&driver_data, axi::ADDR_STATUS, &mut status).unwrap();
+        assert_eq!(u32::from_le_bytes(status), 0);
+    }
+
+    #[test]
+    fn test_signal() {
+        assert_eq!(SimulatedSignal::Ramp.sample(4, 257), 1);
+        let square = SimulatedSignal::Square { period: 4.0, amplitude: 10 };
+        let samples = (0..8).map(|position| square.sample(2, position)).collect::<Vec<_>>();
+        assert_eq!(samples, [10, 10, 10, 10, -10, -10, -10, -10]);
+    }
+}
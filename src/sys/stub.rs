@@ -1,8 +1,15 @@
+use std::time::Duration;
+
 use crate::Result;
+use crate::device::Descriptor;
 
 #[derive(Debug)]
 pub struct DriverData;
 
+pub fn enumerate() -> Result<Vec<Descriptor>> {
+    unimplemented!()
+}
+
 pub fn open(_device_path: &str) -> Result<DriverData> {
     unimplemented!()
 }
@@ -18,3 +25,7 @@ pub fn write_user(_driver_data: &DriverData, _addr: usize, _data: &[u8]) -> Resu
 pub fn read_dma(_driver_data: &DriverData, _addr: usize, _data: &mut [u8]) -> Result<()> {
     unimplemented!()
 }
+
+pub fn wait_event(_driver_data: &DriverData, _timeout: Duration) -> Result<bool> {
+    unimplemented!()
+}
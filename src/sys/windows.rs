@@ -0,0 +1,192 @@
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::time::Duration;
+use std::{io, mem, ptr};
+
+use windows_sys::core::GUID;
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_NO_MORE_ITEMS, GENERIC_READ, GENERIC_WRITE, HANDLE,
+    INVALID_HANDLE_VALUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, ReadFile, WriteFile, FILE_ATTRIBUTE_NORMAL, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::IO::{OVERLAPPED, OVERLAPPED_0, OVERLAPPED_0_0};
+use windows_sys::Win32::Devices::DeviceAndDriverInstallation::{
+    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces, SetupDiGetClassDevsW,
+    SetupDiGetDeviceInterfaceDetailW, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT, HDEVINFO,
+    SP_DEVICE_INTERFACE_DATA, SP_DEVICE_INTERFACE_DETAIL_DATA_W,
+};
+
+use crate::Result;
+use crate::device::Descriptor;
+
+// device interface class registered by the Xilinx XDMA Windows driver
+const GUID_DEVINTERFACE_XDMA: GUID = GUID::from_u128(0x74c7e4a9_6d5d_4a70_bc0d_20691dff9e9d);
+
+fn to_wide(string: &str) -> Vec<u16> {
+    OsStr::new(string).encode_wide().chain(Some(0)).collect()
+}
+
+// on a synchronous handle, `ReadFile`/`WriteFile` with an `OVERLAPPED` structure perform
+// positional I/O like `pread`/`pwrite`, and do not race on the file pointer
+fn overlapped_at(offset: usize) -> OVERLAPPED {
+    OVERLAPPED {
+        Internal: 0,
+        InternalHigh: 0,
+        Anonymous: OVERLAPPED_0 {
+            Anonymous: OVERLAPPED_0_0 {
+                Offset: offset as u32,
+                OffsetHigh: (offset as u64 >> 32) as u32,
+            }
+        },
+        hEvent: 0,
+    }
+}
+
+#[derive(Debug)]
+struct Handle(HANDLE);
+
+impl Handle {
+    fn open(path: &str) -> io::Result<Handle> {
+        let path = to_wide(path);
+        unsafe {
+            let handle = CreateFileW(path.as_ptr(), GENERIC_READ | GENERIC_WRITE, 0, ptr::null(),
+                OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL, 0);
+            if handle == INVALID_HANDLE_VALUE {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Handle(handle))
+            }
+        }
+    }
+
+    fn read_at(&self, offset: usize, data: &mut [u8]) -> io::Result<()> {
+        let mut overlapped = overlapped_at(offset);
+        let mut bytes_read = 0;
+        unsafe {
+            if ReadFile(self.0, data.as_mut_ptr(), data.len() as u32, &mut bytes_read,
+                    &mut overlapped) == 0 {
+                Err(io::Error::last_os_error())
+            } else if bytes_read as usize != data.len() {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "short read"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn write_at(&self, offset: usize, data: &[u8]) -> io::Result<()> {
+        let mut overlapped = overlapped_at(offset);
+        let mut bytes_written = 0;
+        unsafe {
+            if WriteFile(self.0, data.as_ptr(), data.len() as u32, &mut bytes_written,
+                    &mut overlapped) == 0 {
+                Err(io::Error::last_os_error())
+            } else if bytes_written as usize != data.len() {
+                Err(io::Error::new(io::ErrorKind::WriteZero, "short write"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            if CloseHandle(self.0) == 0 {
+                panic!("error closing handle: {}", io::Error::last_os_error())
+            }
+        }
+    }
+}
+
+struct DeviceInfoList(HDEVINFO);
+
+impl Drop for DeviceInfoList {
+    fn drop(&mut self) {
+        unsafe { SetupDiDestroyDeviceInfoList(self.0); }
+    }
+}
+
+fn device_interface_path(list: &DeviceInfoList, index: u32) -> io::Result<Option<String>> {
+    unsafe {
+        let mut interface_data: SP_DEVICE_INTERFACE_DATA = mem::zeroed();
+        interface_data.cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32;
+        if SetupDiEnumDeviceInterfaces(list.0, ptr::null(), &GUID_DEVINTERFACE_XDMA, index,
+                &mut interface_data) == 0 {
+            return match GetLastError() {
+                ERROR_NO_MORE_ITEMS => Ok(None),
+                _ => Err(io::Error::last_os_error()),
+            }
+        }
+        // the first call fails, and returns the size of the variable length structure
+        let mut required_size = 0;
+        SetupDiGetDeviceInterfaceDetailW(list.0, &interface_data, ptr::null_mut(), 0,
+            &mut required_size, ptr::null_mut());
+        let mut buffer = vec![0u32; (required_size as usize).div_ceil(4)];
+        let detail_data = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+        (*detail_data).cbSize = mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+        if SetupDiGetDeviceInterfaceDetailW(list.0, &interface_data, detail_data, required_size,
+                ptr::null_mut(), ptr::null_mut()) == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let path_ptr = ptr::addr_of!((*detail_data).DevicePath) as *const u16;
+        let path_len = (0..).take_while(|&offset| *path_ptr.add(offset) != 0).count();
+        Ok(Some(String::from_utf16_lossy(std::slice::from_raw_parts(path_ptr, path_len))))
+    }
+}
+
+pub fn enumerate() -> Result<Vec<Descriptor>> {
+    let list = unsafe {
+        let handle = SetupDiGetClassDevsW(&GUID_DEVINTERFACE_XDMA, ptr::null(), 0,
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE);
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error().into())
+        }
+        DeviceInfoList(handle)
+    };
+    let mut descriptors = Vec::new();
+    for index in 0.. {
+        let Some(path) = device_interface_path(&list, index)? else { break };
+        descriptors.push(Descriptor {
+            path,
+            // the gateware does not expose a serial number yet
+            serial: None,
+            // the Windows driver does not expose the PCI revision ID
+            gateware_revision: None,
+        });
+    }
+    Ok(descriptors)
+}
+
+#[derive(Debug)]
+pub struct DriverData {
+    user_handle: Handle,
+    c2h_handle: Handle,
+}
+
+pub fn open(device_path: &str) -> Result<DriverData> {
+    Ok(DriverData {
+        user_handle: Handle::open(&format!("{}\\user", device_path))?,
+        c2h_handle: Handle::open(&format!("{}\\c2h_0", device_path))?,
+    })
+}
+
+pub fn read_user(driver_data: &DriverData, addr: usize, data: &mut [u8]) -> Result<()> {
+    Ok(driver_data.user_handle.read_at(addr, data)?)
+}
+
+pub fn write_user(driver_data: &DriverData, addr: usize, data: &[u8]) -> Result<()> {
+    Ok(driver_data.user_handle.write_at(addr, data)?)
+}
+
+pub fn read_dma(driver_data: &DriverData, addr: usize, data: &mut [u8]) -> Result<()> {
+    Ok(driver_data.c2h_handle.read_at(addr, data)?)
+}
+
+pub fn wait_event(_driver_data: &DriverData, _timeout: Duration) -> Result<bool> {
+    // the event interfaces of the Windows driver are not used yet; the status register is polled
+    Ok(false)
+}
@@ -0,0 +1,75 @@
+//! Correlation of positions in the sample stream with host time.
+
+use std::time::{Duration, SystemTime};
+
+fn samples_to_duration(samples: u64, sample_rate: u64) -> Duration {
+    Duration::from_nanos((samples as u128 * 1_000_000_000 / sample_rate as u128) as u64)
+}
+
+fn duration_to_samples(duration: Duration, sample_rate: u64) -> u64 {
+    (duration.as_nanos() * sample_rate as u128 / 1_000_000_000) as u64
+}
+
+/// A pair of an absolute index of a sample in the stream and the host time at which it was
+/// acquired.
+///
+/// The sample index counts all samples in the stream, regardless of which channel they belong
+/// to; for `N` enabled channels (with 3 channels counting as 4), the index of a sample within
+/// its channel is `sample / N`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub sample: u64,
+    pub time: SystemTime,
+    /// Rate at which samples appear in the stream, in samples per second; see
+    /// `DeviceParameters::stream_sample_rate()`.
+    pub sample_rate: u64,
+}
+
+impl Timestamp {
+    /// Create a timestamp for a sample that has just been acquired.
+    pub fn now(sample: u64, sample_rate: u64) -> Timestamp {
+        Timestamp { sample, time: SystemTime::now(), sample_rate }
+    }
+
+    /// Returns the host time at which `sample` was acquired, extrapolating from this timestamp.
+    pub fn sample_to_time(&self, sample: u64) -> SystemTime {
+        if sample >= self.sample {
+            self.time + samples_to_duration(sample - self.sample, self.sample_rate)
+        } else {
+            self.time - samples_to_duration(self.sample - sample, self.sample_rate)
+        }
+    }
+
+    /// Returns the index of the sample acquired at host time `time`, extrapolating from this
+    /// timestamp. Times before the start of the stream map to sample 0.
+    pub fn time_to_sample(&self, time: SystemTime) -> u64 {
+        match time.duration_since(self.time) {
+            Ok(after) =>
+                self.sample + duration_to_samples(after, self.sample_rate),
+            Err(before) =>
+                self.sample.saturating_sub(
+                    duration_to_samples(before.duration(), self.sample_rate)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let timestamp = Timestamp {
+            sample: 1_000_000,
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(100),
+            sample_rate: 1_000_000_000,
+        };
+        for sample in [0, 500_000, 1_000_000, 3_000_000_000] {
+            assert_eq!(timestamp.time_to_sample(timestamp.sample_to_time(sample)), sample);
+        }
+        assert_eq!(timestamp.sample_to_time(2_000_000), timestamp.time + Duration::from_millis(1));
+        assert_eq!(timestamp.time_to_sample(SystemTime::UNIX_EPOCH), 0);
+        let slower = Timestamp { sample_rate: 250_000_000, ..timestamp };
+        assert_eq!(slower.sample_to_time(2_000_000), timestamp.time + Duration::from_millis(4));
+    }
+}
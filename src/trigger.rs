@@ -1,6 +1,12 @@
 //! Implements rising edge/falling edge/both edges trigger with hysteresis using SIMD operations.
 
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::params::DeviceParameters;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgeFilter {
     Rising  = 0b01,
     Falling = 0b10,
@@ -8,6 +14,7 @@ pub enum EdgeFilter {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Edge {
     Rising  = 0b01,
     Falling = 0b10,
@@ -20,12 +27,159 @@ enum State {
     Above
 }
 
+/// An implementation of `Trigger::scan` using a particular SIMD instruction set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanVariant {
+    Generic = 1,
+    Avx     = 2,
+    Avx2    = 3,
+    Neon    = 4,
+}
+
+// 0 if no variant has been selected yet
+static SCAN_VARIANT: AtomicU8 = AtomicU8::new(0);
+
+impl ScanVariant {
+    pub const ALL: [ScanVariant; 4] =
+        [ScanVariant::Generic, ScanVariant::Avx, ScanVariant::Avx2, ScanVariant::Neon];
+
+    /// Returns `true` if this variant can be used on the host.
+    pub fn is_available(self) -> bool {
+        match self {
+            ScanVariant::Generic => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx     => is_x86_feature_detected!("avx"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx2    => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            ScanVariant::Neon    => std::arch::is_aarch64_feature_detected!("neon"),
+            _ => false
+        }
+    }
+
+    /// Returns the variant used by the triggers: the one selected with `select()` or
+    /// `autotune()`, or if there is none, the one using the most capable instruction set
+    /// available.
+    pub fn current() -> ScanVariant {
+        match SCAN_VARIANT.load(Ordering::Relaxed) {
+            1 => ScanVariant::Generic,
+            2 => ScanVariant::Avx,
+            3 => ScanVariant::Avx2,
+            4 => ScanVariant::Neon,
+            _ => Self::ALL.into_iter().rev().find(|variant| variant.is_available()).unwrap(),
+        }
+    }
+
+    /// Use this variant in the triggers from now on, e.g. to restore the result of an earlier
+    /// `autotune()`.
+    ///
+    /// Panics if the variant is not available.
+    pub fn select(self) {
+        assert!(self.is_available(), "{:?} trigger is not available on this host", self);
+        SCAN_VARIANT.store(self as u8, Ordering::Relaxed)
+    }
+
+    /// Benchmark the available variants on the host, and select the fastest one (which is not
+    /// always the one using the most capable instruction set). Takes a few milliseconds.
+    ///
+    /// The 8-bit and 16-bit triggers are assumed to perform alike, and only the 8-bit one is
+    /// benchmarked.
+    pub fn autotune() -> ScanVariant {
+        const BENCHMARK_LENGTH: usize = 1 << 18;
+        const BENCHMARK_ROUNDS: usize = 8;
+
+        // a trigger that never fires scans the entire buffer
+        let samples = vec![0i8; BENCHMARK_LENGTH];
+        let benchmark = |variant: ScanVariant| {
+            let mut fastest = Duration::MAX;
+            for _ in 0..BENCHMARK_ROUNDS {
+                let mut trigger = Trigger::new(100, 1);
+                let mut samples = std::hint::black_box(&samples[..]);
+                let started = Instant::now();
+                // SAFETY: Only the available variants are benchmarked.
+                let edge = unsafe {
+                    trigger.scan_variant(variant, &mut samples, 1, 0, EdgeFilter::Both)
+                };
+                fastest = fastest.min(started.elapsed());
+                debug_assert!(edge.is_none());
+            }
+            log::debug!("trigger: {:?} scans {} samples in {:?}", variant, BENCHMARK_LENGTH,
+                fastest);
+            fastest
+        };
+        let fastest = Self::ALL.into_iter()
+            .filter(|variant| variant.is_available())
+            .min_by_key(|&variant| benchmark(variant))
+            .unwrap();
+        fastest.select();
+        fastest
+    }
+}
+
+/// A trigger mechanism for samples of type `S`, which is `i8`, or `i16` for 12-bit samples
+/// (see `Resolution`).
 #[derive(Debug, Clone, Copy)]
-pub struct Trigger {
+pub struct Trigger<S = i8> {
     state: State,
-    level: i8, // if let Fresh = state { state = if sample < level { Below } else { Above } }
-    below: i8, // if sample < below { state = Below }
-    above: i8, // if sample > above { state = Above }
+    level: S, // if let Fresh = state { state = if sample < level { Below } else { Above } }
+    below: S, // if sample < below { state = Below }
+    above: S, // if sample > above { state = Above }
+}
+
+impl<S: Copy> Trigger<S> {
+    /// Reset the trigger
+    ///
+    /// After this method is called, the next sample will not cause an edge to be detected,
+    /// regardless of what the value of the sample is, as if the trigger was re-created with
+    /// the same parameters.
+    pub fn reset(&mut self) {
+        self.state = State::Fresh
+    }
+
+    /// Returns the trigger level.
+    pub fn level(&self) -> S {
+        self.level
+    }
+
+    /// Returns the thresholds of the below and above conditions, i.e. the level with hysteresis
+    /// applied and clamped to the full scale.
+    pub fn thresholds(&self) -> (S, S) {
+        (self.below, self.above)
+    }
+}
+
+impl<S: Copy + Into<f32>> Trigger<S> {
+    /// Find the sub-sample position at which the signal has crossed the trigger level, for
+    /// an `edge` detected at `samples[index]` of a channel whose samples are `stride` apart.
+    ///
+    /// The position is found by linear interpolation between the two samples straddling
+    /// the level, and is returned in samples of the channel relative to `samples[index]`. It is
+    /// never positive; e.g. `-0.25` means that the level was crossed a quarter of the sample
+    /// period before `samples[index]`. Aligning waveforms on this position instead of on
+    /// the sample that caused the edge to be detected removes up to a sample period of jitter.
+    ///
+    /// Because of hysteresis, the level may have been crossed several samples before the edge
+    /// was detected. Returns `None` if the crossing is not within `samples`, e.g. if it was
+    /// in the data passed to a previous call to `scan`.
+    pub fn crossing(&self, edge: Edge, samples: &[S], index: usize, stride: usize)
+            -> Option<f32> {
+        let level = self.level.into();
+        let mut after = index;
+        while after >= stride {
+            let before = after - stride;
+            let (value_before, value_after) = (samples[before].into(), samples[after].into());
+            let straddles = match edge {
+                Edge::Rising  => value_before < level && value_after >= level,
+                Edge::Falling => value_before >= level && value_after < level,
+            };
+            if straddles {
+                let fraction = (level - value_before) / (value_after - value_before);
+                return Some(fraction - ((index - before) / stride) as f32)
+            }
+            after = before;
+        }
+        None
+    }
 }
 
 impl Trigger {
@@ -57,13 +211,33 @@ impl Trigger {
         }
     }
 
-    /// Reset the trigger
+    /// Move the trigger to `level` with `hysteresis`, with the same meaning as for `new`.
     ///
-    /// After this method is called, the next sample will not cause an edge to be detected,
-    /// regardless of what the value of the sample is, as if the trigger was re-created with
-    /// the same parameters.
-    pub fn reset(&mut self) {
-        self.state = State::Fresh
+    /// Unlike re-creating the trigger, this keeps the last detected condition: e.g. if
+    /// the samples processed so far were below the old level and are above the new one,
+    /// the next sample causes a rising edge to be detected.
+    pub fn set_level(&mut self, level: i8, hysteresis: u8) {
+        *self = Trigger { state: self.state, ..Trigger::new(level, hysteresis) }
+    }
+
+    /// Create a new trigger mechanism on faceplate channel `channel_index` of a sample stream
+    /// acquired with `params`, at `level` with `hysteresis`, both in volts (as measured at
+    /// the probe). The codes are derived as for `DeviceParameters::volts_to_code`, and
+    /// `hysteresis` is rounded to the nearest amount of LSBs.
+    pub fn with_volts(params: &DeviceParameters, channel_index: usize, level: f32,
+                      hysteresis: f32) -> Trigger {
+        let (level, hysteresis) = volts_to_codes(params, channel_index, level, hysteresis);
+        Trigger::new(level, hysteresis)
+    }
+
+    /// Like `set_level`, but with `level` and `hysteresis` in volts, as for `with_volts`.
+    ///
+    /// This should be called whenever `params` change (e.g. the gain of the channel), so that
+    /// the codes are re-derived, while keeping the last detected condition.
+    pub fn set_level_volts(&mut self, params: &DeviceParameters, channel_index: usize,
+                           level: f32, hysteresis: f32) {
+        let (level, hysteresis) = volts_to_codes(params, channel_index, level, hysteresis);
+        self.set_level(level, hysteresis)
     }
 
     /// Scan incoming data for edges.
@@ -74,21 +248,49 @@ impl Trigger {
     ///
     /// This function advances `samples` forward, moving past the samples that have been processed.
     /// Trigger processing is done on groups of samples, and any samples not fitting into a group
-    /// of implementation dependent size (currently 16) are left unprocessed.
+    /// of implementation dependent size (currently 16, or 8 for 16-bit samples) are left
+    /// unprocessed.
     pub fn scan(&mut self, samples: &mut &[i8], filter: EdgeFilter) -> Option<Edge> {
-        // Dispatch to the most efficient implementation.
+        // Dispatch to the selected (usually the most efficient) implementation.
         // Note that this dynamic dispatch is not quite as efficient as building with for example
         // `RUSTFLAGS="-C target-cpu=native"` because the `wide` crate will only use 128-bit
         // registers if AVX2 wasn't detected at compile time, but the difference is quite small.
         // https://github.com/Lokathor/wide/blob/d94cbeadceacb0d9ebe5f18caedf933e0d4398ad/src/i8x32_.rs#L3-L13
-        if !cfg!(test) && is_x86_feature_detected!("avx2") {
-            // SAFETY: The AVX2 function is called only if AVX2 is available, checked above.
-            unsafe { self.scan_avx2(samples, filter) }
-        } else if !cfg!(test) && is_x86_feature_detected!("avx") {
-            // SAFETY: The AVX function is called only if AVX is available, checked above.
-            unsafe { self.scan_avx(samples, filter) }
-        } else {
-            self.scan_generic(samples, filter)
+        let variant = if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() };
+        // SAFETY: `ScanVariant::current()` only returns available variants.
+        unsafe { self.scan_variant(variant, samples, 1, 0, filter) }
+    }
+
+    /// Like `scan`, but for the samples of one channel in data interleaving `channels` channels
+    /// (1, 2, or 4; as in the sample stream), where the channel is at index `lane` of each frame.
+    ///
+    /// The data must start at a frame boundary, and it is advanced by whole frames only: if
+    /// an edge has been detected, after the function returns, `samples` point to the first sample
+    /// of the frame that caused the edge to be detected. The other channels are skipped within
+    /// the SIMD registers, so this is as fast as `scan` on the same amount of data.
+    pub fn scan_interleaved(&mut self, samples: &mut &[i8], channels: usize, lane: usize,
+                            filter: EdgeFilter) -> Option<Edge> {
+        assert!(matches!(channels, 1 | 2 | 4) && lane < channels,
+            "cannot trigger on lane {} of {}", lane, channels);
+        let variant = if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() };
+        // SAFETY: `ScanVariant::current()` only returns available variants.
+        unsafe { self.scan_variant(variant, samples, channels, lane, filter) }
+    }
+
+    /// # Safety
+    ///
+    /// `variant` must be available.
+    unsafe fn scan_variant(&mut self, variant: ScanVariant, samples: &mut &[i8],
+                           stride: usize, lane: usize, filter: EdgeFilter) -> Option<Edge> {
+        match variant {
+            ScanVariant::Generic => self.scan_generic(samples, stride, lane, filter),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx     => self.scan_avx(samples, stride, lane, filter),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx2    => self.scan_avx2(samples, stride, lane, filter),
+            #[cfg(target_arch = "aarch64")]
+            ScanVariant::Neon    => self.scan_neon(samples, stride, lane, filter),
+            _ => unreachable!("{:?} trigger is not available", variant)
         }
     }
 
@@ -99,12 +301,102 @@ impl Trigger {
         let len_after = samples.len();
         (len_before - len_after, edge_opt)
     }
+
+    /// Like `scan_interleaved`, but returns the amount of consumed samples.
+    pub fn find_interleaved(&mut self, mut samples: &[i8], channels: usize, lane: usize,
+                            filter: EdgeFilter) -> (usize, Option<Edge>) {
+        let len_before = samples.len();
+        let edge_opt = self.scan_interleaved(&mut samples, channels, lane, filter);
+        let len_after = samples.len();
+        (len_before - len_after, edge_opt)
+    }
+}
+
+fn volts_to_codes(params: &DeviceParameters, channel_index: usize, level: f32, hysteresis: f32)
+        -> (i8, u8) {
+    let volts_per_code = params.full_scale(channel_index) / 256.0;
+    // saturating casts, as in `volts_to_code`
+    (params.volts_to_code(channel_index, level), (hysteresis / volts_per_code).round() as u8)
+}
+
+impl Trigger<i16> {
+    /// Like `Trigger::new`, but for 16-bit samples.
+    pub fn new_i16(level: i16, hysteresis: u16) -> Trigger<i16> {
+        Trigger {
+            state: State::Fresh,
+            level,
+            below: level.saturating_sub_unsigned(hysteresis).max(i16::MIN + 1),
+            above: level.saturating_add_unsigned(hysteresis).min(i16::MAX - 1),
+        }
+    }
+
+    /// Like `Trigger::set_level`, but for 16-bit samples.
+    pub fn set_level(&mut self, level: i16, hysteresis: u16) {
+        *self = Trigger { state: self.state, ..Trigger::new_i16(level, hysteresis) }
+    }
+
+    /// Like `Trigger::scan`, but for 16-bit samples.
+    pub fn scan(&mut self, samples: &mut &[i16], filter: EdgeFilter) -> Option<Edge> {
+        let variant = if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() };
+        // SAFETY: `ScanVariant::current()` only returns available variants.
+        unsafe { self.scan_variant(variant, samples, 1, 0, filter) }
+    }
+
+    /// Like `Trigger::scan_interleaved`, but for 16-bit samples.
+    pub fn scan_interleaved(&mut self, samples: &mut &[i16], channels: usize, lane: usize,
+                            filter: EdgeFilter) -> Option<Edge> {
+        assert!(matches!(channels, 1 | 2 | 4) && lane < channels,
+            "cannot trigger on lane {} of {}", lane, channels);
+        let variant = if cfg!(test) { ScanVariant::Generic } else { ScanVariant::current() };
+        // SAFETY: `ScanVariant::current()` only returns available variants.
+        unsafe { self.scan_variant(variant, samples, channels, lane, filter) }
+    }
+
+    /// # Safety
+    ///
+    /// `variant` must be available.
+    unsafe fn scan_variant(&mut self, variant: ScanVariant, samples: &mut &[i16],
+                           stride: usize, lane: usize, filter: EdgeFilter) -> Option<Edge> {
+        match variant {
+            ScanVariant::Generic => self.scan_generic(samples, stride, lane, filter),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx     => self.scan_avx(samples, stride, lane, filter),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ScanVariant::Avx2    => self.scan_avx2(samples, stride, lane, filter),
+            #[cfg(target_arch = "aarch64")]
+            ScanVariant::Neon    => self.scan_neon(samples, stride, lane, filter),
+            _ => unreachable!("{:?} trigger is not available", variant)
+        }
+    }
+
+    /// Like `Trigger::find`, but for 16-bit samples.
+    pub fn find(&mut self, mut samples: &[i16], filter: EdgeFilter) -> (usize, Option<Edge>) {
+        let len_before = samples.len();
+        let edge_opt = self.scan(&mut samples, filter);
+        let len_after = samples.len();
+        (len_before - len_after, edge_opt)
+    }
+
+    /// Like `Trigger::find_interleaved`, but for 16-bit samples.
+    pub fn find_interleaved(&mut self, mut samples: &[i16], channels: usize, lane: usize,
+                            filter: EdgeFilter) -> (usize, Option<Edge>) {
+        let len_before = samples.len();
+        let edge_opt = self.scan_interleaved(&mut samples, channels, lane, filter);
+        let len_after = samples.len();
+        (len_before - len_after, edge_opt)
+    }
 }
 
 macro_rules! scan_impl {
-    { < $simd_ty:ident > $( $decl:tt )+ } => {
+    // `any_first` checks whether any lane matches before finding out which ones do; this is
+    // faster on instruction sets (such as NEON) that have no equivalent of `movemask`
+    { $sample_ty:ident < $simd_ty:ident > any_first $( $decl:tt )+ } => {
+        scan_impl! { @impl true, $sample_ty < $simd_ty > $( $decl )+ }
+    };
+    { @impl $any_first:literal, $sample_ty:ident < $simd_ty:ident > $( $decl:tt )+ } => {
         #[inline(never)] // makes assembly more readable; serves no other purpose
-        $( $decl )+(&mut self, samples: &mut &[i8], filter: EdgeFilter) -> Option<Edge> {
+        $( $decl )+(&mut self, samples: &mut &[$sample_ty], stride: usize, lane: usize,
+                    filter: EdgeFilter) -> Option<Edge> {
             // right now it is assumed that this function would be called with a holdoff of
             // the sample window size at least, i.e. that processing (00 ff)*8 with high
             // performance is not a design goal. if Nth trigger is implemented, this might have
@@ -114,33 +406,47 @@ macro_rules! scan_impl {
             const LANES: usize = wide::$simd_ty::LANES as usize;
 
             #[inline] // improves debug builds and makes assembly listings useful
-            fn scan_for<P: Fn($simd_ty) -> $simd_ty>(samples: &mut &[i8], predicate: P) -> bool {
+            fn scan_for<P>(samples: &mut &[$sample_ty], stride: usize, lane_mask: i32,
+                           predicate: P) -> bool
+                    where P: Fn($simd_ty) -> $simd_ty {
                 let mut found = false;
                 let mut offset = 0;
                 for &group in samples.array_chunks::<LANES>() {
-                    let mask = predicate($simd_ty::new(group));
+                    let matches = predicate($simd_ty::new(group));
+                    if $any_first && !matches.any() {
+                        offset += LANES;
+                        continue
+                    }
+                    let mask = matches.move_mask() & lane_mask;
                     // rustc generates ctlz even if the increment is within the condition; might
                     // as well lift it out of the condition
-                    offset += (mask.move_mask().trailing_zeros() as usize).min(LANES);
-                    if mask.any() {
+                    offset += (mask.trailing_zeros() as usize).min(LANES);
+                    if mask != 0 {
                         found = true;
                         break
                     }
                 }
+                // groups hold whole frames, so this is the start of the frame with the edge
+                offset -= offset % stride;
                 *samples = &samples[offset.min(samples.len())..];
                 found
             }
 
-            match (self.state, *samples) {
-                (State::Fresh, []) =>
+            // the SIMD lanes holding samples of the selected channel
+            let lane_mask = (0..LANES)
+                .filter(|index| index % stride == lane)
+                .fold(0i32, |mask, index| mask | 1 << index);
+
+            match self.state {
+                State::Fresh if samples.len() < stride =>
                     return None,
-                (State::Fresh, [first_sample, next_samples @ ..]) => {
-                    self.state = if *first_sample < self.level {
+                State::Fresh => {
+                    self.state = if samples[lane] < self.level {
                         State::Below
                     } else {
                         State::Above
                     };
-                    *samples = next_samples;
+                    *samples = &samples[stride..];
                 }
                 _ => ()
             }
@@ -151,8 +457,10 @@ macro_rules! scan_impl {
                 debug_assert!(!matches!(self.state, State::Fresh));
                 let found = match self.state {
                     State::Fresh => unreachable!(),
-                    State::Below => scan_for(samples, |group| group.cmp_gt(above)),
-                    State::Above => scan_for(samples, |group| group.cmp_lt(below)),
+                    State::Below => scan_for(samples, stride, lane_mask,
+                        |group| group.cmp_gt(above)),
+                    State::Above => scan_for(samples, stride, lane_mask,
+                        |group| group.cmp_lt(below)),
                 };
                 if found {
                     match self.state {
@@ -174,13 +482,46 @@ macro_rules! scan_impl {
                 }
             }
         }
-    }
+    };
+    { $sample_ty:ident < $simd_ty:ident > $( $decl:tt )+ } => {
+        scan_impl! { @impl false, $sample_ty < $simd_ty > $( $decl )+ }
+    };
 }
 
 impl Trigger {
-    scan_impl! { <i8x16> fn scan_generic }
-    scan_impl! { <i8x32> #[target_feature(enable = "avx")]  unsafe fn scan_avx  }
-    scan_impl! { <i8x32> #[target_feature(enable = "avx2")] unsafe fn scan_avx2 }
+    scan_impl! { i8 <i8x16> fn scan_generic }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl Trigger {
+    scan_impl! { i8 <i8x32> #[target_feature(enable = "avx")]  unsafe fn scan_avx  }
+    scan_impl! { i8 <i8x32> #[target_feature(enable = "avx2")] unsafe fn scan_avx2 }
+}
+
+// the strategy used for NEON, instantiated so that it is tested on every host
+#[cfg(test)]
+impl Trigger {
+    scan_impl! { i8 <i8x16> any_first fn scan_any_first }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Trigger {
+    scan_impl! { i8 <i8x16> any_first #[target_feature(enable = "neon")] unsafe fn scan_neon }
+}
+
+impl Trigger<i16> {
+    scan_impl! { i16 <i16x8>  fn scan_generic }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+impl Trigger<i16> {
+    scan_impl! { i16 <i16x16> #[target_feature(enable = "avx")]  unsafe fn scan_avx  }
+    scan_impl! { i16 <i16x16> #[target_feature(enable = "avx2")] unsafe fn scan_avx2 }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl Trigger<i16> {
+    scan_impl! { i16 <i16x8> any_first #[target_feature(enable = "neon")] unsafe fn scan_neon }
 }
 
 #[cfg(test)]
@@ -382,6 +723,42 @@ mod test {
         assert_trigger!(trig.scan(data, Falling) = Some(Falling); +9; _ => Below);
     }
 
+    #[test]
+    fn test_i16() {
+        let mut trig = Trigger::new_i16(0x1000, 0x20);
+        let mut data = [0x0ff0i16; 25];
+        data[17..].fill(0x1030);
+        assert_eq!(trig.find(&data[..1], EdgeFilter::Both), (1, None));
+        assert_eq!(trig.find(&data[1..], EdgeFilter::Rising), (16, Some(Rising)));
+        assert!(matches!(trig.state, Above));
+        // the difference between these is below the resolution of 8-bit samples
+        let mut trig = Trigger::new_i16(0x1000, 0);
+        data[17..].fill(0x1010);
+        assert_eq!(trig.find(&data, EdgeFilter::Rising), (17, Some(Rising)));
+        assert_eq!(Trigger::new_i16(i16::MAX, 3).above, i16::MAX - 1);
+    }
+
+    #[test]
+    fn test_i16_interleaved() {
+        // CH2 of two channels rises at frame 12 by less than one 8-bit code, while CH1 is noisy
+        let data = (0..32).flat_map(|index| [
+            if index % 2 == 0 { 0x3000 } else { -0x3000 },
+            if index < 12 { 0x0ff0 } else { 0x1010 },
+        ]).collect::<Vec<i16>>();
+        let mut trig = Trigger::new_i16(0x1000, 0);
+        assert_eq!(trig.find_interleaved(&data, 2, 1, EdgeFilter::Rising), (24, Some(Rising)));
+        assert_eq!(trig.find_interleaved(&data[24..], 2, 1, EdgeFilter::Both), (40, None));
+        for variant in ScanVariant::ALL.into_iter().filter(|variant| variant.is_available()) {
+            let mut trig = Trigger::new_i16(0x1000, 0);
+            let mut samples = &data[..];
+            // SAFETY: Only the available variants are tested.
+            let edge = unsafe {
+                trig.scan_variant(variant, &mut samples, 2, 1, EdgeFilter::Rising)
+            };
+            assert_eq!((data.len() - samples.len(), edge), (24, Some(Rising)), "{:?}", variant);
+        }
+    }
+
     #[test]
     fn test_bug_move_mask_must_be_cast_to_u16() {
         let mut trig = prime_trigger(Below);
@@ -393,4 +770,124 @@ mod test {
         println!("{:?}", trig);
         assert_trigger!(trig.scan(data, Rising) = Some(Rising); +32; _ => Above);
     }
+
+    #[test]
+    fn test_interleaved() {
+        // CH2 of two channels rises at frame 20, while CH1 rises and falls throughout
+        let data = (0..40).flat_map(|index| {
+            [if index % 3 == 0 { 80 } else { 10 }, if index < 20 { 10 } else { 80 }]
+        }).collect::<Vec<i8>>();
+        let mut trig = Trigger::new(50, 1);
+        assert_eq!(trig.find_interleaved(&data, 2, 1, EdgeFilter::Both), (40, Some(Rising)));
+        assert_eq!(trig.find_interleaved(&data[40..], 2, 1, EdgeFilter::Both), (32, None));
+        let mut trig = Trigger::new(50, 1);
+        assert_eq!(trig.find_interleaved(&data, 2, 0, EdgeFilter::Falling), (2, Some(Falling)));
+        // with one channel, this is the same as `find`
+        let mut trig = prime_trigger(Below);
+        assert_eq!(trig.find_interleaved(&RISING_BLOCK, 1, 0, EdgeFilter::Both), (9, Some(Rising)));
+        // frames of four channels; CH4 falls at frame 9
+        let data = (0..24).flat_map(|index| {
+            [80, 10, 80, if index < 9 { 80 } else { 10 }]
+        }).collect::<Vec<i8>>();
+        let mut trig = Trigger::new(50, 1);
+        assert_eq!(trig.find_interleaved(&data, 4, 3, EdgeFilter::Both), (36, Some(Falling)));
+        // the other variants use wider groups, and find the same edge
+        for variant in ScanVariant::ALL.into_iter().filter(|variant| variant.is_available()) {
+            let mut trig = Trigger::new(50, 1);
+            let mut samples = &data[..];
+            // SAFETY: Only the available variants are tested.
+            let edge = unsafe {
+                trig.scan_variant(variant, &mut samples, 4, 3, EdgeFilter::Both)
+            };
+            assert_eq!((data.len() - samples.len(), edge), (36, Some(Falling)), "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn test_set_level() {
+        let mut trig = Trigger::new(50, 1);
+        assert_eq!((trig.level(), trig.thresholds()), (50, (49, 51)));
+        let data = [10i8; 17];
+        assert_eq!(trig.find(&data, EdgeFilter::Both), (17, None));
+        // the samples were below the old level, and are above the new one
+        trig.set_level(0, 3);
+        assert_eq!((trig.level(), trig.thresholds()), (0, (-3, 3)));
+        assert_eq!(trig.find(&data, EdgeFilter::Both), (0, Some(Rising)));
+        // a fresh trigger stays fresh
+        let mut trig = Trigger::new(50, 1);
+        trig.set_level(0, 3);
+        assert_eq!(trig.find(&data, EdgeFilter::Both), (17, None));
+        let mut trig = Trigger::new_i16(0, 0x10);
+        trig.set_level(i16::MAX, 3);
+        assert_eq!(trig.thresholds(), (i16::MAX - 3, i16::MAX - 1));
+    }
+
+    #[test]
+    fn test_volts() {
+        use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+        let params = |probe_attenuation| DeviceParameters::derive(&DeviceCalibration::default(),
+            &DeviceConfiguration {
+                channels: [Some(ChannelConfiguration { probe_attenuation, ..Default::default() }),
+                           None, None, None],
+                ..Default::default()
+            });
+        let (params_10x, params_1x) = (params(20.0), params(0.0));
+        let volts_per_code = params_10x.full_scale(0) / 256.0;
+        let (level, hysteresis) = (6.45 * volts_per_code, 0.3 * volts_per_code);
+        let mut trig = Trigger::with_volts(&params_10x, 0, level, hysteresis);
+        assert_eq!((trig.level(), trig.thresholds()), (6, (6, 6)));
+        let data = [10i8; 17];
+        assert_eq!(trig.find(&data, EdgeFilter::Both), (17, None));
+        // with a 1X probe, the same voltages are ten times as many codes
+        trig.set_level_volts(&params_1x, 0, level, hysteresis);
+        assert_eq!((trig.level(), trig.thresholds()), (64, (61, 67)));
+        assert_eq!(trig.find(&data, EdgeFilter::Both), (0, Some(Falling)));
+    }
+
+    #[test]
+    fn test_crossing() {
+        // CH2 of two channels ramps up by 20 codes per sample, crossing 50 at 1.5 samples before
+        // the edge is detected at 80
+        let data = (0..32).flat_map(|index| {
+            [80, (index * 20 - 160).clamp(-100, 100) as i8]
+        }).collect::<Vec<i8>>();
+        let mut trig = Trigger::new(50, 20);
+        let (offset, edge) = trig.find_interleaved(&data, 2, 1, EdgeFilter::Rising);
+        assert_eq!((offset, edge), (24, Some(Rising)));
+        assert_eq!(trig.crossing(Rising, &data, offset + 1, 2), Some(-1.5));
+        // the crossing is not within the data
+        assert_eq!(trig.crossing(Rising, &data[offset - 2..], 3, 2), None);
+        // a falling edge is interpolated the same way
+        let data = [-8i16, 100, 60, 20, -20, -60];
+        let trig = Trigger::new_i16(0, 0);
+        assert_eq!(trig.crossing(Falling, &data, 5, 1), Some(-1.5));
+        assert_eq!(trig.crossing(Rising, &data, 5, 1), Some(8.0 / 108.0 - 5.0));
+    }
+
+    #[test]
+    fn test_any_first() {
+        let data = (0..200)
+            .map(|index| if index / 37 % 2 == 0 { 10 } else { 80 })
+            .collect::<Vec<i8>>();
+        for (stride, lane) in [(1, 0), (2, 1), (4, 2)] {
+            let (mut generic, mut any_first) = (Trigger::new(50, 1), Trigger::new(50, 1));
+            let (mut expected, mut actual) = (&data[..], &data[..]);
+            loop {
+                let edge = generic.scan_generic(&mut expected, stride, lane, EdgeFilter::Both);
+                assert_eq!(any_first.scan_any_first(&mut actual, stride, lane, EdgeFilter::Both),
+                    edge);
+                assert_eq!(actual.len(), expected.len());
+                if edge.is_none() { break }
+            }
+        }
+    }
+
+    #[test]
+    fn test_autotune() {
+        let variant = ScanVariant::autotune();
+        assert!(variant.is_available());
+        assert_eq!(ScanVariant::current(), variant);
+        ScanVariant::Generic.select();
+        assert_eq!(ScanVariant::current(), ScanVariant::Generic);
+    }
 }
@@ -0,0 +1,173 @@
+//! Implements conditioning of the trigger path, which is applied to the samples of the trigger
+//! channel before they reach the edge detector, so that noise or high frequency content riding
+//! on a signal does not cause a `Trigger` to fire more than once per edge.
+
+use crate::trigger::Trigger;
+
+/// Length of the moving average used for `TriggerCoupling::HfReject`, in samples of the trigger
+/// channel. At the full sample rate, this attenuates content above ~250 MHz.
+const HF_REJECT_TAPS: usize = 4;
+
+/// Hysteresis used for `TriggerCoupling::NoiseReject`, relative to the RMS noise.
+const NOISE_REJECT_RATIO: f32 = 3.0;
+
+/// Amount of samples of the trigger channel that the noise is estimated from, at least.
+const NOISE_ESTIMATE_SAMPLES: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerCoupling {
+    /// The samples are passed to the edge detector as acquired.
+    #[default]
+    Dc,
+    /// The hysteresis is widened to several times the RMS noise of the signal, which is
+    /// estimated from every chunk of samples, so that the noise cannot cross it.
+    NoiseReject,
+    /// The samples are passed through a low-pass filter (a moving average), so that high
+    /// frequency content cannot cause edges to be detected. This delays the detected edges by
+    /// 1.5 samples.
+    HfReject,
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerConditioner {
+    coupling: TriggerCoupling,
+    hysteresis: u8,
+    // the samples conditioned for `TriggerCoupling::HfReject`
+    filtered: Vec<i8>,
+}
+
+impl TriggerConditioner {
+    /// Create a new conditioner with `coupling`, for a trigger created with `hysteresis`.
+    pub fn new(coupling: TriggerCoupling, hysteresis: u8) -> TriggerConditioner {
+        TriggerConditioner { coupling, hysteresis, filtered: Vec::new() }
+    }
+
+    pub fn coupling(&self) -> TriggerCoupling {
+        self.coupling
+    }
+
+    /// Condition incoming data for `trigger`, where the trigger channel is at index `lane` of
+    /// frames of `channels` channels (as for `Trigger::scan_interleaved`). Returns the data
+    /// to scan for edges instead of `samples`, which has the same length and layout.
+    ///
+    /// The conditioning only depends on `samples`, so any samples that the trigger does not
+    /// consume can be passed again with the next chunk of data.
+    pub fn condition<'a>(&'a mut self, trigger: &mut Trigger, samples: &'a [i8],
+                         channels: usize, lane: usize) -> &'a [i8] {
+        match self.coupling {
+            TriggerCoupling::Dc => samples,
+            TriggerCoupling::NoiseReject => {
+                if let Some(noise) = estimate_noise(samples, channels, lane) {
+                    let hysteresis = (NOISE_REJECT_RATIO * noise).ceil().min(127.0) as u8;
+                    trigger.set_level(trigger.level(), hysteresis.max(self.hysteresis));
+                }
+                samples
+            }
+            TriggerCoupling::HfReject => {
+                self.filtered.clear();
+                self.filtered.extend_from_slice(samples);
+                let mut sum = 0i16;
+                for (count, index) in (lane..samples.len()).step_by(channels).enumerate() {
+                    sum += samples[index] as i16;
+                    if count >= HF_REJECT_TAPS {
+                        sum -= samples[index - HF_REJECT_TAPS * channels] as i16;
+                    }
+                    // at the start of the data, fewer samples are averaged; round to nearest
+                    let taps = (count + 1).min(HF_REJECT_TAPS) as i16;
+                    self.filtered[index] = (sum * 2 + taps).div_euclid(taps * 2) as i8;
+                }
+                &self.filtered
+            }
+        }
+    }
+}
+
+/// Estimates the RMS noise of the samples of one channel from the median of the differences
+/// between consecutive samples, which (unlike their RMS) is not affected by the edges of
+/// the signal. Returns `None` if there are too few samples.
+fn estimate_noise(samples: &[i8], channels: usize, lane: usize) -> Option<f32> {
+    let mut histogram = [0usize; 256];
+    let mut count = 0;
+    let lane_samples = samples.iter().skip(lane).step_by(channels);
+    for (&prev, &next) in lane_samples.clone().zip(lane_samples.skip(1)) {
+        histogram[(next as i16 - prev as i16).unsigned_abs().min(255) as usize] += 1;
+        count += 1;
+    }
+    if count < NOISE_ESTIMATE_SAMPLES {
+        return None
+    }
+    let mut below = 0;
+    let median = histogram.iter().position(|&bin| { below += bin; below > count / 2 })?;
+    // for gaussian noise, the median absolute difference is 0.954σ (with σ√2 being
+    // the deviation of the difference)
+    Some(median as f32 / 0.954)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Edge, EdgeFilter};
+
+    // a square wave with a period of 200 samples, and a tone with a period of 4 samples
+    fn square_wave_with_tone() -> Vec<i8> {
+        (0..1000).map(|index| {
+            let signal = if index % 200 < 100 { -60 } else { 60 };
+            signal + [0, 50, 0, -50][index % 4]
+        }).collect()
+    }
+
+    // a triangle wave with a period of 100 samples, and uniform noise of ±16
+    fn noisy_triangle_wave() -> Vec<i8> {
+        let mut seed = 1u32;
+        (0..1000).map(|index| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let noise = (seed >> 16) as i32 % 33 - 16;
+            let signal = (index % 100 - 50i32).abs() * 4 - 100;
+            (signal + noise) as i8
+        }).collect()
+    }
+
+    fn count_edges(conditioner: &mut TriggerConditioner, level: i8, samples: &[i8]) -> usize {
+        let mut trigger = Trigger::new(level, 2);
+        let mut edges = 0;
+        let mut offset = 0;
+        loop {
+            let conditioned = conditioner.condition(&mut trigger, &samples[offset..], 1, 0);
+            let (processed, edge) = trigger.find(conditioned, EdgeFilter::Rising);
+            offset += processed;
+            if edge.is_none() { break }
+            assert_eq!(edge, Some(Edge::Rising));
+            edges += 1;
+            offset += 1;
+        }
+        edges
+    }
+
+    #[test]
+    fn test_dc() {
+        let mut conditioner = TriggerConditioner::new(TriggerCoupling::Dc, 2);
+        assert!(count_edges(&mut conditioner, 30, &square_wave_with_tone()) > 5);
+        assert!(count_edges(&mut conditioner, 0, &noisy_triangle_wave()) > 10);
+    }
+
+    #[test]
+    fn test_noise_reject() {
+        let samples = noisy_triangle_wave();
+        let mut conditioner = TriggerConditioner::new(TriggerCoupling::NoiseReject, 2);
+        assert_eq!(count_edges(&mut conditioner, 0, &samples), 10);
+        assert_eq!(estimate_noise(&samples[..32], 1, 0), None);
+    }
+
+    #[test]
+    fn test_hf_reject() {
+        let samples = square_wave_with_tone();
+        let mut conditioner = TriggerConditioner::new(TriggerCoupling::HfReject, 2);
+        assert_eq!(count_edges(&mut conditioner, 30, &samples), 5);
+        // the tone on one channel is averaged out, while the other channel is left as-is
+        let interleaved = samples.iter().flat_map(|&sample| [sample, 10]).collect::<Vec<_>>();
+        let mut trigger = Trigger::new(0, 0);
+        let conditioned = conditioner.condition(&mut trigger, &interleaved, 2, 0);
+        assert!(conditioned[6..200].chunks(2).all(|frame| frame == [-60, 10]));
+    }
+}
@@ -0,0 +1,291 @@
+//! Implements a serial trigger, which decodes an asynchronous serial (UART) signal on one channel
+//! and fires when a specific word is received; e.g. a command byte on a debug port.
+//!
+//! The line is searched for the falling edge of a start bit with the edge trigger, so an idle
+//! line is scanned as fast as by `Trigger`. The bit clock is recovered from the sub-sample
+//! position of that edge (see `Trigger::crossing`), and each bit of the frame is then sampled
+//! at its center, like a hardware UART would do.
+
+use crate::params::DeviceParameters;
+use crate::channel_map::ChannelMap;
+use crate::trigger::{Edge, EdgeFilter, Trigger};
+
+/// Least amount of samples per bit at which the bits can be sampled reliably.
+const MIN_SAMPLES_PER_BIT: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UartParity {
+    None,
+    Even,
+    Odd,
+}
+
+/// The framing of the serial signal. The line idles high, and each frame consists of
+/// a start bit, `data_bits` data bits (least significant first), an optional parity bit, and
+/// a stop bit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UartFormat {
+    /// Bits per second.
+    pub baud_rate: f32,
+    /// Amount of data bits, from 5 to 9.
+    pub data_bits: u8,
+    pub parity: UartParity,
+}
+
+impl Default for UartFormat {
+    fn default() -> Self {
+        UartFormat { baud_rate: 115200.0, data_bits: 8, parity: UartParity::None }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    // waiting for the falling edge of a start bit
+    Idle,
+    // receiving a frame, where `start` is the position of the falling edge of the start bit,
+    // `bit` is the index of the next bit to be sampled (0 is the start bit), and `shift` holds
+    // the data and parity bits sampled so far
+    Frame { start: f64, bit: usize, shift: u16 },
+}
+
+#[derive(Debug, Clone)]
+pub struct UartTrigger {
+    channels: usize,
+    lane: usize,
+    format: UartFormat,
+    word: u16,
+    samples_per_bit: f64,
+    edge: Trigger,
+    state: State,
+    // position of the next frame, in samples of the channel since the trigger was reset
+    position: u64,
+}
+
+impl UartTrigger {
+    /// Create a new trigger on the serial signal with `format` on faceplate channel
+    /// `channel_index` in a sample stream acquired with `params`, which fires when `word` is
+    /// received. The signal is high when it is above `level`; the edge of the start bit is
+    /// detected with `hysteresis`, with the same meaning as for `Trigger`.
+    ///
+    /// Returns `None` if the channel is disabled, if the amount of data bits is not supported,
+    /// or if the baud rate is not positive or too high to be decoded at the sample rate.
+    pub fn new(params: &DeviceParameters, channel_index: usize, format: UartFormat, word: u16,
+               level: i8, hysteresis: u8) -> Option<UartTrigger> {
+        if !(5..=9).contains(&format.data_bits) {
+            return None
+        }
+        if !(format.baud_rate.is_finite() && format.baud_rate > 0.0) {
+            return None
+        }
+        let samples_per_bit = params.sample_rate() as f64 / format.baud_rate as f64;
+        if samples_per_bit < MIN_SAMPLES_PER_BIT {
+            return None
+        }
+        let channel_map = ChannelMap::from_params(params);
+        Some(UartTrigger {
+            channels: channel_map.stream_channels(),
+            lane: channel_map.lane(channel_index)?,
+            format,
+            word: word & ((1 << format.data_bits) - 1),
+            samples_per_bit,
+            edge: Trigger::new(level, hysteresis),
+            state: State::Idle,
+            position: 0,
+        })
+    }
+
+    /// Reset the trigger, discarding the frame being received.
+    ///
+    /// After this method is called, the next sample must start a frame.
+    pub fn reset(&mut self) {
+        self.edge.reset();
+        self.state = State::Idle;
+        self.position = 0;
+    }
+
+    // amount of bits in a frame, including the start and stop bits
+    fn frame_bits(&self) -> usize {
+        let parity_bits = if self.format.parity == UartParity::None { 0 } else { 1 };
+        1 + self.format.data_bits as usize + parity_bits + 1
+    }
+
+    fn is_valid(&self, shift: u16) -> bool {
+        let data_bits = self.format.data_bits as u32;
+        let parity_ok = match self.format.parity {
+            UartParity::None => true,
+            // the parity bit is included in `shift`
+            parity => (shift.count_ones() % 2 == 1) == (parity == UartParity::Odd),
+        };
+        parity_ok && shift & ((1 << data_bits) - 1) == self.word
+    }
+
+    /// Scan incoming data (interleaved as in the sample stream, and starting at a frame boundary)
+    /// for a serial frame holding the word.
+    ///
+    /// Returns the amount of consumed samples, and whether the trigger has fired. If it has,
+    /// the last consumed frame is the one where the stop bit of the serial frame was sampled.
+    /// Frames with a parity or framing error are ignored. Any samples that are not consumed
+    /// must be passed again with the next chunk of data.
+    pub fn find(&mut self, samples: &[i8]) -> (usize, bool) {
+        let mut consumed = 0;
+        loop {
+            match self.state {
+                State::Idle => {
+                    let (processed, edge) = self.edge.find_interleaved(&samples[consumed..],
+                        self.channels, self.lane, EdgeFilter::Falling);
+                    consumed += processed;
+                    self.position += (processed / self.channels) as u64;
+                    if edge.is_none() {
+                        return (consumed, false)
+                    }
+                    // the frame with the edge has not been consumed
+                    let crossing = self.edge.crossing(Edge::Falling, samples,
+                        consumed + self.lane, self.channels).unwrap_or(0.0);
+                    self.state = State::Frame {
+                        start: self.position as f64 + crossing as f64,
+                        bit: 0,
+                        shift: 0,
+                    };
+                }
+                State::Frame { start, bit, shift } => {
+                    if samples.len() - consumed < self.channels {
+                        return (consumed, false)
+                    }
+                    let index = self.position as f64;
+                    let high = samples[consumed + self.lane] > self.edge.level();
+                    consumed += self.channels;
+                    self.position += 1;
+                    // each bit is sampled at the sample nearest to its center
+                    if index + 0.5 < start + (bit as f64 + 0.5) * self.samples_per_bit {
+                        continue
+                    }
+                    if bit == 0 && high {
+                        // a glitch rather than a start bit
+                        self.edge.reset();
+                        self.state = State::Idle;
+                    } else if bit == self.frame_bits() - 1 {
+                        // once the line is idle, the next falling edge is a start bit; if there
+                        // is a framing error, the line has to become idle first
+                        self.edge.reset();
+                        self.state = State::Idle;
+                        if high && self.is_valid(shift) {
+                            return (consumed, true)
+                        }
+                    } else {
+                        let shift = match bit {
+                            0 => shift,
+                            _ => shift | (high as u16) << (bit - 1),
+                        };
+                        self.state = State::Frame { start, bit: bit + 1, shift };
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn channels(enabled: [bool; 4]) -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: enabled.map(|enabled| enabled.then(ChannelConfiguration::default)),
+            ..Default::default()
+        })
+    }
+
+    // serial frames of `words` with `format`, and with idle time before and after them
+    fn serial(format: UartFormat, samples_per_bit: f64, words: &[u16]) -> Vec<i8> {
+        let mut bits = vec![true; 5];
+        for &word in words {
+            bits.push(false);
+            bits.extend((0..format.data_bits).map(|bit| word & (1 << bit) != 0));
+            match format.parity {
+                UartParity::None => (),
+                UartParity::Even => bits.push(word.count_ones() % 2 == 1),
+                UartParity::Odd  => bits.push(word.count_ones() % 2 == 0),
+            }
+            bits.push(true);
+        }
+        bits.extend([true; 5]);
+        let length = (bits.len() as f64 * samples_per_bit) as usize;
+        (0..length)
+            .map(|index| if bits[(index as f64 / samples_per_bit) as usize] { 60 } else { -60 })
+            .collect()
+    }
+
+    // feeds `samples` in chunks of `chunk` frames, and returns the positions where it fired
+    fn fire_positions(trigger: &mut UartTrigger, samples: &[i8], chunk: usize) -> Vec<usize> {
+        let mut positions = Vec::new();
+        let mut consumed = 0;
+        let mut available = 0;
+        while consumed < samples.len() {
+            available = (available + chunk).min(samples.len());
+            let (processed, fired) = trigger.find(&samples[consumed..available]);
+            consumed += processed;
+            if fired {
+                positions.push(consumed);
+            } else if available == samples.len() {
+                break
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn test_uart() {
+        let params = channels([true, false, false, false]);
+        let format = UartFormat { baud_rate: params.sample_rate() / 10.4, ..Default::default() };
+        assert!(UartTrigger::new(&params, 1, format, 0, 0, 2).is_none());
+        assert!(UartTrigger::new(&params, 0,
+            UartFormat { data_bits: 10, ..format }, 0, 0, 2).is_none());
+        assert!(UartTrigger::new(&params, 0,
+            UartFormat { baud_rate: params.sample_rate() / 2.0, ..format }, 0, 0, 2).is_none());
+        for baud_rate in [0.0, -9600.0, f32::NAN, f32::INFINITY] {
+            assert!(UartTrigger::new(&params, 0,
+                UartFormat { baud_rate, ..format }, 0, 0, 2).is_none());
+        }
+        let samples = serial(format, 10.4, &[b'H' as u16, b'i' as u16, b'!' as u16]);
+        let mut trigger = UartTrigger::new(&params, 0, format, b'i' as u16, 0, 2).unwrap();
+        // in the stop bit of the second frame, i.e. the 20th bit after 5 bits of idle line
+        for chunk in [samples.len(), 1, 7, 16, 100] {
+            trigger.reset();
+            assert_eq!(fire_positions(&mut trigger, &samples, chunk),
+                [(24.5 * 10.4) as usize + 1]);
+        }
+        // the data bits of 'a' are the same as those of 'i' with the bit 3 cleared
+        let samples = serial(format, 10.4, &[b'a' as u16, b'i' as u16, b'i' as u16]);
+        trigger.reset();
+        assert_eq!(fire_positions(&mut trigger, &samples, 100).len(), 2);
+    }
+
+    #[test]
+    fn test_uart_parity() {
+        let params = channels([true, false, false, false]);
+        let format = UartFormat { baud_rate: params.sample_rate() / 8.0, data_bits: 7,
+            parity: UartParity::Even };
+        let samples = serial(format, 8.0, &[0x55, 0x2a, 0x55]);
+        let mut trigger = UartTrigger::new(&params, 0, format, 0x55, 0, 2).unwrap();
+        assert_eq!(fire_positions(&mut trigger, &samples, 100).len(), 2);
+        // with the wrong parity, every frame has a parity error
+        let mut trigger = UartTrigger::new(&params, 0,
+            UartFormat { parity: UartParity::Odd, ..format }, 0x55, 0, 2).unwrap();
+        assert_eq!(fire_positions(&mut trigger, &samples, 100).len(), 0);
+    }
+
+    #[test]
+    fn test_uart_interleaved() {
+        let params = channels([true, false, true, false]);
+        let format = UartFormat { baud_rate: params.sample_rate() / 12.0, ..Default::default() };
+        // CH3 has a serial signal, and CH1 toggles every sample
+        let samples = serial(format, 12.0, &[0x00, 0xff, 0xa5]).iter().enumerate()
+            .flat_map(|(index, &sample)| [if index % 2 == 0 { 100 } else { -100 }, sample])
+            .collect::<Vec<_>>();
+        let mut trigger = UartTrigger::new(&params, 2, format, 0xa5, 0, 2).unwrap();
+        assert_eq!(fire_positions(&mut trigger, &samples, 64), [2 * (34 * 12 + 6)]);
+    }
+}
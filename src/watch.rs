@@ -0,0 +1,66 @@
+//! Watching of a file for changes, e.g. to apply a configuration file as it is being edited.
+//!
+//! The modification time of the file is polled, which works the same on every platform and is
+//! cheap enough to do a few times per second.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    /// Create a watcher for the file at `path`. The first call to `changed()` returns the
+    /// contents of the file if it exists.
+    pub fn new(path: impl AsRef<Path>) -> FileWatcher {
+        FileWatcher { path: path.as_ref().to_owned(), modified: None }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the contents of the file if it has been modified since the last call.
+    ///
+    /// An editor may replace the file instead of writing to it; if the file is briefly missing,
+    /// it is not treated as modified.
+    pub fn changed(&mut self) -> std::io::Result<Option<String>> {
+        let modified = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.modified()?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        if self.modified == Some(modified) {
+            return Ok(None)
+        }
+        let contents = std::fs::read_to_string(&self.path)?;
+        self.modified = Some(modified);
+        Ok(Some(contents))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_changed() {
+        let path = std::env::temp_dir().join(format!("watch-{}.toml", std::process::id()));
+        let mut watcher = FileWatcher::new(&path);
+        assert_eq!(watcher.changed().unwrap(), None);
+        std::fs::write(&path, "first").unwrap();
+        assert_eq!(watcher.changed().unwrap().as_deref(), Some("first"));
+        assert_eq!(watcher.changed().unwrap(), None);
+        // the resolution of the modification time varies by filesystem
+        std::fs::write(&path, "second").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        assert_eq!(watcher.changed().unwrap().as_deref(), Some("second"));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.changed().unwrap(), None);
+    }
+}
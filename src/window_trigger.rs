@@ -0,0 +1,224 @@
+//! Implements a window trigger, which detects the signal entering or leaving a band of levels,
+//! with hysteresis, using SIMD operations; e.g. to monitor a supply rail for excursions.
+
+use wide::{i8x16, CmpGt, CmpLt};
+
+const LANES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowFilter {
+    Enter = 0b01,
+    Exit  = 0b10,
+    Both  = 0b11,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WindowCrossing {
+    Enter = 0b01,
+    Exit  = 0b10,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Fresh,
+    Inside,
+    Outside,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WindowTrigger {
+    state: State,
+    low: i8,
+    high: i8,
+    // if inner_low < sample < inner_high { state = Inside }
+    inner_low: i8,
+    inner_high: i8,
+    // if sample < outer_low || sample > outer_high { state = Outside }
+    outer_low: i8,
+    outer_high: i8,
+}
+
+impl WindowTrigger {
+    /// Create a new window trigger mechanism for the band between `low` and `high`, inclusive.
+    ///
+    /// The trigger mechanism detects an "inside condition" when it processes a sample that is
+    /// strictly between `low + hysteresis` and `high - hysteresis`, and an "outside condition"
+    /// when it processes a sample that is strictly below `low - hysteresis` or strictly above
+    /// `high + hysteresis`. The signal enters the window at the sample where an outside condition
+    /// crosses into an inside condition, and exits it where the opposite happens.
+    ///
+    /// As with `Trigger`, the band and hysteresis are clamped such that some sequence of sample
+    /// values would cause either crossing to be detected; in particular, the hysteresis is reduced
+    /// if the band is too narrow for it.
+    pub fn new(low: i8, high: i8, hysteresis: u8) -> WindowTrigger {
+        let (low, high) = (low.min(high), low.max(high));
+        let hysteresis = (hysteresis as i16).min((high as i16 - low as i16 - 1) / 2).max(0);
+        let inner_low  = (low  as i16 + hysteresis).min(125) as i8;
+        let inner_high = (high as i16 - hysteresis).max(inner_low as i16 + 2) as i8;
+        WindowTrigger {
+            state: State::Fresh,
+            low,
+            high,
+            inner_low,
+            inner_high,
+            outer_low:  (low  as i16 - hysteresis).max(-127) as i8,
+            outer_high: (high as i16 + hysteresis).min( 126) as i8,
+        }
+    }
+
+    /// Reset the trigger
+    ///
+    /// After this method is called, the next sample will not cause a crossing to be detected,
+    /// regardless of what the value of the sample is, as if the trigger was re-created with
+    /// the same parameters.
+    pub fn reset(&mut self) {
+        self.state = State::Fresh
+    }
+
+    /// Scan incoming data for crossings, with the same semantics as `Trigger::scan`.
+    pub fn scan(&mut self, samples: &mut &[i8], filter: WindowFilter) -> Option<WindowCrossing> {
+        self.scan_interleaved(samples, 1, 0, filter)
+    }
+
+    /// Like `scan`, but for the samples of one channel in data interleaving `channels` channels,
+    /// with the same semantics as `Trigger::scan_interleaved`.
+    pub fn scan_interleaved(&mut self, samples: &mut &[i8], channels: usize, lane: usize,
+                            filter: WindowFilter) -> Option<WindowCrossing> {
+        fn scan_for<P>(samples: &mut &[i8], stride: usize, lane_mask: i32, predicate: P) -> bool
+                where P: Fn(i8x16) -> i8x16 {
+            let mut found = false;
+            let mut offset = 0;
+            for &group in samples.array_chunks::<LANES>() {
+                let mask = predicate(i8x16::new(group)).move_mask() & lane_mask;
+                offset += (mask.trailing_zeros() as usize).min(LANES);
+                if mask != 0 {
+                    found = true;
+                    break
+                }
+            }
+            // groups hold whole frames, so this is the start of the frame with the crossing
+            offset -= offset % stride;
+            *samples = &samples[offset.min(samples.len())..];
+            found
+        }
+
+        assert!(matches!(channels, 1 | 2 | 4) && lane < channels,
+            "cannot trigger on lane {} of {}", lane, channels);
+        // the SIMD lanes holding samples of the selected channel
+        let lane_mask = (0..LANES)
+            .filter(|index| index % channels == lane)
+            .fold(0i32, |mask, index| mask | 1 << index);
+
+        if let State::Fresh = self.state {
+            if samples.len() < channels {
+                return None
+            }
+            self.state = if (self.low..=self.high).contains(&samples[lane]) {
+                State::Inside
+            } else {
+                State::Outside
+            };
+            *samples = &samples[channels..];
+        }
+
+        let (inner_low, inner_high) = (i8x16::splat(self.inner_low), i8x16::splat(self.inner_high));
+        let (outer_low, outer_high) = (i8x16::splat(self.outer_low), i8x16::splat(self.outer_high));
+        loop {
+            let found = match self.state {
+                State::Fresh => unreachable!(),
+                State::Inside => scan_for(samples, channels, lane_mask, |group|
+                    group.cmp_lt(outer_low) | group.cmp_gt(outer_high)),
+                State::Outside => scan_for(samples, channels, lane_mask, |group|
+                    group.cmp_gt(inner_low) & group.cmp_lt(inner_high)),
+            };
+            if !found {
+                return None
+            }
+            let crossing = match self.state {
+                State::Fresh => unreachable!(),
+                State::Inside => { self.state = State::Outside; WindowCrossing::Exit }
+                State::Outside => { self.state = State::Inside; WindowCrossing::Enter }
+            };
+            if crossing as u8 & filter as u8 != 0 {
+                return Some(crossing)
+            }
+        }
+    }
+
+    /// Like `scan`, but returns the amount of consumed samples.
+    pub fn find(&mut self, mut samples: &[i8], filter: WindowFilter)
+            -> (usize, Option<WindowCrossing>) {
+        let len_before = samples.len();
+        let crossing_opt = self.scan(&mut samples, filter);
+        let len_after = samples.len();
+        (len_before - len_after, crossing_opt)
+    }
+
+    /// Like `scan_interleaved`, but returns the amount of consumed samples.
+    pub fn find_interleaved(&mut self, mut samples: &[i8], channels: usize, lane: usize,
+                            filter: WindowFilter) -> (usize, Option<WindowCrossing>) {
+        let len_before = samples.len();
+        let crossing_opt = self.scan_interleaved(&mut samples, channels, lane, filter);
+        let len_after = samples.len();
+        (len_before - len_after, crossing_opt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_crossings() {
+        let mut trig = WindowTrigger::new(-20, 20, 2);
+        let mut data = [0i8; 64];
+        data[10] = 22; // within hysteresis
+        data[20..40].fill(30);
+        data[40] = -18; // within hysteresis
+        assert_eq!(trig.find(&data, WindowFilter::Both), (20, Some(WindowCrossing::Exit)));
+        assert_eq!(trig.find(&data[20..], WindowFilter::Both), (21, Some(WindowCrossing::Enter)));
+        trig.reset();
+        assert_eq!(trig.find(&data, WindowFilter::Enter), (41, Some(WindowCrossing::Enter)));
+        // a sample below the window is outside as well
+        data[50] = -100;
+        assert_eq!(trig.find(&data[41..], WindowFilter::Exit), (9, Some(WindowCrossing::Exit)));
+    }
+
+    #[test]
+    fn test_interleaved() {
+        // CH2 of two channels leaves the window at frame 20, while CH1 is outside throughout
+        let data = (0..40).flat_map(|index| {
+            [100, if index < 20 { 0 } else { 50 }]
+        }).collect::<Vec<i8>>();
+        let mut trig = WindowTrigger::new(-20, 20, 2);
+        assert_eq!(trig.find_interleaved(&data, 2, 1, WindowFilter::Both),
+            (40, Some(WindowCrossing::Exit)));
+        assert_eq!(trig.find_interleaved(&data[40..], 2, 1, WindowFilter::Both), (32, None));
+        // frames of four channels; CH3 enters the window at frame 9, and CH4 at frame 5
+        let data = (0..24).flat_map(|index| {
+            [-100, 100, if index < 9 { 100 } else { 0 }, if index < 5 { -50 } else { 5 }]
+        }).collect::<Vec<i8>>();
+        let mut trig = WindowTrigger::new(-20, 20, 2);
+        assert_eq!(trig.find_interleaved(&data, 4, 2, WindowFilter::Enter),
+            (36, Some(WindowCrossing::Enter)));
+        let mut trig = WindowTrigger::new(-20, 20, 2);
+        assert_eq!(trig.find_interleaved(&data, 4, 3, WindowFilter::Both),
+            (20, Some(WindowCrossing::Enter)));
+        // with one channel, this is the same as `find`
+        let mut trig = WindowTrigger::new(-20, 20, 2);
+        assert_eq!(trig.find_interleaved(&data, 1, 0, WindowFilter::Both),
+            (23, Some(WindowCrossing::Enter)));
+    }
+
+    #[test]
+    fn test_narrow() {
+        let trig = WindowTrigger::new(10, 12, 5);
+        assert_eq!((trig.inner_low, trig.inner_high), (10, 12));
+        assert_eq!((trig.outer_low, trig.outer_high), (10, 12));
+        let trig = WindowTrigger::new(127, 127, 0);
+        assert_eq!((trig.inner_low, trig.inner_high), (125, 127));
+        assert_eq!(trig.outer_high, 126);
+    }
+}
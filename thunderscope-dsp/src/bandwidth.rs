@@ -0,0 +1,125 @@
+//! Measurement of the analog bandwidth of a channel, from its response to sine waves of the same
+//! amplitude and increasing frequency, e.g. stepped through by hand on an external generator.
+//!
+//! The bandwidth of a healthy frontend changes little over time, so comparing it against earlier
+//! measurements of the same channel and gain reveals damaged or degraded components.
+
+use thunderscope::DeviceParameters;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponsePoint {
+    /// Frequency of the input, in Hz.
+    pub frequency: f32,
+    /// Amplitude of the AC component of the input, as RMS volts.
+    pub amplitude: f32,
+}
+
+/// The frequency response of a channel, relative to the response at the lowest frequency.
+#[derive(Debug, Clone, Default)]
+pub struct FrequencyResponse {
+    points: Vec<ResponsePoint>,
+}
+
+impl FrequencyResponse {
+    pub fn new() -> FrequencyResponse {
+        Default::default()
+    }
+
+    /// Returns frequencies from `start` to `stop` (inclusive), spaced evenly on a logarithmic
+    /// scale with `per_decade` frequencies per decade and rounded to 1 kHz, so that they can be
+    /// entered into a generator by hand.
+    pub fn sweep(start: f32, stop: f32, per_decade: usize) -> Vec<f32> {
+        let steps = ((stop / start).log10() * per_decade as f32).ceil() as usize;
+        let mut frequencies = (0..steps)
+            .map(|step| start * 10.0f32.powf(step as f32 / per_decade as f32))
+            .map(|frequency| (frequency / 1e3).round() * 1e3)
+            .collect::<Vec<_>>();
+        frequencies.push(stop);
+        frequencies.dedup();
+        frequencies
+    }
+
+    /// Record the response at `frequency`, measured from `samples` of channel `channel_index`
+    /// acquired with `params`. The samples must belong to a single channel and span many periods
+    /// of the input.
+    ///
+    /// Returns the measured amplitude, as RMS volts.
+    pub fn record(&mut self, frequency: f32, params: &DeviceParameters, channel_index: usize,
+                  samples: &[i8]) -> f32 {
+        let mean = samples.iter().map(|&code| code as f64).sum::<f64>() / samples.len() as f64;
+        let variance = samples.iter()
+            .map(|&code| (code as f64 - mean).powi(2))
+            .sum::<f64>() / samples.len() as f64;
+        let amplitude = variance.sqrt() as f32 * params.full_scale(channel_index) / 256.0;
+        self.insert(ResponsePoint { frequency, amplitude });
+        amplitude
+    }
+
+    /// Record a response point measured by other means.
+    pub fn insert(&mut self, point: ResponsePoint) {
+        let index = self.points.partition_point(|other| other.frequency < point.frequency);
+        self.points.insert(index, point);
+    }
+
+    /// Returns the recorded points, by ascending frequency.
+    pub fn points(&self) -> &[ResponsePoint] {
+        &self.points
+    }
+
+    /// Returns the gain at `point` relative to the lowest recorded frequency, in dB.
+    pub fn relative_gain(&self, point: &ResponsePoint) -> f32 {
+        let reference = self.points.first().map_or(point.amplitude, |first| first.amplitude);
+        20.0 * (point.amplitude / reference).log10()
+    }
+
+    /// Returns the lowest frequency at which the relative gain falls to -3 dB, in Hz, or `None`
+    /// if it does not within the recorded frequencies.
+    ///
+    /// The frequency is interpolated between the recorded points, on a logarithmic scale.
+    pub fn bandwidth(&self) -> Option<f32> {
+        self.points.windows(2).find_map(|pair| {
+            let (above, below) = (self.relative_gain(&pair[0]), self.relative_gain(&pair[1]));
+            if below > -3.0 {
+                return None
+            }
+            let fraction = (above + 3.0) / (above - below);
+            let (low, high) = (pair[0].frequency.log10(), pair[1].frequency.log10());
+            Some(10.0f32.powf(low + fraction * (high - low)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use thunderscope::{DeviceCalibration, DeviceConfiguration};
+
+    #[test]
+    fn test_sweep() {
+        assert_eq!(FrequencyResponse::sweep(1e6, 100e6, 2),
+            [1e6, 3.162e6, 10e6, 31.623e6, 100e6]);
+        assert_eq!(FrequencyResponse::sweep(1e6, 2e6, 1), [1e6, 2e6]);
+    }
+
+    #[test]
+    fn test_single_pole() {
+        let params = DeviceParameters::derive(&DeviceCalibration::default(),
+            &DeviceConfiguration::default());
+        let cutoff = 200e6;
+        let mut response = FrequencyResponse::new();
+        for frequency in FrequencyResponse::sweep(1e6, 450e6, 20) {
+            let attenuation = (1.0 + (frequency / cutoff).powi(2)).sqrt();
+            let samples = (0..10_000)
+                .map(|index| (100.0 / attenuation * (index as f32 * 0.1).sin()).round() as i8)
+                .collect::<Vec<_>>();
+            response.record(frequency, &params, 0, &samples);
+        }
+        assert_eq!(response.relative_gain(&response.points()[0]), 0.0);
+        let bandwidth = response.bandwidth().unwrap();
+        assert!((bandwidth - cutoff).abs() < 0.02 * cutoff, "{}", bandwidth);
+        // the amplitude is RMS
+        let amplitude = response.points()[0].amplitude;
+        let expected = 100.0 / 2.0f32.sqrt() * params.full_scale(0) / 256.0;
+        assert!((amplitude - expected).abs() < 0.01 * expected);
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::{BufRead, Read, Write};
+use std::time::SystemTime;
+
+use thunderscope::{Amplification, ChannelConfiguration, DeviceCalibration, DeviceConfiguration,
+                   DeviceParameters, Filtering};
+use thunderscope_dsp::FrequencyResponse;
+
+const SAMPLE_COUNT: usize = 200000;
+
+/// Range of the sweep, in Hz. The upper end stays clear of the Nyquist frequency.
+const SWEEP_START: f32 = 1e6;
+const SWEEP_STOP: f32 = 450e6;
+const SWEEP_PER_DECADE: usize = 10;
+
+fn usage() -> ! {
+    eprintln!("usage: thunderscope-bandwidth [--channel 1|2|3|4] [--report FILE]");
+    std::process::exit(2)
+}
+
+fn prompt(message: &str) -> std::io::Result<()> {
+    eprint!("{} and press Enter... ", message);
+    std::io::stdin().lock().read_line(&mut String::new())?;
+    Ok(())
+}
+
+fn main() -> thunderscope::Result<()> {
+    env_logger::init();
+    let mut channel_index = 0;
+    let mut report_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next().as_deref()) {
+            ("--channel", Some(number)) => match number.parse::<usize>() {
+                Ok(number @ 1..=4) => channel_index = number - 1,
+                _ => usage()
+            }
+            ("--report", Some(path))    => report_path = Some(path.to_owned()),
+            _ => usage()
+        }
+    }
+    thunderscope::Device::with(|device| {
+        let mut config = DeviceConfiguration { channels: [None; 4], ..Default::default() };
+        config.channels[channel_index] = Some(ChannelConfiguration::default());
+        let mut results = Vec::new();
+        for amplification in [Amplification::dB10, Amplification::dB30] {
+            let mut params = DeviceParameters::derive(&DeviceCalibration::default(), &config);
+            let channel = params.channels[channel_index].as_mut().unwrap();
+            channel.amplification = amplification;
+            // measure the frontend itself rather than its bandwidth limit
+            channel.filtering = Filtering::Off;
+            device.configure(&params)?;
+            let gain = params.gain(channel_index);
+            prompt(&format!("CH{}, gain {:.1} dB: connect a sine wave of {:.3} Vpp to the probe \
+                             (about half of full scale)",
+                channel_index + 1, gain, params.full_scale(channel_index) / 2.0))?;
+            let mut response = FrequencyResponse::new();
+            for frequency in FrequencyResponse::sweep(SWEEP_START, SWEEP_STOP, SWEEP_PER_DECADE) {
+                prompt(&format!("set the frequency to {:.3} MHz", frequency / 1e6))?;
+                let mut samples = vec![0; SAMPLE_COUNT];
+                let mut stream = device.stream_data();
+                stream.read_exact(samples.as_mut())?; // discard data acquired while adjusting
+                stream.read_exact(samples.as_mut())?;
+                let amplitude = response.record(frequency, &params, channel_index,
+                    bytemuck::cast_slice(&samples[..]));
+                let point = *response.points().last().unwrap();
+                println!("{:.3} MHz: {:.4} Vrms, {:+.2} dB",
+                    frequency / 1e6, amplitude, response.relative_gain(&point));
+            }
+            let summary = match response.bandwidth() {
+                Some(bandwidth) => format!("{:.1} MHz", bandwidth / 1e6),
+                None => format!("above {:.1} MHz", SWEEP_STOP / 1e6),
+            };
+            println!("CH{}, gain {:.1} dB: bandwidth {}", channel_index + 1, gain, summary);
+            results.push((gain, summary));
+        }
+        if let Some(path) = report_path {
+            // append, so that the report holds the history of the instrument
+            let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+            let mut report = std::fs::File::options().create(true).append(true).open(path)?;
+            for (gain, summary) in results {
+                writeln!(report, "{} CH{} gain {:.1} dB: bandwidth {}",
+                    time.as_secs(), channel_index + 1, gain, summary)?;
+            }
+        }
+        Ok(())
+    })
+}
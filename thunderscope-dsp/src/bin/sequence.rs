@@ -0,0 +1,48 @@
+use thunderscope::{Device, DeviceCalibration, SimulatedSignal};
+use thunderscope_dsp::Sequence;
+
+fn usage() -> ! {
+    eprintln!("usage: thunderscope-sequence [--simulate] SEQUENCE.toml");
+    std::process::exit(2)
+}
+
+fn main() -> thunderscope::Result<()> {
+    env_logger::init();
+    let mut simulate = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--simulate" => simulate = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage()
+        }
+    }
+    let Some(path) = path else { usage() };
+    let sequence: Sequence = match std::fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| toml::from_str(&text).map_err(|error| error.to_string())) {
+        Ok(sequence) => sequence,
+        Err(error) => {
+            eprintln!("cannot load sequence {}: {}", path, error);
+            std::process::exit(2)
+        }
+    };
+    let run = |device: &mut Device| {
+        let report = sequence.run(device, &DeviceCalibration::default())?;
+        for (index, outcome) in report.outcomes.iter().enumerate() {
+            println!("step {}: {}", index + 1, outcome);
+        }
+        Ok(report.passed())
+    };
+    let passed = if simulate {
+        let signal = SimulatedSignal::Sine { period: 1000.0, amplitude: 100 };
+        let mut guard = Device::simulated(signal).guard()?;
+        let result = run(&mut guard);
+        let shutdown = guard.shutdown();
+        result.and_then(|passed| shutdown.map(|()| passed))?
+    } else {
+        Device::with(run)?
+    };
+    println!("{}", if passed { "PASSED" } else { "FAILED" });
+    std::process::exit(if passed { 0 } else { 1 })
+}
@@ -0,0 +1,264 @@
+//! Decimation of the interleaved sample stream.
+
+use wide::{f32x4, i64x4};
+
+/// Reduces the sample rate of an interleaved sample stream by averaging groups of consecutive
+/// samples of each channel.
+///
+/// The output is interleaved in the same way as the input. Samples that do not complete a group
+/// are retained until the next call to `process()`.
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    factor: usize,
+    channels: usize,
+    sums: Vec<i64>,
+    accumulated: usize,
+    next_channel: usize,
+}
+
+impl Decimator {
+    /// Create a decimator for a stream of `channels` interleaved channels, which will average
+    /// every `factor` consecutive samples of each channel into one.
+    pub fn new(factor: usize, channels: usize) -> Decimator {
+        assert!(factor > 0 && channels > 0);
+        Decimator {
+            factor,
+            channels,
+            sums: vec![0; channels],
+            accumulated: 0,
+            next_channel: 0,
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Discard any partially accumulated groups.
+    pub fn reset(&mut self) {
+        self.sums.fill(0);
+        self.accumulated = 0;
+        self.next_channel = 0;
+    }
+
+    /// Decimate `samples`, appending the results to `output`.
+    pub fn process(&mut self, samples: &[i8], output: &mut Vec<i8>) {
+        let factor = self.factor as i64;
+        for &sample in samples {
+            self.sums[self.next_channel] += sample as i64;
+            self.next_channel += 1;
+            if self.next_channel == self.channels {
+                self.next_channel = 0;
+                self.accumulated += 1;
+                if self.accumulated == self.factor {
+                    self.accumulated = 0;
+                    for sum in self.sums.iter_mut() {
+                        // round to nearest
+                        output.push((*sum * 2 + factor).div_euclid(factor * 2) as i8);
+                        *sum = 0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Order of the CIC filter of `CicDecimator`.
+const CIC_ORDER: usize = 4;
+
+/// Coefficients of the half-band filters of `CicDecimator`, divided by 512. Every other
+/// coefficient except for the center one is zero, so the zero taps are skipped.
+const HALF_BAND_TAPS: [f32; 11] = [
+    3.0 / 512.0, 0.0, -25.0 / 512.0, 0.0, 150.0 / 512.0, 256.0 / 512.0,
+    150.0 / 512.0, 0.0, -25.0 / 512.0, 0.0, 3.0 / 512.0,
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct HalfBand {
+    // most recent input last
+    history: [f32x4; HALF_BAND_TAPS.len()],
+    odd: bool,
+}
+
+impl HalfBand {
+    /// Filter `input`, returning an output for every other input.
+    fn process(&mut self, input: f32x4) -> Option<f32x4> {
+        self.history.copy_within(1.., 0);
+        self.history[HALF_BAND_TAPS.len() - 1] = input;
+        self.odd = !self.odd;
+        if self.odd { return None }
+        let mut output = self.history[HALF_BAND_TAPS.len() / 2] * HALF_BAND_TAPS[5];
+        for tap in (0..HALF_BAND_TAPS.len()).step_by(2) {
+            output = self.history[tap].mul_add(f32x4::splat(HALF_BAND_TAPS[tap]), output);
+        }
+        Some(output)
+    }
+}
+
+/// Reduces the sample rate of an interleaved sample stream with a cascaded integrator-comb (CIC)
+/// filter, followed by a chain of half-band filters that each decimate by 2.
+///
+/// Compared to `Decimator`, whose single averaging stage lets much of the signal above the new
+/// Nyquist frequency alias into the output, the CIC filter attenuates it far more, and
+/// the half-band filters sharpen the cutoff near the new Nyquist frequency, where the CIC filter
+/// alone attenuates little. Up to 4 channels are filtered in parallel, one per SIMD lane.
+///
+/// The output is interleaved in the same way as the input. The filters settle within a few output
+/// samples after the decimator is created or reset.
+#[derive(Debug, Clone)]
+pub struct CicDecimator {
+    cic_factor: usize,
+    channels: usize,
+    frame: [i64; 4],
+    next_channel: usize,
+    accumulated: usize,
+    integrators: [i64x4; CIC_ORDER],
+    // the previous input of each comb stage
+    delays: [i64x4; CIC_ORDER],
+    half_bands: Vec<HalfBand>,
+}
+
+impl CicDecimator {
+    /// Create a decimator for a stream of `channels` interleaved channels, which will decimate
+    /// by `cic_factor` in the CIC filter, and then by 2 in each of `half_band_stages` half-band
+    /// filters.
+    ///
+    /// The gain of the CIC filter, `cic_factor` to the power of its order (4), must not overflow
+    /// 64-bit arithmetic; `cic_factor` may be at most 12 500.
+    pub fn new(cic_factor: usize, half_band_stages: usize, channels: usize) -> CicDecimator {
+        assert!(cic_factor > 0 && (1..=4).contains(&channels));
+        assert!((cic_factor as i64).checked_pow(CIC_ORDER as u32)
+            .and_then(|gain| gain.checked_mul(128)).is_some(), "CIC filter gain is too high");
+        CicDecimator {
+            cic_factor,
+            channels,
+            frame: [0; 4],
+            next_channel: 0,
+            accumulated: 0,
+            integrators: [i64x4::ZERO; CIC_ORDER],
+            delays: [i64x4::ZERO; CIC_ORDER],
+            half_bands: vec![HalfBand::default(); half_band_stages],
+        }
+    }
+
+    /// Returns the overall decimation factor.
+    pub fn factor(&self) -> usize {
+        self.cic_factor << self.half_bands.len()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Discard the state of the filters.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.cic_factor, self.half_bands.len(), self.channels);
+    }
+
+    /// Decimate `samples`, appending the results to `output`.
+    pub fn process(&mut self, samples: &[i8], output: &mut Vec<i8>) {
+        let gain = (self.cic_factor as f32).powi(CIC_ORDER as i32);
+        for &sample in samples {
+            self.frame[self.next_channel] = sample as i64;
+            self.next_channel += 1;
+            if self.next_channel < self.channels { continue }
+            self.next_channel = 0;
+            // integrators run at the input rate; two's complement wraparound is harmless as long
+            // as the output of the combs fits
+            let mut value = i64x4::new(self.frame);
+            for integrator in self.integrators.iter_mut() {
+                *integrator = *integrator + value;
+                value = *integrator;
+            }
+            self.accumulated += 1;
+            if self.accumulated < self.cic_factor { continue }
+            self.accumulated = 0;
+            // combs run at the decimated rate
+            for delay in self.delays.iter_mut() {
+                (value, *delay) = (value - *delay, value);
+            }
+            let mut value = f32x4::new(value.to_array().map(|sum| sum as f32 / gain));
+            let mut decimated = true;
+            for half_band in self.half_bands.iter_mut() {
+                match half_band.process(value) {
+                    Some(filtered) => value = filtered,
+                    None => { decimated = false; break }
+                }
+            }
+            if decimated {
+                output.extend(value.round().to_array()[..self.channels].iter()
+                    .map(|&value| value.clamp(-128.0, 127.0) as i8));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_single_channel() {
+        let mut decimator = Decimator::new(4, 1);
+        let mut output = Vec::new();
+        decimator.process(&[1, 2, 3, 4, 10, 10, -10], &mut output);
+        assert_eq!(output, [3]);
+        decimator.process(&[-10], &mut output);
+        assert_eq!(output, [3, 0]);
+    }
+
+    #[test]
+    fn test_interleaved() {
+        let mut decimator = Decimator::new(2, 2);
+        let mut output = Vec::new();
+        decimator.process(&[1, -1, 3, -3, 5], &mut output);
+        assert_eq!(output, [2, -2]);
+        decimator.process(&[-5, 7, -7], &mut output);
+        assert_eq!(output, [2, -2, 6, -6]);
+    }
+
+    fn tone(frequency: f32, length: usize) -> Vec<i8> {
+        (0..length)
+            .map(|index| (100.0 * (std::f32::consts::TAU * frequency * index as f32).sin()) as i8)
+            .collect()
+    }
+
+    fn amplitude(samples: &[i8]) -> i8 {
+        samples.iter().map(|sample| sample.saturating_abs()).max().unwrap()
+    }
+
+    #[test]
+    fn test_cic_dc() {
+        let mut decimator = CicDecimator::new(5, 2, 2);
+        assert_eq!(decimator.factor(), 20);
+        let mut output = Vec::new();
+        decimator.process(&[50, -30].repeat(20 * 20 + 7), &mut output);
+        assert_eq!(output.len(), 40);
+        assert_eq!(output[30..], [50, -30].repeat(5));
+        decimator.reset();
+        output.clear();
+        decimator.process(&[50, -30].repeat(19), &mut output);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_cic_alias() {
+        // a tone above the output Nyquist frequency aliases to below it
+        let samples = tone(0.8 / 32.0, 32 * 200);
+        let mut averaged = Vec::new();
+        Decimator::new(32, 1).process(&samples, &mut averaged);
+        let mut filtered = Vec::new();
+        CicDecimator::new(8, 2, 1).process(&samples, &mut filtered);
+        assert!(amplitude(&averaged[20..]) > 10, "{:?}", averaged);
+        assert!(amplitude(&filtered[20..]) <= 2, "{:?}", filtered);
+        // while a tone well within the passband is preserved
+        let samples = tone(0.1 / 32.0, 32 * 200);
+        let mut filtered = Vec::new();
+        CicDecimator::new(8, 2, 1).process(&samples, &mut filtered);
+        assert!(amplitude(&filtered[20..]) >= 90, "{:?}", filtered);
+    }
+}
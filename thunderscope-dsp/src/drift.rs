@@ -0,0 +1,135 @@
+//! Tracking of the baseline drift of the analog frontend while it warms up after startup.
+//!
+//! The offset of the frontend changes noticeably over the first minutes of operation. While
+//! a channel is idle (its signal is flat), its baseline is measured periodically and compared
+//! to the first measurement, which assumes that the input of an idle channel does not change.
+
+use std::time::{Duration, Instant};
+
+use thunderscope::{Capture, ChannelParameters, DeviceParameters};
+
+/// Largest peak-to-peak swing of a capture of an idle channel, in ADC codes.
+const IDLE_SWING: i16 = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct Baseline {
+    // the parameters the reference was measured with, without drift correction
+    params: ChannelParameters,
+    // mean code
+    reference: f32,
+    latest: f32,
+}
+
+/// Measures the baseline drift of idle channels for a warm-up period after startup.
+#[derive(Debug, Clone)]
+pub struct DriftTracker {
+    started: Instant,
+    warm_up: Duration,
+    interval: Duration,
+    last_measured: Option<Instant>,
+    baselines: [Option<Baseline>; 4],
+}
+
+impl DriftTracker {
+    /// Create a tracker for a frontend that has been started up at `started`, which measures
+    /// the baseline every `interval` until `warm_up` has elapsed.
+    pub fn new(started: Instant, warm_up: Duration, interval: Duration) -> DriftTracker {
+        DriftTracker { started, warm_up, interval, last_measured: None, baselines: [None; 4] }
+    }
+
+    /// Returns `true` if the frontend is still warming up at `now`.
+    pub fn is_warming_up(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.started) < self.warm_up
+    }
+
+    /// Returns `true` if the baseline should be measured at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.is_warming_up(now) && self.last_measured.is_none_or(|last_measured|
+            now.saturating_duration_since(last_measured) >= self.interval)
+    }
+
+    /// Measure the baseline of the idle channels in `capture`, acquired at `now`. Channels that
+    /// are not idle keep their previous measurement; changing the parameters of a channel
+    /// restarts tracking it.
+    pub fn observe(&mut self, capture: &Capture, now: Instant) {
+        self.last_measured = Some(now);
+        for (channel_index, baseline) in self.baselines.iter_mut().enumerate() {
+            let (Some(samples), Some(params)) =
+                (capture.channel(channel_index), capture.params().channels[channel_index])
+                else { continue };
+            let params = ChannelParameters { drift_correction: 0.0, ..params };
+            if baseline.is_some_and(|baseline| baseline.params != params) {
+                *baseline = None;
+            }
+            let (Some(&min), Some(&max)) = (samples.iter().min(), samples.iter().max())
+                else { continue };
+            if max as i16 - min as i16 > IDLE_SWING {
+                continue
+            }
+            let mean = samples.iter().map(|&code| code as f32).sum::<f32>() / samples.len() as f32;
+            match baseline {
+                Some(baseline) => baseline.latest = mean,
+                None => *baseline = Some(Baseline { params, reference: mean, latest: mean }),
+            }
+        }
+    }
+
+    /// Returns the drift of channel `channel_index` since tracking started, in volts, or `None`
+    /// if it has not been idle yet with `params`.
+    pub fn drift(&self, params: &DeviceParameters, channel_index: usize) -> Option<f32> {
+        let baseline = self.baselines[channel_index]?;
+        let channel = params.channels[channel_index]?;
+        let channel = ChannelParameters { drift_correction: 0.0, ..channel };
+        if channel != baseline.params {
+            return None
+        }
+        Some((baseline.latest - baseline.reference) / 256.0 * params.full_scale(channel_index))
+    }
+
+    /// Set the drift correction of every tracked channel in `params` to its drift.
+    pub fn compensate(&self, params: &mut DeviceParameters) {
+        for channel_index in 0..4 {
+            let drift = self.drift(params, channel_index);
+            if let (Some(channel), Some(drift)) = (params.channels[channel_index].as_mut(), drift) {
+                channel.drift_correction = drift;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_warm_up() {
+        let started = Instant::now();
+        let mut params = DeviceParameters::default();
+        params.channels[3] = None;
+        let mut tracker = DriftTracker::new(started,
+            Duration::from_secs(60), Duration::from_secs(10));
+        let capture = |codes: [i8; 4]| Capture::new(&params, &codes.repeat(100));
+        assert!(tracker.is_due(started));
+        tracker.observe(&capture([10, 20, 0, 0]), started);
+        assert!(!tracker.is_due(started + Duration::from_secs(5)));
+        let later = started + Duration::from_secs(10);
+        assert!(tracker.is_due(later));
+        // CH2 is not idle, so its baseline is not measured
+        let mut codes = [12, 25, 0, 0].repeat(100);
+        codes[1] = 40;
+        tracker.observe(&Capture::new(&params, &codes), later);
+        assert_eq!(tracker.drift(&params, 0), Some(params.code_to_volts(0, 2)));
+        assert_eq!(tracker.drift(&params, 1), Some(0.0));
+        assert_eq!(tracker.drift(&params, 3), None);
+        // once compensated, the baseline reads as it did at startup
+        let mut compensated = params;
+        tracker.compensate(&mut compensated);
+        assert!((compensated.code_to_volts(0, 12) - params.code_to_volts(0, 10)).abs() < 1e-6);
+        assert_eq!(compensated.code_to_volts(1, 20), params.code_to_volts(1, 20));
+        assert!(!tracker.is_due(started + Duration::from_secs(60)));
+        // the drift is unknown with other parameters
+        let mut other = params;
+        other.channels[0].as_mut().unwrap().probe_attenuation += 6.0;
+        assert_eq!(tracker.drift(&other, 0), None);
+    }
+}
@@ -0,0 +1,176 @@
+//! Detection and correction of ADC interleaving spurs.
+//!
+//! In one and two channel modes, the HMCAD1520 samples each channel with four or two of its ADC
+//! branches in turn. A mismatch in offset between the branches appears as spurs at fs/2 and fs/4
+//! (regardless of the input), and a mismatch in gain appears as images of the input signal.
+//! Both are measured from a capture of a grounded input or a single tone, and corrected in
+//! software.
+//!
+//! The branch that acquired a sample is determined by its frame index modulo the amount of
+//! branches, so the analyzed and corrected samples must start at a stream position that is
+//! a multiple of four, as do all captures from `Device::read_data()`.
+
+use thunderscope::DeviceParameters;
+
+/// Below this RMS amplitude (in ADC codes), the input is considered grounded, and the gains of
+/// the branches are not estimated.
+const MIN_GAIN_AMPLITUDE: f32 = 4.0;
+
+/// Returns the amount of ADC branches that sample each channel in turn with `params`.
+pub fn interleaved_branches(params: &DeviceParameters) -> usize {
+    4 / params.stream_channels()
+}
+
+/// Interleaving artifacts in a capture of one channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterleaveAnalysis {
+    /// Amount of ADC branches sampling the channel in turn; 1 in four channel mode.
+    pub branches: usize,
+    /// Amplitude of the spur at fs/2, in ADC codes.
+    pub spur_half: f32,
+    /// Amplitude of the spur at fs/4, in ADC codes. Zero unless there are four branches.
+    pub spur_quarter: f32,
+    /// Offset of each branch relative to the mean of all branches, in ADC codes.
+    pub offsets: [f32; 4],
+    /// Gain of each branch relative to the mean of all branches. One if the input is too small
+    /// for the gain to be estimated.
+    pub gains: [f32; 4],
+}
+
+impl InterleaveAnalysis {
+    /// Analyze `samples` of one channel acquired with `params`. Returns `None` if there are
+    /// fewer samples than branches.
+    ///
+    /// The input should be grounded, or a single tone that is not close to fs/4 or fs/2 (for
+    /// example, a few MHz); the gains can only be estimated from the latter.
+    pub fn new(params: &DeviceParameters, samples: &[i8]) -> Option<InterleaveAnalysis> {
+        let branches = interleaved_branches(params);
+        // use only whole frames of branches, so that each branch has the same weight
+        let samples = &samples[..samples.len() / branches * branches];
+        if samples.is_empty() {
+            return None
+        }
+        let per_branch = (samples.len() / branches) as f32;
+
+        let mut sums = [0.0f32; 4];
+        for (index, &sample) in samples.iter().enumerate() {
+            sums[index % branches] += sample as f32;
+        }
+        let means = sums.map(|sum| sum / per_branch);
+        let mean = means[..branches].iter().sum::<f32>() / branches as f32;
+
+        let mut squares = [0.0f32; 4];
+        for (index, &sample) in samples.iter().enumerate() {
+            squares[index % branches] += (sample as f32 - means[index % branches]).powi(2);
+        }
+        let amplitudes = squares.map(|square| (square / per_branch).sqrt());
+        let amplitude = amplitudes[..branches].iter().sum::<f32>() / branches as f32;
+
+        let mut offsets = [0.0; 4];
+        let mut gains = [1.0; 4];
+        for branch in 0..branches {
+            offsets[branch] = means[branch] - mean;
+            if amplitude >= MIN_GAIN_AMPLITUDE {
+                gains[branch] = amplitudes[branch] / amplitude;
+            }
+        }
+
+        // the DFT bins at fs/2 and fs/4; for a tone that is not close to either, only the offset
+        // mismatch contributes to them
+        let (mut half, mut quarter_re, mut quarter_im) = (0.0f32, 0.0f32, 0.0f32);
+        for (index, &sample) in samples.iter().enumerate() {
+            let sample = sample as f32;
+            half += if index % 2 == 0 { sample } else { -sample };
+            match index % 4 {
+                0 => quarter_re += sample,
+                1 => quarter_im -= sample,
+                2 => quarter_re -= sample,
+                _ => quarter_im += sample,
+            }
+        }
+        let length = samples.len() as f32;
+        let spur_half = if branches > 1 { half.abs() / length } else { 0.0 };
+        let spur_quarter = if branches > 2 {
+            2.0 * quarter_re.hypot(quarter_im) / length
+        } else {
+            0.0
+        };
+
+        Some(InterleaveAnalysis { branches, spur_half, spur_quarter, offsets, gains })
+    }
+
+    /// Returns the correction that removes the measured offset and gain mismatch.
+    pub fn correction(&self) -> InterleaveCorrection {
+        InterleaveCorrection { branches: self.branches, offsets: self.offsets, gains: self.gains }
+    }
+}
+
+/// Software correction of offset and gain mismatch between ADC branches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterleaveCorrection {
+    branches: usize,
+    offsets: [f32; 4],
+    gains: [f32; 4],
+}
+
+impl InterleaveCorrection {
+    /// Correct `samples` of one channel in place. The corrected samples are rounded to the
+    /// nearest code, so mismatch of less than half a code remains.
+    pub fn apply(&self, samples: &mut [i8]) {
+        for (index, sample) in samples.iter_mut().enumerate() {
+            let branch = index % self.branches;
+            let corrected = (*sample as f32 - self.offsets[branch]) / self.gains[branch];
+            *sample = corrected.round().clamp(i8::MIN as f32, i8::MAX as f32) as i8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use thunderscope::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration};
+
+    fn one_channel() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None, None, None],
+            ..Default::default()
+        })
+    }
+
+    fn mismatched(offsets: [f32; 4], gains: [f32; 4], amplitude: f32) -> Vec<i8> {
+        (0..40_000).map(|index| {
+            let branch = index % 4;
+            let signal = amplitude * (index as f32 * 0.0123).sin();
+            (signal * gains[branch] + offsets[branch]).round() as i8
+        }).collect()
+    }
+
+    #[test]
+    fn test_branches() {
+        assert_eq!(interleaved_branches(&one_channel()), 4);
+        assert_eq!(interleaved_branches(&DeviceParameters::default()), 1);
+    }
+
+    #[test]
+    fn test_grounded() {
+        let samples = mismatched([2.0, -1.0, 1.0, -2.0], [1.0; 4], 0.0);
+        let analysis = InterleaveAnalysis::new(&one_channel(), &samples).unwrap();
+        assert_eq!(analysis.offsets, [2.0, -1.0, 1.0, -2.0]);
+        assert_eq!(analysis.gains, [1.0; 4]);
+        // fs/2: (2 - -1 + 1 - -2) / 4; fs/4: 2 * |(2 - 1) + j(-1 - -2)| / 4
+        assert!((analysis.spur_half - 1.5).abs() < 1e-3, "{}", analysis.spur_half);
+        assert!((analysis.spur_quarter - 0.707).abs() < 1e-3, "{}", analysis.spur_quarter);
+    }
+
+    #[test]
+    fn test_correction() {
+        let mut samples = mismatched([3.0, -1.0, 0.0, -2.0], [1.05, 1.0, 0.95, 1.0], 100.0);
+        let analysis = InterleaveAnalysis::new(&one_channel(), &samples).unwrap();
+        assert!(analysis.spur_half > 1.0 && analysis.spur_quarter > 1.0, "{:?}", analysis);
+        assert!((analysis.gains[0] - 1.05).abs() < 0.01, "{:?}", analysis.gains);
+        analysis.correction().apply(&mut samples);
+        let corrected = InterleaveAnalysis::new(&one_channel(), &samples).unwrap();
+        assert!(corrected.spur_half < 0.1 && corrected.spur_quarter < 0.1, "{:?}", corrected);
+        assert!(corrected.gains.iter().all(|gain| (gain - 1.0).abs() < 0.01), "{:?}", corrected);
+    }
+}
@@ -0,0 +1,30 @@
+//! Signal processing and measurement on samples acquired with the `thunderscope` driver.
+
+mod decimate;
+mod interleave;
+mod measure;
+mod bandwidth;
+mod drift;
+mod limit;
+mod sequence;
+
+pub use decimate::{CicDecimator, Decimator};
+
+pub use interleave::{
+    interleaved_branches,
+    InterleaveAnalysis,
+    InterleaveCorrection,
+};
+
+pub use sequence::{Outcome, Report, Sequence, Step};
+
+pub use measure::Measurement;
+
+pub use bandwidth::{FrequencyResponse, ResponsePoint};
+
+pub use drift::DriftTracker;
+
+pub use limit::{
+    Limit,
+    Violation,
+};
@@ -0,0 +1,82 @@
+//! Limit testing of measurements, for unattended monitoring.
+
+use std::fmt;
+
+use thunderscope::DeviceParameters;
+use crate::measure::Measurement;
+
+/// An acceptable range for a measurement on a channel. Either bound may be absent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Limit {
+    pub measurement: Measurement,
+    pub channel: usize,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+/// A measurement that fell outside of its limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub limit: Limit,
+    pub value: f32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Limit { measurement, channel, min, max } = self.limit;
+        write!(f, "CH{} {} = {} {}",
+            channel + 1, measurement.name(), self.value, measurement.unit())?;
+        match (min, max) {
+            (Some(min), _) if self.value < min => write!(f, " < {} {}", min, measurement.unit()),
+            (_, Some(max)) if self.value > max => write!(f, " > {} {}", max, measurement.unit()),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Limit {
+    /// Measure `samples` of the limit's channel (not interleaved), captured with `params`, and
+    /// check the result against the limit.
+    ///
+    /// A measurement that cannot be determined is not considered a violation.
+    pub fn check(&self, params: &DeviceParameters, samples: &[i8]) -> Option<Violation> {
+        let value = self.measurement.measure(params, self.channel, samples)?;
+        self.check_value(value)
+    }
+
+    /// Check an already determined measurement `value` against the limit.
+    pub fn check_value(&self, value: f32) -> Option<Violation> {
+        let below = self.min.is_some_and(|min| value < min);
+        let above = self.max.is_some_and(|max| value > max);
+        if below || above {
+            Some(Violation { limit: *self, value })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use thunderscope::{DeviceCalibration, DeviceConfiguration};
+
+    #[test]
+    fn test_check() {
+        let params = DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(Default::default()), None, None, None],
+            ..Default::default()
+        });
+        let volts = params.code_to_volts(0, 50);
+        let samples = [50i8; 64];
+        let limit = Limit { measurement: Measurement::Mean, channel: 0, min: None, max: None };
+        assert_eq!(limit.check(&params, &samples), None);
+        let limit = Limit { max: Some(volts * 2.0), ..limit };
+        assert_eq!(limit.check(&params, &samples), None);
+        let limit = Limit { max: Some(volts / 2.0), ..limit };
+        assert_eq!(limit.check(&params, &samples), Some(Violation { limit, value: volts }));
+        let limit = Limit { min: Some(volts * 2.0), max: None, ..limit };
+        assert_eq!(limit.check(&params, &samples), Some(Violation { limit, value: volts }));
+    }
+}
@@ -0,0 +1,172 @@
+//! Automatic measurements of waveform parameters.
+
+use thunderscope::{DeviceParameters, Edge, EdgeFilter, Trigger};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Measurement {
+    Mean,
+    Rms,
+    PeakToPeak,
+    Frequency,
+    DutyCycle,
+    /// Amount of rising edges, e.g. to count events within a capture.
+    EdgeCount,
+}
+
+impl Measurement {
+    pub const ALL: [Measurement; 6] = [
+        Measurement::Mean,
+        Measurement::Rms,
+        Measurement::PeakToPeak,
+        Measurement::Frequency,
+        Measurement::DutyCycle,
+        Measurement::EdgeCount,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Measurement::Mean       => "Mean",
+            Measurement::Rms        => "RMS",
+            Measurement::PeakToPeak => "Vpp",
+            Measurement::Frequency  => "Frequency",
+            Measurement::DutyCycle  => "Duty",
+            Measurement::EdgeCount  => "Edges",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Measurement::Mean |
+            Measurement::Rms |
+            Measurement::PeakToPeak => "V",
+            Measurement::Frequency  => "Hz",
+            Measurement::DutyCycle  => "%",
+            Measurement::EdgeCount  => "",
+        }
+    }
+
+    /// Measure the parameter of `samples`, captured on channel `channel_index` with `params`.
+    ///
+    /// The samples must belong to a single channel (i.e. not be interleaved). Returns `None` if
+    /// the parameter cannot be determined; e.g. the frequency of a waveform without at least two
+    /// rising edges.
+    pub fn measure(self, params: &DeviceParameters, channel_index: usize,
+            samples: &[i8]) -> Option<f32> {
+        if samples.is_empty() {
+            return None
+        }
+        let to_volts = |code: i8| params.code_to_volts(channel_index, code);
+        match self {
+            Measurement::Mean => {
+                let sum = samples.iter().map(|&code| code as i64).sum::<i64>();
+                Some(to_volts(1) * sum as f32 / samples.len() as f32)
+            }
+            Measurement::Rms => {
+                let sum = samples.iter().map(|&code| (code as i64).pow(2)).sum::<i64>();
+                Some(to_volts(1) * (sum as f32 / samples.len() as f32).sqrt())
+            }
+            Measurement::PeakToPeak => {
+                let (min, max) = min_max(samples);
+                Some(to_volts(max) - to_volts(min))
+            }
+            Measurement::Frequency => {
+                let (level, edges) = rising_edges(samples).filter(|(_, edges)| edges.len() >= 2)?;
+                // the period is measured between the interpolated crossings of the mid-level
+                let trigger = Trigger::new(level, 0);
+                let crossing = |index: usize| index as f32 +
+                    trigger.crossing(Edge::Rising, samples, index, 1).unwrap_or(0.0);
+                let (first, last) = (crossing(edges[0]), crossing(edges[edges.len() - 1]));
+                Some((edges.len() - 1) as f32 * params.corrected_sample_rate() / (last - first))
+            }
+            Measurement::DutyCycle => {
+                let (level, edges) = rising_edges(samples).filter(|(_, edges)| edges.len() >= 2)?;
+                let (first, last) = (edges[0], edges[edges.len() - 1]);
+                let above = samples[first..last].iter().filter(|&&code| code >= level).count();
+                Some(100.0 * above as f32 / (last - first) as f32)
+            }
+            Measurement::EdgeCount => {
+                // a waveform without enough signal to have edges has none
+                Some(rising_edges(samples).map_or(0, |(_, edges)| edges.len()) as f32)
+            }
+        }
+    }
+}
+
+fn min_max(samples: &[i8]) -> (i8, i8) {
+    samples.iter().fold((i8::MAX, i8::MIN), |(min, max), &code| (min.min(code), max.max(code)))
+}
+
+/// Returns the mid-level of the waveform and the positions of its rising edges, if there is
+/// enough signal to distinguish them from noise.
+fn rising_edges(samples: &[i8]) -> Option<(i8, Vec<usize>)> {
+    let (min, max) = min_max(samples);
+    let swing = max as i16 - min as i16;
+    if swing < 4 {
+        return None // not enough signal to distinguish edges from noise
+    }
+    let level = (min as i16 + swing / 2) as i8;
+    let mut trigger = Trigger::new(level, (swing / 8) as u8);
+    let mut edges = Vec::new();
+    let mut offset = 0;
+    loop {
+        let (processed, edge) = trigger.find(&samples[offset..], EdgeFilter::Rising);
+        offset += processed;
+        if edge.is_none() { break }
+        edges.push(offset);
+        offset += 1;
+    }
+    Some((level, edges))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use thunderscope::{DeviceCalibration, DeviceConfiguration};
+
+    fn params() -> DeviceParameters {
+        DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(Default::default()), None, None, None],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_square_wave() {
+        // 20 samples per period at 1 GS/s, 5 high and 15 low
+        let samples = (0..1000).map(|index| if index % 20 < 5 { 100 } else { -100 })
+            .collect::<Vec<i8>>();
+        let params = params();
+        let frequency = Measurement::Frequency.measure(&params, 0, &samples).unwrap();
+        assert!((frequency - 50e6).abs() < 1.0, "{}", frequency);
+        let duty = Measurement::DutyCycle.measure(&params, 0, &samples).unwrap();
+        assert!((duty - 25.0).abs() < 0.1, "{}", duty);
+        let edges = Measurement::EdgeCount.measure(&params, 0, &samples).unwrap();
+        assert_eq!(edges, 49.0); // the first period starts high
+        let vpp = Measurement::PeakToPeak.measure(&params, 0, &samples).unwrap();
+        assert_eq!(vpp, params.code_to_volts(0, 100) - params.code_to_volts(0, -100));
+    }
+
+    #[test]
+    fn test_sine_wave() {
+        // 12.3 samples per period at 1 GS/s, which edges at whole samples cannot resolve
+        let samples = (0..1000)
+            .map(|index| (100.0 * (index as f32 * std::f32::consts::TAU / 12.3).sin()) as i8)
+            .collect::<Vec<i8>>();
+        let params = params();
+        let frequency = Measurement::Frequency.measure(&params, 0, &samples).unwrap();
+        assert!((frequency - 1e9 / 12.3).abs() < 1e9 / 12.3 * 1e-4, "{}", frequency);
+    }
+
+    #[test]
+    fn test_dc() {
+        let samples = [10i8; 100];
+        let params = params();
+        assert_eq!(Measurement::Frequency.measure(&params, 0, &samples), None);
+        assert_eq!(Measurement::EdgeCount.measure(&params, 0, &samples), Some(0.0));
+        let mean = Measurement::Mean.measure(&params, 0, &samples).unwrap();
+        assert!((mean - params.code_to_volts(0, 10)).abs() < 1e-6);
+        let rms = Measurement::Rms.measure(&params, 0, &samples).unwrap();
+        assert!((rms - params.code_to_volts(0, 10)).abs() < 1e-6);
+    }
+}
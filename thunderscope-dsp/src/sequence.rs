@@ -0,0 +1,240 @@
+//! Scripted test sequences, for automated testing of hardware with the device.
+//!
+//! A sequence is a list of steps that configure the device, acquire a capture, and check
+//! measurements of the capture against limits. With the `serde` feature, sequences can be
+//! deserialized from e.g. TOML; the `thunderscope-sequence` tool runs them from a file.
+
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use thunderscope::{Error, Result};
+use thunderscope::{Capture, ChannelConfiguration, Device, DeviceCalibration, DeviceConfiguration,
+                   DeviceParameters, SampleRate};
+use crate::limit::{Limit, Violation};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "action", rename_all = "snake_case"))]
+pub enum Step {
+    /// Configure the device. Channels that are absent are disabled.
+    Configure {
+        #[cfg_attr(feature = "serde", serde(default))]
+        ch1: Option<ChannelConfiguration>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        ch2: Option<ChannelConfiguration>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        ch3: Option<ChannelConfiguration>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        ch4: Option<ChannelConfiguration>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        sample_rate: SampleRate,
+    },
+    /// Acquire a capture of `samples` samples per channel, replacing the previous one. The first
+    /// `settle` samples per channel are discarded, e.g. to let the signal path stabilize after
+    /// the device is configured.
+    Acquire {
+        samples: usize,
+        #[cfg_attr(feature = "serde", serde(default))]
+        settle: usize,
+    },
+    /// Wait for `milliseconds`, e.g. for the device under test to change state.
+    Wait {
+        milliseconds: u64,
+    },
+    /// Measure the most recent capture, and check the measurement against the limit. A failure
+    /// to determine the measurement is also a failure of the check.
+    Assert(Limit),
+}
+
+impl Step {
+    fn configuration(&self) -> Option<DeviceConfiguration> {
+        match *self {
+            Step::Configure { ch1, ch2, ch3, ch4, sample_rate } =>
+                Some(DeviceConfiguration { channels: [ch1, ch2, ch3, ch4], sample_rate,
+                                           ..Default::default() }),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sequence {
+    #[cfg_attr(feature = "serde", serde(rename = "step"))]
+    pub steps: Vec<Step>,
+    /// Directory to save the captures that failed a check into, as raw interleaved codes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub export_on_failure: Option<PathBuf>,
+}
+
+/// Outcome of a step of a sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Done,
+    Passed { value: f32 },
+    /// The check failed, either because the measurement is outside of the limit or because it
+    /// could not be determined (`value` is `None`). The capture was saved to `export`.
+    Failed { limit: Limit, value: Option<f32>, export: Option<PathBuf> },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Done => write!(f, "done"),
+            Outcome::Passed { value } => write!(f, "passed ({})", value),
+            Outcome::Failed { limit, value, export } => {
+                match *value {
+                    Some(value) => write!(f, "FAILED: {}", Violation { limit: *limit, value })?,
+                    None => write!(f, "FAILED: CH{} {} cannot be determined",
+                        limit.channel + 1, limit.measurement.name())?,
+                }
+                if let Some(path) = export {
+                    write!(f, " (saved to {})", path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Outcomes of the steps of a sequence, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        !self.outcomes.iter().any(|outcome| matches!(outcome, Outcome::Failed { .. }))
+    }
+}
+
+impl Sequence {
+    /// Run the sequence on `device`, which must be started up already, deriving the device
+    /// parameters using `calibration`.
+    ///
+    /// All steps are run even if some of them fail. Returns an error if acquisition fails, or if
+    /// a check is done before anything is acquired.
+    pub fn run(&self, device: &Device, calibration: &DeviceCalibration) -> Result<Report> {
+        // the state of the device after `Device::startup()`
+        let mut params = DeviceParameters::default();
+        let mut capture: Option<(Vec<i8>, Capture)> = None;
+        let mut outcomes = Vec::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            log::info!("sequence: step {}: {:?}", index + 1, step);
+            let outcome = match *step {
+                Step::Configure { .. } => {
+                    params = DeviceParameters::derive(calibration, &step.configuration().unwrap());
+                    device.configure(&params)?;
+                    Outcome::Done
+                }
+                Step::Acquire { samples, settle } => {
+                    let data = Self::acquire(device, &params, settle, samples)?;
+                    capture = Some((data.clone(), Capture::new(&params, &data)));
+                    Outcome::Done
+                }
+                Step::Wait { milliseconds } => {
+                    std::thread::sleep(Duration::from_millis(milliseconds));
+                    Outcome::Done
+                }
+                Step::Assert(limit) => {
+                    let Some((data, capture)) = capture.as_ref() else {
+                        return Err(Error::Other(
+                            format!("step {}: nothing has been acquired", index + 1).into()))
+                    };
+                    let value = capture.channel(limit.channel).and_then(|samples|
+                        limit.measurement.measure(capture.params(), limit.channel, samples));
+                    match value {
+                        Some(value) if limit.check_value(value).is_none() =>
+                            Outcome::Passed { value },
+                        value => {
+                            let export = self.export(index, data);
+                            Outcome::Failed { limit, value, export }
+                        }
+                    }
+                }
+            };
+            log::info!("sequence: step {}: {}", index + 1, outcome);
+            outcomes.push(outcome);
+        }
+        Ok(Report { outcomes })
+    }
+
+    fn acquire(device: &Device, params: &DeviceParameters, settle: usize,
+            samples: usize) -> Result<Vec<i8>> {
+        let stream_channels = params.stream_channels();
+        let wanted = (settle + samples) * stream_channels;
+        let mut data = Vec::with_capacity(wanted);
+        device.read_data(|chunk| {
+            let length = chunk.len().min(wanted - data.len());
+            data.extend_from_slice(&chunk[..length]);
+            Ok(if data.len() == wanted { ControlFlow::Break(()) }
+               else { ControlFlow::Continue(()) })
+        })?;
+        data.drain(..settle * stream_channels);
+        Ok(data)
+    }
+
+    fn export(&self, index: usize, data: &[i8]) -> Option<PathBuf> {
+        let path = self.export_on_failure.as_ref()?.join(format!("step{}.data", index + 1));
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|()| std::fs::write(&path, bytemuck::cast_slice(data)));
+        match result {
+            Ok(()) => Some(path),
+            Err(error) => {
+                log::error!("sequence: cannot save capture to {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::measure::Measurement;
+    use thunderscope::SimulatedSignal;
+
+    #[test]
+    fn test_simulated() {
+        let device = Device::simulated(SimulatedSignal::Sine { period: 1000.0, amplitude: 100 });
+        device.startup().unwrap();
+        let export_dir = std::env::temp_dir().join(format!("sequence-{}", std::process::id()));
+        let frequency = Limit {
+            measurement: Measurement::Frequency,
+            channel: 0,
+            min: Some(0.99e6),
+            max: Some(1.01e6),
+        };
+        let sequence = Sequence {
+            steps: vec![
+                Step::Configure { ch1: Some(Default::default()), ch2: None, ch3: None, ch4: None,
+                                  sample_rate: SampleRate::MSps1000 },
+                Step::Acquire { samples: 10_000, settle: 1000 },
+                Step::Assert(frequency),
+                Step::Assert(Limit { min: Some(2e6), max: None, ..frequency }),
+            ],
+            export_on_failure: Some(export_dir.clone()),
+        };
+        let report = sequence.run(&device, &DeviceCalibration::default()).unwrap();
+        device.shutdown().unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.outcomes[..2], [Outcome::Done, Outcome::Done]);
+        assert!(matches!(report.outcomes[2], Outcome::Passed { .. }), "{}", report.outcomes[2]);
+        let Outcome::Failed { export: Some(ref path), .. } = report.outcomes[3] else {
+            panic!("{}", report.outcomes[3])
+        };
+        assert_eq!(std::fs::metadata(path).unwrap().len(), 10_000);
+        std::fs::remove_dir_all(export_dir).unwrap();
+    }
+
+    #[test]
+    fn test_assert_without_acquire() {
+        let device = Device::simulated(SimulatedSignal::Ramp);
+        let limit = Limit { measurement: Measurement::Mean, channel: 0, min: None, max: None };
+        let sequence = Sequence { steps: vec![Step::Assert(limit)], ..Default::default() };
+        assert!(sequence.run(&device, &DeviceCalibration::default()).is_err());
+    }
+}
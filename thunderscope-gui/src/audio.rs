@@ -0,0 +1,265 @@
+//! Audio monitor, which plays one channel of the sample stream through the host audio output,
+//! e.g. to listen to an audio circuit or to hear intermittent glitches while probing.
+//!
+//! The sampler decimates the stream to about `AUDIO_RATE` samples per second per channel and
+//! sends it in chunks; the audio output callback picks the monitored channel out of the chunks,
+//! and resamples it to the rate of the output device.
+
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use thunderscope::{ChannelMap, DeviceParameters, Result};
+use thunderscope::acquire::SampleSource;
+use thunderscope_dsp::Decimator;
+
+/// Approximate rate of the decimated stream, in samples per second per channel.
+const AUDIO_RATE: f32 = 48_000.0;
+
+/// Largest amount of samples buffered for playback; 100 ms at `AUDIO_RATE`. Older samples are
+/// dropped to keep the latency low.
+const MAX_PENDING: usize = 4800;
+
+/// A chunk of the sample stream, decimated for the audio monitor.
+#[derive(Debug, Clone)]
+pub struct AudioChunk {
+    channel_map: ChannelMap,
+    sample_rate: f32,
+    samples: Vec<i8>,
+}
+
+/// Fans out the sample stream, decimated to about `AUDIO_RATE`, to the audio monitor, while
+/// passing it through unchanged.
+///
+/// If the audio monitor falls behind (or is paused), chunks are dropped instead of stalling
+/// the acquisition.
+pub struct AudioTap<R: Read> {
+    inner: R,
+    decimator: Decimator,
+    channel_map: ChannelMap,
+    sample_rate: f32,
+    audio_send: Option<SyncSender<AudioChunk>>,
+}
+
+impl<R: Read> AudioTap<R> {
+    pub fn new(inner: R, audio_send: Option<SyncSender<AudioChunk>>) -> Self {
+        let mut tap = Self {
+            inner,
+            decimator: Decimator::new(1, 1),
+            channel_map: ChannelMap::new([true; 4]),
+            sample_rate: 0.0,
+            audio_send,
+        };
+        tap.configure(&DeviceParameters::default());
+        tap
+    }
+
+    fn configure(&mut self, params: &DeviceParameters) {
+        let factor = (params.sample_rate() / AUDIO_RATE).round() as usize;
+        self.decimator = Decimator::new(factor, params.stream_channels());
+        self.channel_map = ChannelMap::from_params(params);
+        self.sample_rate = params.sample_rate() / factor as f32;
+    }
+}
+
+impl<R: Read> Read for AudioTap<R> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(data)?;
+        let Some(audio_send) = self.audio_send.as_ref() else { return Ok(length) };
+        let mut samples = Vec::new();
+        self.decimator.process(bytemuck::cast_slice(&data[..length]), &mut samples);
+        if !samples.is_empty() {
+            let chunk = AudioChunk {
+                channel_map: self.channel_map,
+                sample_rate: self.sample_rate,
+                samples,
+            };
+            match audio_send.try_send(chunk) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+                Err(TrySendError::Full(_)) => log::debug!("sampler: dropped audio chunk"),
+            }
+        }
+        Ok(length)
+    }
+}
+
+impl<R: SampleSource> SampleSource for AudioTap<R> {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        self.configure(params);
+        self.inner.reconfigure(params)
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        self.decimator.reset();
+        self.inner.recover()
+    }
+
+    fn pause(&mut self, paused: bool) -> Result<()> {
+        self.inner.pause(paused)
+    }
+}
+
+/// Settings shared between the user interface and the audio output callback.
+#[derive(Debug)]
+struct Controls {
+    channel: AtomicUsize,
+    volume: AtomicU32, // bits of `f32`
+}
+
+/// State of the audio output callback.
+struct Playback {
+    // only locked by the callback, but kept by the monitor in case the output has to be reopened
+    chunk_recv: Arc<Mutex<Receiver<AudioChunk>>>,
+    controls: Arc<Controls>,
+    output_rate: f32,
+    output_channels: usize,
+    // monitored channel, normalized to [-1, 1]
+    pending: VecDeque<f32>,
+    pending_rate: f32,
+    // position between the first and the second pending sample
+    phase: f32,
+}
+
+impl Playback {
+    fn fill(&mut self, data: &mut [f32]) {
+        let channel = self.controls.channel.load(Ordering::Relaxed);
+        let volume = f32::from_bits(self.controls.volume.load(Ordering::Relaxed));
+        let chunk_recv = self.chunk_recv.lock().unwrap();
+        while let Ok(chunk) = chunk_recv.try_recv() {
+            let Some(lane) = chunk.channel_map.lane(channel) else { continue };
+            let stream_channels = chunk.channel_map.stream_channels();
+            self.pending.extend(chunk.samples.iter().skip(lane).step_by(stream_channels)
+                .map(|&code| code as f32 / 128.0));
+            self.pending_rate = chunk.sample_rate;
+        }
+        drop(chunk_recv);
+        let excess = self.pending.len().saturating_sub(MAX_PENDING);
+        self.pending.drain(..excess);
+
+        let step = self.pending_rate / self.output_rate;
+        for frame in data.chunks_mut(self.output_channels) {
+            // play silence on underrun
+            let value = self.pending.front().map_or(0.0, |sample| sample * volume);
+            frame.fill(value.clamp(-1.0, 1.0));
+            self.phase += step;
+            while self.phase >= 1.0 {
+                if self.pending.pop_front().is_none() {
+                    self.phase = 0.0;
+                    break
+                }
+                self.phase -= 1.0;
+            }
+        }
+    }
+}
+
+pub struct AudioMonitor {
+    controls: Arc<Controls>,
+    chunk_recv: Arc<Mutex<Receiver<AudioChunk>>>,
+    stream: Option<cpal::Stream>,
+    playing: bool,
+    error: Option<String>,
+}
+
+impl std::fmt::Debug for AudioMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // `cpal::Stream` does not implement `Debug`
+        f.debug_struct("AudioMonitor")
+            .field("controls", &self.controls)
+            .field("playing", &self.playing)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AudioMonitor {
+    pub fn new(chunk_recv: Receiver<AudioChunk>) -> AudioMonitor {
+        AudioMonitor {
+            controls: Arc::new(Controls {
+                channel: AtomicUsize::new(0),
+                volume: AtomicU32::new(1.0f32.to_bits()),
+            }),
+            chunk_recv: Arc::new(Mutex::new(chunk_recv)),
+            stream: None,
+            playing: false,
+            error: None,
+        }
+    }
+
+    fn build_stream(&self) -> std::result::Result<cpal::Stream, String> {
+        let device = cpal::default_host().default_output_device()
+            .ok_or_else(|| "no audio output device".to_owned())?;
+        let config = device.default_output_config().map_err(|error| error.to_string())?;
+        if config.sample_format() != cpal::SampleFormat::F32 {
+            return Err(format!("unsupported sample format {}", config.sample_format()))
+        }
+        let config = cpal::StreamConfig::from(config);
+        let mut playback = Playback {
+            chunk_recv: self.chunk_recv.clone(),
+            controls: self.controls.clone(),
+            output_rate: config.sample_rate.0 as f32,
+            output_channels: config.channels as usize,
+            pending: VecDeque::new(),
+            pending_rate: AUDIO_RATE,
+            phase: 0.0,
+        };
+        device.build_output_stream(&config,
+            move |data: &mut [f32], _| playback.fill(data),
+            |error| log::error!("audio monitor: {}", error),
+            None
+        ).map_err(|error| error.to_string())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        self.error = None;
+        if self.stream.is_none() {
+            match self.build_stream() {
+                Ok(stream) => self.stream = Some(stream),
+                Err(error) => {
+                    log::error!("audio monitor: cannot open audio output: {}", error);
+                    self.error = Some(error);
+                    return
+                }
+            }
+        }
+        let stream = self.stream.as_ref().unwrap();
+        let result = if playing {
+            stream.play().map_err(|error| error.to_string())
+        } else {
+            stream.pause().map_err(|error| error.to_string())
+        };
+        match result {
+            Ok(()) => self.playing = playing,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Returns the reason why playback could not be started, if it could not.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn channel(&self) -> usize {
+        self.controls.channel.load(Ordering::Relaxed)
+    }
+
+    pub fn set_channel(&self, channel: usize) {
+        self.controls.channel.store(channel, Ordering::Relaxed)
+    }
+
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.controls.volume.load(Ordering::Relaxed))
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.controls.volume.store(volume.to_bits(), Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,72 @@
+//! Central memory budget for the buffers whose size depends on what the user asks for.
+//!
+//! Instead of allocating as much as is requested, each consumer reserves an amount between
+//! the minimum it needs to work at all and the amount it would like to have. Once the budget is
+//! exhausted, consumers degrade gracefully (e.g. keep a shorter history) rather than grow without
+//! bound.
+
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+struct State {
+    limit: usize, // in bytes
+    used: usize,  // in bytes
+}
+
+#[derive(Debug)]
+pub struct MemoryBudget {
+    state: Mutex<State>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Arc<MemoryBudget> {
+        Arc::new(MemoryBudget { state: Mutex::new(State { limit, used: 0 }) })
+    }
+
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Change the limit. Existing reservations are kept; only the new ones are affected.
+    pub fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().limit = limit;
+    }
+
+    pub fn used(&self) -> usize {
+        self.state.lock().unwrap().used
+    }
+
+    /// Reserve `wanted` bytes for `purpose`, or as much as is available if that is less.
+    /// At least `minimum` bytes are always reserved, even if this exceeds the limit, since
+    /// the consumer cannot work with less.
+    pub fn reserve(self: &Arc<Self>, purpose: &str, minimum: usize, wanted: usize) -> Reservation {
+        let mut state = self.state.lock().unwrap();
+        let available = state.limit.saturating_sub(state.used);
+        let size = wanted.min(available).max(minimum);
+        if size < wanted {
+            log::warn!("memory budget: {} limited to {} of {} bytes", purpose, size, wanted);
+        }
+        state.used += size;
+        Reservation { budget: self.clone(), size }
+    }
+}
+
+/// Part of a memory budget, returned to it once dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    size: usize,
+}
+
+impl Reservation {
+    /// Returns how many items of `item_size` bytes fit into the reservation.
+    pub fn count(&self, item_size: usize) -> usize {
+        self.size / item_size
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.state.lock().unwrap().used -= self.size;
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender, SyncSender, TrySendError};
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use thunderscope::{Result, DeviceParameters, ThreadScheduling, SampleRate, ScanVariant};
+use thunderscope::{PeakDetector, ReplaySource};
+use thunderscope::acquire::{self, Acquisition, AcquisitionStep, Parameters};
+use thunderscope::acquire::{SampleSource, SamplerHooks, TriggerEvent, Waveform};
+use thunderscope_dsp::{CicDecimator, Decimator, DriftTracker, Limit};
+
+use crate::budget::{MemoryBudget, Reservation};
+use crate::scenario::{Scenario, ScenarioGenerator};
+use crate::import::{Import, ImportedSamples};
+use crate::session::{Change, ReplayedSamples, SampleRecorder, Session, SessionRecorder};
+use crate::session::SessionSource;
+use crate::settings::DriftTracking;
+use crate::notify::{self, Notifier, NotifyEvent};
+use crate::writer::DiskWriter;
+use crate::profile::{Profiler, Stage};
+use crate::readout::{Readouts, ReadoutTap};
+
+const SAMPLE_COUNT: usize = 1000;
+
+/// Decimation factor of the continuous stream; at the full sample rate,
+/// 1 GS/s / 100_000 = 10 kS/s in total.
+const SLOW_DECIMATION: usize = 100_000;
+
+/// Amount of half-band stages that the continuous stream is decimated by, after it is decimated
+/// by `SLOW_DECIMATION >> SLOW_HALF_BAND_STAGES` in a CIC filter.
+const SLOW_HALF_BAND_STAGES: usize = 3;
+
+/// Amount of samples acquired per displayed sample in the peak detect and high resolution
+/// acquisition modes.
+const ACQUISITION_DECIMATION: usize = 16;
+
+/// How often the baseline drift is measured while the frontend warms up.
+const DRIFT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often a paused sampler checks whether it should resume.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Baseline drift that is worth warning about, as a fraction of the full scale of a channel.
+const DRIFT_WARNING: f32 = 0.01;
+
+/// Amount of FIFO overflows within `OVERFLOW_WINDOW` after which the sample rate is lowered, if
+/// the sampler is allowed to do so.
+const OVERFLOW_LIMIT: usize = 3;
+
+const OVERFLOW_WINDOW: Duration = Duration::from_secs(10);
+
+/// How captures are processed before they are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AcquisitionMode {
+    /// Samples are displayed as acquired.
+    #[default]
+    Sample,
+    /// The minimum and maximum of each group of samples are displayed, so that narrow glitches
+    /// remain visible over a longer time span.
+    PeakDetect,
+    /// The given amount of most recent captures are averaged, which reduces the noise that is
+    /// not correlated with the trigger.
+    Average(usize),
+    /// Each group of samples is averaged, which increases vertical resolution over a longer
+    /// time span.
+    HighRes,
+}
+
+impl AcquisitionMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sample     => "Sample",
+            Self::PeakDetect => "Peak Detect",
+            Self::Average(_) => "Average",
+            Self::HighRes    => "High Res",
+        }
+    }
+
+    /// Amount of samples acquired per displayed sample.
+    fn decimation(self) -> usize {
+        match self {
+            Self::Sample | Self::Average(_) => 1,
+            Self::PeakDetect | Self::HighRes => ACQUISITION_DECIMATION,
+        }
+    }
+}
+
+/// Processes captures for display according to the acquisition mode.
+#[derive(Debug, Default)]
+struct Postprocessor {
+    mode: AcquisitionMode,
+    // most recent captures and their sums, for `AcquisitionMode::Average`
+    history: VecDeque<Vec<i8>>,
+    sums: Vec<i32>,
+    history_budget: Option<Reservation>,
+}
+
+impl Postprocessor {
+    fn set_mode(&mut self, mode: AcquisitionMode, budget: &Arc<MemoryBudget>) {
+        self.mode = mode;
+        self.history_budget = None;
+        if let AcquisitionMode::Average(count) = mode {
+            self.history_budget = Some(budget.reserve("averaging history",
+                SAMPLE_COUNT, SAMPLE_COUNT * count));
+        }
+        self.reset();
+    }
+
+    /// Discard the captures being averaged.
+    fn reset(&mut self) {
+        self.history.clear();
+        self.sums.clear();
+    }
+
+    /// Process `samples` of `channels` interleaved channels, which must start at a frame
+    /// boundary, replacing the contents of `output`.
+    fn process(&mut self, channels: usize, samples: &[i8], output: &mut Vec<i8>) {
+        output.clear();
+        match self.mode {
+            AcquisitionMode::Sample =>
+                output.extend_from_slice(samples),
+            // each group produces two frames
+            AcquisitionMode::PeakDetect =>
+                PeakDetector::new(ACQUISITION_DECIMATION * 2, channels).process(samples, output),
+            AcquisitionMode::HighRes =>
+                Decimator::new(ACQUISITION_DECIMATION, channels).process(samples, output),
+            AcquisitionMode::Average(count) => {
+                if self.sums.len() != samples.len() {
+                    self.reset();
+                    self.sums.resize(samples.len(), 0);
+                }
+                // average fewer captures if there is not enough memory for all of them
+                let count = self.history_budget.as_ref()
+                    .map_or(count, |budget| count.min(budget.count(samples.len().max(1))));
+                if self.history.len() >= count {
+                    let oldest = self.history.pop_front().unwrap();
+                    for (sum, &sample) in self.sums.iter_mut().zip(oldest.iter()) {
+                        *sum -= sample as i32;
+                    }
+                }
+                for (sum, &sample) in self.sums.iter_mut().zip(samples.iter()) {
+                    *sum += sample as i32;
+                }
+                self.history.push_back(samples.to_vec());
+                let count = self.history.len() as i32;
+                // round to nearest
+                output.extend(self.sums.iter()
+                    .map(|&sum| (sum * 2 + count).div_euclid(count * 2) as i8));
+            }
+        }
+    }
+}
+
+/// What to do when a measurement falls outside of its limit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlarmAction {
+    StopAcquisition,
+    /// Save the offending capture as raw codes into the working directory.
+    SaveCapture,
+    /// Run a command with `sh -c`, without waiting for it to finish. The violation is passed in
+    /// the `THUNDERSCOPE_VIOLATION` environment variable.
+    RunCommand(String),
+    /// Ring the terminal bell.
+    Beep,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitRule {
+    pub limit: Limit,
+    pub actions: Vec<AlarmAction>,
+}
+
+/// Acquisition status, reported by the sampler to the user interface.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcquisitionStatus {
+    Running,
+    /// Acquisition has stopped because of an error, and will resume once recovery is requested.
+    Failed(String),
+    /// The baseline of an idle channel has drifted by `drift` volts while the frontend has been
+    /// warming up.
+    Drifting { channel: usize, drift: f32 },
+    /// The host has repeatedly failed to keep up with the sample stream, so the sample rate has
+    /// been lowered to `sample_rate` until acquisition restarts.
+    Degraded { sample_rate: SampleRate },
+}
+
+/// A chunk of the continuous, decimated sample stream.
+#[derive(Debug, Clone)]
+pub struct SlowChunk {
+    /// Amount of channels interleaved in `samples`.
+    pub channels: usize,
+    pub samples: Vec<i8>,
+}
+
+/// Fans out the sample stream into a heavily decimated continuous stream, which is used for
+/// roll mode display, while passing the full-rate data through for triggered captures. The stream
+/// is filtered before it is decimated, so that e.g. a signal at a multiple of the decimated
+/// sample rate does not alias into a spurious slow waveform.
+///
+/// If the consumer of the decimated stream falls behind, chunks are dropped instead of stalling
+/// the acquisition.
+struct DecimatingTap<R: Read> {
+    inner: R,
+    decimator: CicDecimator,
+    slow_send: SyncSender<SlowChunk>,
+}
+
+impl<R: Read> DecimatingTap<R> {
+    fn new(inner: R, slow_send: SyncSender<SlowChunk>) -> Self {
+        Self { inner, decimator: Self::decimator(1), slow_send }
+    }
+
+    fn decimator(channels: usize) -> CicDecimator {
+        CicDecimator::new(SLOW_DECIMATION >> SLOW_HALF_BAND_STAGES, SLOW_HALF_BAND_STAGES, channels)
+    }
+}
+
+impl<R: Read> Read for DecimatingTap<R> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(data)?;
+        let mut samples = Vec::new();
+        self.decimator.process(bytemuck::cast_slice(&data[..length]), &mut samples);
+        if !samples.is_empty() {
+            let chunk = SlowChunk { channels: self.decimator.channels(), samples };
+            match self.slow_send.try_send(chunk) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+                Err(TrySendError::Full(_)) => log::debug!("sampler: dropped slow chunk"),
+            }
+        }
+        Ok(length)
+    }
+}
+
+impl<R: SampleSource> SampleSource for DecimatingTap<R> {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        self.decimator = Self::decimator(params.stream_channels());
+        self.inner.reconfigure(params)
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        self.decimator.reset();
+        self.inner.recover()
+    }
+
+    fn pause(&mut self, paused: bool) -> Result<()> {
+        self.inner.pause(paused)
+    }
+}
+
+#[derive(Debug)]
+pub enum DataSource {
+    Hardware(thunderscope::Device),
+    Simulation(Scenario),
+    Replay(Session),
+    Import(Import),
+    /// A recording of the sample stream (see `thunderscope::StreamRecorder`).
+    Recording(ReplaySource),
+}
+
+/// Ends of the channels through which the user interface controls the sampler, and through which
+/// the sampler reports the status of acquisition; see `control_channels()`.
+pub struct SamplerRemote {
+    pub limits_send: Sender<Vec<LimitRule>>,
+    pub acquisition_send: Sender<AcquisitionMode>,
+    pub status_recv: Receiver<AcquisitionStatus>,
+    pub recover_send: Sender<()>,
+    pub pause_send: Sender<bool>,
+}
+
+/// Ends of the `SamplerRemote` channels held by the sampler.
+pub struct SamplerControl {
+    limits_recv: Receiver<Vec<LimitRule>>,
+    acquisition_recv: Receiver<AcquisitionMode>,
+    status_send: Sender<AcquisitionStatus>,
+    recover_recv: Receiver<()>,
+    pause_recv: Receiver<bool>,
+}
+
+pub fn control_channels() -> (SamplerRemote, SamplerControl) {
+    let (limits_send, limits_recv) = channel();
+    let (acquisition_send, acquisition_recv) = channel();
+    let (status_send, status_recv) = channel();
+    let (recover_send, recover_recv) = channel();
+    let (pause_send, pause_recv) = channel();
+    (SamplerRemote { limits_send, acquisition_send, status_recv, recover_send, pause_send },
+     SamplerControl { limits_recv, acquisition_recv, status_send, recover_recv, pause_recv })
+}
+
+/// Runs acquisition on a thread of its own, with the stages specific to the user interface
+/// (postprocessing, limit checking, drift tracking, pausing, and session recording) interleaved
+/// with the acquisition steps of `thunderscope::acquire::Sampler`.
+pub struct Sampler {
+    engine: acquire::Sampler,
+    frontend: Frontend,
+}
+
+/// State of the stages of acquisition specific to the user interface; see `SamplerHooks`.
+struct Frontend {
+    // Decimated continuous stream, produced in parallel with triggered captures.
+    slow_send: SyncSender<SlowChunk>,
+    #[cfg(feature = "audio")]
+    audio_send: Option<SyncSender<crate::audio::AudioChunk>>,
+    readout_send: Option<SyncSender<Readouts>>,
+    limits_recv: Receiver<Vec<LimitRule>>,
+    acquisition_recv: Receiver<AcquisitionMode>,
+    // Acquisition errors are reported through `status_send`; the sampler then waits for
+    // a request on `recover_recv` before trying to resume.
+    status_send: Sender<AcquisitionStatus>,
+    recover_recv: Receiver<()>,
+    // While paused, the source is not read, and the most recent waveform stays on display.
+    pause_recv: Receiver<bool>,
+    // If a session is being recorded, every change and (for hardware sources) every sample is
+    // written into it; if a session is being replayed, its changes are applied instead of those
+    // requested by the user interface.
+    record_path: Option<PathBuf>,
+    scheduling: ThreadScheduling,
+    drift_tracking: DriftTracking,
+    warm_up: Duration,
+    // times of the recent FIFO overflows, if the sample rate is lowered when they repeat
+    degrade_on_overflow: bool,
+    overflows: VecDeque<Instant>,
+    recorder: Option<SessionRecorder>,
+    replay: Option<VecDeque<(u64, Change)>>,
+    budget: Arc<MemoryBudget>,
+    // every trigger event is offered to each subscriber, without waiting for it
+    trigger_sends: Vec<SyncSender<TriggerEvent>>,
+    // all files are written through `disk_writer`, which never blocks acquisition
+    disk_writer: DiskWriter,
+    profiler: Arc<Profiler>,
+    notifier: Option<Notifier>,
+    postprocessor: Postprocessor,
+    rules: Vec<LimitRule>,
+    alarmed: Vec<bool>,
+    // parameters as requested, and the highest sample rate that the host has kept up with
+    requested_params: Parameters,
+    rate_limit: Option<SampleRate>,
+    drift_tracker: DriftTracker,
+    drifting: bool,
+}
+
+impl Sampler {
+    pub fn new(
+        params_recv: Receiver<Parameters>,
+        waveform_recv: Receiver<Waveform>,
+        waveform_send: Sender<Waveform>,
+        slow_send: SyncSender<SlowChunk>,
+        control: SamplerControl,
+        budget: Arc<MemoryBudget>,
+        disk_writer: DiskWriter,
+    ) -> Sampler {
+        let SamplerControl {
+            limits_recv, acquisition_recv, status_send, recover_recv, pause_recv } = control;
+        let frontend = Frontend {
+            slow_send, limits_recv, acquisition_recv, status_send, recover_recv, pause_recv,
+            record_path: None, scheduling: Default::default(), drift_tracking: DriftTracking::Off,
+            warm_up: Duration::ZERO, degrade_on_overflow: false, overflows: VecDeque::new(),
+            recorder: None, replay: None, budget, trigger_sends: Vec::new(), readout_send: None,
+            disk_writer, profiler: Profiler::new(), notifier: None,
+            postprocessor: Postprocessor::default(), rules: Vec::new(), alarmed: Vec::new(),
+            requested_params: Parameters::default(), rate_limit: None,
+            drift_tracker: DriftTracker::new(Instant::now(), Duration::ZERO, DRIFT_INTERVAL),
+            drifting: false,
+            #[cfg(feature = "audio")]
+            audio_send: None,
+        };
+        Sampler {
+            engine: acquire::Sampler::new(params_recv, waveform_recv, waveform_send, SAMPLE_COUNT),
+            frontend,
+        }
+    }
+
+    /// Send the decimated sample stream to the audio monitor once acquisition starts.
+    #[cfg(feature = "audio")]
+    pub fn monitor_audio(&mut self, audio_send: SyncSender<crate::audio::AudioChunk>) {
+        self.frontend.audio_send = Some(audio_send);
+    }
+
+    /// Send the DC and AC RMS readouts of every enabled channel once acquisition starts.
+    pub fn show_readouts(&mut self, readout_send: SyncSender<Readouts>) {
+        self.frontend.readout_send = Some(readout_send);
+    }
+
+    /// Send an event through `trigger_send` every time the trigger fires. Events are dropped if
+    /// the subscriber falls behind, since the acquisition thread never waits for it.
+    pub fn notify_triggers(&mut self, trigger_send: SyncSender<TriggerEvent>) {
+        self.frontend.trigger_sends.push(trigger_send);
+    }
+
+    /// Schedule the acquisition thread according to `scheduling` once acquisition starts.
+    pub fn schedule_with(&mut self, scheduling: ThreadScheduling) {
+        self.frontend.scheduling = scheduling;
+    }
+
+    /// Track the baseline drift for `warm_up` after acquisition starts, handling it according
+    /// to `drift_tracking`.
+    pub fn track_drift(&mut self, drift_tracking: DriftTracking, warm_up: Duration) {
+        self.frontend.drift_tracking = drift_tracking;
+        self.frontend.warm_up = warm_up;
+    }
+
+    /// Lower the sample rate whenever the FIFO overflows repeatedly, instead of continuing to
+    /// lose samples at the requested one.
+    pub fn degrade_on_overflow(&mut self, degrade: bool) {
+        self.frontend.degrade_on_overflow = degrade;
+    }
+
+    /// Record the session into the directory at `path` once acquisition starts.
+    pub fn record_to(&mut self, path: PathBuf) {
+        self.frontend.record_path = Some(path);
+    }
+
+    /// Record the timings of acquisition stages into `profiler`.
+    pub fn profile_with(&mut self, profiler: Arc<Profiler>) {
+        self.frontend.profiler = profiler;
+    }
+
+    /// Notify the user through `notifier` whenever a limit starts being violated.
+    pub fn notify_with(&mut self, notifier: Notifier) {
+        self.frontend.notifier = Some(notifier);
+    }
+
+    pub fn run(self, source: DataSource) -> std::thread::JoinHandle<Result<()>> {
+        let Sampler { mut engine, mut frontend } = self;
+        std::thread::spawn(move || {
+            if let Err(error) = frontend.scheduling.apply() {
+                log::warn!("sampler: cannot apply {:?}: {}", frontend.scheduling, error);
+            }
+            // benchmark after applying the scheduling, since it affects which core is used
+            log::info!("sampler: using {:?} trigger", ScanVariant::autotune());
+            if let Some(path) = frontend.record_path.take() {
+                let session_source = match &source {
+                    DataSource::Hardware(_) => SessionSource::Samples,
+                    DataSource::Simulation(scenario) => SessionSource::Scenario(scenario.clone()),
+                    DataSource::Replay(session) => session.source.clone(),
+                    DataSource::Import(_) | DataSource::Recording(_) => SessionSource::Samples,
+                };
+                match SessionRecorder::create(&path, &session_source, &frontend.disk_writer) {
+                    Ok(recorder) => {
+                        log::info!("sampler: recording session to {}", path.display());
+                        frontend.recorder = Some(recorder);
+                    }
+                    Err(error) =>
+                        log::error!("sampler: cannot record session to {}: {}",
+                            path.display(), error),
+                }
+            }
+            match source {
+                DataSource::Simulation(scenario) => {
+                    frontend.acquire_from(&mut engine, ScenarioGenerator::new(scenario),
+                        |_params| Ok(()))?
+                }
+                DataSource::Replay(session) => {
+                    log::info!("sampler: replaying session from {}", session.path.display());
+                    frontend.replay = Some(session.changes);
+                    match session.source {
+                        SessionSource::Scenario(scenario) =>
+                            frontend.acquire_from(&mut engine, ScenarioGenerator::new(scenario),
+                                |_params| Ok(()))?,
+                        SessionSource::Samples => {
+                            let samples = ReplayedSamples::open(&session.path, session.start)?;
+                            frontend.acquire_from(&mut engine, samples, |_params| Ok(()))?
+                        }
+                    }
+                }
+                DataSource::Import(import) => {
+                    log::info!("sampler: playing back {}", import.path.display());
+                    frontend.acquire_from(&mut engine, ImportedSamples::new(import),
+                        |_params| Ok(()))?
+                }
+                DataSource::Recording(source) => {
+                    log::info!("sampler: playing back recording from sample {}",
+                        source.position());
+                    frontend.acquire_from(&mut engine, source.with_throttling(true),
+                        |_params| Ok(()))?
+                }
+                DataSource::Hardware(instrument) => {
+                    // the guard shuts the device down even if acquisition panics, or after ^C
+                    let instrument = match instrument.guard() {
+                        Ok(instrument) => instrument,
+                        Err(error) => {
+                            let _ = frontend.status_send.send(
+                                AcquisitionStatus::Failed(error.to_string()));
+                            return Err(error)
+                        }
+                    };
+                    let control = instrument.control();
+                    let result = frontend.acquire_from(&mut engine, instrument.stream_data(),
+                        |params| control.configure(params));
+                    let shutdown = instrument.shutdown();
+                    result.and(shutdown)?
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Frontend {
+    /// Acquire from `reader` with `engine`, also feeding the continuous stream, the readouts,
+    /// the audio monitor, and the session recorder, until acquisition stops.
+    fn acquire_from<F>(&mut self, engine: &mut acquire::Sampler, reader: impl SampleSource,
+                       configure: F) -> Result<()>
+            where F: FnMut(&DeviceParameters) -> Result<()> {
+        self.drift_tracker = DriftTracker::new(Instant::now(), self.warm_up, DRIFT_INTERVAL);
+        let samples_output = self.recorder.as_mut().and_then(|recorder| recorder.take_samples());
+        let reader = DecimatingTap::new(
+            SampleRecorder::new(reader, samples_output), self.slow_send.clone());
+        let reader = ReadoutTap::new(reader, self.readout_send.clone());
+        #[cfg(feature = "audio")]
+        let reader = crate::audio::AudioTap::new(reader, self.audio_send.clone());
+        engine.run_with_hooks(reader, configure, self)
+    }
+
+    /// Checks the capture in `waveform` against the limit rules, and performs the actions of
+    /// the rules whose limits have just started being violated. Returns `true` if acquisition
+    /// should stop.
+    ///
+    /// Actions are performed only on the transition into violation (tracked in `alarmed`) so
+    /// that a persistent violation does not e.g. spawn a command for every capture.
+    fn check_limits(&mut self, waveform: &Waveform) -> bool {
+        let Some(data) = waveform.capture_data() else { return false };
+        let Some(capture) = waveform.capture() else { return false };
+        let mut stop = false;
+        for (rule, alarmed) in self.rules.iter().zip(self.alarmed.iter_mut()) {
+            let Some(samples) = capture.channel(rule.limit.channel) else { continue };
+            let violation = rule.limit.check(capture.params(), samples);
+            let was_alarmed = std::mem::replace(alarmed, violation.is_some());
+            let Some(violation) = violation else { continue };
+            if was_alarmed { continue }
+            log::warn!("sampler: limit violated: {}", violation);
+            if let Some(notifier) = self.notifier.as_ref() {
+                notifier.notify(NotifyEvent::LimitAlarm, &violation.to_string());
+            }
+            for action in rule.actions.iter() {
+                match action {
+                    AlarmAction::StopAcquisition => stop = true,
+                    AlarmAction::SaveCapture => {
+                        let since_epoch = std::time::SystemTime::now()
+                            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                            .unwrap_or_default();
+                        let filename = format!("alarm-{}.data", since_epoch.as_millis());
+                        let data = bytemuck::cast_slice(data).to_vec();
+                        match self.disk_writer.save(&filename, data) {
+                            Ok(()) => log::info!("sampler: saving capture to {}", filename),
+                            Err(error) => log::error!("sampler: failed to save capture: {}", error),
+                        }
+                    }
+                    AlarmAction::RunCommand(command) => {
+                        let result = notify::spawn_detached(
+                            std::process::Command::new("sh")
+                                .arg("-c")
+                                .arg(command)
+                                .env("THUNDERSCOPE_VIOLATION", violation.to_string()));
+                        if let Err(error) = result {
+                            log::error!("sampler: failed to run {:?}: {}", command, error);
+                        }
+                    }
+                    AlarmAction::Beep => {
+                        use std::io::Write;
+                        let _ = std::io::stderr().write_all(b"\x07");
+                    }
+                }
+            }
+        }
+        stop
+    }
+
+    /// Returns the changes requested since the last call: by the session being replayed (those
+    /// that were applied at or before stream `position`) if there is one, or by the user interface
+    /// (which has sent `received` parameters, if any) otherwise. If a session is being recorded,
+    /// the changes are recorded at `position`.
+    fn poll_changes(&mut self, position: u64, received: Option<Parameters>) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if let Some(replay) = self.replay.as_mut() {
+            while replay.front().is_some_and(|&(at, _)| at <= position) {
+                changes.push(replay.pop_front().unwrap().1);
+            }
+        } else {
+            changes.extend(received.map(Change::Parameters));
+            changes.extend(self.acquisition_recv.try_recv().ok().map(Change::AcquisitionMode));
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            for change in changes.iter() {
+                if let Err(error) = recorder.record(position, change) {
+                    log::error!("sampler: cannot record session, stopping: {}", error);
+                    self.recorder = None;
+                    break
+                }
+            }
+        }
+        changes
+    }
+
+    /// Measures the baseline drift in the capture in `waveform` if it is due, and either
+    /// compensates it in `params` or warns about it. Returns `true` if `params` have changed.
+    fn track_drift_in(&mut self, waveform: &Waveform, params: &mut Parameters) -> bool {
+        let tracker = &mut self.drift_tracker;
+        let now = Instant::now();
+        if self.drift_tracking == DriftTracking::Off || !tracker.is_due(now) {
+            return false
+        }
+        let Some(capture) = waveform.capture() else { return false };
+        tracker.observe(&capture, now);
+        match self.drift_tracking {
+            DriftTracking::Off => false,
+            DriftTracking::Warn => {
+                let worst = (0..4)
+                    .filter_map(|channel| Some((channel, tracker.drift(&params.device, channel)?)))
+                    .filter(|&(channel, drift)|
+                        drift.abs() > DRIFT_WARNING * params.device.full_scale(channel))
+                    .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()));
+                if let Some((channel, drift)) = worst {
+                    log::warn!("sampler: CH{} baseline drifted by {:.4} V", channel + 1, drift);
+                    let _ = self.status_send.send(AcquisitionStatus::Drifting { channel, drift });
+                    self.drifting = true;
+                } else if std::mem::take(&mut self.drifting) {
+                    let _ = self.status_send.send(AcquisitionStatus::Running);
+                }
+                false
+            }
+            DriftTracking::Compensate => {
+                tracker.compensate(&mut params.device);
+                log::debug!("sampler: compensating drift of {:?}", (0..4)
+                    .map(|channel| tracker.drift(&params.device, channel))
+                    .collect::<Vec<_>>());
+                true
+            }
+        }
+    }
+
+    /// Returns a sample rate lower than the one in `params` if the FIFO has overflowed too often
+    /// recently and there is one, and reports it to the user interface.
+    fn degrade_sample_rate(&mut self, params: &Parameters) -> Option<SampleRate> {
+        if !self.degrade_on_overflow || self.overflows.len() < OVERFLOW_LIMIT {
+            return None
+        }
+        self.overflows.clear();
+        let sample_rate = params.device.sample_rate();
+        let Some(lower) = SampleRate::ALL.into_iter()
+                .find(|rate| rate.samples_per_second() < sample_rate) else {
+            log::warn!("sampler: overflowing at the lowest sample rate");
+            return None
+        };
+        log::warn!("sampler: overflowing repeatedly; lowering sample rate to {:?}", lower);
+        let _ = self.status_send.send(AcquisitionStatus::Degraded { sample_rate: lower });
+        Some(lower)
+    }
+
+    fn send_trigger_event(&mut self, event: TriggerEvent) {
+        self.trigger_sends.retain(|trigger_send| match trigger_send.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::debug!("sampler: trigger subscriber is behind; dropping {:?}", event);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+impl SamplerHooks for Frontend {
+    fn start<R: SampleSource>(&mut self, acquisition: &mut Acquisition<R>) {
+        let profiler = self.profiler.clone();
+        acquisition.profile_with(move |step, duration| if profiler.is_enabled() {
+            profiler.record(match step {
+                AcquisitionStep::Read => Stage::Read,
+                AcquisitionStep::Trigger => Stage::Trigger,
+            }, duration)
+        });
+    }
+
+    fn switch_params<R: SampleSource>(&mut self, acquisition: &Acquisition<R>,
+                                      received: Option<Parameters>) -> Option<Parameters> {
+        // switch capture parameters and acquisition mode, if requested
+        let mut pending_params = None;
+        for change in self.poll_changes(acquisition.position(), received) {
+            match change {
+                Change::Parameters(new_params) =>
+                    pending_params = Some(new_params),
+                Change::AcquisitionMode(new_mode) => {
+                    log::info!("sampler: switching acquisition mode to {:?}", new_mode);
+                    self.postprocessor.set_mode(new_mode, &self.budget);
+                }
+            }
+        }
+        // lower the sample rate, if the host cannot keep up with it
+        if let Some(lower) = self.degrade_sample_rate(acquisition.params()) {
+            self.rate_limit = Some(lower);
+            pending_params.get_or_insert(self.requested_params);
+        }
+        let mut params = pending_params?;
+        self.requested_params = params;
+        if let Some(rate_limit) = self.rate_limit {
+            let max_sample_rate = &mut params.device.max_sample_rate;
+            if max_sample_rate.samples_per_second() > rate_limit.samples_per_second() {
+                *max_sample_rate = rate_limit;
+            }
+        }
+        if self.drift_tracking == DriftTracking::Compensate {
+            self.drift_tracker.compensate(&mut params.device);
+        }
+        self.postprocessor.reset();
+        Some(params)
+    }
+
+    fn poll_pause(&mut self, paused: bool) -> Option<bool> {
+        // the configuration of the source is retained while it is paused
+        if paused {
+            self.pause_recv.recv_timeout(PAUSE_POLL_INTERVAL).ok()
+        } else {
+            self.pause_recv.try_recv().ok()
+        }
+    }
+
+    fn capture_length(&self, capture_length: usize) -> usize {
+        capture_length * self.postprocessor.mode.decimation()
+    }
+
+    fn on_trigger(&mut self, event: &TriggerEvent) {
+        self.send_trigger_event(*event)
+    }
+
+    fn on_acquired<R: SampleSource>(&mut self, acquisition: &mut Acquisition<R>,
+                                    waveform: &mut Waveform,
+                                    trigger_event: Option<TriggerEvent>) {
+        // if a session is being recorded, index the capture, so that it can be replayed from
+        let recorder = self.recorder.as_mut().filter(|recorder| recorder.is_indexing());
+        if let (Some(recorder), Some(capture)) = (recorder, waveform.capture()) {
+            let range = capture.channels().map(|samples| samples.and_then(|samples|
+                Some((*samples.iter().min()?, *samples.iter().max()?))));
+            recorder.index(waveform.capture_position(),
+                waveform.start().map(|start| start.time), trigger_event, range);
+        }
+        // while the frontend warms up, track the baseline drift of idle channels
+        let mut params = *acquisition.params();
+        if self.track_drift_in(waveform, &mut params) {
+            acquisition.adjust_params(waveform, params);
+        }
+        // switch limits, if requested
+        if let Ok(new_rules) = self.limits_recv.try_recv() {
+            log::info!("sampler: switching limits to {:#?}", new_rules);
+            self.alarmed = vec![false; new_rules.len()];
+            self.rules = new_rules;
+        }
+        // if there is a capture, check it against limits
+        if waveform.is_captured() && self.check_limits(waveform) {
+            log::info!("sampler: stopping acquisition on limit violation");
+            acquisition.stop();
+        }
+    }
+
+    fn process_display(&mut self, waveform: &mut Waveform) {
+        let postprocessor = &mut self.postprocessor;
+        self.profiler.time(Stage::Deinterleave, || waveform.process_display(
+            |channels, samples, display| postprocessor.process(channels, samples, display)));
+    }
+
+    /// Reports the error to the user interface and waits until recovery is requested and
+    /// succeeds, unless the source has recovered by itself.
+    fn recover<R: SampleSource>(&mut self, acquisition: &mut Acquisition<R>,
+                                mut error: thunderscope::Error) -> ControlFlow<Result<()>> {
+        if let thunderscope::Error::Overflow { .. } = error {
+            let now = Instant::now();
+            self.overflows.retain(|&overflowed_at| now - overflowed_at < OVERFLOW_WINDOW);
+            self.overflows.push_back(now);
+            return ControlFlow::Continue(())
+        }
+        if let thunderscope::Error::Interrupted = error {
+            // ^C; the device is shut down once the sampler stops, and the application quits
+            log::info!("sampler: acquisition interrupted");
+            return ControlFlow::Break(Err(error))
+        }
+        loop {
+            log::error!("sampler: acquisition failed: {}", error);
+            let _ = self.status_send.send(AcquisitionStatus::Failed(error.to_string()));
+            if self.recover_recv.recv().is_err() {
+                log::debug!("sampler: done while waiting for recovery");
+                return ControlFlow::Break(Ok(()))
+            }
+            match acquisition.recover() {
+                Ok(()) => {
+                    log::info!("sampler: acquisition recovered");
+                    let _ = self.status_send.send(AcquisitionStatus::Running);
+                    return ControlFlow::Continue(())
+                }
+                Err(recover_error) => error = recover_error,
+            }
+        }
+    }
+}
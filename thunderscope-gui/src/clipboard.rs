@@ -0,0 +1,70 @@
+//! Copying of readouts and of waveform data to the system clipboard, as CSV text with values in
+//! volts, so that they can be pasted into a lab notebook or a chat without exporting a file.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use thunderscope::Capture;
+
+use crate::readout::Readouts;
+
+/// Clipboard backend for ImGui that uses the system clipboard.
+pub struct SystemClipboard(arboard::Clipboard);
+
+impl SystemClipboard {
+    /// Returns `None` if the system clipboard is not available, e.g. without a running
+    /// clipboard manager.
+    pub fn new() -> Option<SystemClipboard> {
+        match arboard::Clipboard::new() {
+            Ok(clipboard) => Some(SystemClipboard(clipboard)),
+            Err(error) => {
+                log::warn!("cannot access the clipboard: {}", error);
+                None
+            }
+        }
+    }
+}
+
+impl imgui::ClipboardBackend for SystemClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.0.get_text().ok()
+    }
+
+    fn set(&mut self, value: &str) {
+        if let Err(error) = self.0.set_text(value) {
+            log::error!("failed to copy to the clipboard: {}", error);
+        }
+    }
+}
+
+/// Formats the readouts of the enabled channels as CSV.
+pub fn readouts_csv(readouts: &Readouts) -> String {
+    let mut csv = String::from("channel,dc (V),ac rms (V)\n");
+    for (channel_index, readout) in readouts.iter().enumerate() {
+        let Some(readout) = readout else { continue };
+        writeln!(csv, "CH{},{},{}", channel_index + 1, readout.dc, readout.ac_rms).unwrap();
+    }
+    csv
+}
+
+/// Formats the samples within `range` of each enabled channel of `capture` as CSV, with
+/// the time since the start of the capture in the first column.
+pub fn capture_csv(capture: &Capture, range: Range<usize>) -> String {
+    let params = capture.params();
+    let channels = capture.channels();
+    let mut csv = String::from("time (s)");
+    for (channel_index, _) in channels.iter().enumerate().filter(|(_, data)| data.is_some()) {
+        write!(csv, ",CH{} (V)", channel_index + 1).unwrap();
+    }
+    csv.push('\n');
+    let sample_period = 1.0 / params.corrected_sample_rate() as f64;
+    for index in range {
+        write!(csv, "{:.12}", index as f64 * sample_period).unwrap();
+        for (channel_index, data) in channels.iter().enumerate() {
+            let Some(data) = data else { continue };
+            write!(csv, ",{}", params.code_to_volts(channel_index, data[index])).unwrap();
+        }
+        csv.push('\n');
+    }
+    csv
+}
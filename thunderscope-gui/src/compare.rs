@@ -0,0 +1,141 @@
+//! Comparison of captures against a stored reference waveform, e.g. of boards in production
+//! against a known good unit.
+//!
+//! Triggered captures start at the trigger point, so a capture and a reference acquired with
+//! the same settings are aligned already, and are subtracted sample by sample. The difference
+//! (the residual) is then checked against a tolerance band.
+
+use std::time::SystemTime;
+
+use thunderscope::acquire::Waveform;
+
+use crate::writer::DiskWriter;
+
+#[derive(Debug)]
+struct Reference {
+    samples: Vec<i8>,
+    channels: usize,
+}
+
+/// Outcome of comparing the most recent capture against the reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    NoReference,
+    /// The capture has a different channel configuration than the reference.
+    Incompatible,
+    Pass,
+    /// The given amount of samples is outside of the tolerance band.
+    Fail(usize),
+}
+
+#[derive(Debug)]
+pub struct Comparison {
+    reference: Option<Reference>,
+    /// Largest difference from the reference that is within tolerance, in ADC codes.
+    pub tolerance: u32,
+    live: Vec<i8>,
+    live_channels: usize,
+    live_triggered: bool,
+    // live minus reference, over the samples both of them have; empty if incompatible
+    residual: Vec<i16>,
+}
+
+impl Default for Comparison {
+    fn default() -> Self {
+        Self {
+            reference: None,
+            tolerance: 4,
+            live: Vec::new(),
+            live_channels: 0,
+            live_triggered: false,
+            residual: Vec::new(),
+        }
+    }
+}
+
+impl Comparison {
+    /// Compare `waveform` against the reference, and keep it to be stored as the next reference.
+    pub fn update(&mut self, waveform: &Waveform) {
+        let Some(samples) = waveform.display_data() else { return };
+        self.live.clear();
+        self.live.extend_from_slice(samples);
+        self.live_channels = waveform.device_params().stream_channels();
+        self.live_triggered = waveform.trigger().is_some();
+        self.compare();
+    }
+
+    fn compare(&mut self) {
+        self.residual.clear();
+        let Some(reference) = self.reference.as_ref() else { return };
+        if reference.channels != self.live_channels { return }
+        self.residual.extend(self.live.iter().zip(reference.samples.iter())
+            .map(|(&live, &reference)| live as i16 - reference as i16));
+    }
+
+    pub fn has_live(&self) -> bool {
+        !self.live.is_empty()
+    }
+
+    /// Returns `true` if the most recent capture was not triggered, and so may not be aligned
+    /// to the reference.
+    pub fn is_unaligned(&self) -> bool {
+        self.has_live() && !self.live_triggered
+    }
+
+    pub fn has_reference(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    /// Store the most recent capture as the reference.
+    pub fn store_reference(&mut self) {
+        self.reference = Some(Reference {
+            samples: self.live.clone(),
+            channels: self.live_channels,
+        });
+        self.compare();
+    }
+
+    pub fn clear_reference(&mut self) {
+        self.reference = None;
+        self.residual.clear();
+    }
+
+    pub fn residual(&self) -> &[i16] {
+        &self.residual
+    }
+
+    pub fn verdict(&self) -> Verdict {
+        if self.reference.is_none() {
+            Verdict::NoReference
+        } else if self.residual.is_empty() {
+            Verdict::Incompatible
+        } else {
+            let tolerance = self.tolerance as i16;
+            match self.residual.iter().filter(|&&value| value.abs() > tolerance).count() {
+                0 => Verdict::Pass,
+                count => Verdict::Fail(count),
+            }
+        }
+    }
+
+    /// Export the reference, the most recent capture, and the residual into the working
+    /// directory; returns the name of the file.
+    pub fn export_csv(&self, writer: &DiskWriter) -> std::io::Result<String> {
+        use std::io::Write;
+
+        let Some(reference) = self.reference.as_ref() else {
+            return Err(std::io::Error::other("no reference is stored"))
+        };
+        let since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default().as_secs();
+        let filename = format!("residual-{}.csv", since_epoch);
+        let mut data = Vec::new();
+        writeln!(data, "index,reference,capture,residual,within_tolerance")?;
+        for (index, &residual) in self.residual.iter().enumerate() {
+            writeln!(data, "{},{},{},{},{}", index, reference.samples[index], self.live[index],
+                residual, residual.unsigned_abs() as u32 <= self.tolerance)?;
+        }
+        writer.save(&filename, data)?;
+        Ok(filename)
+    }
+}
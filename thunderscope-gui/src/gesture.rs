@@ -0,0 +1,101 @@
+//! Recognition of touch gestures, for use on touchscreen laptops and tablets.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use winit::event::TouchPhase;
+
+/// How long a finger must be held in place to be recognized as a long press.
+const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+
+/// How far (in logical pixels) a finger may move while still being held in place.
+const LONG_PRESS_SLOP: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// Two fingers moved: scale the horizontal axis by `zoom` around `center`, and move it
+    /// by `pan`. Positions and distances are in logical pixels.
+    Transform { center: f32, zoom: f32, pan: f32 },
+    /// A single finger was held in place at `position`.
+    LongPress { position: [f32; 2] },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TouchPoint {
+    origin: [f32; 2],
+    position: [f32; 2],
+    started_at: Instant,
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+#[derive(Debug, Default)]
+pub struct GestureRecognizer {
+    touches: BTreeMap<u64, TouchPoint>,
+    // whether the finger that is down may still turn out to be a long press
+    long_press_pending: bool,
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns `true` if more than one finger is down, in which case the touches are a gesture
+    /// rather than a click.
+    pub fn is_multi_touch(&self) -> bool {
+        self.touches.len() > 1
+    }
+
+    /// Process a touch event for finger `id` at `position`, in logical pixels.
+    pub fn touch(&mut self, id: u64, phase: TouchPhase, position: [f32; 2], now: Instant)
+            -> Option<Gesture> {
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, TouchPoint { origin: position, position, started_at: now });
+                self.long_press_pending = self.touches.len() == 1;
+                None
+            }
+            TouchPhase::Moved => {
+                let before = self.two_finger_span();
+                let touch = self.touches.get_mut(&id)?;
+                touch.position = position;
+                if distance(touch.origin, touch.position) > LONG_PRESS_SLOP {
+                    self.long_press_pending = false;
+                }
+                let ((center_before, span_before), (center_after, span_after)) =
+                    (before?, self.two_finger_span()?);
+                if span_before < 1.0 { return None }
+                Some(Gesture::Transform {
+                    center: center_after[0],
+                    zoom: span_after / span_before,
+                    pan: center_after[0] - center_before[0],
+                })
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                self.long_press_pending = false;
+                None
+            }
+        }
+    }
+
+    /// Returns the center of and the distance between the fingers, if exactly two are down.
+    fn two_finger_span(&self) -> Option<([f32; 2], f32)> {
+        if self.touches.len() != 2 { return None }
+        let mut touches = self.touches.values();
+        let (a, b) = (touches.next()?.position, touches.next()?.position);
+        Some(([(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0], distance(a, b)))
+    }
+
+    /// Check for gestures that are recognized by the passage of time rather than by movement.
+    pub fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        if !self.long_press_pending { return None }
+        let touch = self.touches.values().next()?;
+        if now.duration_since(touch.started_at) < LONG_PRESS_DURATION { return None }
+        self.long_press_pending = false;
+        Some(Gesture::LongPress { position: touch.position })
+    }
+}
@@ -0,0 +1,89 @@
+//! History of the most recent captures, shown as a strip of thumbnails, so that a capture that
+//! has already been replaced on the screen (e.g. one with a rare glitch) can be found and shown
+//! in the main view again.
+//!
+//! Only the data processed for display is kept for each capture. Its thumbnail keeps the minimum
+//! and the maximum of each column, so that glitches narrower than a column remain visible.
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use thunderscope::PeakDetector;
+
+/// Amount of captures that are kept.
+const HISTORY_LENGTH: usize = 32;
+
+/// Width of a thumbnail, in columns.
+pub const THUMBNAIL_COLUMNS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub time: SystemTime,
+    /// Samples processed for display, and the delay of the trace (see `Waveform::display_delay`).
+    pub display: Vec<i8>,
+    pub delay: f32,
+    /// Minimum and maximum of the samples within each column of the thumbnail.
+    pub thumbnail: Vec<[i8; 2]>,
+    /// Whether the capture matched the reference, if it was compared to one.
+    pub passed: Option<bool>,
+    /// Value and unit of the trend measurement, if it could be measured.
+    pub value: Option<(f32, &'static str)>,
+}
+
+#[derive(Debug, Default)]
+pub struct CaptureHistory {
+    entries: VecDeque<HistoryEntry>,
+    // index of the entry shown in the main view instead of the most recent capture
+    selected: Option<usize>,
+}
+
+impl CaptureHistory {
+    /// Add a capture to the history, discarding the oldest one if the history is full.
+    ///
+    /// While an entry is selected, captures are not added, so that the strip of thumbnails does
+    /// not move under the cursor.
+    pub fn push(&mut self, display: &[i8], delay: f32, passed: Option<bool>,
+                value: Option<(f32, &'static str)>) {
+        if self.selected.is_some() || display.is_empty() {
+            return
+        }
+        let mut peaks = Vec::new();
+        PeakDetector::new(display.len().div_ceil(THUMBNAIL_COLUMNS), 1)
+            .process(display, &mut peaks);
+        let thumbnail = peaks.chunks_exact(2).map(|pair| [pair[0], pair[1]]).collect();
+        if self.entries.len() >= HISTORY_LENGTH {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            time: SystemTime::now(),
+            display: display.to_vec(),
+            delay,
+            thumbnail,
+            passed,
+            value,
+        });
+    }
+
+    /// Returns the captures in the history, oldest first.
+    pub fn entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.entries
+    }
+
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Returns the entry to be shown in the main view, or `None` to show the live capture.
+    pub fn selected(&self) -> Option<&HistoryEntry> {
+        self.entries.get(self.selected?)
+    }
+
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index.filter(|&index| index < self.entries.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.selected = None;
+    }
+}
@@ -0,0 +1,279 @@
+//! Translation of user-visible strings.
+//!
+//! Strings are translated gettext-style: the English text is the message identifier, and it is
+//! displayed as-is if there is no translation for the current language. Arguments are substituted
+//! into `{}` placeholders in order, so that translations may reorder the surrounding text.
+//!
+//! Messages produced by the library (e.g. event log entries and errors) are not translated; they
+//! are primarily meant to be included in bug reports.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    /// Name of the language in that language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German  => "Deutsch",
+        }
+    }
+
+    /// Determine the language from the POSIX locale environment variables.
+    pub fn from_environment() -> Language {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        match locale.split(['_', '.', '@']).next() {
+            Some("de") => Language::German,
+            _ => Language::English,
+        }
+    }
+
+    fn translate(self, message: &'static str) -> Option<&'static str> {
+        match self {
+            Language::English => None,
+            Language::German  => german(message),
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+
+pub fn set_language(language: Language) {
+    log::info!("using {:?} language", language);
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    Language::ALL[LANGUAGE.load(Ordering::Relaxed) as usize]
+}
+
+/// Translate `message` into the current language.
+pub fn tr(message: &'static str) -> &'static str {
+    language().translate(message).unwrap_or(message)
+}
+
+/// Translate `message` into the current language, and substitute `args` into its placeholders.
+pub fn tr_format(message: &'static str, args: &[&dyn Display]) -> String {
+    let mut pieces = tr(message).split("{}");
+    let mut result = pieces.next().unwrap_or_default().to_owned();
+    for (index, piece) in pieces.enumerate() {
+        if let Some(arg) = args.get(index) {
+            result += &arg.to_string();
+        }
+        result += piece;
+    }
+    result
+}
+
+fn german(message: &'static str) -> Option<&'static str> {
+    Some(match message {
+        // controls
+        "STOP" => "STOPP",
+        "RUN" => "START",
+        "↑ Rising" => "↑ Steigend",
+        "↓ Falling" => "↓ Fallend",
+        "⇅ Both" => "⇅ Beide",
+        "Level" => "Pegel",
+        "Trigger settings" => "Triggereinstellungen",
+        "Trigger level: {}" => "Triggerpegel: {}",
+        "Press Space to edit." => "Zum Bearbeiten die Leertaste drücken.",
+        "Acquisition" => "Erfassung",
+        "Acquisition mode" => "Erfassungsmodus",
+        "Sample" => "Abtastung",
+        "Peak Detect" => "Spitzenwert",
+        "Average" => "Mittelung",
+        "High Res" => "Hochauflösend",
+        "Avg ×{}" => "Mittel ×{}",
+        "Average of" => "Mittelung über",
+        "UNCAL" => "UNKAL",
+        "No calibration data is available for this channel; \
+         absolute voltages may be inaccurate by several percent." =>
+            "Für diesen Kanal sind keine Kalibrierdaten vorhanden; \
+             absolute Spannungen können um einige Prozent abweichen.",
+        // event log
+        "Event Log" => "Ereignisprotokoll",
+        // roll and trend views
+        "Roll" => "Rollmodus",
+        "Export CSV" => "CSV exportieren",
+        "Copy CSV" => "CSV kopieren",
+        "Copy visible data as CSV" => "Sichtbare Daten als CSV kopieren",
+        "no data" => "keine Daten",
+        // readouts
+        "Readouts" => "Messwerte",
+        "CH{}  DC {}  AC RMS {}" => "CH{}  DC {}  AC-Effektivwert {}",
+        // waveform area
+        "Zoom in" => "Vergrößern",
+        "Zoom out" => "Verkleinern",
+        "Reset zoom" => "Zoom zurücksetzen",
+        // measurements
+        "Mean" => "Mittelwert",
+        "Frequency" => "Frequenz",
+        "Duty" => "Tastgrad",
+        "Edges" => "Flanken",
+        // limits
+        "Limits" => "Grenzwerte",
+        "Enabled" => "Aktiviert",
+        "Measurement" => "Messung",
+        "Channel" => "Kanal",
+        "On violation:" => "Bei Überschreitung:",
+        "Stop acquisition" => "Erfassung stoppen",
+        "Resume acquisition" => "Erfassung fortsetzen",
+        "Save capture" => "Aufzeichnung speichern",
+        "Beep" => "Signalton",
+        "Command" => "Befehl",
+        "Apply" => "Übernehmen",
+        // comparison
+        "Compare" => "Vergleich",
+        "Store reference" => "Referenz speichern",
+        "Clear reference" => "Referenz löschen",
+        "Tolerance, codes" => "Toleranz, Codes",
+        "No reference stored." => "Keine Referenz gespeichert.",
+        "The channel configuration differs from the reference." =>
+            "Die Kanalkonfiguration weicht von der Referenz ab.",
+        "Pass" => "Bestanden",
+        "Fail" => "Fehler",
+        "History" => "Verlauf",
+        "Show live capture" => "Aktuelle Erfassung zeigen",
+        "Clear" => "Leeren",
+        "Showing capture {} of {}" => "Erfassung {} von {} wird gezeigt",
+        "Click a capture to show it in the main view." =>
+            "Eine Erfassung anklicken, um sie in der Hauptansicht zu zeigen.",
+        "Captured {} s ago" => "Vor {} s erfasst",
+        "Fail: {} samples outside of tolerance" => "Fehler: {} Abtastwerte außerhalb der Toleranz",
+        "(capture is not triggered, and may not be aligned)" =>
+            "(Aufzeichnung nicht getriggert, möglicherweise nicht ausgerichtet)",
+        // audio monitor
+        "Audio Monitor" => "Audiomonitor",
+        "Play" => "Wiedergabe",
+        "Volume" => "Lautstärke",
+        "Cannot play audio: {}" => "Audiowiedergabe nicht möglich: {}",
+        // profiler
+        "Read" => "Lesen",
+        "De-interleave" => "Entschachteln",
+        "Upload" => "Hochladen",
+        "Interface" => "Oberfläche",
+        "Swap" => "Anzeigen",
+        "mean, ms" => "Mittel, ms",
+        "max, ms" => "Max., ms",
+        "per s" => "pro s",
+        "Log" => "Protokollieren",
+        // preferences
+        "Preferences" => "Einstellungen",
+        "Channel colors" => "Kanalfarben",
+        "Classic" => "Klassisch",
+        "Okabe-Ito (color-blind safe)" => "Okabe-Ito (für Farbenblinde geeignet)",
+        "Tol Bright (color-blind safe)" => "Tol Bright (für Farbenblinde geeignet)",
+        "Acquisition thread (takes effect after restart)" =>
+            "Erfassungsthread (wirksam nach Neustart)",
+        "Real-time priority" => "Echtzeitpriorität",
+        "Priority" => "Priorität",
+        "CPU cores" => "CPU-Kerne",
+        "Comma-separated; empty to allow any core." =>
+            "Durch Kommas getrennt; leer für beliebige Kerne.",
+        "Verify captures" => "Aufzeichnungen prüfen",
+        "Discard captures corrupted in memory after acquisition." =>
+            "Nach der Erfassung im Speicher beschädigte Aufzeichnungen verwerfen.",
+        "Lower sample rate on overflow" => "Abtastrate bei Überlauf senken",
+        "Keep capturing at a lower sample rate if the computer cannot keep up." =>
+            "Mit niedrigerer Abtastrate weiter erfassen, wenn der Rechner nicht mithält.",
+        "Memory budget, MiB" => "Speicherbudget, MiB",
+        "In use: {} of {} MiB" => "Belegt: {} von {} MiB",
+        "Warm-up drift" => "Aufwärmdrift",
+        "Off" => "Aus",
+        "Warn" => "Warnen",
+        "Compensate" => "Ausgleichen",
+        "Track the baseline of idle channels while warming up." =>
+            "Grundlinie ruhender Kanäle während des Aufwärmens verfolgen.",
+        "Warm-up, minutes" => "Aufwärmzeit, Minuten",
+        "Notify when" => "Benachrichtigen, wenn",
+        "A single capture is triggered" => "eine Einzelaufzeichnung ausgelöst wird",
+        "A capture differs from the reference" => "eine Aufzeichnung von der Referenz abweicht",
+        "A limit is violated" => "ein Grenzwert verletzt wird",
+        "Play a sound" => "Ton abspielen",
+        "Sound file" => "Tondatei",
+        "Empty to ring the terminal bell." => "Leer für die Terminalglocke.",
+        "Show a desktop notification" => "Desktop-Benachrichtigung anzeigen",
+        // notifications
+        "Single capture triggered" => "Einzelaufzeichnung ausgelöst",
+        "Capture differs from reference" => "Aufzeichnung weicht von der Referenz ab",
+        "Limit violated" => "Grenzwert verletzt",
+        "{} samples outside of tolerance" => "{} Abtastwerte außerhalb der Toleranz",
+        "Triggered at sample {}" => "Ausgelöst bei Abtastwert {}",
+        // status bar
+        "Acquisition stopped: {}" => "Erfassung angehalten: {}",
+        "Restart acquisition" => "Erfassung neu starten",
+        "CH{} baseline drifted by {} while warming up" =>
+            "Grundlinie von CH{} ist beim Aufwärmen um {} gedriftet",
+        "Sample rate lowered to {} MS/s, since the computer could not keep up" =>
+            "Abtastrate auf {} MS/s gesenkt, da der Rechner nicht mithalten konnte",
+        // demo tour
+        "Free running: every capture is shown as acquired, without a trigger." =>
+            "Freilauf: jede Aufzeichnung wird ohne Trigger so angezeigt, wie sie erfasst wurde.",
+        "Auto trigger: captures start at a rising edge, or after a timeout." =>
+            "Auto-Trigger: Aufzeichnungen beginnen an steigender Flanke oder nach Zeitablauf.",
+        "Normal trigger: only captures that start at a falling edge are shown." =>
+            "Normaler Trigger: nur an fallender Flanke beginnende Aufzeichnungen erscheinen.",
+        "Window trigger: captures start where the signal leaves a voltage range." =>
+            "Fenstertrigger: Aufzeichnung beginnt, wo das Signal den Spannungsbereich verlässt.",
+        "Peak detect: narrow glitches remain visible over a longer time span." =>
+            "Spitzenerkennung: schmale Störimpulse bleiben über längere Zeit sichtbar.",
+        "Averaging: noise that is not correlated with the trigger is reduced." =>
+            "Mittelung: Rauschen, das nicht mit dem Trigger korreliert ist, wird verringert.",
+        "Readouts: the DC level and the AC RMS of each channel, like a multimeter." =>
+            "Messwerte: Gleichanteil und AC-Effektivwert jedes Kanals, wie ein Multimeter.",
+        "Trend: a measurement of every capture, plotted over time." =>
+            "Trend: eine Messung jeder Aufzeichnung, über die Zeit aufgetragen.",
+        "Roll: the continuous signal, decimated, for slow changes." =>
+            "Rollmodus: das fortlaufende Signal, dezimiert, für langsame Änderungen.",
+        // setup
+        "Setup" => "Einrichtung",
+        "Language" => "Sprache",
+        "Next" => "Weiter",
+        "Step 1: Connect the instrument" => "Schritt 1: Gerät anschließen",
+        "ThunderScope detected." => "ThunderScope erkannt.",
+        "{} ThunderScopes detected." => "{} ThunderScopes erkannt.",
+        "Device" => "Gerät",
+        "No ThunderScope detected: {}" => "Kein ThunderScope erkannt: {}",
+        "Check that the device is connected and the XDMA driver is loaded." =>
+            "Prüfen Sie, ob das Gerät angeschlossen und der XDMA-Treiber geladen ist.",
+        "This build has no hardware driver; only the demo mode is available." =>
+            "Dieser Build enthält keinen Hardwaretreiber; nur der Demomodus ist verfügbar.",
+        "Retry" => "Erneut versuchen",
+        "Use demo mode" => "Demomodus verwenden",
+        "Step 2: Self-test" => "Schritt 2: Selbsttest",
+        "The self-test powers up the frontend and checks that samples are acquired." =>
+            "Der Selbsttest schaltet das Frontend ein und prüft, ob Abtastwerte erfasst werden.",
+        "Not run yet." => "Noch nicht ausgeführt.",
+        "Running…" => "Wird ausgeführt…",
+        "the self-test panicked" => "der Selbsttest ist abgestürzt",
+        "Passed: {}" => "Bestanden: {}",
+        "Failed: {}" => "Fehlgeschlagen: {}",
+        "received {} KiB of samples in {} ms" => "{} KiB Abtastwerte in {} ms empfangen",
+        "received only {} of {} bytes" => "nur {} von {} Bytes empfangen",
+        "Run self-test" => "Selbsttest ausführen",
+        "Skip" => "Überspringen",
+        "Step 3: Calibration" => "Schritt 3: Kalibrierung",
+        "No calibration data is available for this instrument; the nominal calibration\n\
+         will be used. Absolute voltages may be inaccurate by several percent." =>
+            "Für dieses Gerät sind keine Kalibrierdaten vorhanden; es wird die nominelle\n\
+             Kalibrierung verwendet. Absolute Spannungen können um einige Prozent abweichen.",
+        "Step {}: Probes" => "Schritt {}: Tastköpfe",
+        "Finish" => "Fertigstellen",
+        _ => return None
+    })
+}
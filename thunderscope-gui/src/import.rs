@@ -0,0 +1,204 @@
+//! Import of waveforms acquired by other instruments, from CSV or raw binary files.
+//!
+//! An imported waveform is played back as the sample stream of a device, resampled to the sample
+//! rate of the device and looped, so that it can be triggered on, measured, and displayed in
+//! the same way as a live signal. Column or channel `n` of the file becomes CH`n+1`; channels
+//! that the file does not have carry 0 V.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use thunderscope::{ChannelMap, DeviceParameters};
+use thunderscope::acquire::SampleSource;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    /// Text, with a row per sample and a column per channel, in volts. Rows that do not start
+    /// with a number (e.g. headers) are skipped. If the first column is titled e.g. `Time` or
+    /// `X`, it holds the time of each sample in seconds, from which the sample rate is derived.
+    Csv,
+    /// Little-endian 32-bit floats, in volts, with the channels interleaved.
+    F32,
+    /// Little-endian 16-bit integers, with the channels interleaved, and the value of one code
+    /// in volts.
+    I16(f32),
+    /// 8-bit integers, with the channels interleaved, and the value of one code in volts.
+    I8(f32),
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    /// Parses e.g. `csv`, `f32`, or `i16:0.001`. Without the value of one code, the full range
+    /// of an integer format corresponds to -1 V to +1 V.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, scale) = match text.split_once(':') {
+            Some((name, scale)) => (name, Some(scale.parse::<f32>()
+                .map_err(|_| format!("invalid value of one code {:?}", scale))?)),
+            None => (text, None),
+        };
+        match (name, scale) {
+            ("csv", None) => Ok(ImportFormat::Csv),
+            ("f32", None) => Ok(ImportFormat::F32),
+            ("i16", scale) => Ok(ImportFormat::I16(scale.unwrap_or(1.0 / 32768.0))),
+            ("i8",  scale) => Ok(ImportFormat::I8(scale.unwrap_or(1.0 / 128.0))),
+            _ => Err(format!("unknown format {:?}", text))
+        }
+    }
+}
+
+/// A waveform loaded from a file.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: PathBuf,
+    /// Samples of each channel, in volts.
+    pub channels: Vec<Vec<f32>>,
+    /// Rate at which each channel was sampled, in samples per second.
+    pub sample_rate: f32,
+}
+
+impl Import {
+    /// Load the waveform from the file at `path`. The sample rate is required unless it can be
+    /// derived from the file, and `raw_channels` is the amount of channels in a binary file.
+    pub fn load(path: &Path, format: ImportFormat, sample_rate: Option<f32>,
+                raw_channels: usize) -> Result<Import, String> {
+        let data = std::fs::read(path).map_err(|error| error.to_string())?;
+        let (channels, file_sample_rate) = match format {
+            ImportFormat::Csv => Self::parse_csv(&String::from_utf8_lossy(&data))?,
+            _ => (Self::parse_raw(&data, format, raw_channels)?, None),
+        };
+        if channels.is_empty() || channels[0].is_empty() {
+            return Err("no samples".to_owned())
+        }
+        if channels.len() > 4 {
+            return Err(format!("{} channels, at most 4 are supported", channels.len()))
+        }
+        let sample_rate = sample_rate.or(file_sample_rate)
+            .filter(|&sample_rate| sample_rate > 0.0)
+            .ok_or_else(|| "sample rate is not specified".to_owned())?;
+        Ok(Import { path: path.to_owned(), channels, sample_rate })
+    }
+
+    fn parse_csv(text: &str) -> Result<(Vec<Vec<f32>>, Option<f32>), String> {
+        fn split(line: &str) -> Vec<&str> {
+            line.split([',', ';', '\t']).map(|field| field.trim().trim_matches('"')).collect()
+        }
+
+        let mut has_time = false;
+        let mut times = Vec::new();
+        let mut channels: Vec<Vec<f32>> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let fields = split(line);
+            if fields[0].parse::<f64>().is_err() {
+                if channels.is_empty() {
+                    let title = fields[0].to_lowercase();
+                    has_time = title.starts_with("time") || title == "x" || title == "t";
+                }
+                continue
+            }
+            let mut values = fields.iter().map(|field| field.parse::<f64>()
+                .map_err(|_| format!("line {}: invalid number {:?}", index + 1, field)));
+            if has_time {
+                times.push(values.next().unwrap()?);
+            }
+            let values = values.collect::<Result<Vec<_>, _>>()?;
+            if channels.is_empty() {
+                channels.resize(values.len(), Vec::new());
+            } else if values.len() != channels.len() {
+                return Err(format!("line {}: expected {} columns", index + 1,
+                    channels.len() + has_time as usize))
+            }
+            for (channel, value) in channels.iter_mut().zip(values) {
+                channel.push(value as f32);
+            }
+        }
+        let sample_rate = match (times.first(), times.last()) {
+            (Some(&first), Some(&last)) if last > first =>
+                Some(((times.len() - 1) as f64 / (last - first)) as f32),
+            _ => None
+        };
+        Ok((channels, sample_rate))
+    }
+
+    fn parse_raw(data: &[u8], format: ImportFormat, channels: usize)
+            -> Result<Vec<Vec<f32>>, String> {
+        let samples = match format {
+            ImportFormat::Csv => unreachable!(),
+            ImportFormat::F32 => data.chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect::<Vec<_>>(),
+            ImportFormat::I16(scale) => data.chunks_exact(2)
+                .map(|bytes| i16::from_le_bytes(bytes.try_into().unwrap()) as f32 * scale)
+                .collect(),
+            ImportFormat::I8(scale) => data.iter()
+                .map(|&byte| byte as i8 as f32 * scale)
+                .collect(),
+        };
+        if channels == 0 {
+            return Err("no channels".to_owned())
+        }
+        Ok((0..channels)
+            .map(|channel| samples.iter().skip(channel).step_by(channels).copied().collect())
+            .collect())
+    }
+
+    /// Returns the value of channel `channel_index` at `time`, looping the waveform.
+    fn value(&self, channel_index: usize, time: f64) -> f32 {
+        let Some(samples) = self.channels.get(channel_index) else { return 0.0 };
+        let index = (time * self.sample_rate as f64) as u64 % samples.len() as u64;
+        samples[index as usize]
+    }
+}
+
+/// Generates the interleaved sample stream for an imported waveform.
+pub struct ImportedSamples {
+    import: Import,
+    params: DeviceParameters,
+    // faceplate channel carried by each lane
+    lane_channels: Vec<Option<usize>>,
+    position: u64,
+}
+
+impl ImportedSamples {
+    pub fn new(import: Import) -> ImportedSamples {
+        let mut generator = ImportedSamples {
+            import,
+            params: DeviceParameters::default(),
+            lane_channels: Vec::new(),
+            position: 0,
+        };
+        generator.reconfigure(&DeviceParameters::default());
+        generator
+    }
+
+    fn sample(&self, position: u64) -> i8 {
+        let lanes = self.lane_channels.len() as u64;
+        let Some(channel_index) = self.lane_channels[(position % lanes) as usize]
+            else { return 0 };
+        let time = (position / lanes) as f64 / self.params.sample_rate() as f64;
+        self.params.volts_to_code(channel_index, self.import.value(channel_index, time))
+    }
+}
+
+impl Read for ImportedSamples {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        for sample in data.iter_mut() {
+            *sample = self.sample(self.position) as u8;
+            self.position += 1;
+        }
+        // simulate 1 GS/s capture rate
+        std::thread::sleep(std::time::Duration::from_nanos(1) * (data.len() as u32));
+        Ok(data.len())
+    }
+}
+
+impl SampleSource for ImportedSamples {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        let channel_map = ChannelMap::from_params(params);
+        self.params = *params;
+        self.lane_channels = (0..channel_map.stream_channels())
+            .map(|lane| channel_map.lane_channel(lane))
+            .collect();
+    }
+}
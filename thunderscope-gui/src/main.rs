@@ -0,0 +1,2465 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::num::NonZeroU32;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, TryRecvError};
+
+use raw_window_handle::HasRawWindowHandle;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+use winit::event::{Event, StartCause, TouchPhase, WindowEvent};
+use winit::window::{Window, WindowBuilder};
+
+use glutin_winit::DisplayBuilder;
+use glutin::config::ConfigTemplateBuilder;
+use glutin::context::{Version, ContextApi, ContextAttributesBuilder};
+use glutin::context::{NotCurrentGlContext, PossiblyCurrentContext};
+use glutin::surface::{GlSurface, Surface, SurfaceAttributesBuilder, WindowSurface};
+use glutin::display::{GetGlDisplay, GlDisplay};
+
+use glow::{Context as GlowContext, HasContext};
+
+#[cfg(feature = "audio")]
+mod audio;
+mod budget;
+mod capture;
+mod clipboard;
+mod compare;
+mod gesture;
+mod history;
+mod i18n;
+mod import;
+mod notify;
+mod palette;
+mod profile;
+mod readout;
+mod scenario;
+mod session;
+mod settings;
+mod setup;
+mod tour;
+mod writer;
+
+use thunderscope::{AnnotationFeed, EdgeFilter, PeakDetector};
+use thunderscope::export::Marker;
+use thunderscope::acquire::{Parameters, TriggerCause, Waveform};
+use thunderscope_dsp::{Limit, Measurement};
+use capture::{AcquisitionMode, AcquisitionStatus, AlarmAction, LimitRule, SamplerRemote, SlowChunk};
+use settings::{DriftTracking, Settings};
+use budget::{MemoryBudget, Reservation};
+use compare::{Comparison, Verdict};
+use gesture::{Gesture, GestureRecognizer};
+use history::{CaptureHistory, THUMBNAIL_COLUMNS};
+use i18n::{tr, tr_format};
+use notify::{Notifier, NotifyEvent};
+use palette::Palette;
+use profile::{Profiler, Stage, StageSummary};
+use readout::Readouts;
+use tour::{Tour, TourStep, TourWindow};
+use writer::DiskWriter;
+
+const TRIGGER_EDGE: EdgeFilter = EdgeFilter::Rising;
+const SAMPLE_COUNT: usize = 128_000;
+const RENDER_LINES: bool = true;
+const ROLL_LENGTH: usize = 10_000;
+const TREND_LENGTH: usize = 1_000_000;
+const WAVEFORM_POOL_SIZE: usize = 4;
+/// Frame rate below which the displayed waveform is decimated further.
+const RENDER_TARGET_FPS: f32 = 30.0;
+/// Amount of samples per horizontal pixel beyond which the displayed waveform is decimated
+/// regardless of the frame rate, since they could not be told apart anyway.
+const RENDER_SAMPLES_PER_PIXEL: usize = 4;
+/// Period over which the profiler overlay averages the timings of each stage.
+const PROFILE_INTERVAL: Duration = Duration::from_secs(1);
+const RENDER_MAX_DECIMATION: usize = 1 << 16;
+const REALTIME_PRIORITY: u8 = 40; // default; below the threaded interrupt handlers at 50
+
+/// Portion of the capture shown in the waveform area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TimeView {
+    /// Magnification of the time axis; at 1, the whole capture is shown.
+    zoom: f32,
+    /// Position of the left edge of the waveform area, as a fraction of the capture.
+    offset: f32,
+}
+
+impl Default for TimeView {
+    fn default() -> Self {
+        Self { zoom: 1.0, offset: 0.0 }
+    }
+}
+
+impl TimeView {
+    const MAX_ZOOM: f32 = 1000.0;
+
+    fn is_zoomed(&self) -> bool {
+        self.zoom > 1.0
+    }
+
+    /// Scale the time axis by `factor`, keeping the point at `center` (as a fraction of
+    /// the width of the waveform area) in place.
+    fn zoom_at(&mut self, center: f32, factor: f32) {
+        let fixed = self.offset + center / self.zoom;
+        self.zoom = (self.zoom * factor).clamp(1.0, Self::MAX_ZOOM);
+        self.offset = fixed - center / self.zoom;
+        self.clamp();
+    }
+
+    /// Move the time axis by `delta`, as a fraction of the width of the waveform area.
+    fn pan(&mut self, delta: f32) {
+        self.offset -= delta / self.zoom;
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.offset = self.offset.clamp(0.0, 1.0 - 1.0 / self.zoom);
+    }
+
+    /// Returns the range of samples shown out of `length` samples.
+    fn visible(&self, length: usize) -> Range<usize> {
+        let count = ((length as f32 / self.zoom).ceil() as usize).clamp(2, length.max(2));
+        let start = ((self.offset * length as f32) as usize).min(length.saturating_sub(count));
+        start..(start + count).min(length)
+    }
+}
+
+struct WaveformRenderer {
+    program: <glow::Context as HasContext>::Program,
+    vertex_array: <glow::Context as HasContext>::VertexArray,
+    sample_array: <glow::Context as HasContext>::Buffer,
+    waveform_recv: Receiver<Waveform>,
+    waveform_send: Sender<Waveform>,
+    current: Option<Waveform>,
+    width: u32,
+    // decimation chosen according to the frame rate, and the time the last frame was rendered
+    decimation: usize,
+    last_frame: Option<Instant>,
+    decimated: Vec<i8>,
+}
+
+impl WaveformRenderer {
+    pub fn new(
+        gl: &glow::Context,
+        waveform_recv: Receiver<Waveform>,
+        waveform_send: Sender<Waveform>
+    ) -> Self {
+        let shaders = [
+            (glow::VERTEX_SHADER,   include_str!("wave_vert.glsl")),
+            (glow::FRAGMENT_SHADER, include_str!("wave_frag.glsl")),
+        ];
+
+        unsafe {
+            let program = gl.create_program().expect("failed to create program");
+            let mut native_shaders = Vec::new();
+            for (kind, source) in shaders {
+                let shader = gl.create_shader(kind).expect("failed to create shader");
+                gl.shader_source(shader, source);
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    panic!("could not compile shader: {}", gl.get_shader_info_log(shader));
+                }
+                gl.attach_shader(program, shader);
+                native_shaders.push(shader);
+            }
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                panic!("{}", gl.get_program_info_log(program));
+            }
+            for shader in native_shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+
+            let vertex_array = gl.create_vertex_array().expect("failed to create vertex array");
+            let data_array = gl.create_buffer().expect("failed to create buffer");
+            Self {
+                program,
+                vertex_array,
+                sample_array: data_array,
+                waveform_recv,
+                waveform_send,
+                current: None,
+                width: 0,
+                decimation: 1,
+                last_frame: None,
+                decimated: Vec::new(),
+            }
+        }
+    }
+
+    pub fn poll(&mut self) -> bool {
+        match self.waveform_recv.try_recv() {
+            err @ Err(TryRecvError::Disconnected) =>
+                panic!("renderer: failed to receive waveform: {:?}", err),
+            Err(TryRecvError::Empty) => false,
+            Ok(new_waveform) => {
+                if let Some(trigger) = new_waveform.trigger() {
+                    log::debug!("renderer: acquired waveform triggered at sample {} ({:?})",
+                        trigger.sample, trigger.time);
+                } else {
+                    log::debug!("renderer: acquired waveform");
+                }
+                if let Some(old_waveform) = self.current.replace(new_waveform) {
+                    self.waveform_send.send(old_waveform).expect("failed to return waveform");
+                }
+                true
+            }
+        }
+    }
+
+    pub fn current(&self) -> Option<&Waveform> {
+        self.current.as_ref()
+    }
+
+    /// Copy the samples of the capture that are within `view` to the clipboard.
+    pub fn copy_visible(&self, ui: &imgui::Ui, view: &TimeView) {
+        let Some(capture) = self.current.as_ref().and_then(Waveform::capture) else {
+            log::warn!("no waveform to copy");
+            return
+        };
+        let range = view.visible(capture.len());
+        log::info!("copying {} samples to the clipboard", range.len());
+        ui.set_clipboard_text(clipboard::capture_csv(&capture, range));
+    }
+
+    pub fn resize(&mut self, gl: &glow::Context, width: u32, height: u32) {
+        self.width = width;
+        unsafe {
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(gl.get_uniform_location(self.program, "resolution").as_ref(),
+                width as f32, height as f32);
+        }
+    }
+
+    /// Adjust the decimation so that rendering keeps up with the target frame rate.
+    fn adapt_decimation(&mut self) {
+        let now = Instant::now();
+        let Some(last_frame) = self.last_frame.replace(now) else { return };
+        let frame_time = (now - last_frame).as_secs_f32();
+        // if frames are far apart, they are not being rendered continuously
+        if frame_time > 1.0 { return }
+        let target = 1.0 / RENDER_TARGET_FPS;
+        if frame_time > target && self.decimation < RENDER_MAX_DECIMATION {
+            self.decimation *= 2;
+            log::debug!("renderer: frame took {:.1} ms, decimating by {}",
+                frame_time * 1e3, self.decimation);
+        } else if frame_time < target / 2.0 && self.decimation > 1 {
+            self.decimation /= 2;
+            log::debug!("renderer: frame took {:.1} ms, decimating by {}",
+                frame_time * 1e3, self.decimation);
+        }
+    }
+
+    /// Render the current waveform, or the `pinned` display data and delay instead if given
+    /// (e.g. a capture from the history).
+    pub fn render(&mut self, gl: &glow::Context, view: &TimeView, pinned: Option<(&[i8], f32)>,
+                  color: [f32; 4]) {
+        self.adapt_decimation();
+        unsafe {
+            gl.clear_color(0.1, 0.0, 0.1, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+
+            let (samples, delay) = match pinned {
+                Some(pinned) => pinned,
+                None => {
+                    let Some(waveform) = self.current.as_ref() else { return };
+                    let Some(samples) = waveform.display_data() else { return };
+                    (samples, waveform.display_delay())
+                }
+            };
+            let samples = &samples[view.visible(samples.len())];
+            let visible_len = samples.len();
+            // only the displayed samples are decimated; the capture itself is kept as-is for
+            // measurements and export. the minimum and maximum of each group are kept, so that
+            // narrow glitches remain visible
+            let max_samples = self.width.max(1) as usize * RENDER_SAMPLES_PER_PIXEL;
+            let factor = self.decimation.max(samples.len() / max_samples);
+            let samples: &[u8] = if factor > 2 {
+                self.decimated.clear();
+                PeakDetector::new(factor, 1).process(samples, &mut self.decimated);
+                bytemuck::cast_slice(&self.decimated)
+            } else {
+                bytemuck::cast_slice(samples)
+            };
+
+            let draw_lines_loc = gl.get_uniform_location(self.program, "draw_lines");
+            let channel_color_loc = gl.get_uniform_location(self.program, "channel_color");
+            let sample_count_loc = gl.get_uniform_location(self.program, "sample_count");
+            let sample_delay_loc = gl.get_uniform_location(self.program, "sample_delay");
+            let sample_value0_loc = gl.get_attrib_location(self.program, "sample_value0")
+                .expect("could not retrieve attribute location");
+            let sample_value1_loc = gl.get_attrib_location(self.program, "sample_value1")
+                .expect("could not retrieve attribute location");
+
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.enable(glow::BLEND);
+
+            gl.use_program(Some(self.program));
+            gl.uniform_1_u32(draw_lines_loc.as_ref(), RENDER_LINES as u32);
+            gl.uniform_3_f32(channel_color_loc.as_ref(), color[0], color[1], color[2]);
+            gl.uniform_1_i32(sample_count_loc.as_ref(), samples.len() as i32);
+            // align the trace on the interpolated crossing of the trigger level instead of
+            // the sample where the trigger fired, which removes jitter of up to one sample
+            gl.uniform_1_f32(sample_delay_loc.as_ref(),
+                delay * samples.len() as f32 / visible_len.max(1) as f32);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.sample_array));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, samples, glow::STREAM_DRAW);
+            gl.enable_vertex_attrib_array(sample_value0_loc);
+            gl.vertex_attrib_pointer_f32(sample_value0_loc, 1, glow::BYTE, true, 1, 0);
+            gl.vertex_attrib_divisor(sample_value0_loc, 1);
+            gl.enable_vertex_attrib_array(sample_value1_loc);
+            gl.vertex_attrib_pointer_f32(sample_value1_loc, 1, glow::BYTE, true, 1, 1);
+            gl.vertex_attrib_divisor(sample_value1_loc, 1);
+            gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, samples.len() as i32);
+            gl.disable_vertex_attrib_array(sample_value0_loc);
+            gl.disable_vertex_attrib_array(sample_value1_loc);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            gl.disable(glow::BLEND);
+        }
+    }
+
+    pub fn destroy(&mut self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+        }
+    }
+}
+
+mod ui_defs {
+    pub const FONT_DEFAULT_DATA: &[u8] = include_bytes!("DejaVuSansMono.ttf");
+    pub const FONT_DEFAULT_SIZE: f32 = 18.0;
+
+    pub const FONT_CONTROLS_DATA: &[u8] = include_bytes!("DejaVuSans-Bold.ttf");
+    pub const FONT_CONTROLS_SIZE: f32 = 22.0;
+
+    pub const FONT_LOGO_DATA: &[u8] = include_bytes!("DejaVuSerif.ttf");
+    pub const FONT_LOGO_SIZE: f32  = 30.0;
+
+    pub const LOGO_TEXT: &str = "ThunderScope";
+    pub const LOGO_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub const CONTROLS_V_MARGIN: f32 = 12.0;
+    pub const CONTROLS_H_SPACING: f32 = 14.0;
+    pub const CONTROLS_TRIGGER_WIDTH: f32 = 120.0;
+    pub const CONTROLS_ACQUISITION_WIDTH: f32 = 160.0;
+    pub const CONTROLS_RUN_STOP_WIDTH: f32 = 72.0;
+
+    pub const CHANNEL_V_PADDING: f32 = 10.0;
+
+    pub const UNCAL_FILL_COLOR: [f32; 4] = [0.9, 0.1, 0.1, 1.0];
+    pub const UNCAL_TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+    pub const ANNOTATION_COLOR: [f32; 4] = [1.0, 0.6, 0.0, 1.0];
+
+    pub const DEBUG_COLOR: [f32; 4] = [0.8, 0.0, 0.8, 1.0];
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelLayoutMetrics {
+    inner_height: f32, // in logical px
+    outer_height: f32, // in logical px; inner_height + padding
+    zero_offset:  f32, // in volts
+    full_scale:   f32, // in volts
+}
+
+impl ChannelLayoutMetrics {
+    fn volts_to_pixels(&self, volts: f32) -> f32 {
+        // the nominal full scale is 2 V: -1 V to 1 V
+        let normalized_volts = (-volts - self.zero_offset) / self.full_scale * 2.0;
+        (normalized_volts + 1.0) * self.outer_height / 2.0
+    }
+
+    fn pixels_to_volts(&self, pixels: f32) -> f32 {
+        let normalized_volts = pixels * 2.0 / self.outer_height - 1.0;
+        -(normalized_volts / 2.0 * self.full_scale + self.zero_offset)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct InterfaceLayoutMetrics {
+    overall_size:       [f32; 2], // in logical px
+    logo_width:         f32,      // in logical px
+    control_bar_height: f32,      // in logical px
+    horz_scale_height:  f32,      // in logical px
+    vert_scale_width:   f32,      // in logical px
+    channels:           [ChannelLayoutMetrics; 4],
+}
+
+impl InterfaceLayoutMetrics {
+    fn new(ui: &imgui::Ui, logo_font: imgui::FontId,
+                channel_count: usize) -> InterfaceLayoutMetrics {
+        let [overall_width, overall_height] = ui.window_size();
+        let [logo_width, logo_height] = {
+            let _t = ui.push_font(logo_font);
+            ui.calc_text_size(ui_defs::LOGO_TEXT)
+        };
+        let control_bar_height = logo_height + ui_defs::CONTROLS_V_MARGIN * 2.0;
+        let horz_scale_height = 32.0; // FIXME
+        let vert_scale_width = 100.0;  // FIXME
+        let channel_area_height = overall_height - control_bar_height - horz_scale_height -
+            ui_defs::CHANNEL_V_PADDING;
+        let channel_outer_height = channel_area_height / channel_count as f32;
+        let mut channels = [ChannelLayoutMetrics::default(); 4];
+        for index in 0..channel_count {
+            channels[index].outer_height = channel_outer_height;
+            channels[index].inner_height = channel_outer_height - ui_defs::CHANNEL_V_PADDING * 2.0;
+            channels[index].zero_offset = 0.0; // FIXME
+            channels[index].full_scale  = 10.0; // FIXME
+        }
+        InterfaceLayoutMetrics {
+            overall_size: [overall_width, overall_height],
+            logo_width,
+            control_bar_height,
+            horz_scale_height,
+            vert_scale_width,
+            channels,
+        }
+    }
+
+    fn volts_to_pixels(&self, index: usize, volts: f32) -> f32 {
+        let mut offset = self.control_bar_height + self.horz_scale_height;
+        for index_above in 0..index {
+            offset += self.channels[index_above].outer_height;
+        }
+        offset += self.channels[index].volts_to_pixels(volts);
+        offset
+    }
+
+    fn pixels_to_volts(&self, index: usize, pixels: f32) -> f32 {
+        let mut offset = pixels;
+        offset -= self.control_bar_height + self.horz_scale_height;
+        for index_above in 0..index {
+            offset -= self.channels[index_above].outer_height;
+        }
+        dbg!(offset);
+        self.channels[index].pixels_to_volts(offset)
+    }
+
+    fn channel_rect(&self, index: usize) -> ([f32; 2], [f32; 2]) {
+        let mut vert_offset = self.control_bar_height + self.horz_scale_height;
+        for index_above in 0..index {
+            vert_offset += self.channels[index_above].outer_height;
+        }
+        let [overall_width, _] = self.overall_size;
+        let channel_height = self.channels[index].outer_height;
+        ([self.vert_scale_width, vert_offset],
+         [overall_width - ui_defs::CONTROLS_H_SPACING, vert_offset + channel_height])
+    }
+
+    fn trace_origin(&self, index: usize) -> [f32; 2] {
+        let mut vert_offset = 0.0;
+        for index_above in 0..index {
+            vert_offset += self.channels[index_above].outer_height;
+        }
+        vert_offset += self.channels[index].outer_height / 2.0;
+        [self.vert_scale_width, vert_offset]
+    }
+}
+
+/// Draft of a limit rule being edited in the UI.
+#[derive(Debug, Default)]
+struct LimitEditor {
+    enabled: bool,
+    measurement_index: usize,
+    channel_index: usize,
+    min: Option<f32>,
+    max: Option<f32>,
+    stop: bool,
+    save: bool,
+    beep: bool,
+    command: String,
+}
+
+impl LimitEditor {
+    fn rules(&self) -> Vec<LimitRule> {
+        if !self.enabled {
+            return Vec::new()
+        }
+        let mut actions = Vec::new();
+        if self.stop { actions.push(AlarmAction::StopAcquisition) }
+        if self.save { actions.push(AlarmAction::SaveCapture) }
+        if self.beep { actions.push(AlarmAction::Beep) }
+        if !self.command.is_empty() {
+            actions.push(AlarmAction::RunCommand(self.command.clone()))
+        }
+        vec![LimitRule {
+            limit: Limit {
+                measurement: Measurement::ALL[self.measurement_index],
+                channel: self.channel_index,
+                min: self.min,
+                max: self.max,
+            },
+            actions
+        }]
+    }
+}
+
+/// Show `description` when the last item is hovered or focused by keyboard navigation.
+///
+/// Dear ImGui does not expose widgets to screen readers, so this is the only way to explain
+/// controls whose label is abbreviated or absent.
+fn describe_item(ui: &imgui::Ui, description: &str) {
+    if ui.is_item_hovered() || (ui.is_item_focused() && ui.io().nav_visible) {
+        ui.tooltip_text(description);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Default)]
+struct InterfaceState {
+    trigger_clicked: bool,
+    acquisition_clicked: bool,
+    run_stop_clicked: bool,
+}
+
+#[derive(Debug)]
+struct InterfaceRenderer {
+    controls_font: imgui::FontId,
+    logo_font: imgui::FontId,
+
+    dragging_h_marker: Cell<bool>,
+    h_marker_pos: Cell<f32>,
+
+    dragging_v_marker: Cell<bool>,
+    v_marker_pos: Cell<f32>,
+
+    event_log: Option<thunderscope::EventLog>,
+    event_log_opened: bool,
+
+    // enabled channels that use the nominal calibration
+    uncalibrated: [bool; 4],
+
+    annotations: AnnotationFeed,
+    // annotations placed on the current capture, and its length
+    markers: Vec<Marker>,
+    markers_length: usize,
+
+    roll_recv: Receiver<SlowChunk>,
+    roll_history: VecDeque<f32>,
+    roll_budget: Option<Reservation>,
+    roll_opened: bool,
+
+    readout_recv: Receiver<Readouts>,
+    readouts: Readouts,
+    readouts_opened: bool,
+
+    trend_measurement: Measurement,
+    trend_history: VecDeque<(SystemTime, f32)>,
+    trend_budget: Option<Reservation>,
+    trend_opened: bool,
+
+    limits_send: Sender<Vec<LimitRule>>,
+    limit_editor: LimitEditor,
+    limits_opened: bool,
+
+    comparison: Comparison,
+    compare_opened: bool,
+    // whether the most recent capture differed from the reference, to notify only once it starts
+    mask_failed: bool,
+
+    notifier: Notifier,
+
+    history: CaptureHistory,
+    history_opened: bool,
+
+    profiler: Arc<Profiler>,
+    profile: [StageSummary; Stage::ALL.len()],
+    profiled_at: Instant,
+    profiler_opened: bool,
+
+    #[cfg(feature = "audio")]
+    audio_monitor: Option<audio::AudioMonitor>,
+    #[cfg(feature = "audio")]
+    audio_opened: bool,
+
+    acquisition_send: Sender<AcquisitionMode>,
+    acquisition_mode: AcquisitionMode,
+    average_count: u32,
+
+    time_view: TimeView,
+    waveform_menu_request: Option<[f32; 2]>,
+    waveform_menu_position: [f32; 2],
+    // whether the visible region of the waveform is to be copied once the frame is rendered
+    copy_waveform_request: bool,
+    popup_open: bool,
+
+    status_recv: Receiver<AcquisitionStatus>,
+    recover_send: Sender<()>,
+    acquisition_status: AcquisitionStatus,
+    pause_send: Sender<bool>,
+    paused: bool,
+
+    settings: Settings,
+    budget: Arc<MemoryBudget>,
+    disk_writer: DiskWriter,
+    preferences_opened: bool,
+    // comma-separated list of cores, as edited in the preferences
+    cpu_affinity_text: String,
+
+    // caption of the current step of the demo tour, if it is running
+    tour_caption: Option<&'static str>,
+}
+
+impl InterfaceRenderer {
+    fn font_config(scale_factor: f64) -> imgui::FontConfig {
+        imgui::FontConfig {
+            rasterizer_density: scale_factor as f32,
+            oversample_h: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Add the fonts to the atlas; returns the controls and the logo font.
+    fn load_fonts(context: &mut imgui::Context, font_config: imgui::FontConfig)
+            -> (imgui::FontId, imgui::FontId) {
+        use imgui::*;
+
+        let ttf_font = |data, size_pixels| [
+            FontSource::TtfData { data, size_pixels, config: Some(font_config.clone()) },
+            FontSource::TtfData { data, size_pixels,
+                config: Some(FontConfig {
+                    glyph_ranges: FontGlyphRanges::from_slice(&[
+                        '↑' as u32, '↑' as u32,
+                        '↓' as u32, '↓' as u32,
+                        '⇅' as u32, '⇅' as u32,
+                        0
+                    ]),
+                    ..font_config.clone()
+                }),
+            },
+        ];
+        let _default_font = context.fonts().add_font(
+            &ttf_font(ui_defs::FONT_DEFAULT_DATA, ui_defs::FONT_DEFAULT_SIZE));
+        let controls_font = context.fonts().add_font(
+            &ttf_font(ui_defs::FONT_CONTROLS_DATA, ui_defs::FONT_CONTROLS_SIZE));
+        let logo_font = context.fonts().add_font(
+            &ttf_font(ui_defs::FONT_LOGO_DATA, ui_defs::FONT_LOGO_SIZE));
+        (controls_font, logo_font)
+    }
+
+    fn new(context: &mut imgui::Context, font_config: imgui::FontConfig,
+            roll_recv: Receiver<SlowChunk>, readout_recv: Receiver<Readouts>,
+            remote: SamplerRemote, budget: Arc<MemoryBudget>, disk_writer: DiskWriter) -> Self {
+        let SamplerRemote {
+            limits_send, acquisition_send, status_recv, recover_send, pause_send } = remote;
+        let (controls_font, logo_font) = Self::load_fonts(context, font_config);
+        let mut renderer = Self {
+            controls_font,
+            logo_font,
+            dragging_h_marker: Cell::new(false),
+            h_marker_pos: Cell::new(100.0),
+            dragging_v_marker: Cell::new(false),
+            v_marker_pos: Cell::new(3.3),
+            event_log: None,
+            event_log_opened: false,
+            uncalibrated: [false; 4],
+            annotations: AnnotationFeed::new(),
+            markers: Vec::new(),
+            markers_length: 0,
+            roll_recv,
+            roll_history: VecDeque::new(),
+            roll_budget: None,
+            roll_opened: false,
+            readout_recv,
+            readouts: [None; 4],
+            readouts_opened: false,
+            trend_measurement: Measurement::Frequency,
+            trend_history: VecDeque::new(),
+            trend_budget: None,
+            trend_opened: false,
+            limits_send,
+            limit_editor: LimitEditor::default(),
+            limits_opened: false,
+            comparison: Comparison::default(),
+            compare_opened: false,
+            mask_failed: false,
+            notifier: Notifier::default(),
+            history: CaptureHistory::default(),
+            history_opened: false,
+            profiler: Profiler::new(),
+            profile: Default::default(),
+            profiled_at: Instant::now(),
+            profiler_opened: false,
+            #[cfg(feature = "audio")]
+            audio_monitor: None,
+            #[cfg(feature = "audio")]
+            audio_opened: false,
+            acquisition_send,
+            acquisition_mode: AcquisitionMode::Sample,
+            average_count: 16,
+            time_view: TimeView::default(),
+            copy_waveform_request: false,
+            waveform_menu_request: None,
+            waveform_menu_position: [0.0, 0.0],
+            popup_open: false,
+            status_recv,
+            recover_send,
+            acquisition_status: AcquisitionStatus::Running,
+            pause_send,
+            paused: false,
+            settings: Settings::default(),
+            budget,
+            disk_writer,
+            preferences_opened: false,
+            cpu_affinity_text: String::new(),
+            tour_caption: None,
+        };
+        renderer.reserve_history();
+        renderer
+    }
+
+    /// Reserve memory for the roll and trend history, shortening it if there is not enough.
+    fn reserve_history(&mut self) {
+        const MINIMUM_LENGTH: usize = 1_000;
+        self.roll_budget = None;
+        self.trend_budget = None;
+        let roll_item_size = std::mem::size_of::<f32>();
+        self.roll_budget = Some(self.budget.reserve("roll history",
+            MINIMUM_LENGTH * roll_item_size, ROLL_LENGTH * roll_item_size));
+        let trend_item_size = std::mem::size_of::<(SystemTime, f32)>();
+        self.trend_budget = Some(self.budget.reserve("trend history",
+            MINIMUM_LENGTH * trend_item_size, TREND_LENGTH * trend_item_size));
+        let (roll_length, trend_length) = (self.roll_length(), self.trend_length());
+        self.roll_history.drain(..self.roll_history.len().saturating_sub(roll_length));
+        self.trend_history.drain(..self.trend_history.len().saturating_sub(trend_length));
+    }
+
+    fn roll_length(&self) -> usize {
+        self.roll_budget.as_ref().map_or(0, |budget| budget.count(std::mem::size_of::<f32>()))
+    }
+
+    fn trend_length(&self) -> usize {
+        self.trend_budget.as_ref()
+            .map_or(0, |budget| budget.count(std::mem::size_of::<(SystemTime, f32)>()))
+    }
+
+    /// Rebuild the fonts, which are rasterized for a specific scale factor. The font atlas
+    /// texture must be uploaded again afterwards.
+    fn rescale(&mut self, context: &mut imgui::Context, scale_factor: f64) {
+        context.fonts().clear();
+        (self.controls_font, self.logo_font) =
+            Self::load_fonts(context, Self::font_config(scale_factor));
+    }
+
+    fn palette(&self) -> Palette {
+        self.settings.palette
+    }
+
+    fn render_logo(&self, ui: &imgui::Ui) -> [f32; 2] {
+        let _t = ui.push_font(self.logo_font);
+        let [w, _] = ui.cursor_pos();
+        let [_, mut h] = ui.clone_style().window_padding;
+        h -= ui.clone_style().frame_padding[1];
+        ui.set_cursor_pos([w, h]);
+        let logo_color = unsafe {
+            let (mut r, mut g, mut b) = (0.0f32, 0.0f32, 0.0f32);
+            let h = ui.frame_count() as f32 / 1000.0;
+            imgui::sys::igColorConvertHSVtoRGB(h, 1.0, 1.0,
+                &mut r as *mut f32,
+                &mut g as *mut f32,
+                &mut b as *mut f32);
+            [r, g, b, 1.0]
+        };
+        ui.text_colored(logo_color, ui_defs::LOGO_TEXT);
+        ui.calc_text_size(ui_defs::LOGO_TEXT)
+    }
+
+    fn render_minimap(&self, ui: &imgui::Ui, width: f32, height: f32) {
+        let minimap = ui.child_window("##minimap")
+            .size([width, height])
+            .movable(false)
+            .bring_to_front_on_focus(false);
+        minimap.build(|| {
+            let draw_list = ui.get_window_draw_list();
+            let ([x, y], [w, h]) = (ui.window_pos(), ui.window_size());
+            draw_list
+                .add_rect([x, y], [x + w, y + h], ui_defs::DEBUG_COLOR)
+                .build();
+        });
+    }
+
+    fn with_controls_style<F: FnOnce() -> R, R>(&self, ui: &imgui::Ui, f: F) -> R {
+        use imgui::*;
+
+        let _t = ui.push_font(self.controls_font);
+        let _t = ui.push_style_color(StyleColor::Button,        [0.00, 0.00, 0.00, 1.00]);
+        let _t = ui.push_style_color(StyleColor::ButtonHovered, [0.20, 0.20, 0.20, 1.00]);
+        let _t = ui.push_style_color(StyleColor::ButtonActive,  [0.40, 0.40, 0.40, 1.00]);
+        let _t = ui.push_style_color(StyleColor::Border,        [0.25, 0.25, 0.25, 1.00]);
+        let _t = ui.push_style_var(StyleVar::FrameRounding(5.0));
+        let _t = ui.push_style_var(StyleVar::FrameBorderSize(2.0));
+        f()
+    }
+
+    fn render_trigger_config(&self, ui: &imgui::Ui, width: f32, height: f32) -> bool {
+        use imgui::*;
+
+        self.with_controls_style(ui, || {
+            let _t = ui.push_style_color(StyleColor::Text, self.palette().channel_color(0));
+            let clicked = ui.button_with_size("T: CH1↑", [width, height]);
+            describe_item(ui, tr("Trigger settings"));
+            clicked
+        })
+    }
+
+    fn render_acquisition_mode(&self, ui: &imgui::Ui, width: f32, height: f32) -> bool {
+        use imgui::*;
+
+        self.with_controls_style(ui, || {
+            let _t = ui.push_style_color(StyleColor::Text, [1.0, 1.0, 1.0, 1.0]);
+            let label = match self.acquisition_mode {
+                AcquisitionMode::Average(count) => tr_format("Avg ×{}", &[&count]),
+                mode => tr(mode.name()).to_owned(),
+            };
+            let clicked = ui.button_with_size(format!("{}##acquisition", label), [width, height]);
+            describe_item(ui, tr("Acquisition mode"));
+            clicked
+        })
+    }
+
+    fn render_run_stop(&self, ui: &imgui::Ui, width: f32, height: f32) -> bool {
+        use imgui::*;
+
+        self.with_controls_style(ui, || {
+            if self.paused {
+                let _t = ui.push_style_color(StyleColor::Text, [0.0, 1.0, 0.0, 1.0]);
+                let clicked = ui.button_with_size(tr("RUN"), [width, height]);
+                describe_item(ui, tr("Resume acquisition"));
+                clicked
+            } else {
+                let _t = ui.push_style_color(StyleColor::Text, [1.0, 0.0, 0.0, 1.0]);
+                let clicked = ui.button_with_size(tr("STOP"), [width, height]);
+                describe_item(ui, tr("Stop acquisition"));
+                clicked
+            }
+        })
+    }
+
+    /*
+    fn render_trigger_offset_marker(&self, ui: &imgui::Ui) {
+        let draw_list = ui.get_window_draw_list();
+
+        let text = "-50ps";
+
+        let [x, y] = [self.h_marker_pos.get(), 90.0];
+        let [wt, ht] = ui.calc_text_size(text);
+        let [wp, hp] = [wt+5.0, ht+5.0];
+        let mut marker_outline = vec![
+            [x, y],
+            [x-wp/2.0, y-5.0],
+            [x-wp/2.0, y-5.0-hp],
+            [x+wp/2.0, y-5.0-hp],
+            [x+wp/2.0, y-5.0],
+            [x, y],
+        ];
+        let color = ui_style::MARKER_FILL_COLOR;
+        if self.dragging_h_marker.get() {
+            if ui.is_mouse_down(imgui::MouseButton::Left) {
+                let [x, _] = ui.io().mouse_pos;
+                self.h_marker_pos.set(x);
+            } else {
+                self.dragging_h_marker.set(false);
+            }
+        } else if ui.is_mouse_hovering_rect([x-wp/2.0,y-5.0-hp], [x+wp/2.0,y]) {
+            if ui.is_mouse_down(imgui::MouseButton::Left) {
+                self.dragging_h_marker.set(true)
+            }
+        }
+        draw_list.add_polyline(marker_outline.clone(), color)
+            .filled(true).build();
+        marker_outline.push([x, y+400.0]);
+        draw_list.add_polyline(marker_outline, ui_style::MARKER_LINE_COLOR)
+            .thickness(1.0).build();
+        draw_list.add_text([x-wt/2.0, y-2.5-ht-5.0], ui_style::MARKER_TEXT_COLOR, text);
+    }
+    */
+
+    /// Returns `true` if the marker was activated from the keyboard.
+    fn render_trigger_level_marker(&self, ui: &imgui::Ui, metrics: &InterfaceLayoutMetrics)
+            -> bool {
+        let draw_list = ui.get_window_draw_list();
+
+        let channel_index = 0;
+
+        let ([l, t], [r, b]) = metrics.channel_rect(channel_index);
+        draw_list.add_rect([l, t], [r, b], ui_defs::DEBUG_COLOR).build();
+
+        let volts = self.v_marker_pos.get();
+        let text = format!("{:+.2}V", volts);
+
+        let [x, y] = [metrics.vert_scale_width-8.0, metrics.volts_to_pixels(channel_index, volts)];
+        let [wt, ht] = ui.calc_text_size(text.as_str());
+        let [wp, hp] = [wt+5.0, ht+5.0];
+        let mut marker_outline = vec![
+            [x, y],
+            [x-5.0, y-hp/2.0],
+            [x-5.0-wp, y-hp/2.0],
+            [x-5.0-wp, y+hp/2.0],
+            [x-5.0, y+hp/2.0],
+            [x, y],
+        ];
+        let color = self.palette().channel_color(channel_index);
+        if !self.dragging_h_marker.get() {
+            if self.dragging_v_marker.get() {
+                if ui.is_mouse_down(imgui::MouseButton::Left) {
+                    let [_, y] = ui.io().mouse_pos;
+                    self.v_marker_pos.set(metrics.pixels_to_volts(channel_index, y.max(t).min(b)));
+                } else {
+                    self.dragging_v_marker.set(false);
+                }
+            } else if ui.is_mouse_hovering_rect([x-5.0-wp, y-hp/2.0], [x, y+hp/2.0]) {
+                if ui.is_mouse_down(imgui::MouseButton::Left) {
+                    self.dragging_v_marker.set(true)
+                }
+            }
+        }
+        draw_list.add_polyline(marker_outline.clone(), color)
+            .filled(true).build();
+        marker_outline.push([r, y]);
+        draw_list.add_polyline(marker_outline, self.palette().channel_line_color(channel_index))
+            .thickness(1.0).build();
+        draw_list.add_text([x-wt-7.5, y-ht/2.0], self.palette().channel_text_color(channel_index),
+            text.as_str());
+
+        // make the marker reachable by keyboard navigation; it is still dragged as before, and
+        // activating it opens the trigger settings, where the level can be typed in
+        ui.set_cursor_screen_pos([x-5.0-wp, y-hp/2.0]);
+        let pressed = ui.invisible_button("##trigger_level", [wp+5.0, hp]);
+        describe_item(ui, &format!("{}\n{}",
+            tr_format("Trigger level: {}", &[&text]), tr("Press Space to edit.")));
+        pressed && ui.io().nav_visible
+    }
+
+    fn render_uncal_badges(&self, ui: &imgui::Ui, metrics: &InterfaceLayoutMetrics) {
+        let draw_list = ui.get_window_draw_list();
+
+        let text = tr("UNCAL");
+        let [wt, ht] = ui.calc_text_size(text);
+        let [wp, hp] = [wt+8.0, ht+4.0];
+        for channel_index in 0..4 {
+            if !self.uncalibrated[channel_index] { continue }
+            if metrics.channels[channel_index].outer_height == 0.0 { continue }
+            let ([_, t], [r, _]) = metrics.channel_rect(channel_index);
+            let [x, y] = [r-wp-ui_defs::CHANNEL_V_PADDING, t+ui_defs::CHANNEL_V_PADDING];
+            draw_list.add_rect([x, y], [x+wp, y+hp], ui_defs::UNCAL_FILL_COLOR)
+                .filled(true).rounding(3.0).build();
+            draw_list.add_text([x+4.0, y+2.0], ui_defs::UNCAL_TEXT_COLOR, text);
+            if ui.is_mouse_hovering_rect([x, y], [x+wp, y+hp]) {
+                ui.tooltip_text(tr("No calibration data is available for this channel; \
+                    absolute voltages may be inaccurate by several percent."));
+            }
+        }
+    }
+
+    fn render_annotation_flags(&self, ui: &imgui::Ui, metrics: &InterfaceLayoutMetrics) {
+        let draw_list = ui.get_window_draw_list();
+
+        let [width, height] = metrics.overall_size;
+        let top = metrics.control_bar_height;
+        let view = &self.time_view;
+        for marker in self.markers.iter() {
+            let fraction = marker.position as f32 / self.markers_length as f32;
+            let x = (fraction - view.offset) * view.zoom * width;
+            if !(0.0..width).contains(&x) { continue }
+            let [wt, ht] = ui.calc_text_size(&marker.label);
+            draw_list.add_line([x, top], [x, height], ui_defs::ANNOTATION_COLOR).build();
+            draw_list.add_rect([x, top], [x+wt+8.0, top+ht+4.0], ui_defs::ANNOTATION_COLOR)
+                .filled(true).build();
+            draw_list.add_text([x+4.0, top+2.0], ui_defs::UNCAL_TEXT_COLOR, &marker.label);
+        }
+    }
+
+    fn render_controls(&self, ui: &imgui::Ui, state: &mut InterfaceState) {
+        use imgui::*;
+
+        let _t = ui.push_style_var(StyleVar::WindowPadding(
+            [ui_defs::CONTROLS_H_SPACING, ui_defs::CONTROLS_V_MARGIN]));
+        let _t = ui.window("##main")
+            .size(ui.io().display_size, Condition::Always)
+            .position([0.0, 0.0], Condition::Always)
+            .no_decoration()
+            .draw_background(false)
+            .bring_to_front_on_focus(false)
+            .begin();
+        let metrics = InterfaceLayoutMetrics::new(ui, self.logo_font, 2);
+        ui.group(|| {
+            let _t = ui.push_style_var(StyleVar::ItemSpacing(
+                [ui_defs::CONTROLS_H_SPACING, 0.0]));
+            let control_height = metrics.control_bar_height - ui_defs::CONTROLS_V_MARGIN * 2.0;
+            state.run_stop_clicked = self.render_run_stop(ui,
+                ui_defs::CONTROLS_RUN_STOP_WIDTH, control_height);
+            ui.same_line();
+            state.trigger_clicked = self.render_trigger_config(ui,
+                ui_defs::CONTROLS_TRIGGER_WIDTH, control_height);
+            ui.same_line();
+            state.acquisition_clicked = self.render_acquisition_mode(ui,
+                ui_defs::CONTROLS_ACQUISITION_WIDTH, control_height);
+            ui.same_line();
+            let logo_width = metrics.logo_width + ui_defs::CONTROLS_H_SPACING;
+            self.render_minimap(ui, -logo_width, control_height);
+            ui.same_line();
+            self.render_logo(ui);
+
+            // self.render_trigger_offset_marker(ui);
+            if self.render_trigger_level_marker(ui, &metrics) {
+                state.trigger_clicked = true;
+            }
+            self.render_uncal_badges(ui, &metrics);
+            self.render_annotation_flags(ui, &metrics);
+        });
+    }
+
+    fn render_trigger_config_popup(&self, ui: &imgui::Ui) -> bool {
+        let mut open = false;
+        ui.popup(tr("Trigger"), || {
+            open = true;
+            use thunderscope::EdgeFilter;
+
+            for (channel, label) in ["CH1", "CH2", "CH3", "CH4"].iter().enumerate() {
+                let _t = ui.push_style_color(imgui::StyleColor::Text,
+                    self.palette().channel_color(channel));
+                if ui.menu_item_config(label).selected(channel == 0).build() {
+                    // FIXME
+                }
+            }
+
+            ui.separator();
+            for (edge_filter, label) in [
+                (EdgeFilter::Rising,  "↑ Rising"),
+                (EdgeFilter::Falling, "↓ Falling"),
+                (EdgeFilter::Both,    "⇅ Both"),
+            ] {
+                if ui.menu_item_config(tr(label)).selected(TRIGGER_EDGE == edge_filter).build() {
+                    // FIXME
+                }
+            }
+
+            ui.separator();
+            ui.align_text_to_frame_padding();
+            ui.text(tr("Level"));
+            ui.same_line();
+            ui.set_next_item_width(150.0);
+            let mut level = self.v_marker_pos.get();
+            if ui.input_float("V##Level", &mut level)
+                    .step(0.01)
+                    .step_fast(0.1)
+                    .display_format("%.2f")
+                    .build() {
+                self.v_marker_pos.set(level);
+            }
+        });
+        open
+    }
+
+    fn render_acquisition_mode_popup(&mut self, ui: &imgui::Ui) -> bool {
+        let mut open = false;
+        ui.popup(tr("Acquisition"), || {
+            open = true;
+            let average = AcquisitionMode::Average(self.average_count as usize);
+            let mut new_mode = None;
+            for mode in [
+                AcquisitionMode::Sample,
+                AcquisitionMode::PeakDetect,
+                average,
+                AcquisitionMode::HighRes,
+            ] {
+                if ui.menu_item_config(tr(mode.name()))
+                        .selected(std::mem::discriminant(&self.acquisition_mode) ==
+                            std::mem::discriminant(&mode))
+                        .build() {
+                    new_mode = Some(mode);
+                }
+            }
+
+            ui.separator();
+            ui.align_text_to_frame_padding();
+            ui.text(tr("Average of"));
+            ui.same_line();
+            ui.set_next_item_width(150.0);
+            if ui.slider("##average_count", 2, 256, &mut self.average_count) &&
+                    matches!(self.acquisition_mode, AcquisitionMode::Average(_)) {
+                new_mode = Some(AcquisitionMode::Average(self.average_count as usize));
+            }
+
+            if let Some(new_mode) = new_mode {
+                self.acquisition_mode = new_mode;
+                // the sampler thread only goes away when the application is exiting
+                let _ = self.acquisition_send.send(new_mode);
+            }
+        });
+        open
+    }
+
+    fn render_event_log(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let Some(event_log) = self.event_log.as_ref() else { return };
+        ui.window(tr("Event Log"))
+            .opened(&mut self.event_log_opened)
+            .size([600.0, 300.0], Condition::FirstUseEver)
+            .build(|| {
+                for event in event_log.events().iter().rev() {
+                    ui.text(event.to_string());
+                }
+            });
+    }
+
+    fn update_roll(&mut self) {
+        // keep the stream flowing even if the roll view is closed, so that it is up to date
+        // when it is opened
+        while let Ok(chunk) = self.roll_recv.try_recv() {
+            // only the first channel in the stream is displayed
+            let roll_length = self.roll_length();
+            for &code in chunk.samples.iter().step_by(chunk.channels) {
+                if self.roll_history.len() >= roll_length {
+                    self.roll_history.pop_front();
+                }
+                self.roll_history.push_back(code as f32 / 128.0);
+            }
+        }
+    }
+
+    fn render_roll(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let values = self.roll_history.iter().copied().collect::<Vec<_>>();
+        ui.window(tr("Roll"))
+            .opened(&mut self.roll_opened)
+            .size([600.0, 200.0], Condition::FirstUseEver)
+            .build(|| {
+                ui.plot_lines("##roll", &values)
+                    .graph_size(ui.content_region_avail())
+                    .scale_min(-1.0)
+                    .scale_max(1.0)
+                    .build();
+            });
+    }
+
+    fn render_readouts(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let palette = self.palette();
+        ui.window(tr("Readouts"))
+            .opened(&mut self.readouts_opened)
+            .always_auto_resize(true)
+            .build(|| {
+                for (channel, readout) in self.readouts.iter().enumerate() {
+                    let Some(readout) = readout else { continue };
+                    let _t = ui.push_style_color(StyleColor::Text,
+                        palette.channel_color(channel));
+                    let dc = format!("{:+.4} V", readout.dc);
+                    let ac_rms = format!("{:.2} mV", readout.ac_rms * 1e3);
+                    ui.text(tr_format("CH{}  DC {}  AC RMS {}", &[&(channel + 1), &dc, &ac_rms]));
+                }
+                if self.readouts.iter().all(Option::is_none) {
+                    ui.text(tr("no data"));
+                } else if ui.small_button(tr("Copy CSV")) {
+                    ui.set_clipboard_text(clipboard::readouts_csv(&self.readouts));
+                }
+            });
+    }
+
+    /// Switch the acquisition mode and the open windows to those of `step` of the demo tour.
+    fn show_tour_step(&mut self, step: &TourStep) {
+        self.tour_caption = Some(step.caption);
+        if self.acquisition_mode != step.acquisition_mode {
+            self.acquisition_mode = step.acquisition_mode;
+            let _ = self.acquisition_send.send(step.acquisition_mode);
+        }
+        self.readouts_opened = step.windows.contains(&TourWindow::Readouts);
+        self.trend_opened = step.windows.contains(&TourWindow::Trend);
+        self.roll_opened = step.windows.contains(&TourWindow::Roll);
+    }
+
+    fn render_tour_caption(&self, ui: &imgui::Ui, caption: &'static str) {
+        use imgui::*;
+
+        let [width, _] = ui.io().display_size;
+        let _t = ui.push_style_color(StyleColor::WindowBg, [0.10, 0.10, 0.10, 0.85]);
+        ui.window("##tour")
+            .position([width / 2.0, 60.0], Condition::Always)
+            .position_pivot([0.5, 0.0])
+            .always_auto_resize(true)
+            .title_bar(false)
+            .movable(false)
+            .build(|| {
+                ui.text(tr(caption));
+            });
+    }
+
+    fn update_calibration(&mut self, waveform: &Waveform) {
+        let params = waveform.device_params();
+        self.uncalibrated = std::array::from_fn(|channel_index|
+            params.channels[channel_index].is_some() && !params.is_calibrated(channel_index));
+    }
+
+    fn update_annotations(&mut self, waveform: &Waveform) {
+        self.markers.clear();
+        let (Some(capture), Some(start)) = (waveform.capture(), waveform.start()) else { return };
+        let channels = capture.params().stream_channels();
+        self.markers = self.annotations.markers(&start, channels, capture.len());
+        self.markers_length = capture.len();
+    }
+
+    /// Measure the capture for the trend; returns the value, if it could be measured.
+    fn update_trend(&mut self, waveform: &Waveform) -> Option<f32> {
+        let capture = waveform.capture()?;
+        let (channel_index, samples) = capture.channels().into_iter().enumerate()
+            .find_map(|(index, samples)| samples.map(|samples| (index, samples)))?;
+        let params = capture.params();
+        let value = self.trend_measurement.measure(params, channel_index, samples)?;
+        if self.trend_history.len() >= self.trend_length() {
+            self.trend_history.pop_front();
+        }
+        self.trend_history.push_back((SystemTime::now(), value));
+        Some(value)
+    }
+
+    fn export_trend(&self) -> std::io::Result<String> {
+        use std::io::Write;
+
+        let since_epoch = |time: SystemTime|
+            time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+        let filename = format!("trend-{}.csv", since_epoch(SystemTime::now()) as u64);
+        let mut data = Vec::new();
+        writeln!(data, "time,{} ({})",
+            self.trend_measurement.name(), self.trend_measurement.unit())?;
+        for &(time, value) in self.trend_history.iter() {
+            writeln!(data, "{:.6},{}", since_epoch(time), value)?;
+        }
+        self.disk_writer.save(&filename, data)?;
+        Ok(filename)
+    }
+
+    fn render_trend(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let mut opened = self.trend_opened;
+        ui.window(tr("Trend"))
+            .opened(&mut opened)
+            .size([600.0, 250.0], Condition::FirstUseEver)
+            .build(|| {
+                let names = Measurement::ALL.map(|measurement| tr(measurement.name()));
+                let mut index = Measurement::ALL.iter()
+                    .position(|&measurement| measurement == self.trend_measurement).unwrap();
+                ui.set_next_item_width(150.0);
+                if ui.combo_simple_string("##measurement", &mut index, &names) {
+                    // values of different measurements cannot be plotted together
+                    self.trend_measurement = Measurement::ALL[index];
+                    self.trend_history.clear();
+                }
+                ui.same_line();
+                if ui.button(tr("Export CSV")) {
+                    match self.export_trend() {
+                        Ok(filename) => log::info!("exporting trend to {}", filename),
+                        Err(error) => log::error!("failed to export trend: {}", error),
+                    }
+                }
+                ui.same_line();
+                let values = self.trend_history.iter().map(|&(_, value)| value)
+                    .collect::<Vec<_>>();
+                match values.last() {
+                    Some(value) =>
+                        ui.text(format!("{:.4} {}", value, self.trend_measurement.unit())),
+                    None =>
+                        ui.text(tr("no data")),
+                }
+                ui.plot_lines("##trend", &values)
+                    .graph_size(ui.content_region_avail())
+                    .build();
+            });
+        self.trend_opened = opened;
+    }
+
+    fn render_limits(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        fn optional_bound(ui: &imgui::Ui, label: &str, bound: &mut Option<f32>) {
+            let mut enabled = bound.is_some();
+            if ui.checkbox(label, &mut enabled) {
+                *bound = if enabled { Some(0.0) } else { None };
+            }
+            if let Some(value) = bound.as_mut() {
+                ui.same_line();
+                ui.set_next_item_width(100.0);
+                ui.input_float(format!("##{}", label), value).build();
+            }
+        }
+
+        let mut opened = self.limits_opened;
+        ui.window(tr("Limits"))
+            .opened(&mut opened)
+            .size([350.0, 0.0], Condition::FirstUseEver)
+            .build(|| {
+                let editor = &mut self.limit_editor;
+                ui.checkbox(tr("Enabled"), &mut editor.enabled);
+                let names = Measurement::ALL.map(|measurement| tr(measurement.name()));
+                ui.combo_simple_string(tr("Measurement"), &mut editor.measurement_index, &names);
+                ui.combo_simple_string(tr("Channel"), &mut editor.channel_index,
+                    &["CH1", "CH2", "CH3", "CH4"]);
+                let unit = Measurement::ALL[editor.measurement_index].unit();
+                optional_bound(ui, &format!("Min ({})", unit), &mut editor.min);
+                optional_bound(ui, &format!("Max ({})", unit), &mut editor.max);
+                ui.separator();
+                ui.text(tr("On violation:"));
+                ui.checkbox(tr("Stop acquisition"), &mut editor.stop);
+                ui.checkbox(tr("Save capture"), &mut editor.save);
+                ui.checkbox(tr("Beep"), &mut editor.beep);
+                ui.input_text(tr("Command"), &mut editor.command).build();
+                if ui.button(tr("Apply")) {
+                    // the sampler thread only goes away when the application is exiting
+                    let _ = self.limits_send.send(editor.rules());
+                }
+            });
+        self.limits_opened = opened;
+    }
+
+    #[cfg(feature = "audio")]
+    fn render_audio_monitor(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let palette = self.palette();
+        let Some(monitor) = self.audio_monitor.as_mut() else { return };
+        ui.window(tr("Audio Monitor"))
+            .opened(&mut self.audio_opened)
+            .always_auto_resize(true)
+            .build(|| {
+                let mut playing = monitor.is_playing();
+                if ui.checkbox(tr("Play"), &mut playing) {
+                    monitor.set_playing(playing);
+                }
+                for (channel, label) in ["CH1", "CH2", "CH3", "CH4"].iter().enumerate() {
+                    ui.same_line();
+                    let _t = ui.push_style_color(StyleColor::Text,
+                        palette.channel_color(channel));
+                    if ui.radio_button_bool(label, monitor.channel() == channel) {
+                        monitor.set_channel(channel);
+                    }
+                }
+                let mut volume = monitor.volume();
+                ui.set_next_item_width(200.0);
+                if ui.slider_config(tr("Volume"), 0.0, 4.0)
+                        .display_format("%.2fx")
+                        .build(&mut volume) {
+                    monitor.set_volume(volume);
+                }
+                if let Some(error) = monitor.error() {
+                    ui.text_colored([0.8, 0.0, 0.0, 1.0],
+                        tr_format("Cannot play audio: {}", &[&error]));
+                }
+            });
+    }
+
+    fn update_comparison(&mut self, waveform: &Waveform) {
+        // copying the capture is only worth it while the comparison is in use
+        if self.compare_opened {
+            self.comparison.update(waveform);
+            let failed = match self.comparison.verdict() {
+                Verdict::Fail(count) => Some(count),
+                _ => None,
+            };
+            if let (Some(count), false) = (failed, self.mask_failed) {
+                self.notifier.notify(NotifyEvent::MaskFailure,
+                    &tr_format("{} samples outside of tolerance", &[&count]));
+            }
+            self.mask_failed = failed.is_some();
+        }
+    }
+
+    fn update_notifications(&mut self, waveform: &Waveform) {
+        // in single mode, every waveform that is acquired has been triggered
+        if let (true, Some(trigger)) = (waveform.params().mode.is_single(), waveform.trigger()) {
+            self.notifier.notify(NotifyEvent::SingleTrigger,
+                &tr_format("Triggered at sample {}", &[&trigger.sample]));
+        }
+    }
+
+    fn update_history(&mut self, waveform: &Waveform, value: Option<f32>) {
+        let Some(display) = waveform.display_data() else { return };
+        let passed = match self.comparison.verdict() {
+            _ if !self.compare_opened => None,
+            Verdict::Pass => Some(true),
+            Verdict::Fail(_) => Some(false),
+            _ => None,
+        };
+        let value = value.map(|value| (value, self.trend_measurement.unit()));
+        self.history.push(display, waveform.display_delay(), passed, value);
+    }
+
+    fn render_history(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        const THUMBNAIL_SIZE: [f32; 2] = [THUMBNAIL_COLUMNS as f32 * 1.5, 80.0];
+
+        let color = self.palette().channel_color(0);
+        let mut opened = self.history_opened;
+        ui.window(tr("History"))
+            .opened(&mut opened)
+            .size([800.0, 0.0], Condition::FirstUseEver)
+            .build(|| {
+                let history = &mut self.history;
+                ui.disabled(history.selected().is_none(), || {
+                    if ui.button(tr("Show live capture")) {
+                        history.select(None);
+                    }
+                });
+                ui.same_line();
+                if ui.button(tr("Clear")) {
+                    history.clear();
+                }
+                ui.same_line();
+                match history.selected_index() {
+                    Some(index) => ui.text(tr_format("Showing capture {} of {}",
+                        &[&(index + 1), &history.entries().len()])),
+                    None => ui.text(tr("Click a capture to show it in the main view.")),
+                }
+                let line_height = ui.text_line_height_with_spacing();
+                let mut clicked = None;
+                ui.child_window("##strip")
+                    .size([0.0, THUMBNAIL_SIZE[1] + line_height * 2.0])
+                    .horizontal_scrollbar(true)
+                    .build(|| {
+                        for (index, entry) in history.entries().iter().enumerate() {
+                            if index > 0 {
+                                ui.same_line();
+                            }
+                            ui.group(|| {
+                                if ui.invisible_button(format!("##capture{}", index),
+                                        THUMBNAIL_SIZE) {
+                                    clicked = Some(index);
+                                }
+                                if ui.is_item_hovered() {
+                                    let age = entry.time.elapsed().unwrap_or_default();
+                                    ui.tooltip_text(tr_format("Captured {} s ago",
+                                        &[&format!("{:.1}", age.as_secs_f32())]));
+                                }
+                                let ([l, t], [r, b]) = (ui.item_rect_min(), ui.item_rect_max());
+                                let draw_list = ui.get_window_draw_list();
+                                draw_list.add_rect([l, t], [r, b], [0.1, 0.0, 0.1, 1.0])
+                                    .filled(true).build();
+                                let column_width = (r - l) / entry.thumbnail.len() as f32;
+                                let y = |code: i8| (t + b) / 2.0 - code as f32 / 128.0 *
+                                    (b - t) / 2.0;
+                                for (column, &[min, max]) in entry.thumbnail.iter().enumerate() {
+                                    let x = l + (column as f32 + 0.5) * column_width;
+                                    draw_list.add_line([x, y(min)], [x, y(max) - 1.0], color)
+                                        .build();
+                                }
+                                if history.selected_index() == Some(index) {
+                                    draw_list.add_rect([l, t], [r, b], [1.0, 1.0, 0.0, 1.0])
+                                        .thickness(2.0).build();
+                                }
+                                match entry.passed {
+                                    Some(true) =>
+                                        ui.text_colored([0.0, 0.6, 0.0, 1.0], tr("Pass")),
+                                    Some(false) =>
+                                        ui.text_colored([0.8, 0.0, 0.0, 1.0], tr("Fail")),
+                                    None =>
+                                        ui.text(""),
+                                }
+                                if let Some((value, unit)) = entry.value {
+                                    ui.text(format!("{:.4} {}", value, unit));
+                                }
+                            });
+                        }
+                        // follow the most recent capture unless one is being looked at
+                        if history.selected().is_none() {
+                            ui.set_scroll_x(ui.scroll_max_x());
+                        }
+                    });
+                if clicked.is_some() {
+                    history.select(clicked);
+                }
+            });
+        self.history_opened = opened;
+    }
+
+    fn render_compare(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let mut opened = self.compare_opened;
+        ui.window(tr("Compare"))
+            .opened(&mut opened)
+            .size([600.0, 250.0], Condition::FirstUseEver)
+            .build(|| {
+                let comparison = &mut self.comparison;
+                ui.disabled(!comparison.has_live(), || {
+                    if ui.button(tr("Store reference")) {
+                        comparison.store_reference();
+                    }
+                });
+                ui.same_line();
+                ui.disabled(!comparison.has_reference(), || {
+                    if ui.button(tr("Clear reference")) {
+                        comparison.clear_reference();
+                    }
+                    ui.same_line();
+                    if ui.button(tr("Export CSV")) {
+                        match comparison.export_csv(&self.disk_writer) {
+                            Ok(filename) => log::info!("exporting residual to {}", filename),
+                            Err(error) => log::error!("failed to export residual: {}", error),
+                        }
+                    }
+                });
+                ui.same_line();
+                ui.set_next_item_width(150.0);
+                ui.slider(tr("Tolerance, codes"), 0, 127, &mut comparison.tolerance);
+                match comparison.verdict() {
+                    Verdict::NoReference =>
+                        ui.text(tr("No reference stored.")),
+                    Verdict::Incompatible =>
+                        ui.text(tr("The channel configuration differs from the reference.")),
+                    Verdict::Pass =>
+                        ui.text_colored([0.0, 0.6, 0.0, 1.0], tr("Pass")),
+                    Verdict::Fail(count) =>
+                        ui.text_colored([0.8, 0.0, 0.0, 1.0],
+                            tr_format("Fail: {} samples outside of tolerance", &[&count])),
+                }
+                if comparison.is_unaligned() {
+                    ui.same_line();
+                    ui.text(tr("(capture is not triggered, and may not be aligned)"));
+                }
+                let values = comparison.residual().iter().map(|&value| value as f32)
+                    .collect::<Vec<_>>();
+                let scale = (comparison.tolerance as f32 * 2.0).max(16.0);
+                ui.plot_lines("##residual", &values)
+                    .graph_size(ui.content_region_avail())
+                    .scale_min(-scale)
+                    .scale_max(scale)
+                    .build();
+                // draw the tolerance band over the plot
+                let ([l, t], [r, b]) = (ui.item_rect_min(), ui.item_rect_max());
+                let draw_list = ui.get_window_draw_list();
+                for bound in [-1.0, 1.0] {
+                    let y = (t + b) / 2.0 -
+                        bound * comparison.tolerance as f32 / scale * (b - t) / 2.0;
+                    draw_list.add_line([l, y], [r, y], [0.8, 0.0, 0.0, 1.0]).build();
+                }
+            });
+        self.compare_opened = opened;
+    }
+
+    fn render_profiler(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        if self.profiled_at.elapsed() >= PROFILE_INTERVAL {
+            self.profile = self.profiler.take();
+            self.profiled_at = Instant::now();
+        }
+        let lines = Stage::ALL.iter().zip(self.profile.iter()).map(|(stage, summary)| {
+            format!("{:<14} {:>8.2} {:>8.2} {:>6}", tr(stage.name()),
+                summary.mean.as_secs_f64() * 1e3, summary.max.as_secs_f64() * 1e3,
+                summary.count as f64 / PROFILE_INTERVAL.as_secs_f64())
+        }).collect::<Vec<_>>();
+        let [width, height] = ui.io().display_size;
+        ui.window("##profiler")
+            .position([width, height], Condition::Always)
+            .position_pivot([1.0, 1.0])
+            .always_auto_resize(true)
+            .bg_alpha(0.75)
+            .no_decoration()
+            .movable(false)
+            .build(|| {
+                ui.text(format!("{:<14} {:>8} {:>8} {:>6}", "", tr("mean, ms"), tr("max, ms"),
+                    tr("per s")));
+                for line in lines.iter() {
+                    ui.text(line);
+                }
+                if ui.small_button(tr("Log")) {
+                    log::info!("profile (stage, mean ms, max ms, per s):\n{}", lines.join("\n"));
+                }
+            });
+    }
+
+    fn render_waveform_menu(&mut self, ui: &imgui::Ui) -> bool {
+        let mut open = false;
+        // the popup opens at the mouse cursor, which is where the finger is
+        if let Some(position) = self.waveform_menu_request.take() {
+            self.waveform_menu_position = position;
+            ui.open_popup("##waveform");
+        }
+        ui.popup("##waveform", || {
+            open = true;
+            let [width, _] = ui.io().display_size;
+            let center = self.waveform_menu_position[0] / width;
+            if ui.menu_item(tr("Zoom in")) {
+                self.time_view.zoom_at(center, 2.0);
+            }
+            if ui.menu_item_config(tr("Zoom out")).enabled(self.time_view.is_zoomed()).build() {
+                self.time_view.zoom_at(center, 0.5);
+            }
+            if ui.menu_item_config(tr("Reset zoom")).enabled(self.time_view.is_zoomed()).build() {
+                self.time_view = TimeView::default();
+            }
+            ui.separator();
+            // only the display data is kept for the captures in the history
+            if ui.menu_item_config(tr("Copy visible data as CSV"))
+                    .enabled(self.history.selected().is_none()).build() {
+                self.copy_waveform_request = true;
+            }
+        });
+        open
+    }
+
+    fn render_preferences(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        let mut opened = self.preferences_opened;
+        ui.window(tr("Preferences"))
+            .opened(&mut opened)
+            .size([400.0, 0.0], Condition::FirstUseEver)
+            .build(|| {
+                let names = Palette::ALL.map(|palette| tr(palette.name()));
+                let mut index = Palette::ALL.iter()
+                    .position(|&palette| palette == self.settings.palette).unwrap();
+                if ui.combo_simple_string(tr("Channel colors"), &mut index, &names) {
+                    self.settings.palette = Palette::ALL[index];
+                    self.settings.save();
+                }
+                for channel_index in 0..4 {
+                    let color = self.settings.palette.channel_color(channel_index);
+                    ui.text_colored(color, format!("CH{}", channel_index + 1));
+                    ui.same_line();
+                }
+                ui.new_line();
+                ui.separator();
+                ui.text(tr("Acquisition thread (takes effect after restart)"));
+                let scheduling = &mut self.settings.acquisition_scheduling;
+                let mut realtime = scheduling.realtime_priority.is_some();
+                if ui.checkbox(tr("Real-time priority"), &mut realtime) {
+                    scheduling.realtime_priority = realtime.then_some(REALTIME_PRIORITY);
+                    self.settings.save();
+                }
+                let scheduling = &mut self.settings.acquisition_scheduling;
+                if let Some(priority) = scheduling.realtime_priority.as_mut() {
+                    ui.slider(tr("Priority"), 1, 99, priority);
+                    if ui.is_item_deactivated_after_edit() {
+                        self.settings.save();
+                    }
+                }
+                if ui.input_text(tr("CPU cores"), &mut self.cpu_affinity_text)
+                        .enter_returns_true(true)
+                        .build() {
+                    let cores = self.cpu_affinity_text.split(',')
+                        .map(str::trim)
+                        .filter(|core| !core.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<Vec<usize>, _>>();
+                    match cores {
+                        Ok(cores) => {
+                            self.settings.acquisition_scheduling.cpu_affinity = cores;
+                            self.settings.save();
+                        }
+                        Err(error) => log::warn!("ignoring CPU cores {:?}: {}",
+                            self.cpu_affinity_text, error),
+                    }
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(tr("Comma-separated; empty to allow any core."));
+                }
+                if ui.checkbox(tr("Verify captures"), &mut self.settings.verify_captures) {
+                    self.settings.save();
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(tr("Discard captures corrupted in memory after acquisition."));
+                }
+                if ui.checkbox(tr("Lower sample rate on overflow"),
+                        &mut self.settings.degrade_on_overflow) {
+                    self.settings.save();
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(
+                        tr("Keep capturing at a lower sample rate if the computer cannot keep up."));
+                }
+                let names = DriftTracking::ALL.map(|drift_tracking| tr(drift_tracking.name()));
+                let mut index = DriftTracking::ALL.iter()
+                    .position(|&drift_tracking| drift_tracking == self.settings.drift_tracking)
+                    .unwrap();
+                if ui.combo_simple_string(tr("Warm-up drift"), &mut index, &names) {
+                    self.settings.drift_tracking = DriftTracking::ALL[index];
+                    self.settings.save();
+                }
+                if ui.is_item_hovered() {
+                    ui.tooltip_text(tr("Track the baseline of idle channels while warming up."));
+                }
+                if self.settings.drift_tracking != DriftTracking::Off {
+                    let mut warm_up = (self.settings.warm_up().as_secs() / 60) as u32;
+                    if ui.slider(tr("Warm-up, minutes"), 1, 120, &mut warm_up) {
+                        self.settings.warm_up_minutes = Some(warm_up);
+                    }
+                    if ui.is_item_deactivated_after_edit() {
+                        self.settings.save();
+                    }
+                }
+                ui.separator();
+                self.render_notification_preferences(ui);
+                ui.separator();
+                let mut memory_budget = (self.settings.memory_budget() >> 20) as u32;
+                if ui.slider(tr("Memory budget, MiB"), 16, 4096, &mut memory_budget) {
+                    self.settings.memory_budget = Some(memory_budget);
+                }
+                if ui.is_item_deactivated_after_edit() {
+                    self.budget.set_limit(self.settings.memory_budget());
+                    self.reserve_history();
+                    self.settings.save();
+                }
+                let used = format!("{:.1}", self.budget.used() as f32 / (1 << 20) as f32);
+                let limit = self.budget.limit() >> 20;
+                ui.text(tr_format("In use: {} of {} MiB", &[&used, &limit]));
+            });
+        self.preferences_opened = opened;
+    }
+
+    fn render_notification_preferences(&mut self, ui: &imgui::Ui) {
+        let notifications = &mut self.settings.notifications;
+        let mut changed = false;
+        ui.text(tr("Notify when"));
+        changed |= ui.checkbox(tr("A single capture is triggered"),
+            &mut notifications.single_trigger);
+        changed |= ui.checkbox(tr("A capture differs from the reference"),
+            &mut notifications.mask_failure);
+        changed |= ui.checkbox(tr("A limit is violated"), &mut notifications.limit_alarm);
+        changed |= ui.checkbox(tr("Play a sound"), &mut notifications.sound);
+        if notifications.sound {
+            let mut sound_file = notifications.sound_file.clone().unwrap_or_default();
+            if ui.input_text(tr("Sound file"), &mut sound_file).build() {
+                notifications.sound_file = Some(sound_file).filter(|path| !path.is_empty());
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text(tr("Empty to ring the terminal bell."));
+            }
+            changed |= ui.is_item_deactivated_after_edit();
+        }
+        changed |= ui.checkbox(tr("Show a desktop notification"), &mut notifications.desktop);
+        if changed {
+            self.notifier.configure(self.settings.notifications.clone());
+            self.settings.save();
+        }
+    }
+
+    fn render_status_bar(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        while let Ok(status) = self.status_recv.try_recv() {
+            self.acquisition_status = status;
+        }
+        let [_, height] = ui.io().display_size;
+        if let AcquisitionStatus::Drifting { channel, drift } = self.acquisition_status {
+            let _t = ui.push_style_color(StyleColor::WindowBg, [1.00, 0.95, 0.70, 1.00]);
+            ui.window("##status")
+                .position([0.0, height], Condition::Always)
+                .position_pivot([0.0, 1.0])
+                .always_auto_resize(true)
+                .title_bar(false)
+                .movable(false)
+                .build(|| {
+                    let drift = format!("{:+.1} mV", drift * 1e3);
+                    ui.text(tr_format("CH{} baseline drifted by {} while warming up",
+                        &[&(channel + 1), &drift]));
+                });
+        }
+        if let AcquisitionStatus::Degraded { sample_rate } = self.acquisition_status {
+            let _t = ui.push_style_color(StyleColor::WindowBg, [1.00, 0.95, 0.70, 1.00]);
+            ui.window("##status")
+                .position([0.0, height], Condition::Always)
+                .position_pivot([0.0, 1.0])
+                .always_auto_resize(true)
+                .title_bar(false)
+                .movable(false)
+                .build(|| {
+                    let rate = sample_rate.samples_per_second() / 1e6;
+                    ui.text(tr_format(
+                        "Sample rate lowered to {} MS/s, since the computer could not keep up",
+                        &[&rate]));
+                });
+        }
+        let AcquisitionStatus::Failed(ref error) = self.acquisition_status else { return };
+        let _t = ui.push_style_color(StyleColor::WindowBg, [1.00, 0.85, 0.85, 1.00]);
+        ui.window("##status")
+            .position([0.0, height], Condition::Always)
+            .position_pivot([0.0, 1.0])
+            .always_auto_resize(true)
+            .title_bar(false)
+            .movable(false)
+            .build(|| {
+                ui.text(tr_format("Acquisition stopped: {}", &[error]));
+                ui.same_line();
+                if ui.button(tr("Restart acquisition")) {
+                    // the sampler thread may have exited if the error was not recoverable
+                    if self.recover_send.send(()).is_err() {
+                        log::warn!("cannot restart acquisition: sampler has exited");
+                    }
+                }
+            });
+    }
+
+    fn render(&mut self, ui: &imgui::Ui) {
+        use imgui::*;
+
+        // single key shortcuts are disabled while typing into a text field
+        let shortcuts = !ui.io().want_text_input;
+        // `Escape` closes a popup (processed before the frame starts) instead of exiting
+        let popup_was_open = std::mem::replace(&mut self.popup_open, false);
+
+        let mut state = InterfaceState::default();
+        self.render_controls(ui, &mut state);
+
+        if state != InterfaceState::default() {
+            log::info!("{:?}", state)
+        }
+        if state.run_stop_clicked {
+            // the configuration and the waveform on display are kept while paused
+            self.paused = !self.paused;
+            if self.pause_send.send(self.paused).is_err() {
+                log::warn!("cannot pause or resume acquisition: sampler has exited");
+            }
+        }
+        if state.trigger_clicked {
+            ui.open_popup(tr("Trigger"));
+        }
+        self.popup_open |= self.render_trigger_config_popup(ui);
+        if state.acquisition_clicked {
+            ui.open_popup(tr("Acquisition"));
+        }
+        self.popup_open |= self.render_acquisition_mode_popup(ui);
+
+        if shortcuts && ui.is_key_pressed(Key::L) {
+            self.event_log_opened = !self.event_log_opened;
+        }
+        if self.event_log_opened {
+            self.render_event_log(ui);
+        }
+
+        self.update_roll();
+        // the readouts are computed from the stream, so they are updated even while idle
+        if let Some(readouts) = self.readout_recv.try_iter().last() {
+            self.readouts = readouts;
+        }
+        if shortcuts && ui.is_key_pressed(Key::V) {
+            self.readouts_opened = !self.readouts_opened;
+        }
+        if self.readouts_opened {
+            self.render_readouts(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::R) {
+            self.roll_opened = !self.roll_opened;
+        }
+        if self.roll_opened {
+            self.render_roll(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::T) {
+            self.trend_opened = !self.trend_opened;
+        }
+        if self.trend_opened {
+            self.render_trend(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::A) {
+            self.limits_opened = !self.limits_opened;
+        }
+        if self.limits_opened {
+            self.render_limits(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::C) {
+            self.compare_opened = !self.compare_opened;
+        }
+        if self.compare_opened {
+            self.render_compare(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::H) {
+            self.history_opened = !self.history_opened;
+        }
+        if self.history_opened {
+            self.render_history(ui);
+        }
+
+        #[cfg(feature = "audio")]
+        {
+            if shortcuts && ui.is_key_pressed(Key::M) {
+                self.audio_opened = !self.audio_opened;
+            }
+            if self.audio_opened {
+                self.render_audio_monitor(ui);
+            }
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::F) {
+            self.profiler_opened = !self.profiler_opened;
+            self.profiler.set_enabled(self.profiler_opened);
+            // timings recorded before the overlay was closed would skew the first period
+            self.profiler.take();
+            self.profiled_at = Instant::now();
+        }
+        if self.profiler_opened {
+            self.render_profiler(ui);
+        }
+
+        if shortcuts && ui.is_key_pressed(Key::P) {
+            self.preferences_opened = !self.preferences_opened;
+        }
+        if self.preferences_opened {
+            self.render_preferences(ui);
+        }
+
+        if shortcuts && (ui.is_key_pressed(Key::Equal) || ui.is_key_pressed(Key::KeypadAdd)) {
+            self.time_view.zoom_at(0.5, 2.0);
+        }
+        if shortcuts && (ui.is_key_pressed(Key::Minus) || ui.is_key_pressed(Key::KeypadSubtract)) {
+            self.time_view.zoom_at(0.5, 0.5);
+        }
+        if shortcuts && ui.is_key_pressed(Key::Alpha0) {
+            self.time_view = TimeView::default();
+        }
+        if shortcuts && ui.is_key_pressed(Key::Comma) {
+            self.time_view.pan(0.25);
+        }
+        if shortcuts && ui.is_key_pressed(Key::Period) {
+            self.time_view.pan(-0.25);
+        }
+        self.popup_open |= self.render_waveform_menu(ui);
+
+        if let Some(caption) = self.tour_caption {
+            self.render_tour_caption(ui, caption);
+        }
+        self.render_status_bar(ui);
+
+        if shortcuts && !popup_was_open && ui.is_key_pressed(Key::Escape) {
+            std::process::exit(0);
+        }
+
+        // ui.show_demo_window(&mut true);
+    }
+}
+
+struct Application {
+    gl_context: PossiblyCurrentContext,
+    gl_surface: Surface<WindowSurface>,
+    gl_library: GlowContext,
+    wfm_renderer: WaveformRenderer,
+    imgui_context: imgui::Context,
+    imgui_platform: imgui_winit_support::WinitPlatform,
+    imgui_texture_map: imgui_glow_renderer::SimpleTextureMap,
+    imgui_renderer: imgui_glow_renderer::Renderer,
+    ui_state: InterfaceRenderer,
+    window: Window,
+    setup: Option<setup::SetupWizard>,
+    params_send: Sender<Parameters>,
+    sampler: Option<capture::Sampler>,
+    sampler_thread: Option<std::thread::JoinHandle<thunderscope::Result<()>>>,
+    tour: Option<Tour>,
+    gestures: GestureRecognizer,
+    // the finger that acts as the mouse, if any
+    touch_mouse: Option<u64>,
+}
+
+impl Application {
+    fn start_acquisition(&mut self, data_source: capture::DataSource, settings: &Settings) {
+        if let capture::DataSource::Hardware(ref device) = data_source {
+            self.ui_state.event_log = Some(device.event_log());
+        }
+        let params = Parameters::demo(settings.probe_attenuation());
+        // the recorded samples were acquired with parameters that cannot be changed
+        let params = match &data_source {
+            capture::DataSource::Recording(source) =>
+                Parameters { device: *source.params(), ..params },
+            _ => params,
+        };
+        self.params_send.send(params).unwrap();
+        self.ui_state.settings = settings.clone();
+        self.ui_state.notifier.configure(settings.notifications.clone());
+        self.ui_state.cpu_affinity_text = settings.acquisition_scheduling.cpu_affinity.iter()
+            .map(|core| core.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut sampler = self.sampler.take().expect("acquisition already started");
+        sampler.schedule_with(settings.acquisition_scheduling.clone());
+        sampler.track_drift(settings.drift_tracking, settings.warm_up());
+        sampler.degrade_on_overflow(settings.degrade_on_overflow);
+        self.sampler_thread = Some(sampler.run(data_source));
+    }
+
+    /// Let the first finger act as the mouse, so that the widgets can be operated by touch.
+    /// The emulated mouse button is released as soon as the touches are recognized as a gesture,
+    /// to avoid e.g. dragging a window while zooming, or activating a context menu item.
+    fn emulate_mouse(&mut self, id: u64, phase: TouchPhase, position: [f32; 2],
+                     gesture_recognized: bool) {
+        let io = self.imgui_context.io_mut();
+        match phase {
+            TouchPhase::Started if self.touch_mouse.is_none() &&
+                    !self.gestures.is_multi_touch() => {
+                self.touch_mouse = Some(id);
+                io.add_mouse_pos_event(position);
+                io.add_mouse_button_event(imgui::MouseButton::Left, true);
+            }
+            TouchPhase::Moved if self.touch_mouse == Some(id) =>
+                io.add_mouse_pos_event(position),
+            TouchPhase::Ended | TouchPhase::Cancelled if self.touch_mouse == Some(id) => {
+                self.touch_mouse = None;
+                io.add_mouse_button_event(imgui::MouseButton::Left, false);
+            }
+            _ => ()
+        }
+        if gesture_recognized || self.gestures.is_multi_touch() {
+            self.release_touch_mouse();
+        }
+    }
+
+    fn release_touch_mouse(&mut self) {
+        if self.touch_mouse.take().is_some() {
+            self.imgui_context.io_mut().add_mouse_button_event(imgui::MouseButton::Left, false);
+        }
+    }
+
+    fn handle_gesture(&mut self, gesture: Gesture) {
+        let [width, _] = self.imgui_context.io().display_size;
+        match gesture {
+            Gesture::Transform { center, zoom, pan } => {
+                let view = &mut self.ui_state.time_view;
+                view.pan(pan / width);
+                view.zoom_at(center / width, zoom);
+            }
+            Gesture::LongPress { position } => {
+                self.release_touch_mouse();
+                // widgets under the finger take priority over the waveform area
+                if !self.imgui_context.io().want_capture_mouse {
+                    self.ui_state.waveform_menu_request = Some(position);
+                }
+            }
+        }
+        self.window.request_redraw();
+    }
+
+    fn rescale(&mut self, scale_factor: f64) {
+        log::info!("rescaling UI by a factor of {:.2}×", scale_factor);
+        self.imgui_platform.attach_window(self.imgui_context.io_mut(), &self.window,
+            imgui_winit_support::HiDpiMode::Locked(scale_factor));
+        self.ui_state.rescale(&mut self.imgui_context, scale_factor);
+        // the renderer only uploads the font atlas when it is created
+        self.imgui_renderer.destroy(&self.gl_library);
+        self.imgui_renderer = imgui_glow_renderer::Renderer::initialize(&self.gl_library,
+                &mut self.imgui_context, &mut self.imgui_texture_map, /*output_srgb=*/true)
+            .expect("failed to create UI renderer");
+        self.window.request_redraw();
+    }
+
+    fn process_event<T>(&mut self, event: Event<T>, window_target: &EventLoopWindowTarget<T>) {
+        match event {
+            Event::NewEvents(StartCause::ResumeTimeReached { requested_resume, .. }) => {
+                // handle waveform updates
+                if self.wfm_renderer.poll() {
+                    if let Some(waveform) = self.wfm_renderer.current() {
+                        self.ui_state.update_calibration(waveform);
+                        let value = self.ui_state.update_trend(waveform);
+                        self.ui_state.update_annotations(waveform);
+                        self.ui_state.update_comparison(waveform);
+                        self.ui_state.update_notifications(waveform);
+                        self.ui_state.update_history(waveform, value);
+                    }
+                    self.window.request_redraw();
+                }
+                // advance the demo tour, if it is running
+                if let Some(tour) = self.tour.as_mut() {
+                    if let Some(step) = tour.poll(Instant::now()).cloned() {
+                        let _ = self.params_send.send(tour.params(&step));
+                        self.ui_state.show_tour_step(&step);
+                        self.window.request_redraw();
+                    }
+                }
+                // quit once acquisition has been interrupted with ^C (and the device shut down)
+                if self.sampler_thread.as_ref().is_some_and(|thread| thread.is_finished()) {
+                    let sampler_thread = self.sampler_thread.take().unwrap();
+                    match sampler_thread.join().expect("acquisition thread panicked") {
+                        Ok(()) => (),
+                        Err(thunderscope::Error::Interrupted) => window_target.exit(),
+                        // errors have already been shown in the status bar
+                        Err(error) => log::error!("acquisition failed: {}", error),
+                    }
+                }
+                // keep rendering the setup wizard while it waits for the self-test to finish
+                if self.setup.as_ref().is_some_and(|setup| setup.is_busy()) {
+                    self.window.request_redraw();
+                }
+                // handle gestures that are recognized without any touch events
+                if let Some(gesture) = self.gestures.poll(Instant::now()) {
+                    self.handle_gesture(gesture);
+                }
+                // handle UI updates
+                self.imgui_context.io_mut().update_delta_time(
+                    Instant::now().duration_since(requested_resume));
+                self.imgui_platform.prepare_frame(self.imgui_context.io_mut(), &self.window)
+                    .expect("failed to prepare UI frame");
+                // The `winit` documentation recommends `Poll`, but if no waveforms are acquired,
+                // this results in a busy loop waiting on `self.renderer.poll()`, pegging a core.
+                // A 5 ms delay should be enough for even a 200 Hz display.
+                window_target.set_control_flow(
+                    ControlFlow::wait_duration(Duration::from_millis(5)));
+            }
+            Event::WindowEvent { event: WindowEvent::RedrawRequested, .. } => {
+                self.window.pre_present_notify();
+                let profiler = self.ui_state.profiler.clone();
+                // draw waveforms
+                let pinned = self.ui_state.history.selected()
+                    .map(|entry| (&entry.display[..], entry.delay));
+                profiler.time(Stage::Upload, || self.wfm_renderer.render(&self.gl_library,
+                    &self.ui_state.time_view, pinned, self.ui_state.palette().channel_color(0)));
+                // draw UI widgets
+                let setup_result = profiler.time(Stage::Interface, || {
+                    let ui = self.imgui_context.frame();
+                    self.ui_state.render(ui);
+                    if std::mem::take(&mut self.ui_state.copy_waveform_request) {
+                        self.wfm_renderer.copy_visible(ui, &self.ui_state.time_view);
+                    }
+                    let setup_result = self.setup.as_mut().and_then(|setup| setup.render(ui));
+                    self.imgui_platform.prepare_render(ui, &self.window);
+                    self.imgui_renderer.render(
+                            &self.gl_library, &self.imgui_texture_map, self.imgui_context.render())
+                        .expect("failed to render UI");
+                    setup_result
+                });
+                // start acquisition once setup is complete
+                if let Some((data_source, settings)) = setup_result {
+                    self.setup = None;
+                    self.start_acquisition(data_source, &settings);
+                    self.window.request_redraw();
+                }
+                // handle OpenGL
+                profiler.time(Stage::Swap, || self.gl_surface.swap_buffers(&self.gl_context))
+                    .expect("failed to swap buffers");
+            }
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. }
+                    if size.width != 0 && size.height != 0 => {
+                self.wfm_renderer.resize(&self.gl_library, size.width, size.height);
+                self.imgui_platform.handle_event(self.imgui_context.io_mut(), &self.window, &event);
+                self.gl_surface.resize(&self.gl_context,
+                    NonZeroU32::new(size.width).unwrap(),
+                    NonZeroU32::new(size.height).unwrap(),
+                );
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ScaleFactorChanged { scale_factor, .. }, ..
+            } => {
+                // a `Resized` event follows, which updates the waveform renderer and surface
+                self.rescale(scale_factor);
+            }
+            Event::WindowEvent { event: WindowEvent::Touch(touch), .. } => {
+                let location = touch.location.to_logical::<f32>(self.window.scale_factor());
+                let position = [location.x, location.y];
+                let gesture = self.gestures.touch(touch.id, touch.phase, position, Instant::now());
+                self.emulate_mouse(touch.id, touch.phase, position, gesture.is_some());
+                if let Some(gesture) = gesture {
+                    self.handle_gesture(gesture);
+                }
+                self.window.request_redraw();
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                window_target.exit();
+            }
+            Event::LoopExiting => {
+                self.wfm_renderer.destroy(&self.gl_library);
+                self.imgui_renderer.destroy(&self.gl_library);
+            }
+            event => {
+                self.imgui_platform.handle_event(self.imgui_context.io_mut(), &self.window, &event);
+                self.window.request_redraw();
+            }
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: thunderscope-gui [--record SESSION-DIR | --replay SESSION-DIR \
+               [--seek CAPTURE]] [--tour] [--play RECORDING] \
+               [--annotations FILE] [--trigger-log FILE] [--import FILE \
+               [--import-format csv|f32|i16[:VOLTS]|i8[:VOLTS]] \
+               [--import-rate SAMPLES-PER-SECOND] [--import-channels COUNT]]");
+    std::process::exit(2)
+}
+
+fn main() {
+    let mut record_path = None;
+    let mut replay_path = None;
+    let mut annotations_path = None;
+    let mut trigger_log_path = None;
+    let mut import_path = None;
+    let mut play_path = None;
+    let mut import_format = import::ImportFormat::Csv;
+    let mut import_rate = None;
+    let mut import_channels = 1;
+    let mut seek_capture: Option<usize> = None;
+    let mut tour = false;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        let target = match arg.to_str() {
+            Some("--record") => &mut record_path,
+            Some("--replay") => &mut replay_path,
+            Some("--annotations") => &mut annotations_path,
+            Some("--trigger-log") => &mut trigger_log_path,
+            Some("--import") => &mut import_path,
+            Some("--play") => &mut play_path,
+            Some("--tour") => {
+                tour = true;
+                continue
+            }
+            Some(option @ ("--import-format" | "--import-rate" | "--import-channels" |
+                           "--seek")) => {
+                let value = args.next().and_then(|value| value.into_string().ok())
+                    .unwrap_or_else(|| usage());
+                let valid = match option {
+                    "--import-format" => value.parse().map(|format| import_format = format).is_ok(),
+                    "--import-rate" => value.parse().map(|rate| import_rate = Some(rate)).is_ok(),
+                    "--seek" => value.parse().map(|capture| seek_capture = Some(capture)).is_ok(),
+                    _ => value.parse().map(|count| import_channels = count).is_ok(),
+                };
+                if !valid { usage() }
+                continue
+            }
+            _ => usage()
+        };
+        *target = Some(PathBuf::from(args.next().unwrap_or_else(|| usage())));
+    }
+    let sources = [replay_path.is_some(), import_path.is_some(), play_path.is_some(), tour];
+    if sources.iter().filter(|&&source| source).count() > 1 ||
+            seek_capture.is_some() && replay_path.is_none() {
+        usage()
+    }
+    let import = import_path.map(|path| {
+        import::Import::load(&path, import_format, import_rate, import_channels)
+            .unwrap_or_else(|error| {
+                eprintln!("cannot import {}: {}", path.display(), error);
+                std::process::exit(1)
+            })
+    });
+    let recording = play_path.map(|path| {
+        thunderscope::ReplaySource::open(&path).unwrap_or_else(|error| {
+            eprintln!("cannot open recording {}: {}", path.display(), error);
+            std::process::exit(1)
+        })
+    });
+    let replay_session = replay_path.map(|path| {
+        let mut session = session::Session::load(&path).unwrap_or_else(|error| {
+            eprintln!("cannot load session {}: {}", path.display(), error);
+            std::process::exit(1)
+        });
+        if let Some(capture) = seek_capture {
+            let index = session.load_index().unwrap_or_else(|error| {
+                eprintln!("cannot load index of session {}: {}", path.display(), error);
+                std::process::exit(1)
+            });
+            let Some(entry) = index.get(capture) else {
+                eprintln!("session {} has only {} captures", path.display(), index.len());
+                std::process::exit(1)
+            };
+            session.seek(entry);
+        }
+        session
+    });
+    env_logger::Builder::from_default_env()
+        .format_timestamp_micros()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .init();
+    log::debug!("library built with: {}", thunderscope::capabilities());
+    let settings = Settings::load();
+    i18n::set_language(settings.language.unwrap_or_else(i18n::Language::from_environment));
+    // create a window
+    let event_loop = EventLoop::new().expect("failed to create event loop");
+    event_loop.set_control_flow(ControlFlow::wait_duration(Duration::ZERO));
+    let window_builder = WindowBuilder::new()
+        .with_title("ThunderScope");
+    let config_template_builder = ConfigTemplateBuilder::new()
+        .prefer_hardware_accelerated(Some(true));
+    let (window, gl_config) = DisplayBuilder::new()
+        .with_window_builder(Some(window_builder))
+        .build(&event_loop, config_template_builder, |mut configs|
+            configs.next().expect("no GL configurations available"))
+        .expect("failed to create window");
+    let window = window.unwrap();
+    let (width, height) = window.inner_size().into();
+    // create an OpenGL context
+    let context_attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
+        .build(Some(window.raw_window_handle()));
+    let gl_context = unsafe {
+        gl_config.display().create_context(&gl_config, &context_attributes)
+            .expect("failed to create GL context")
+    };
+    let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new()
+        .build(window.raw_window_handle(),
+            NonZeroU32::new(width).unwrap(),
+            NonZeroU32::new(height).unwrap(),
+        );
+    let gl_surface = unsafe {
+        gl_config.display().create_window_surface(&gl_config, &surface_attributes)
+            .expect("failed to create GL surface")
+    };
+    let gl_context = gl_context.make_current(&gl_surface)
+        .expect("failed to make GL context current");
+    let gl_library = unsafe {
+        GlowContext::from_loader_function_cstr(|func|
+            gl_config.display().get_proc_address(func).cast())
+    };
+    // determine UI scale
+    let scale_factor = window.scale_factor();
+    log::info!("scaling UI by a factor of {:.2}×", scale_factor);
+    let window_size = LogicalSize::new(1280.0, 720.0);
+    let _ = window.request_inner_size(
+        PhysicalSize::<f64>::from_logical(window_size, scale_factor));
+    // create ImGui context
+    let mut imgui_context = imgui::Context::create();
+    imgui_context.style_mut().use_light_colors();
+    imgui_context.set_ini_filename(None); // disable ini autosaving
+    imgui_context.io_mut().config_flags |= imgui::ConfigFlags::NAV_ENABLE_KEYBOARD;
+    if let Some(clipboard) = clipboard::SystemClipboard::new() {
+        imgui_context.set_clipboard_backend(clipboard);
+    }
+    // create UI state
+    let font_config = InterfaceRenderer::font_config(scale_factor);
+    let (slow_send, slow_recv) = sync_channel(64);
+    let (readout_send, readout_recv) = sync_channel(4);
+    let (sampler_remote, sampler_control) = capture::control_channels();
+    let budget = MemoryBudget::new(settings.memory_budget());
+    let (disk_writer, disk_writer_thread) = DiskWriter::spawn();
+    let ui_state = InterfaceRenderer::new(&mut imgui_context, font_config,
+        slow_recv, readout_recv, sampler_remote, budget.clone(), disk_writer.clone());
+    if let Some(path) = annotations_path {
+        // each line read from e.g. a serial port or a named pipe is shown as a flag
+        let annotations = ui_state.annotations.clone();
+        std::thread::spawn(move || {
+            let result = std::fs::File::open(&path)
+                .and_then(|file| annotations.follow(std::io::BufReader::new(file)));
+            if let Err(error) = result {
+                log::error!("cannot read annotations from {}: {}", path.display(), error);
+            }
+        });
+    }
+    // create ImGui renderer
+    let mut imgui_platform = imgui_winit_support::WinitPlatform::init(&mut imgui_context);
+    imgui_platform.attach_window(imgui_context.io_mut(), &window,
+        imgui_winit_support::HiDpiMode::Locked(scale_factor));
+    let mut imgui_texture_map = imgui_glow_renderer::SimpleTextureMap::default();
+    let imgui_renderer = imgui_glow_renderer::Renderer::initialize(&gl_library,
+            &mut imgui_context, &mut imgui_texture_map, /*output_srgb=*/true)
+        .expect("failed to create UI renderer");
+    // create communication channels and prime the bucket brigade
+    let (params_send, params_recv) = channel();
+    let (sampler_to_renderer_send, sampler_to_renderer_recv) = channel();
+    let (renderer_to_sampler_send, renderer_to_sampler_recv) = channel();
+    // at least two buffers are needed: one to capture into, and one standby; the reservation
+    // is held until the application exits
+    let waveform_pool_budget = budget.reserve("waveform pool",
+        2 * SAMPLE_COUNT, WAVEFORM_POOL_SIZE * SAMPLE_COUNT);
+    for _ in 0..waveform_pool_budget.count(SAMPLE_COUNT) {
+        let waveform = Waveform::new(SAMPLE_COUNT, settings.verify_captures)
+            .expect("failed to create a ring buffer for acquisition");
+        renderer_to_sampler_send.send(waveform).unwrap();
+    }
+    // set up the acquisition and processing pipeline
+    let mut sampler = capture::Sampler::new(
+        params_recv, renderer_to_sampler_recv, sampler_to_renderer_send, slow_send,
+        sampler_control, budget.clone(), disk_writer);
+    if let Some(path) = record_path {
+        sampler.record_to(path);
+    }
+    sampler.profile_with(ui_state.profiler.clone());
+    sampler.notify_with(ui_state.notifier.clone());
+    sampler.show_readouts(readout_send);
+    if let Some(path) = trigger_log_path {
+        // a line is written for each time the trigger fires, e.g. to correlate the triggers with
+        // events logged by other instruments
+        let (trigger_send, trigger_recv) = sync_channel(1024);
+        sampler.notify_triggers(trigger_send);
+        std::thread::spawn(move || {
+            use std::io::Write;
+            let result = std::fs::File::create(&path).and_then(|file| {
+                let mut file = std::io::LineWriter::new(file);
+                writeln!(file, "sample,channel,cause,crossing")?;
+                for event in trigger_recv {
+                    let cause = match event.cause {
+                        TriggerCause::Edge(edge) => format!("{:?}", edge),
+                        TriggerCause::Window(crossing) => format!("{:?}", crossing),
+                    };
+                    writeln!(file, "{},{},{},{}", event.sample, event.channel, cause,
+                        event.crossing)?;
+                }
+                Ok(())
+            });
+            if let Err(error) = result {
+                log::error!("cannot write trigger events to {}: {}", path.display(), error);
+            }
+        });
+    }
+    #[cfg(feature = "audio")]
+    let audio_monitor = {
+        let (audio_send, audio_recv) = sync_channel(16);
+        sampler.monitor_audio(audio_send);
+        audio::AudioMonitor::new(audio_recv)
+    };
+    let wfm_renderer = WaveformRenderer::new(&gl_library,
+        sampler_to_renderer_recv, renderer_to_sampler_send);
+    let mut application = Application {
+        gl_context,
+        gl_surface,
+        gl_library,
+        wfm_renderer,
+        imgui_context,
+        imgui_platform,
+        imgui_texture_map,
+        imgui_renderer,
+        ui_state,
+        window,
+        setup: None,
+        params_send,
+        sampler: Some(sampler),
+        sampler_thread: None,
+        tour: None,
+        gestures: GestureRecognizer::new(),
+        touch_mouse: None,
+    };
+    #[cfg(feature = "audio")]
+    {
+        application.ui_state.audio_monitor = Some(audio_monitor);
+    }
+    // set up acquisition, or guide the user through setup if it cannot be done yet
+    let data_source = match (replay_session, import, recording) {
+        (Some(session), _, _) => Some(capture::DataSource::Replay(session)),
+        (None, Some(import), _) => Some(capture::DataSource::Import(import)),
+        (None, None, Some(recording)) => Some(capture::DataSource::Recording(recording)),
+        // the tour runs on a simulated signal, whether or not setup has been completed
+        (None, None, None) if tour => {
+            let params = Parameters::demo(settings.probe_attenuation());
+            application.tour = Some(Tour::new(params));
+            Some(capture::DataSource::Simulation(tour::scenario()))
+        }
+        (None, None, None) => setup::data_source(&settings),
+    };
+    match data_source {
+        Some(data_source) => application.start_acquisition(data_source, &settings),
+        None => application.setup = Some(setup::SetupWizard::new(settings)),
+    }
+    // run the application
+    let result = event_loop.run(|event, window_target|
+        application.process_event(event, window_target));
+    // the sampler thread exits once the waveform channels are disconnected
+    let sampler_thread = application.sampler_thread.take();
+    drop(application);
+    result.expect("failed to run application");
+    // clean up acquisition
+    if let Some(sampler_thread) = sampler_thread {
+        // errors have already been shown in the status bar
+        if let Err(error) = sampler_thread.join().expect("acquisition thread panicked") {
+            log::error!("acquisition failed: {}", error);
+        }
+    }
+    // finish writing files; the writer thread exits once every handle to it is dropped
+    disk_writer_thread.join().expect("writer thread panicked");
+}
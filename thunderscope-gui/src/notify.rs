@@ -0,0 +1,155 @@
+//! Notifications on the host when events occur that the user may be waiting for, so that a long
+//! unattended capture does not require watching the screen.
+//!
+//! A notification is an alert sound, a desktop notification, or both. The sound is a file chosen
+//! by the user, or the terminal bell if there is none; both are played or shown by external
+//! programs that are started without waiting for them (see `spawn_detached`), so that notifying
+//! never blocks acquisition.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+
+/// An event that the user can be notified about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyEvent {
+    /// A single capture has been triggered.
+    SingleTrigger,
+    /// A capture has started to differ from the reference by more than the tolerance.
+    MaskFailure,
+    /// A measurement has started to fall outside of its limit.
+    LimitAlarm,
+}
+
+impl NotifyEvent {
+    /// Untranslated summary of the event.
+    pub fn summary(self) -> &'static str {
+        match self {
+            NotifyEvent::SingleTrigger => "Single capture triggered",
+            NotifyEvent::MaskFailure   => "Capture differs from reference",
+            NotifyEvent::LimitAlarm    => "Limit violated",
+        }
+    }
+}
+
+/// Which events are notified about, and how.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notifications {
+    pub single_trigger: bool,
+    pub mask_failure: bool,
+    pub limit_alarm: bool,
+    /// Whether to play an alert sound.
+    pub sound: bool,
+    /// Sound file to play; if not set, the terminal bell is rung instead.
+    pub sound_file: Option<String>,
+    /// Whether to show a desktop notification.
+    pub desktop: bool,
+}
+
+impl Notifications {
+    fn is_enabled(&self, event: NotifyEvent) -> bool {
+        match event {
+            NotifyEvent::SingleTrigger => self.single_trigger,
+            NotifyEvent::MaskFailure   => self.mask_failure,
+            NotifyEvent::LimitAlarm    => self.limit_alarm,
+        }
+    }
+}
+
+/// Notifies the user about events according to the notification settings. Clones share
+/// the settings, so that e.g. the acquisition thread follows changes made in the preferences.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    notifications: Arc<Mutex<Notifications>>,
+}
+
+impl Notifier {
+    pub fn configure(&self, notifications: Notifications) {
+        *self.notifications.lock().unwrap() = notifications;
+    }
+
+    /// Notify about `event`, if notifications are enabled for it; `detail` describes it further.
+    pub fn notify(&self, event: NotifyEvent, detail: &str) {
+        let notifications = self.notifications.lock().unwrap().clone();
+        if !notifications.is_enabled(event) {
+            return
+        }
+        log::debug!("notifying about {:?}: {}", event, detail);
+        if notifications.sound {
+            match notifications.sound_file.as_deref().filter(|path| !path.is_empty()) {
+                Some(path) => spawn(play_sound_command(Path::new(path))),
+                None => {
+                    use std::io::Write;
+                    let _ = std::io::stderr().write_all(b"\x07");
+                }
+            }
+        }
+        if notifications.desktop {
+            spawn(desktop_notification_command(tr(event.summary()), detail));
+        }
+    }
+}
+
+fn spawn(command: Option<Command>) {
+    let Some(mut command) = command else {
+        log::warn!("notifications of this kind are not supported on this platform");
+        return
+    };
+    if let Err(error) = spawn_detached(&mut command) {
+        log::error!("failed to run {:?}: {}", command.get_program(), error);
+    }
+}
+
+/// Start `command` without waiting for it to exit. The process is waited for on a thread of its
+/// own, so that it does not linger as a zombie once it exits.
+pub fn spawn_detached(command: &mut Command) -> std::io::Result<()> {
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || {
+        if let Err(error) = child.wait() {
+            log::warn!("failed to wait for process {}: {}", child.id(), error);
+        }
+    });
+    Ok(())
+}
+
+fn play_sound_command(path: &Path) -> Option<Command> {
+    if cfg!(target_os = "linux") {
+        let mut command = Command::new("paplay");
+        command.arg(path);
+        Some(command)
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command",
+            "(New-Object Media.SoundPlayer $env:THUNDERSCOPE_SOUND).PlaySync()"]);
+        command.env("THUNDERSCOPE_SOUND", path);
+        Some(command)
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("afplay");
+        command.arg(path);
+        Some(command)
+    } else {
+        None
+    }
+}
+
+fn desktop_notification_command(summary: &str, body: &str) -> Option<Command> {
+    if cfg!(target_os = "linux") {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=ThunderScope", summary, body]);
+        Some(command)
+    } else if cfg!(target_os = "macos") {
+        // the text is passed through the environment, so that it does not need to be quoted
+        let mut command = Command::new("osascript");
+        command.args(["-e", "display notification (system attribute \"THUNDERSCOPE_BODY\") \
+            with title (system attribute \"THUNDERSCOPE_SUMMARY\")"]);
+        command.env("THUNDERSCOPE_SUMMARY", summary).env("THUNDERSCOPE_BODY", body);
+        Some(command)
+    } else {
+        None
+    }
+}
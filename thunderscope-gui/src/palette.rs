@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Set of colors used to tell channels apart, in traces, markers, and labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Palette {
+    /// The colors conventionally used by oscilloscopes.
+    #[default]
+    Classic,
+    /// Okabe and Ito, "Color Universal Design"; distinguishable with deuteranopia, protanopia,
+    /// and tritanopia.
+    OkabeIto,
+    /// Paul Tol's "bright" scheme; distinguishable with deuteranopia and protanopia.
+    TolBright,
+}
+
+const fn rgb(value: u32) -> [f32; 4] {
+    [
+        ((value >> 16) & 0xff) as f32 / 255.0,
+        ((value >>  8) & 0xff) as f32 / 255.0,
+        ( value        & 0xff) as f32 / 255.0,
+        1.0
+    ]
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Classic, Palette::OkabeIto, Palette::TolBright];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Classic   => "Classic",
+            Palette::OkabeIto  => "Okabe-Ito (color-blind safe)",
+            Palette::TolBright => "Tol Bright (color-blind safe)",
+        }
+    }
+
+    fn colors(self) -> [[f32; 4]; 4] {
+        match self {
+            Palette::Classic   => [rgb(0xffff00), rgb(0x00ffff), rgb(0xff00ff), rgb(0x4080ff)],
+            Palette::OkabeIto  => [rgb(0xe69f00), rgb(0x56b4e9), rgb(0xf0e442), rgb(0xcc79a7)],
+            Palette::TolBright => [rgb(0xccbb44), rgb(0x66ccee), rgb(0xee6677), rgb(0xaa3377)],
+        }
+    }
+
+    pub fn channel_color(self, channel_index: usize) -> [f32; 4] {
+        self.colors()[channel_index]
+    }
+
+    /// Returns a darker shade of the channel color, for lines drawn next to filled areas.
+    pub fn channel_line_color(self, channel_index: usize) -> [f32; 4] {
+        let [r, g, b, a] = self.channel_color(channel_index);
+        [r * 0.8, g * 0.8, b * 0.8, a]
+    }
+
+    /// Returns black or white, whichever is more legible on top of the channel color.
+    pub fn channel_text_color(self, channel_index: usize) -> [f32; 4] {
+        let [r, g, b, _] = self.channel_color(channel_index);
+        if 0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5 {
+            [0.0, 0.0, 0.0, 1.0]
+        } else {
+            [1.0, 1.0, 1.0, 1.0]
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Timing of the stages that acquired data passes through on its way to the screen.
+//!
+//! The stages run on the acquisition and render threads, which record their durations into
+//! a shared `Profiler` while it is enabled; the profiler overlay periodically takes the mean and
+//! maximum duration of each stage since it last did so. A stage that takes most of a frame time
+//! is the one that makes the interface feel sluggish.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Reading samples from the device into the ring buffer (acquisition thread).
+    Read,
+    /// Scanning the samples for a trigger (acquisition thread).
+    Trigger,
+    /// De-interleaving and decimating a capture for display (acquisition thread).
+    Deinterleave,
+    /// Decimating the displayed samples and uploading them to the GPU (render thread).
+    Upload,
+    /// Laying out and drawing the user interface (render thread).
+    Interface,
+    /// Presenting the frame (render thread).
+    Swap,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Read,
+        Stage::Trigger,
+        Stage::Deinterleave,
+        Stage::Upload,
+        Stage::Interface,
+        Stage::Swap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Read         => "Read",
+            Stage::Trigger      => "Trigger",
+            Stage::Deinterleave => "De-interleave",
+            Stage::Upload       => "Upload",
+            Stage::Interface    => "Interface",
+            Stage::Swap         => "Swap",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StageTimes {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+/// Timings of a stage over a period.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StageSummary {
+    /// How many times the stage has run.
+    pub count: u64,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    enabled: AtomicBool,
+    stages: [StageTimes; Stage::ALL.len()],
+}
+
+impl Profiler {
+    pub fn new() -> Arc<Profiler> {
+        Arc::new(Profiler::default())
+    }
+
+    /// Start or stop recording timings. While stopped, timing a stage costs an atomic load.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, stage: Stage, duration: Duration) {
+        let nanos = duration.as_nanos() as u64;
+        let times = &self.stages[stage as usize];
+        times.count.fetch_add(1, Ordering::Relaxed);
+        times.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        times.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Run `f` as `stage`, recording its duration if the profiler is enabled.
+    pub fn time<R>(&self, stage: Stage, f: impl FnOnce() -> R) -> R {
+        if !self.is_enabled() {
+            return f()
+        }
+        let started_at = Instant::now();
+        let result = f();
+        self.record(stage, started_at.elapsed());
+        result
+    }
+
+    /// Returns the timings of each stage (in the order of `Stage::ALL`) recorded since the last
+    /// call, and starts a new period.
+    pub fn take(&self) -> [StageSummary; Stage::ALL.len()] {
+        self.stages.each_ref().map(|times| {
+            let count = times.count.swap(0, Ordering::Relaxed);
+            let total = times.total_nanos.swap(0, Ordering::Relaxed);
+            let max = times.max_nanos.swap(0, Ordering::Relaxed);
+            StageSummary {
+                count,
+                mean: Duration::from_nanos(total.checked_div(count).unwrap_or(0)),
+                max: Duration::from_nanos(max),
+            }
+        })
+    }
+}
@@ -0,0 +1,142 @@
+//! Multimeter-style readouts of the DC level and the AC RMS of each enabled channel, computed
+//! from every sample of the stream (whether or not the trigger fires), e.g. to check voltages
+//! while probing around a circuit.
+
+use std::io::Read;
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::time::{Duration, Instant};
+
+use thunderscope::{ChannelMap, DeviceParameters, Result};
+use thunderscope::acquire::SampleSource;
+
+/// Interval over which the statistics of each readout are accumulated.
+const READOUT_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelReadout {
+    /// Mean of the signal, in volts.
+    pub dc: f32,
+    /// RMS of the signal with the mean removed, in volts.
+    pub ac_rms: f32,
+}
+
+/// Readouts of faceplate channels; `None` for the disabled ones.
+pub type Readouts = [Option<ChannelReadout>; 4];
+
+// running sums of the samples in one lane
+#[derive(Debug, Clone, Copy, Default)]
+struct LaneStatistics {
+    count: u64,
+    sum: i64,
+    sum_of_squares: u64,
+}
+
+impl LaneStatistics {
+    fn readout(&self, params: &DeviceParameters, channel_index: usize)
+            -> Option<ChannelReadout> {
+        if self.count == 0 {
+            return None
+        }
+        let mean = self.sum as f64 / self.count as f64;
+        let variance = (self.sum_of_squares as f64 / self.count as f64 - mean * mean).max(0.0);
+        let volts_per_code = params.full_scale(channel_index) / 256.0;
+        Some(ChannelReadout {
+            dc: params.code_to_volts(channel_index, 0) + mean as f32 * volts_per_code,
+            ac_rms: variance.sqrt() as f32 * volts_per_code,
+        })
+    }
+}
+
+/// Accumulates the statistics of each lane of the sample stream and sends the readouts every
+/// `READOUT_INTERVAL`, while passing the stream through unchanged.
+///
+/// If the user interface falls behind, readouts are dropped instead of stalling the acquisition.
+pub struct ReadoutTap<R: Read> {
+    inner: R,
+    params: DeviceParameters,
+    channel_map: ChannelMap,
+    // lane of the next sample
+    phase: usize,
+    lanes: [LaneStatistics; 4],
+    started_at: Instant,
+    readout_send: Option<SyncSender<Readouts>>,
+}
+
+impl<R: Read> ReadoutTap<R> {
+    pub fn new(inner: R, readout_send: Option<SyncSender<Readouts>>) -> Self {
+        let mut tap = Self {
+            inner,
+            params: DeviceParameters::default(),
+            channel_map: ChannelMap::new([true; 4]),
+            phase: 0,
+            lanes: Default::default(),
+            started_at: Instant::now(),
+            readout_send,
+        };
+        tap.configure(&DeviceParameters::default());
+        tap
+    }
+
+    fn configure(&mut self, params: &DeviceParameters) {
+        self.params = *params;
+        self.channel_map = ChannelMap::from_params(params);
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.phase = 0;
+        self.lanes = Default::default();
+        self.started_at = Instant::now();
+    }
+
+    fn accumulate(&mut self, samples: &[i8]) {
+        let channels = self.channel_map.stream_channels();
+        for &sample in samples {
+            let lane = &mut self.lanes[self.phase];
+            lane.count += 1;
+            lane.sum += sample as i64;
+            lane.sum_of_squares += (sample as i64 * sample as i64) as u64;
+            self.phase = (self.phase + 1) % channels;
+        }
+    }
+}
+
+impl<R: Read> Read for ReadoutTap<R> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(data)?;
+        if self.readout_send.is_none() {
+            return Ok(length)
+        }
+        self.accumulate(bytemuck::cast_slice(&data[..length]));
+        if self.started_at.elapsed() >= READOUT_INTERVAL {
+            let readouts = std::array::from_fn(|channel_index| {
+                let lane = self.channel_map.lane(channel_index)?;
+                self.lanes[lane].readout(&self.params, channel_index)
+            });
+            match self.readout_send.as_ref().unwrap().try_send(readouts) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+                Err(TrySendError::Full(_)) => log::debug!("sampler: dropped readouts"),
+            }
+            // a frame may straddle the reads, so only the sums are restarted
+            self.lanes = Default::default();
+            self.started_at = Instant::now();
+        }
+        Ok(length)
+    }
+}
+
+impl<R: SampleSource> SampleSource for ReadoutTap<R> {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        self.configure(params);
+        self.inner.reconfigure(params)
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        self.reset();
+        self.inner.recover()
+    }
+
+    fn pause(&mut self, paused: bool) -> Result<()> {
+        self.inner.pause(paused)
+    }
+}
@@ -0,0 +1,290 @@
+//! Scripted signals for the demo mode.
+//!
+//! A scenario is a sequence of segments, each of which generates a signal for some duration,
+//! optionally with impairments: noise, jitter, baseline wander, and glitches. The signal is
+//! a function of the stream position and a seed only (the impairments use a counter-based
+//! pseudorandom generator), so the same scenario always produces the same samples, which makes it
+//! usable for demonstrating and regression testing triggers and measurements. For example:
+//!
+//! ```toml
+//! [[segments]]
+//! duration = 2.0
+//! signal = { kind = "sine", frequency = 1e3, amplitude = 0.5 }
+//!
+//! [[segments]]
+//! duration = 2.0
+//! signal = { kind = "sine", frequency = 1e3, amplitude = 0.5 }
+//! glitches = { interval = 10e-3, width = 50e-9, amplitude = 1.0 }
+//! noise = 0.01
+//! jitter = 1e-9
+//! wander = { frequency = 0.5, amplitude = 0.1 }
+//! ```
+
+use std::f32::consts::PI;
+use std::f64::consts::PI as PI64;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use thunderscope::{ChannelMap, DeviceParameters};
+use thunderscope::acquire::SampleSource;
+
+// distinguish the pseudorandom sequences used for different impairments
+const NOISE_SALT: u64 = 1;
+const JITTER_SALT: u64 = 2;
+
+fn splitmix64(mut state: u64) -> u64 {
+    state = state.wrapping_add(0x9e3779b97f4a7c15);
+    state = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94d049bb133111eb);
+    state ^ (state >> 31)
+}
+
+/// Returns a pseudorandom number with the standard normal distribution, which only depends on
+/// `seed`, `salt`, and `index`.
+fn gaussian(seed: u64, salt: u64, index: u64) -> f32 {
+    let uniform = |half: u64| {
+        let bits = splitmix64(splitmix64(splitmix64(seed) ^ salt << 1 ^ half) ^ index);
+        // in (0, 1), so that the logarithm below is finite
+        ((bits >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    };
+    // Box-Muller transform
+    ((-2.0 * uniform(0).ln()).sqrt() * (2.0 * PI64 * uniform(1)).cos()) as f32
+}
+
+/// Periodic or constant signal, with voltages as measured at the probe.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Signal {
+    Dc { level: f32 }, // in V
+    Sine {
+        frequency: f32, // in Hz
+        amplitude: f32, // in Vpp
+        #[serde(default)]
+        offset: f32,    // in V
+    },
+    Square {
+        frequency: f32, // in Hz
+        amplitude: f32, // in Vpp
+        #[serde(default)]
+        offset: f32,    // in V
+        #[serde(default = "Signal::default_duty")]
+        duty: f32,      // fraction of the period spent high
+    },
+}
+
+impl Signal {
+    fn default_duty() -> f32 {
+        0.5
+    }
+
+    /// Returns the voltage at `time` seconds since the start of the segment.
+    fn value(&self, time: f64) -> f32 {
+        match *self {
+            Signal::Dc { level } => level,
+            Signal::Sine { frequency, amplitude, offset } => {
+                let phase = (time * frequency as f64).fract() as f32;
+                offset + amplitude / 2.0 * (2.0 * PI * phase).sin()
+            }
+            Signal::Square { frequency, amplitude, offset, duty } => {
+                let phase = (time * frequency as f64).fract() as f32;
+                offset + if phase < duty { amplitude / 2.0 } else { -amplitude / 2.0 }
+            }
+        }
+    }
+
+    /// Returns the frequency of a periodic signal, and the phase at which its periods are
+    /// delimited for the purpose of jitter. The phase is chosen away from the edges (or zero
+    /// crossings) so that displacing each period as a whole does not add spurious edges.
+    fn period(&self) -> Option<(f32, f32)> {
+        match *self {
+            Signal::Dc { .. } => None,
+            Signal::Sine { frequency, .. } => Some((frequency, 0.75)),
+            Signal::Square { frequency, duty, .. } => Some((frequency, (1.0 + duty) / 2.0)),
+        }
+    }
+}
+
+/// Slow sinusoidal variation of the baseline, e.g. from a drifting ground reference.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Wander {
+    pub frequency: f32, // in Hz
+    pub amplitude: f32, // in V
+}
+
+/// Short pulses added to the signal at regular intervals.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Glitches {
+    pub interval: f32,  // in s
+    pub width: f32,     // in s
+    pub amplitude: f32, // in V, added to the signal
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Segment {
+    pub duration: f32, // in s
+    pub signal: Signal,
+    #[serde(default)]
+    pub glitches: Option<Glitches>,
+    /// RMS value of the Gaussian noise added to the signal, in V.
+    #[serde(default)]
+    pub noise: f32,
+    /// RMS value of the random displacement in time of each period of the signal, in s.
+    /// Should be well below a quarter of the period.
+    #[serde(default)]
+    pub jitter: f32,
+    #[serde(default)]
+    pub wander: Option<Wander>,
+}
+
+impl Segment {
+    pub fn new(duration: f32, signal: Signal) -> Segment {
+        Segment { duration, signal, glitches: None, noise: 0.0, jitter: 0.0, wander: None }
+    }
+
+    /// Returns the voltage at `time` seconds since the start of the segment, for the sample
+    /// with stream position `index`.
+    fn value(&self, time: f64, index: u64, seed: u64) -> f32 {
+        let mut signal_time = time;
+        if let (Some((frequency, boundary)), true) = (self.signal.period(), self.jitter > 0.0) {
+            let period_index = (time * frequency as f64 + (1.0 - boundary) as f64).floor();
+            signal_time += (gaussian(seed, JITTER_SALT, period_index as i64 as u64) *
+                self.jitter) as f64;
+        }
+        let mut value = self.signal.value(signal_time);
+        if let Some(glitches) = self.glitches {
+            if time % (glitches.interval as f64) < glitches.width as f64 {
+                value += glitches.amplitude;
+            }
+        }
+        if let Some(wander) = self.wander {
+            value += wander.amplitude * (2.0 * PI64 * wander.frequency as f64 * time).sin() as f32;
+        }
+        if self.noise > 0.0 {
+            value += gaussian(seed, NOISE_SALT, index) * self.noise;
+        }
+        value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub segments: Vec<Segment>,
+    /// Whether to start over after the last segment, rather than continue it indefinitely.
+    #[serde(default = "Scenario::default_repeat")]
+    pub repeat: bool,
+    /// Seed of the pseudorandom generator used for noise and jitter.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Scenario {
+    fn default_repeat() -> bool {
+        true
+    }
+
+    pub fn new() -> Scenario {
+        Scenario { segments: Vec::new(), repeat: true, seed: 0 }
+    }
+
+    pub fn segment(mut self, segment: Segment) -> Scenario {
+        self.segments.push(segment);
+        self
+    }
+
+    /// A sine wave, continuing indefinitely.
+    pub fn sine(frequency: f32, amplitude: f32) -> Scenario {
+        // the duration is finite so that the scenario can be serialized as JSON
+        let mut scenario = Scenario::new().segment(Segment::new(1.0,
+            Signal::Sine { frequency, amplitude, offset: 0.0 }));
+        scenario.repeat = false;
+        scenario
+    }
+
+    /// Load a scenario from a TOML file.
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let scenario: Scenario = toml::from_str(&text).map_err(|error| error.to_string())?;
+        if scenario.segments.is_empty() {
+            return Err("scenario has no segments".to_owned())
+        }
+        Ok(scenario)
+    }
+
+    fn duration(&self) -> f64 {
+        self.segments.iter().map(|segment| segment.duration as f64).sum()
+    }
+
+    /// Returns the voltage at `time` seconds since the start of the scenario, for the sample
+    /// with stream position `index`.
+    pub fn value(&self, mut time: f64, index: u64) -> f32 {
+        if self.repeat && self.duration().is_finite() && self.duration() > 0.0 {
+            time %= self.duration();
+        }
+        for segment in self.segments.iter() {
+            if time < segment.duration as f64 {
+                return segment.value(time, index, self.seed)
+            }
+            time -= segment.duration as f64;
+        }
+        match self.segments.last() {
+            // continue the last segment
+            Some(segment) => segment.value(time + segment.duration as f64, index, self.seed),
+            None => 0.0
+        }
+    }
+}
+
+/// Generates the interleaved sample stream for a scenario, with every enabled channel carrying
+/// the same signal.
+pub struct ScenarioGenerator {
+    scenario: Scenario,
+    params: DeviceParameters,
+    // faceplate channel carried by each lane
+    lane_channels: Vec<Option<usize>>,
+    position: u64,
+}
+
+impl ScenarioGenerator {
+    pub fn new(scenario: Scenario) -> ScenarioGenerator {
+        let mut generator = ScenarioGenerator {
+            scenario,
+            params: DeviceParameters::default(),
+            lane_channels: Vec::new(),
+            position: 0,
+        };
+        generator.reconfigure(&DeviceParameters::default());
+        generator
+    }
+
+    fn sample(&self, position: u64) -> i8 {
+        let lanes = self.lane_channels.len() as u64;
+        let Some(channel_index) = self.lane_channels[(position % lanes) as usize]
+            else { return 0 };
+        let time = (position / lanes) as f64 / self.params.sample_rate() as f64;
+        self.params.volts_to_code(channel_index, self.scenario.value(time, position))
+    }
+}
+
+impl Read for ScenarioGenerator {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        for sample in data.iter_mut() {
+            *sample = self.sample(self.position) as u8;
+            self.position += 1;
+        }
+        // simulate 1 GS/s capture rate
+        std::thread::sleep(std::time::Duration::from_nanos(1) * (data.len() as u32));
+        Ok(data.len())
+    }
+}
+
+impl SampleSource for ScenarioGenerator {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        let channel_map = ChannelMap::from_params(params);
+        self.params = *params;
+        self.lane_channels = (0..channel_map.stream_channels())
+            .map(|lane| channel_map.lane_channel(lane))
+            .collect();
+    }
+}
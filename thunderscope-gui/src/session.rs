@@ -0,0 +1,353 @@
+//! Recording and deterministic replay of acquisition sessions.
+//!
+//! A session is a directory containing `events.jsonl`, a log with one JSON record per line, and,
+//! if the samples were acquired from the hardware, `samples.bin` with the raw sample stream,
+//! as journal records (see `thunderscope::JournalReader`), one per read.
+//! The first record describes the source of the samples; each of the following records is
+//! a change requested of the sampler, tagged with the stream position at which it was applied.
+//! Since the sampler only applies changes between reads, and (given the same samples) always
+//! reads in the same way, replaying a session reproduces exactly the same sequence of captures,
+//! which turns bugs that depend on timing of user interaction into replayable test cases.
+//!
+//! Sessions with recorded samples also contain `index.jsonl`, with one JSON record per capture:
+//! its stream position, trigger, and the range of each channel, as well as the position of
+//! the journal record in `samples.bin` that it starts in. This lets a long recording be replayed
+//! from any capture (see `Session::seek`) without reading the samples before it.
+//!
+//! All files are written so that a session interrupted by a crash or power loss can still be
+//! replayed, up to the last complete record.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use thunderscope::{journal_record, DeviceParameters, JournalReader};
+
+use thunderscope::acquire::{Parameters, SampleSource, TriggerEvent};
+
+use crate::capture::AcquisitionMode;
+use crate::scenario::Scenario;
+use crate::writer::{DiskWriter, WriterFile};
+
+const EVENTS_FILENAME: &str = "events.jsonl";
+const SAMPLES_FILENAME: &str = "samples.bin";
+const INDEX_FILENAME: &str = "index.jsonl";
+
+/// Where the samples of a session come from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSource {
+    /// The samples are generated from a scenario, and are not recorded.
+    Scenario(Scenario),
+    /// The samples are recorded in `samples.bin`.
+    Samples,
+}
+
+/// A change requested of the sampler.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Parameters(Parameters),
+    AcquisitionMode(AcquisitionMode),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Source(SessionSource),
+    Change { position: u64, change: Change },
+}
+
+/// A journal record in `samples.bin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedChunk {
+    /// Stream position of the first sample in the record.
+    pub position: u64,
+    /// Offset of the record in `samples.bin`, in bytes.
+    pub offset: u64,
+}
+
+/// A capture made while recording a session, as listed in `index.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Stream position of the first sample of the capture.
+    pub position: u64,
+    /// The journal record that the first sample of the capture is in.
+    pub chunk: RecordedChunk,
+    /// Host time of the first sample of the capture, if known.
+    pub time: Option<SystemTime>,
+    pub trigger: Option<TriggerEvent>,
+    /// Lowest and highest code of each faceplate channel in the capture; `None` for
+    /// the disabled ones.
+    pub range: [Option<(i8, i8)>; 4],
+}
+
+/// A recorded session, loaded for replay.
+#[derive(Debug)]
+pub struct Session {
+    pub path: PathBuf,
+    pub source: SessionSource,
+    /// Changes and the stream positions they were applied at, in the order they were applied.
+    pub changes: VecDeque<(u64, Change)>,
+    /// The journal record in `samples.bin` that the replay starts at; the stream positions of
+    /// `changes` are relative to it.
+    pub start: RecordedChunk,
+}
+
+// the complete JSON records of a `.jsonl` file, one per line; a record is complete once its
+// line is terminated, and a record that is not was being written when the application crashed
+fn read_records<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let mut records = Vec::new();
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let Some(line) = line.strip_suffix('\n') else {
+            log::warn!("session: ignoring incomplete record on line {}", index + 1);
+            break
+        };
+        records.push(serde_json::from_str(line)
+            .map_err(|error| format!("line {}: {}", index + 1, error))?);
+    }
+    Ok(records)
+}
+
+impl Session {
+    /// Load a session from the directory at `path`.
+    pub fn load(path: &Path) -> Result<Session, String> {
+        let mut source = None;
+        let mut changes = VecDeque::new();
+        for (index, record) in read_records(&path.join(EVENTS_FILENAME))?.into_iter().enumerate() {
+            match (record, &source) {
+                (Record::Source(new_source), None) => source = Some(new_source),
+                (Record::Change { position, change }, Some(_)) =>
+                    changes.push_back((position, change)),
+                _ => return Err(format!("line {}: unexpected record", index + 1))
+            }
+        }
+        let source = source.ok_or_else(|| "session has no source".to_owned())?;
+        Ok(Session {
+            path: path.to_owned(),
+            source,
+            changes,
+            start: RecordedChunk { position: 0, offset: 0 },
+        })
+    }
+
+    /// Load the index of the captures made while the session was recorded. Sessions with
+    /// samples generated from a scenario have no index.
+    pub fn load_index(&self) -> Result<Vec<IndexEntry>, String> {
+        read_records(&self.path.join(INDEX_FILENAME))
+    }
+
+    /// Start the replay at the journal record where the capture `entry` starts, as if
+    /// the session was recorded from there on. The changes applied before it are collapsed into
+    /// the most recent change of each kind.
+    pub fn seek(&mut self, entry: &IndexEntry) {
+        let start = entry.chunk;
+        let (mut parameters, mut acquisition_mode) = (None, None);
+        while self.changes.front().is_some_and(|&(at, _)| at <= start.position) {
+            match self.changes.pop_front().unwrap().1 {
+                change @ Change::Parameters(_) => parameters = Some(change),
+                change @ Change::AcquisitionMode(_) => acquisition_mode = Some(change),
+            }
+        }
+        for (at, _) in self.changes.iter_mut() {
+            *at -= start.position;
+        }
+        for change in [acquisition_mode, parameters].into_iter().flatten() {
+            self.changes.push_front((0, change));
+        }
+        self.start = start;
+    }
+}
+
+/// Records a session into a directory.
+pub struct SessionRecorder {
+    events: WriterFile,
+    samples: Option<(WriterFile, Sender<RecordedChunk>)>,
+    index: Option<WriterFile>,
+    // the journal records written by `SampleRecorder`, starting with the one that the earliest
+    // capture that may be indexed is in
+    chunk_recv: Receiver<RecordedChunk>,
+    chunks: VecDeque<RecordedChunk>,
+}
+
+impl SessionRecorder {
+    /// Create the directory at `path` (if it does not exist) and start recording a session with
+    /// samples from `source` into it, overwriting any session recorded there before. The files
+    /// are written by `writer`.
+    pub fn create(path: &Path, source: &SessionSource, writer: &DiskWriter)
+            -> std::io::Result<SessionRecorder> {
+        std::fs::create_dir_all(path)?;
+        let open = |filename| {
+            let path = path.join(filename);
+            writer.stream(path.clone(), File::create(path)?)
+        };
+        let (chunk_send, chunk_recv) = channel();
+        let (samples, index) = match source {
+            SessionSource::Scenario(_) => (None, None),
+            SessionSource::Samples =>
+                (Some((open(SAMPLES_FILENAME)?, chunk_send)), Some(open(INDEX_FILENAME)?)),
+        };
+        let mut recorder = SessionRecorder {
+            events: open(EVENTS_FILENAME)?,
+            samples,
+            index,
+            chunk_recv,
+            chunks: VecDeque::new(),
+        };
+        recorder.write(&Record::Source(source.clone()))?;
+        Ok(recorder)
+    }
+
+    fn write(&mut self, record: &Record) -> std::io::Result<()> {
+        // each record is written as soon as it is dequeued, so that the session is complete
+        // (up to the last few records) even if the application crashes
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.events.write(line.into_bytes())
+    }
+
+    /// Record that `change` was applied at stream `position`.
+    pub fn record(&mut self, position: u64, change: &Change) -> std::io::Result<()> {
+        self.write(&Record::Change { position, change: change.clone() })
+    }
+
+    /// Returns the file to record samples into, and the sender to report the journal records
+    /// written into it with, if the source requires recording them.
+    pub fn take_samples(&mut self) -> Option<(WriterFile, Sender<RecordedChunk>)> {
+        self.samples.take()
+    }
+
+    /// Returns `true` if the captures are being added to the index.
+    pub fn is_indexing(&self) -> bool {
+        self.index.is_some()
+    }
+
+    /// Add a capture, starting at stream `position`, to the index, if the session has one.
+    /// The capture must have been read through the `SampleRecorder`.
+    pub fn index(&mut self, position: u64, time: Option<SystemTime>,
+                 trigger: Option<TriggerEvent>, range: [Option<(i8, i8)>; 4]) {
+        let Some(index) = self.index.as_mut() else { return };
+        loop {
+            match self.chunk_recv.try_recv() {
+                Ok(chunk) => self.chunks.push_back(chunk),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // the samples are no longer recorded, so the capture cannot be replayed
+                    log::warn!("sampler: samples are not recorded, stopping index");
+                    self.index = None;
+                    return
+                }
+            }
+        }
+        // captures are made in the order of their positions
+        while self.chunks.get(1).is_some_and(|chunk| chunk.position <= position) {
+            self.chunks.pop_front();
+        }
+        let Some(&chunk) = self.chunks.front() else { return };
+        let entry = IndexEntry { position, chunk, time, trigger, range };
+        let mut line = serde_json::to_string(&entry).expect("failed to serialize index entry");
+        line.push('\n');
+        if let Err(error) = index.write(line.into_bytes()) {
+            log::error!("sampler: cannot index session, stopping: {}", error);
+            self.index = None;
+        }
+    }
+}
+
+/// Passes the sample stream through, writing it into `output`, if any, and reporting each
+/// journal record written.
+pub struct SampleRecorder<R: Read> {
+    inner: R,
+    output: Option<(WriterFile, Sender<RecordedChunk>)>,
+    next_chunk: RecordedChunk,
+}
+
+impl<R: Read> SampleRecorder<R> {
+    pub fn new(inner: R, output: Option<(WriterFile, Sender<RecordedChunk>)>) -> Self {
+        Self { inner, output, next_chunk: RecordedChunk { position: 0, offset: 0 } }
+    }
+}
+
+impl<R: Read> Read for SampleRecorder<R> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.inner.read(data)?;
+        if let Some((output, chunk_send)) = self.output.as_mut() {
+            let record = journal_record(&data[..length]);
+            let record_length = record.len() as u64;
+            // a discarded chunk would make the rest of the recording unusable
+            match output.write(record) {
+                Ok(()) => {
+                    let _ = chunk_send.send(self.next_chunk);
+                    self.next_chunk.position += length as u64;
+                    self.next_chunk.offset += record_length;
+                }
+                Err(error) => {
+                    log::error!("sampler: cannot record samples, stopping: {}", error);
+                    self.output = None;
+                }
+            }
+        }
+        Ok(length)
+    }
+}
+
+impl<R: SampleSource> SampleSource for SampleRecorder<R> {
+    fn reconfigure(&mut self, params: &DeviceParameters) {
+        self.inner.reconfigure(params)
+    }
+
+    fn recover(&mut self) -> thunderscope::Result<()> {
+        self.inner.recover()
+    }
+
+    fn pause(&mut self, paused: bool) -> thunderscope::Result<()> {
+        self.inner.pause(paused)
+    }
+}
+
+/// Reads the samples recorded in a session, with the same lengths as they were originally read.
+/// Once all of them are read, reports an error.
+pub struct ReplayedSamples {
+    journal: JournalReader<BufReader<File>>,
+    chunk: Vec<u8>,
+    // samples of the current chunk already read
+    offset: usize,
+}
+
+impl ReplayedSamples {
+    /// Open the samples recorded in the session at `path`, starting with the journal record at
+    /// `start`.
+    pub fn open(path: &Path, start: RecordedChunk) -> std::io::Result<ReplayedSamples> {
+        let mut file = File::open(path.join(SAMPLES_FILENAME))?;
+        file.seek(SeekFrom::Start(start.offset))?;
+        Ok(ReplayedSamples {
+            journal: JournalReader::new(BufReader::new(file)),
+            chunk: Vec::new(),
+            offset: 0,
+        })
+    }
+}
+
+impl Read for ReplayedSamples {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        if self.offset == self.chunk.len() {
+            self.chunk = self.journal.read_record()?.ok_or_else(||
+                std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "end of recorded samples"))?;
+            self.offset = 0;
+        }
+        let length = (self.chunk.len() - self.offset).min(data.len());
+        data[..length].copy_from_slice(&self.chunk[self.offset..][..length]);
+        self.offset += length;
+        // simulate 1 GS/s capture rate
+        std::thread::sleep(std::time::Duration::from_nanos(1) * (length as u32));
+        Ok(length)
+    }
+}
+
+impl SampleSource for ReplayedSamples {}
@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use thunderscope::ThreadScheduling;
+
+use crate::i18n::Language;
+use crate::notify::Notifications;
+use crate::palette::Palette;
+
+const DEFAULT_MEMORY_BUDGET: u32 = 256; // in MiB
+
+const DEFAULT_WARM_UP: u32 = 30; // in minutes
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ProbeType {
+    X1,
+    #[default]
+    X10,
+}
+
+impl ProbeType {
+    pub const ALL: [ProbeType; 2] = [ProbeType::X1, ProbeType::X10];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProbeType::X1  => "1X",
+            ProbeType::X10 => "10X",
+        }
+    }
+
+    /// Probe attenuation in dB.
+    pub fn attenuation(self) -> f32 {
+        match self {
+            ProbeType::X1  =>  0.0,
+            ProbeType::X10 => 20.0,
+        }
+    }
+}
+
+/// What to do about the baseline drift of the analog frontend while it warms up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DriftTracking {
+    #[default]
+    Off,
+    /// Warn the user if the baseline of an idle channel drifts noticeably.
+    Warn,
+    /// Correct the measured voltages by the baseline drift of idle channels.
+    Compensate,
+}
+
+impl DriftTracking {
+    pub const ALL: [DriftTracking; 3] =
+        [DriftTracking::Off, DriftTracking::Warn, DriftTracking::Compensate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DriftTracking::Off        => "Off",
+            DriftTracking::Warn       => "Warn",
+            DriftTracking::Compensate => "Compensate",
+        }
+    }
+}
+
+/// User settings persisted between runs of the application.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Whether the first-run setup has been completed.
+    pub setup_complete: bool,
+    /// Whether to use a simulated signal instead of the hardware.
+    pub demo_mode: bool,
+    /// Scenario file describing the simulated signal; if not set, a sine wave is used.
+    pub demo_scenario: Option<PathBuf>,
+    /// Path of the device to use, if there is more than one.
+    pub device_path: Option<String>,
+    pub probes: [ProbeType; 4],
+    /// Language of the user interface; if not set, it is determined from the locale.
+    pub language: Option<Language>,
+    /// Colors used to tell channels apart.
+    pub palette: Palette,
+    /// Scheduling of the acquisition thread; takes effect when acquisition starts.
+    pub acquisition_scheduling: ThreadScheduling,
+    /// Limit on the memory used by buffers and history, in MiB; if not set, a default is used.
+    pub memory_budget: Option<u32>,
+    /// Whether to checksum the samples as they are acquired, and discard captures that were
+    /// corrupted in memory afterwards; takes effect when the application starts.
+    pub verify_captures: bool,
+    /// Whether to lower the sample rate if the FIFO overflows repeatedly, instead of continuing
+    /// to lose samples; takes effect when acquisition starts.
+    pub degrade_on_overflow: bool,
+    /// Whether to track the baseline drift while the frontend warms up; takes effect when
+    /// acquisition starts.
+    pub drift_tracking: DriftTracking,
+    /// Duration of the warm-up, in minutes; if not set, a default is used.
+    pub warm_up_minutes: Option<u32>,
+    /// Events to notify about, and how.
+    pub notifications: Notifications,
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("thunderscope").join("settings.toml"))
+    }
+
+    /// Load settings, falling back to defaults if there are none or they cannot be read.
+    pub fn load() -> Settings {
+        let Some(path) = Self::path() else { return Default::default() };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str(&text) {
+                Ok(settings) => settings,
+                Err(error) => {
+                    log::warn!("ignoring malformed settings in {}: {}", path.display(), error);
+                    Default::default()
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound =>
+                Default::default(),
+            Err(error) => {
+                log::warn!("cannot read settings from {}: {}", path.display(), error);
+                Default::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else { return };
+        let text = toml::to_string_pretty(self).expect("failed to serialize settings");
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|()| std::fs::write(&path, text));
+        match result {
+            Ok(()) => log::debug!("saved settings to {}", path.display()),
+            Err(error) => log::warn!("cannot save settings to {}: {}", path.display(), error),
+        }
+    }
+
+    /// Limit on the memory used by buffers and history, in bytes.
+    pub fn memory_budget(&self) -> usize {
+        (self.memory_budget.unwrap_or(DEFAULT_MEMORY_BUDGET) as usize) << 20
+    }
+
+    /// Duration of the warm-up of the frontend, during which its baseline drift is tracked.
+    pub fn warm_up(&self) -> Duration {
+        Duration::from_secs(self.warm_up_minutes.unwrap_or(DEFAULT_WARM_UP) as u64 * 60)
+    }
+
+    pub fn probe_attenuation(&self) -> [f32; 4] {
+        self.probes.map(|probe| probe.attenuation())
+    }
+}
@@ -0,0 +1,324 @@
+use std::io::Read;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use thunderscope::{ChannelConfiguration, DeviceCalibration, DeviceConfiguration, DeviceParameters};
+use thunderscope::{Descriptor, Device};
+
+use crate::capture::DataSource;
+use crate::i18n::{self, tr, tr_format, Language};
+use crate::scenario::Scenario;
+use crate::settings::{ProbeType, Settings};
+
+const DEMO_FREQUENCY: f32 = 1e5; // in Hz
+const DEMO_AMPLITUDE: f32 = 5.0; // in Vpp
+
+/// Returns the simulated data source for the demo mode, which uses the scenario configured in
+/// `settings`, if any.
+fn demo_source(settings: &Settings) -> DataSource {
+    let scenario = settings.demo_scenario.as_ref().and_then(|path| {
+        match Scenario::load(path) {
+            Ok(scenario) => Some(scenario),
+            Err(error) => {
+                log::warn!("ignoring scenario {}: {}", path.display(), error);
+                None
+            }
+        }
+    });
+    DataSource::Simulation(scenario
+        .unwrap_or_else(|| Scenario::sine(DEMO_FREQUENCY, DEMO_AMPLITUDE)))
+}
+
+/// Returns the data source to use according to `settings`, or `None` if the first-run setup
+/// has to be (re-)run, e.g. because the device that was used before is no longer connected.
+pub fn data_source(settings: &Settings) -> Option<DataSource> {
+    if !settings.setup_complete {
+        None
+    } else if settings.demo_mode {
+        Some(demo_source(settings))
+    } else {
+        let result = Device::list().and_then(|descriptors| {
+            let descriptor = descriptors.iter()
+                .find(|descriptor| Some(&descriptor.path) == settings.device_path.as_ref())
+                .or(descriptors.first().filter(|_| settings.device_path.is_none()))
+                .ok_or(thunderscope::Error::NotFound)?;
+            Device::open(descriptor)
+        });
+        match result {
+            Ok(device) => Some(DataSource::Hardware(device)),
+            Err(error) => {
+                log::warn!("cannot open device: {}", error);
+                None
+            }
+        }
+    }
+}
+
+/// Checks that the device powers up and the data mover delivers samples. The device is shut down
+/// afterwards, even if the test fails.
+fn run_self_test(device: Device) -> thunderscope::Result<String> {
+    const TEST_SIZE: usize = 1 << 20;
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    let device = device.guard()?;
+    let result = (|| {
+        let params = DeviceParameters::derive(&DeviceCalibration::default(), &DeviceConfiguration {
+            channels: [Some(ChannelConfiguration::default()), None, None, None],
+            ..Default::default()
+        });
+        device.configure(&params)?;
+        let mut stream = device.stream_data();
+        let mut buffer = vec![0; TEST_SIZE];
+        let started_at = Instant::now();
+        let mut received = 0;
+        while received < TEST_SIZE {
+            if started_at.elapsed() > TIMEOUT {
+                return Err(thunderscope::Error::Other(
+                    tr_format("received only {} of {} bytes", &[&received, &TEST_SIZE]).into()))
+            }
+            received += stream.read(&mut buffer[received..])?;
+        }
+        let elapsed = format!("{:.1}", started_at.elapsed().as_secs_f32() * 1e3);
+        Ok(tr_format("received {} KiB of samples in {} ms", &[&(received / 1024), &elapsed]))
+    })();
+    let shutdown = device.shutdown();
+    result.and_then(|report| shutdown.map(|()| report))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Detect,
+    SelfTest,
+    Calibration,
+    Probes,
+}
+
+/// Guided first-run flow: detects the device (or offers demo mode), runs a self-test, explains
+/// the calibration status, and lets the user pick probe types.
+#[derive(Debug)]
+pub struct SetupWizard {
+    step: Step,
+    settings: Settings,
+    descriptors: Vec<Descriptor>,
+    selected: usize,
+    device: Option<Device>,
+    detect_error: Option<String>,
+    self_test: Option<Result<String, String>>,
+    // the self-test runs on its own thread, which takes the device, so that the window keeps
+    // being rendered in the meantime
+    self_test_thread: Option<JoinHandle<Result<String, String>>>,
+}
+
+impl SetupWizard {
+    pub fn new(settings: Settings) -> Self {
+        let mut wizard = SetupWizard {
+            step: Step::Detect,
+            settings,
+            descriptors: Vec::new(),
+            selected: 0,
+            device: None,
+            detect_error: None,
+            self_test: None,
+            self_test_thread: None,
+        };
+        wizard.detect();
+        wizard
+    }
+
+    fn detect(&mut self) {
+        self.device = None;
+        match Device::list() {
+            Ok(descriptors) if descriptors.is_empty() => {
+                self.descriptors = Vec::new();
+                self.detect_error = Some(thunderscope::Error::NotFound.to_string());
+            }
+            Ok(descriptors) => {
+                let device_path = self.settings.device_path.as_ref();
+                self.selected = descriptors.iter()
+                    .position(|descriptor| Some(&descriptor.path) == device_path)
+                    .unwrap_or(0);
+                self.descriptors = descriptors;
+                self.detect_error = None;
+            }
+            Err(error) => {
+                self.descriptors = Vec::new();
+                self.detect_error = Some(error.to_string());
+            }
+        }
+    }
+
+    fn open_selected(&mut self) {
+        let descriptor = &self.descriptors[self.selected];
+        match Device::open(descriptor) {
+            Ok(device) => {
+                self.settings.device_path = Some(descriptor.path.clone());
+                self.settings.demo_mode = false;
+                self.device = Some(device);
+                self.step = Step::SelfTest;
+            }
+            Err(error) => self.detect_error = Some(error.to_string()),
+        }
+    }
+
+    fn start_self_test(&mut self) {
+        let device = self.device.take().unwrap();
+        self.self_test = None;
+        self.self_test_thread = Some(std::thread::spawn(move || {
+            run_self_test(device).map_err(|error| error.to_string())
+        }));
+    }
+
+    fn poll_self_test(&mut self) {
+        if !self.self_test_thread.as_ref().is_some_and(|thread| thread.is_finished()) {
+            return
+        }
+        let result = self.self_test_thread.take().unwrap().join()
+            .unwrap_or_else(|_| Err(tr("the self-test panicked").to_owned()));
+        self.self_test = Some(result);
+        // the device was consumed by the self-test, so it has to be opened again
+        match Device::open(&self.descriptors[self.selected]) {
+            Ok(device) => self.device = Some(device),
+            Err(error) => {
+                self.detect_error = Some(error.to_string());
+                self.step = Step::Detect;
+            }
+        }
+    }
+
+    /// Returns `true` while the wizard is waiting for the self-test to finish.
+    pub fn is_busy(&self) -> bool {
+        self.self_test_thread.is_some()
+    }
+
+    /// Render the wizard. Returns the chosen data source and the updated settings once
+    /// the setup is complete.
+    pub fn render(&mut self, ui: &imgui::Ui) -> Option<(DataSource, Settings)> {
+        use imgui::*;
+
+        let mut result = None;
+        let [width, height] = ui.io().display_size;
+        // the window is identified by `###setup` since its title changes with the language
+        ui.window(format!("{}###setup", tr("Setup")))
+            .position([width / 2.0, height / 2.0], Condition::Always)
+            .position_pivot([0.5, 0.5])
+            .always_auto_resize(true)
+            .collapsible(false)
+            .movable(false)
+            .build(|| {
+                match self.step {
+                    Step::Detect => self.render_detect(ui),
+                    Step::SelfTest => self.render_self_test(ui),
+                    Step::Calibration => self.render_calibration(ui),
+                    Step::Probes => result = self.render_probes(ui),
+                }
+            });
+        result
+    }
+
+    fn render_detect(&mut self, ui: &imgui::Ui) {
+        ui.text(tr("Step 1: Connect the instrument"));
+        ui.separator();
+        let names = Language::ALL.map(|language| language.name());
+        let mut language_index = Language::ALL.iter()
+            .position(|&language| language == i18n::language()).unwrap();
+        ui.set_next_item_width(100.0);
+        if ui.combo_simple_string(tr("Language"), &mut language_index, &names) {
+            self.settings.language = Some(Language::ALL[language_index]);
+            i18n::set_language(Language::ALL[language_index]);
+        }
+        match &self.detect_error {
+            None if self.descriptors.len() == 1 => {
+                ui.text(tr("ThunderScope detected."));
+                if ui.button(tr("Next")) {
+                    self.open_selected();
+                }
+            }
+            None => {
+                ui.text(tr_format("{} ThunderScopes detected.", &[&self.descriptors.len()]));
+                let names = self.descriptors.iter()
+                    .map(|descriptor| descriptor.to_string())
+                    .collect::<Vec<_>>();
+                ui.combo_simple_string(tr("Device"), &mut self.selected, &names);
+                if ui.button(tr("Next")) {
+                    self.open_selected();
+                }
+            }
+            Some(_) if !thunderscope::capabilities().hardware => {
+                ui.text(tr("This build has no hardware driver; only the demo mode is available."));
+                if ui.button(tr("Use demo mode")) {
+                    self.settings.demo_mode = true;
+                    self.step = Step::Probes;
+                }
+            }
+            Some(error) => {
+                ui.text(tr_format("No ThunderScope detected: {}", &[error]));
+                ui.text(tr("Check that the device is connected and the XDMA driver is loaded."));
+                if ui.button(tr("Retry")) {
+                    self.detect();
+                }
+                ui.same_line();
+                if ui.button(tr("Use demo mode")) {
+                    self.settings.demo_mode = true;
+                    self.step = Step::Probes;
+                }
+            }
+        }
+    }
+
+    fn render_self_test(&mut self, ui: &imgui::Ui) {
+        ui.text(tr("Step 2: Self-test"));
+        ui.separator();
+        ui.text(tr("The self-test powers up the frontend and checks that samples are acquired."));
+        self.poll_self_test();
+        if self.self_test_thread.is_some() {
+            ui.text(tr("Running…"));
+            return
+        }
+        match &self.self_test {
+            None => ui.text(tr("Not run yet.")),
+            Some(Ok(message)) => ui.text(tr_format("Passed: {}", &[message])),
+            Some(Err(message)) => ui.text(tr_format("Failed: {}", &[message])),
+        }
+        if ui.button(tr("Run self-test")) {
+            self.start_self_test();
+        }
+        ui.same_line();
+        if ui.button(tr(if self.self_test.is_some() { "Next" } else { "Skip" })) {
+            self.step = Step::Calibration;
+        }
+    }
+
+    fn render_calibration(&mut self, ui: &imgui::Ui) {
+        ui.text(tr("Step 3: Calibration"));
+        ui.separator();
+        ui.text(tr("No calibration data is available for this instrument; the nominal calibration\n\
+                    will be used. Absolute voltages may be inaccurate by several percent."));
+        if ui.button(tr("Next")) {
+            self.step = Step::Probes;
+        }
+    }
+
+    fn render_probes(&mut self, ui: &imgui::Ui) -> Option<(DataSource, Settings)> {
+        ui.text(tr_format("Step {}: Probes", &[&if self.settings.demo_mode { 2 } else { 4 }]));
+        ui.separator();
+        let names = ProbeType::ALL.map(|probe| probe.name());
+        for (index, probe) in self.settings.probes.iter_mut().enumerate() {
+            let mut probe_index = ProbeType::ALL.iter().position(|p| p == probe).unwrap();
+            ui.set_next_item_width(100.0);
+            if ui.combo_simple_string(format!("CH{}", index + 1), &mut probe_index, &names) {
+                *probe = ProbeType::ALL[probe_index];
+            }
+        }
+        if ui.button(tr("Finish")) {
+            self.settings.setup_complete = true;
+            self.settings.save();
+            let data_source = match self.device.take() {
+                Some(device) if !self.settings.demo_mode => DataSource::Hardware(device),
+                _ => demo_source(&self.settings),
+            };
+            Some((data_source, self.settings.clone()))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! A scripted tour of the features of the application, for demonstrating it e.g. at a booth, or
+//! to a new user.
+//!
+//! The tour runs on a simulated signal that has something to show for every step: a square wave
+//! with noise (for averaging) and periodic glitches (for the window trigger and peak detection).
+//! Each step switches the operation and acquisition modes, opens the windows it is about, and
+//! shows a caption; once the last step is over, the tour starts over.
+
+use std::time::{Duration, Instant};
+
+use thunderscope::{EdgeFilter, WindowFilter};
+use thunderscope::acquire::{OperationMode, Parameters, AUTO_TIMEOUT};
+use thunderscope::acquire::{TriggerParameters, WindowParameters};
+
+use crate::capture::AcquisitionMode;
+use crate::scenario::{Glitches, Scenario, Segment, Signal};
+
+/// How long each step of the tour is shown.
+const STEP_DURATION: Duration = Duration::from_secs(8);
+
+/// A window that can be opened by a step of the tour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TourWindow {
+    Readouts,
+    Trend,
+    Roll,
+}
+
+#[derive(Debug, Clone)]
+pub struct TourStep {
+    /// Untranslated caption describing the step.
+    pub caption: &'static str,
+    pub mode: OperationMode,
+    pub acquisition_mode: AcquisitionMode,
+    /// Windows that are open during the step; the others are closed.
+    pub windows: &'static [TourWindow],
+}
+
+impl TourStep {
+    fn new(caption: &'static str, mode: OperationMode) -> TourStep {
+        TourStep { caption, mode, acquisition_mode: AcquisitionMode::Sample, windows: &[] }
+    }
+
+    fn acquire(self, acquisition_mode: AcquisitionMode) -> TourStep {
+        TourStep { acquisition_mode, ..self }
+    }
+
+    fn open(self, windows: &'static [TourWindow]) -> TourStep {
+        TourStep { windows, ..self }
+    }
+}
+
+/// Returns the simulated signal for the tour.
+pub fn scenario() -> Scenario {
+    let mut segment = Segment::new(1.0, Signal::Square {
+        frequency: 1e5,
+        amplitude: 5.0,
+        offset: 0.0,
+        duty: 0.5,
+    });
+    segment.glitches = Some(Glitches { interval: 1.05e-3, width: 50e-9, amplitude: 2.0 });
+    segment.noise = 0.1;
+    Scenario::new().segment(segment)
+}
+
+fn steps() -> Vec<TourStep> {
+    let rising = TriggerParameters::new(0, 0.0, EdgeFilter::Rising);
+    let falling = TriggerParameters::new(0, 0.0, EdgeFilter::Falling);
+    let window = WindowParameters::new(0, -3.5, 3.5, WindowFilter::Exit);
+    let auto = OperationMode::Auto(rising, AUTO_TIMEOUT);
+    vec![
+        TourStep::new("Free running: every capture is shown as acquired, without a trigger.",
+            OperationMode::FreeRunning),
+        TourStep::new("Auto trigger: captures start at a rising edge, or after a timeout.",
+            auto),
+        TourStep::new("Normal trigger: only captures that start at a falling edge are shown.",
+            OperationMode::RepeatTrigger(falling)),
+        TourStep::new("Window trigger: captures start where the signal leaves a voltage range.",
+            OperationMode::RepeatWindow(window)),
+        TourStep::new("Peak detect: narrow glitches remain visible over a longer time span.",
+            auto).acquire(AcquisitionMode::PeakDetect),
+        TourStep::new("Averaging: noise that is not correlated with the trigger is reduced.",
+            auto).acquire(AcquisitionMode::Average(16)),
+        TourStep::new("Readouts: the DC level and the AC RMS of each channel, like a multimeter.",
+            auto).open(&[TourWindow::Readouts]),
+        TourStep::new("Trend: a measurement of every capture, plotted over time.",
+            auto).open(&[TourWindow::Trend]),
+        TourStep::new("Roll: the continuous signal, decimated, for slow changes.",
+            auto).open(&[TourWindow::Roll]),
+    ]
+}
+
+/// Steps through the tour as time passes.
+#[derive(Debug)]
+pub struct Tour {
+    params: Parameters,
+    steps: Vec<TourStep>,
+    // index of the current step, and when it started; `None` until the tour starts
+    current: Option<(usize, Instant)>,
+}
+
+impl Tour {
+    /// Create a tour that switches the operation mode of `params`, which are otherwise kept.
+    pub fn new(params: Parameters) -> Tour {
+        Tour { params, steps: steps(), current: None }
+    }
+
+    /// Returns the step to switch to, if it is time to switch to the next one.
+    pub fn poll(&mut self, now: Instant) -> Option<&TourStep> {
+        let index = match self.current {
+            None => 0,
+            Some((index, started_at)) if now.duration_since(started_at) >= STEP_DURATION =>
+                (index + 1) % self.steps.len(),
+            Some(_) => return None,
+        };
+        self.current = Some((index, now));
+        Some(&self.steps[index])
+    }
+
+    /// Returns the parameters to acquire with during `step`.
+    pub fn params(&self, step: &TourStep) -> Parameters {
+        self.params.with_mode(step.mode)
+    }
+}
+
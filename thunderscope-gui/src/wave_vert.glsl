@@ -27,6 +27,7 @@ mat2 line_rotation(vec2 line_a, vec2 line_b) {
 
 uniform vec2 resolution;
 uniform int sample_count;
+uniform float sample_delay;
 uniform bool draw_lines;
 
 in float sample_value0;
@@ -37,7 +38,7 @@ out vec2 prim_offset;
 
 vec2 project_sample(int index, float value) {
     return vec2(
-        float(resolution.x) * (float(index) / float(sample_count - 1)),
+        float(resolution.x) * ((float(index) + sample_delay) / float(sample_count - 1)),
         float(resolution.y) * (0.5f + value / 2.0f)
     );
 }
@@ -0,0 +1,192 @@
+//! Writing of files on a dedicated thread.
+//!
+//! A write may block for a long time, e.g. on a slow or spun down disk, or while the kernel is
+//! flushing the page cache. Neither the acquisition thread (which would let the device FIFO
+//! overflow) nor the render thread may wait for that, so they queue data for the writer thread
+//! instead. The queue is bounded; if it is full, the data is rejected at once rather than waited
+//! for. Files are synced to disk in batches, at most once per `SYNC_INTERVAL`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Maximum number of writes that may be waiting in the queue.
+const QUEUE_LENGTH: usize = 256;
+
+/// Maximum number of bytes that may be waiting in the queue.
+const QUEUE_BYTES: usize = 64 << 20;
+
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+enum Job {
+    Save { path: PathBuf, data: Vec<u8> },
+    Open { id: usize, path: PathBuf, file: File, failed: Arc<AtomicBool> },
+    Write { id: usize, data: Vec<u8> },
+    Close { id: usize },
+}
+
+impl Job {
+    fn size(&self) -> usize {
+        match self {
+            Job::Save { data, .. } | Job::Write { data, .. } => data.len(),
+            Job::Open { .. } | Job::Close { .. } => 0,
+        }
+    }
+}
+
+struct Stream {
+    path: PathBuf,
+    file: File,
+    failed: Arc<AtomicBool>,
+    // whether anything was written since the last sync
+    dirty: bool,
+}
+
+impl Stream {
+    fn sync(&mut self) {
+        if std::mem::take(&mut self.dirty) {
+            if let Err(error) = self.file.sync_data() {
+                log::error!("writer: cannot sync {}: {}", self.path.display(), error);
+            }
+        }
+    }
+}
+
+/// Queues writes for the writer thread. Cloning the handle is cheap; the thread exits once every
+/// handle (including those held by `WriterFile`s) is dropped and the queue is drained.
+#[derive(Debug, Clone)]
+pub struct DiskWriter {
+    send: SyncSender<Job>,
+    queued: Arc<AtomicUsize>,
+    next_id: Arc<AtomicUsize>,
+}
+
+impl DiskWriter {
+    pub fn spawn() -> (DiskWriter, std::thread::JoinHandle<()>) {
+        let (send, recv) = sync_channel(QUEUE_LENGTH);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let thread = std::thread::spawn({
+            let queued = queued.clone();
+            move || Self::run(recv, queued)
+        });
+        (DiskWriter { send, queued, next_id: Arc::new(AtomicUsize::new(0)) }, thread)
+    }
+
+    fn enqueue(&self, job: Job) -> std::io::Result<()> {
+        let size = job.size();
+        // a write larger than the limit is accepted into an empty queue, so that it can be done
+        let previous = self.queued.fetch_add(size, Ordering::Relaxed);
+        if previous > 0 && previous + size > QUEUE_BYTES {
+            self.queued.fetch_sub(size, Ordering::Relaxed);
+            return Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "writer is behind"))
+        }
+        match self.send.try_send(job) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.queued.fetch_sub(size, Ordering::Relaxed);
+                Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "writer is behind"))
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.queued.fetch_sub(size, Ordering::Relaxed);
+                Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "writer has exited"))
+            }
+        }
+    }
+
+    /// Queue `data` to be written into the file at `path`, replacing its contents. Errors that
+    /// happen once the data is dequeued are logged.
+    pub fn save(&self, path: impl Into<PathBuf>, data: Vec<u8>) -> std::io::Result<()> {
+        self.enqueue(Job::Save { path: path.into(), data })
+    }
+
+    /// Hand over `file`, opened from `path`, to the writer thread, to append data to it.
+    pub fn stream(&self, path: impl Into<PathBuf>, file: File) -> std::io::Result<WriterFile> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let failed = Arc::new(AtomicBool::new(false));
+        self.enqueue(Job::Open { id, path: path.into(), file, failed: failed.clone() })?;
+        Ok(WriterFile { writer: self.clone(), id, failed })
+    }
+
+    fn run(recv: Receiver<Job>, queued: Arc<AtomicUsize>) {
+        let mut streams: HashMap<usize, Stream> = HashMap::new();
+        let mut synced_at = Instant::now();
+        loop {
+            match recv.recv_timeout(SYNC_INTERVAL.saturating_sub(synced_at.elapsed())) {
+                Ok(job) => {
+                    queued.fetch_sub(job.size(), Ordering::Relaxed);
+                    Self::perform(job, &mut streams)
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if synced_at.elapsed() >= SYNC_INTERVAL {
+                streams.values_mut().for_each(Stream::sync);
+                synced_at = Instant::now();
+            }
+        }
+        streams.values_mut().for_each(Stream::sync);
+        log::debug!("writer: done");
+    }
+
+    fn perform(job: Job, streams: &mut HashMap<usize, Stream>) {
+        match job {
+            Job::Save { path, data } => {
+                let result = File::create(&path)
+                    .and_then(|mut file| file.write_all(&data).and_then(|()| file.sync_data()));
+                match result {
+                    Ok(()) => log::info!("writer: saved {}", path.display()),
+                    Err(error) => log::error!("writer: cannot save {}: {}", path.display(), error),
+                }
+            }
+            Job::Open { id, path, file, failed } => {
+                streams.insert(id, Stream { path, file, failed, dirty: false });
+            }
+            Job::Write { id, data } => {
+                // the stream is gone if writing to it failed before
+                let Some(stream) = streams.get_mut(&id) else { return };
+                if let Err(error) = stream.file.write_all(&data) {
+                    log::error!("writer: cannot write to {}: {}", stream.path.display(), error);
+                    stream.failed.store(true, Ordering::Relaxed);
+                    streams.remove(&id);
+                    return
+                }
+                stream.dirty = true;
+            }
+            Job::Close { id } => {
+                if let Some(mut stream) = streams.remove(&id) {
+                    stream.sync();
+                }
+            }
+        }
+    }
+}
+
+/// A file that data is appended to by the writer thread. The file is closed once dropped.
+pub struct WriterFile {
+    writer: DiskWriter,
+    id: usize,
+    failed: Arc<AtomicBool>,
+}
+
+impl WriterFile {
+    /// Queue `data` to be appended to the file. Returns an error if the queue is full (and
+    /// the data was discarded), or if an earlier write has failed.
+    pub fn write(&self, data: Vec<u8>) -> std::io::Result<()> {
+        if self.failed.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("an earlier write has failed"))
+        }
+        self.writer.enqueue(Job::Write { id: self.id, data })
+    }
+}
+
+impl Drop for WriterFile {
+    fn drop(&mut self) {
+        // if the queue is full, the file is closed once the writer thread exits instead
+        let _ = self.writer.enqueue(Job::Close { id: self.id });
+    }
+}